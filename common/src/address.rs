@@ -382,6 +382,17 @@ impl Address {
         }
     }
 
+    pub fn is_multisig(&self) -> bool {
+        match &self.payload {
+            AddressPayload::Short { index, .. } => index == &CodeHashIndex::Multisig,
+            AddressPayload::Full {
+                hash_type,
+                code_hash,
+                ..
+            } => hash_type == &ScriptHashType::Type && code_hash == &MULTISIG_TYPE_HASH.pack(),
+        }
+    }
+
     pub fn is_acp(&self) -> bool {
         match &self.payload {
             AddressPayload::Short { index, .. } => index == &CodeHashIndex::AnyoneCanPay,
@@ -3,7 +3,10 @@ pub mod hash;
 pub mod utils;
 
 pub use address::{Address, AddressPayload, AddressType, CodeHashIndex};
-pub use {anyhow, anyhow::Result, async_trait::async_trait, creep::Context, derive_more, minstant};
+pub use {
+    anyhow, anyhow::Result, async_trait::async_trait, creep::Context, derive_more, minstant,
+    secp256k1,
+};
 
 use ckb_types::{bytes::Bytes, core::BlockNumber, h256, packed, H256};
 use derive_more::Display;
@@ -28,9 +31,12 @@ pub const NETWORK_TESTNET: &str = "ckb_testnet";
 pub const NETWORK_STAGING: &str = "ckb_staging";
 pub const NETWORK_DEV: &str = "ckb_dev";
 pub const SECP256K1: &str = "secp256k1_blake160";
+pub const MULTISIG: &str = "secp256k1_blake160_multisig_all";
 pub const SUDT: &str = "sudt";
+pub const XUDT: &str = "xudt";
 pub const ACP: &str = "anyone_can_pay";
 pub const CHEQUE: &str = "cheque";
+pub const OMNI_LOCK: &str = "omni_lock";
 pub const DAO: &str = "dao";
 
 #[derive(Clone, Debug, PartialEq, Eq)]
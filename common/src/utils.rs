@@ -101,6 +101,15 @@ pub fn encode_udt_amount(amount: u128) -> Vec<u8> {
     amount.to_le_bytes().to_vec()
 }
 
+/// The xUDT cell data may carry extension data after the amount, which is kept here.
+pub fn update_udt_amount(data: &[u8], amount: u128) -> Vec<u8> {
+    let mut ret = encode_udt_amount(amount);
+    if data.len() > 16 {
+        ret.extend_from_slice(&data[16..]);
+    }
+    ret
+}
+
 pub fn decode_nonce(data: &[u8]) -> u128 {
     u128::from_be_bytes(to_fixed_array(&data[0..16]))
 }
@@ -158,6 +167,19 @@ mod test {
         assert_eq!(address.payload().clone(), payload);
     }
 
+    #[test]
+    fn test_update_udt_amount() {
+        let data = encode_udt_amount(100);
+        assert_eq!(update_udt_amount(&data, 50), encode_udt_amount(50));
+
+        let extension = rand_bytes(8);
+        let mut data = encode_udt_amount(100);
+        data.extend_from_slice(&extension);
+        let res = update_udt_amount(&data, 50);
+        assert_eq!(decode_udt_amount(&res), 50);
+        assert_eq!(&res[16..], &extension[..]);
+    }
+
     #[test]
     fn test_find() {
         let test = (0..10).collect::<Vec<_>>();
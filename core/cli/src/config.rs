@@ -19,8 +19,13 @@ pub struct NetworkConfig {
     #[serde(default = "default_ckb_uri")]
     pub ckb_uri: String,
 
+    #[serde(default)]
+    pub ckb_uris: Vec<String>,
+
     #[serde(default = "default_listen_uri")]
     pub listen_uri: String,
+
+    pub ws_listen_uri: Option<String>,
 }
 
 #[derive(Deserialize, Default, Clone, Debug)]
@@ -73,6 +78,7 @@ pub struct ScriptConfig {
 pub struct SyncConfig {
     pub sync_block_batch_size: usize,
     pub max_task_count: usize,
+    pub block_dump_path: Option<String>,
 }
 
 #[derive(Deserialize, Default, Clone, Debug)]
@@ -107,6 +113,15 @@ pub struct MercuryConfig {
     #[serde(default = "default_cellbase_maturity")]
     pub cellbase_maturity: u64,
 
+    #[serde(default = "default_coin_selection")]
+    pub coin_selection: String,
+
+    #[serde(default = "default_reservation_ttl")]
+    pub reservation_ttl: u64,
+
+    #[serde(default = "default_max_reorg_depth")]
+    pub max_reorg_depth: u64,
+
     #[serde(default = "default_extensions_config")]
     pub extensions_config: Vec<ExtensionConfig>,
 }
@@ -114,7 +129,8 @@ pub struct MercuryConfig {
 impl MercuryConfig {
     pub fn check(&mut self) {
         self.build_uri();
-        self.check_rpc_thread_num()
+        self.check_rpc_thread_num();
+        self.check_sync_config()
     }
 
     pub fn to_script_map(&self) -> HashMap<String, ScriptInfo> {
@@ -133,9 +149,9 @@ impl MercuryConfig {
     }
 
     fn build_uri(&mut self) {
-        if !self.network_config.ckb_uri.starts_with("http") {
-            let uri = self.network_config.ckb_uri.clone();
-            self.network_config.ckb_uri = format!("http://{}", uri);
+        self.network_config.ckb_uri = with_http_scheme(&self.network_config.ckb_uri);
+        for uri in self.network_config.ckb_uris.iter_mut() {
+            *uri = with_http_scheme(uri);
         }
     }
 
@@ -144,6 +160,20 @@ impl MercuryConfig {
             panic!("The rpc thread number must be at least 2");
         }
     }
+
+    fn check_sync_config(&self) {
+        if self.sync_config.sync_block_batch_size < 1 || self.sync_config.max_task_count < 1 {
+            panic!("The sync block batch size and max task count must be at least 1");
+        }
+    }
+}
+
+fn with_http_scheme(uri: &str) -> String {
+    if uri.starts_with("http") {
+        uri.to_string()
+    } else {
+        format!("http://{}", uri)
+    }
 }
 
 fn default_need_sync() -> bool {
@@ -190,6 +220,18 @@ fn default_cheque_since() -> u64 {
     6u64
 }
 
+fn default_coin_selection() -> String {
+    String::from("Sequential")
+}
+
+fn default_reservation_ttl() -> u64 {
+    120
+}
+
+fn default_max_reorg_depth() -> u64 {
+    100
+}
+
 fn default_extensions_config() -> Vec<ExtensionConfig> {
     vec![]
 }
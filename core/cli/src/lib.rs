@@ -3,11 +3,11 @@ pub mod config;
 use crate::config::{parse, MercuryConfig};
 
 use common_logger::init_jaeger;
-use core_service::Service;
+use core_service::{export_blocks, BlockDumpFormat, Service};
 
 use ansi_term::Colour::Green;
-use clap::{crate_version, App, Arg, ArgMatches, SubCommand};
-use log::{info, LevelFilter};
+use clap::{crate_version, value_t, App, Arg, ArgMatches, SubCommand};
+use log::{error, info, LevelFilter};
 
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -62,6 +62,12 @@ impl<'a> Cli<'a> {
                     .help("Mercury listen uri")
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("ws_listen_uri")
+                    .long("ws_listen_uri")
+                    .help("Mercury websocket listen uri")
+                    .takes_value(true),
+            )
             .arg(
                 Arg::with_name("ckb_uri")
                     .long("ckb_uri")
@@ -69,6 +75,39 @@ impl<'a> Cli<'a> {
                     .takes_value(true),
             )
             .subcommand(SubCommand::with_name("run").about("run the mercury process"))
+            .subcommand(
+                SubCommand::with_name("export")
+                    .about("export the blocks of the ckb node to a block dump")
+                    .arg(
+                        Arg::with_name("output")
+                            .short("o")
+                            .long("output")
+                            .help("Block dump path, a directory for json or a file for molecule")
+                            .required(true)
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name("format")
+                            .long("format")
+                            .help("Block dump format")
+                            .possible_values(&["json", "molecule"])
+                            .default_value("molecule")
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name("from")
+                            .long("from")
+                            .help("The first block number to export")
+                            .default_value("0")
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name("to")
+                            .long("to")
+                            .help("The last block number to export, the node tip by default")
+                            .takes_value(true),
+                    ),
+            )
             .get_matches();
 
         let mut config: MercuryConfig =
@@ -82,6 +121,7 @@ impl<'a> Cli<'a> {
     pub async fn start(&self) {
         match self.matches.subcommand() {
             ("run", None) => self.run().await,
+            ("export", Some(sub_matches)) => self.export(sub_matches).await,
 
             _ => self.run().await,
         }
@@ -116,9 +156,15 @@ impl<'a> Cli<'a> {
             &self.config.network_config.network_type,
             self.config.to_script_map(),
             self.config.cellbase_maturity,
-            self.parse_cmd_args("ckb_uri", self.config.network_config.ckb_uri.clone()),
+            self.ckb_uris(),
             self.config.cheque_since,
+            &self.config.coin_selection,
+            self.config.reservation_ttl,
+            self.config.max_reorg_depth,
             LevelFilter::from_str(&self.config.db_config.db_log_level).unwrap(),
+            // the database name is the directory of the rocksdb
+            (self.config.db_config.db_type == "rocksdb")
+                .then(|| self.config.db_config.db_name.clone()),
         );
 
         let stop_handle = service
@@ -133,28 +179,83 @@ impl<'a> Cli<'a> {
             )
             .await;
 
+        let ws_stop_handle = match self
+            .matches
+            .value_of("ws_listen_uri")
+            .map(ToString::to_string)
+            .or_else(|| self.config.network_config.ws_listen_uri.clone())
+        {
+            Some(uri) => Some(service.init_ws(uri).await),
+            None => None,
+        };
+
         if self.config.need_sync {
             service
                 .do_sync(
                     self.config.sync_config.sync_block_batch_size,
                     self.config.sync_config.max_task_count,
+                    self.config.sync_config.block_dump_path.clone(),
                 )
                 .await
                 .unwrap();
         }
 
-        if self.config.indexer_mode {
+        let res = if self.config.indexer_mode {
             service
                 .start(self.config.flush_tx_pool_cache_interval)
-                .await;
+                .await
         } else {
-            service.start_rpc_mode().await.unwrap();
-        }
+            service.start_rpc_mode().await
+        };
 
         stop_handle.stop().unwrap().await.unwrap();
+        if let Some(handle) = ws_stop_handle {
+            handle.stop().unwrap().await.unwrap();
+        }
+
+        if let Err(err) = res {
+            error!("[alert] mercury halted, error: {}", err);
+            std::process::exit(1);
+        }
         info!("Closing!");
     }
 
+    /// The ckb node given by the command or by `ckb_uri` comes first, so its chain is the
+    /// one followed when the database is empty.
+    fn ckb_uris(&self) -> Vec<String> {
+        let mut uris =
+            vec![self.parse_cmd_args("ckb_uri", self.config.network_config.ckb_uri.clone())];
+        for uri in self.config.network_config.ckb_uris.iter() {
+            if !uris.contains(uri) {
+                uris.push(uri.clone());
+            }
+        }
+        uris
+    }
+
+    async fn export(&self, sub_matches: &ArgMatches<'a>) {
+        self.log_init();
+
+        let format = value_t!(sub_matches, "format", BlockDumpFormat).unwrap_or_else(|e| e.exit());
+        let from = value_t!(sub_matches, "from", u64).unwrap_or_else(|e| e.exit());
+        let to = if sub_matches.is_present("to") {
+            Some(value_t!(sub_matches, "to", u64).unwrap_or_else(|e| e.exit()))
+        } else {
+            None
+        };
+
+        export_blocks(
+            self.parse_cmd_args("ckb_uri", self.config.network_config.ckb_uri.clone()),
+            sub_matches.value_of("output").unwrap().to_string(),
+            format,
+            from,
+            to,
+        )
+        .await
+        .unwrap();
+        info!("Export finished!");
+    }
+
     fn log_init(&self) {
         let is_output_console = self.config.log_config.log_path.as_str() == CONSOLE;
         common_logger::init(
@@ -1,11 +1,12 @@
-use crate::{error::RpcErrorMessage, CkbRpc};
+use crate::{error::RpcErrorMessage, types::TxPoolInfo, CkbRpc};
 
 use common::{MercuryError, Result};
 use core_synchronization::SyncAdapter;
 
 use async_trait::async_trait;
 use ckb_jsonrpc_types::{
-    BlockView, EpochView, JsonBytes, LocalNode, RawTxPool, TransactionWithStatus, Uint32, Uint64,
+    BlockView, Consensus, EpochView, JsonBytes, LocalNode, RawTxPool, TransactionWithStatus,
+    Uint32, Uint64,
 };
 use ckb_types::{core, core::BlockNumber, packed, prelude::Entity, H256};
 use jsonrpc_core::types::{
@@ -20,12 +21,15 @@ use std::sync::Arc;
 
 const LOCAL_NODE_INFO_REQ: &str = "local_node_info";
 const GET_RAW_TX_POOL_REQ: &str = "get_raw_tx_pool";
+const TX_POOL_INFO_REQ: &str = "tx_pool_info";
 const GET_TRANSACTION_REQ: &str = "get_transaction";
 const GET_BLOCK_REQ: &str = "get_block";
 const GET_BLOCK_BY_NUMBER_REQ: &str = "get_block_by_number";
+const GET_BLOCK_HASH_REQ: &str = "get_block_hash";
 const GET_TIP_BLOCK_NUMBER_REQ: &str = "get_tip_block_number";
 const GET_EPOCH_BY_NUMBER_REQ: &str = "get_epoch_by_number";
 const GET_CURRENT_EPOCH_REQ: &str = "get_current_epoch";
+const GET_CONSENSUS_REQ: &str = "get_consensus";
 
 #[derive(Clone, Debug)]
 pub struct CkbRpcClient {
@@ -63,6 +67,12 @@ impl CkbRpc for CkbRpcClient {
         handle_response(resp)
     }
 
+    async fn get_tx_pool_info(&self) -> Result<TxPoolInfo> {
+        let (id, request) = self.build_request(TX_POOL_INFO_REQ, ())?;
+        let resp = self.rpc_exec(&request, id).await?;
+        handle_response(resp)
+    }
+
     async fn get_transactions(
         &self,
         hashes: Vec<H256>,
@@ -91,6 +101,13 @@ impl CkbRpc for CkbRpcClient {
         handle_batch_response(resp)
     }
 
+    async fn get_block_hash(&self, block_number: BlockNumber) -> Result<Option<H256>> {
+        let block_number: Uint64 = block_number.into();
+        let (id, request) = self.build_request(GET_BLOCK_HASH_REQ, vec![block_number])?;
+        let resp = self.rpc_exec(&request, id).await?;
+        handle_response(resp)
+    }
+
     async fn get_epoch_by_number(&self, epoch_number: Uint64) -> Result<EpochView> {
         let (id, request) = self.build_request(GET_EPOCH_BY_NUMBER_REQ, vec![epoch_number])?;
         let resp = self.rpc_exec(&request, id).await?;
@@ -137,6 +154,12 @@ impl CkbRpc for CkbRpcClient {
         let resp = self.rpc_exec(&request, id).await?;
         handle_response(resp)
     }
+
+    async fn get_consensus(&self) -> Result<Consensus> {
+        let (id, request) = self.build_request(GET_CONSENSUS_REQ, ())?;
+        let resp = self.rpc_exec(&request, id).await?;
+        handle_response(resp)
+    }
 }
 
 impl CkbRpcClient {
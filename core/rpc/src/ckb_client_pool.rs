@@ -0,0 +1,384 @@
+use crate::{types::TxPoolInfo, CkbRpc, CkbRpcClient};
+
+use common::{anyhow::anyhow, Result};
+use core_synchronization::SyncAdapter;
+
+use async_trait::async_trait;
+use ckb_jsonrpc_types::{
+    BlockView, Consensus, EpochView, LocalNode, RawTxPool, TransactionWithStatus, Uint64,
+};
+use ckb_types::{core, core::BlockNumber, H256};
+use parking_lot::RwLock;
+use tokio::time::{sleep, timeout};
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The nodes behind the highest tip by more than `MAX_TIP_LAG` blocks are used only when
+/// the nodes closer to the tip fail.
+const MAX_TIP_LAG: u64 = 3;
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Default)]
+struct NodeHealth {
+    // none if the node is not checked yet or the last request to it failed
+    tip: Option<BlockNumber>,
+    latency: Duration,
+    // the node is on another chain
+    refused: bool,
+}
+
+#[derive(Debug)]
+struct CkbNode {
+    uri: String,
+    client: CkbRpcClient,
+    health: RwLock<NodeHealth>,
+}
+
+/// A client of several ckb nodes. The requests are routed to the fastest node among the
+/// ones close to the highest tip, and fail over to the other nodes on errors. The chain is
+/// identified by the genesis hash, which is the one set by `set_genesis_hash` or the one
+/// of the first healthy node, and the nodes on another chain are refused.
+#[derive(Clone, Debug)]
+pub struct CkbRpcClientPool {
+    nodes: Arc<Vec<CkbNode>>,
+    genesis_hash: Arc<RwLock<Option<H256>>>,
+    // the index of the node all the requests are sent to, see `pinned`
+    pinned: Option<usize>,
+}
+
+#[async_trait]
+impl SyncAdapter for CkbRpcClientPool {
+    async fn pull_blocks(&self, block_numbers: Vec<BlockNumber>) -> Result<Vec<core::BlockView>> {
+        self.call(|client| client.pull_blocks(block_numbers.clone()))
+            .await
+    }
+}
+
+#[async_trait]
+impl CkbRpc for CkbRpcClientPool {
+    async fn local_node_info(&self) -> Result<LocalNode> {
+        self.call(|client| client.local_node_info()).await
+    }
+
+    async fn get_tip_block_number(&self) -> Result<u64> {
+        self.call(|client| client.get_tip_block_number()).await
+    }
+
+    async fn get_raw_tx_pool(&self, verbose: Option<bool>) -> Result<RawTxPool> {
+        self.call(|client| client.get_raw_tx_pool(verbose)).await
+    }
+
+    async fn get_tx_pool_info(&self) -> Result<TxPoolInfo> {
+        self.call(|client| client.get_tx_pool_info()).await
+    }
+
+    async fn get_transactions(
+        &self,
+        hashes: Vec<H256>,
+    ) -> Result<Vec<Option<TransactionWithStatus>>> {
+        self.call(|client| client.get_transactions(hashes.clone()))
+            .await
+    }
+
+    async fn get_blocks_by_number(
+        &self,
+        block_number: Vec<BlockNumber>,
+    ) -> Result<Vec<Option<BlockView>>> {
+        self.call(|client| client.get_blocks_by_number(block_number.clone()))
+            .await
+    }
+
+    async fn get_block_hash(&self, block_number: BlockNumber) -> Result<Option<H256>> {
+        self.call(|client| client.get_block_hash(block_number))
+            .await
+    }
+
+    async fn get_epoch_by_number(&self, epoch_number: Uint64) -> Result<EpochView> {
+        self.call(|client| client.get_epoch_by_number(epoch_number))
+            .await
+    }
+
+    async fn get_current_epoch(&self) -> Result<EpochView> {
+        self.call(|client| client.get_current_epoch()).await
+    }
+
+    async fn get_block(&self, block_hash: H256, use_hex_format: bool) -> Result<Option<BlockView>> {
+        self.call(|client| client.get_block(block_hash.clone(), use_hex_format))
+            .await
+    }
+
+    async fn get_consensus(&self) -> Result<Consensus> {
+        self.call(|client| client.get_consensus()).await
+    }
+}
+
+impl CkbRpcClientPool {
+    pub fn new(uris: Vec<String>) -> Self {
+        let nodes = uris
+            .into_iter()
+            .map(|uri| CkbNode {
+                client: CkbRpcClient::new(uri.clone()),
+                uri,
+                health: RwLock::new(NodeHealth::default()),
+            })
+            .collect();
+
+        CkbRpcClientPool {
+            nodes: Arc::new(nodes),
+            genesis_hash: Arc::new(RwLock::new(None)),
+            pinned: None,
+        }
+    }
+
+    /// A client sending all the requests to the best node at the moment, without failover.
+    /// The requests of one round, such as the tip and the epoch, are then answered by the
+    /// same node, so they are consistent. The round fails if the node fails, and the next
+    /// round is pinned to another node.
+    pub fn pinned(&self) -> Self {
+        let pinned = self
+            .ranked_nodes()
+            .first()
+            .and_then(|best| self.nodes.iter().position(|node| std::ptr::eq(node, *best)));
+
+        CkbRpcClientPool {
+            nodes: Arc::clone(&self.nodes),
+            genesis_hash: Arc::clone(&self.genesis_hash),
+            pinned,
+        }
+    }
+
+    pub fn uris(&self) -> Vec<String> {
+        self.nodes.iter().map(|node| node.uri.clone()).collect()
+    }
+
+    /// Set the genesis hash of the chain, such as the one in the database.
+    pub fn set_genesis_hash(&self, genesis_hash: H256) {
+        *self.genesis_hash.write() = Some(genesis_hash);
+    }
+
+    /// Check the tip, latency and genesis hash of all the nodes.
+    pub async fn check_health(&self) {
+        let handles = (0..self.nodes.len())
+            .map(|index| {
+                let pool = self.clone();
+                tokio::spawn(async move { pool.check_node(index).await })
+            })
+            .collect::<Vec<_>>();
+
+        // the results are handled in the configured order, so the genesis hash is taken
+        // from the first healthy node
+        for (index, handle) in handles.into_iter().enumerate() {
+            let result = match handle.await {
+                Ok(result) => result,
+                Err(err) => Err(err.into()),
+            };
+            self.update_health(index, result);
+        }
+    }
+
+    pub fn spawn_health_check(&self, interval: Duration) {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            loop {
+                sleep(interval).await;
+                pool.check_health().await;
+            }
+        });
+    }
+
+    async fn check_node(&self, index: usize) -> Result<(BlockNumber, Duration, H256)> {
+        let client = &self.nodes[index].client;
+        let start = Instant::now();
+        let tip = timeout(HEALTH_CHECK_TIMEOUT, client.get_tip_block_number()).await??;
+        let latency = start.elapsed();
+        let genesis_hash = timeout(HEALTH_CHECK_TIMEOUT, client.get_block_hash(0))
+            .await??
+            .ok_or_else(|| anyhow!("no genesis block"))?;
+
+        Ok((tip, latency, genesis_hash))
+    }
+
+    fn update_health(&self, index: usize, result: Result<(BlockNumber, Duration, H256)>) {
+        let node = &self.nodes[index];
+        let mut health = node.health.write();
+
+        match result {
+            Ok((tip, latency, genesis_hash)) => {
+                let expected = self
+                    .genesis_hash
+                    .write()
+                    .get_or_insert_with(|| genesis_hash.clone())
+                    .clone();
+                if genesis_hash != expected {
+                    if !health.refused {
+                        log::error!(
+                            "refuse ckb node {}, genesis hash {} mismatches {}",
+                            node.uri,
+                            genesis_hash,
+                            expected
+                        );
+                    }
+                    health.refused = true;
+                    health.tip = None;
+                    return;
+                }
+
+                health.refused = false;
+                health.tip = Some(tip);
+                health.latency = latency;
+            }
+
+            Err(err) => {
+                log::warn!("ckb node {} is unavailable, error: {}", node.uri, err);
+                health.tip = None;
+            }
+        }
+    }
+
+    /// The nodes in the order to route the requests. The nodes close to the highest tip
+    /// come first, ordered by latency, then the lagging nodes and the unavailable ones.
+    /// The refused nodes are excluded.
+    fn ranked_nodes(&self) -> Vec<&CkbNode> {
+        let healths = self
+            .nodes
+            .iter()
+            .map(|node| node.health.read().clone())
+            .collect::<Vec<_>>();
+        let highest_tip = healths.iter().filter_map(|health| health.tip).max();
+
+        let mut ranked = self
+            .nodes
+            .iter()
+            .zip(healths.into_iter())
+            .filter(|(_, health)| !health.refused)
+            .collect::<Vec<_>>();
+        // the sort is stable, so the unavailable nodes keep the configured order
+        ranked.sort_by_key(|(_, health)| match (health.tip, highest_tip) {
+            (Some(tip), Some(highest)) if tip + MAX_TIP_LAG >= highest => (0, 0, health.latency),
+            (Some(tip), Some(highest)) => (1, highest - tip, health.latency),
+            _ => (2, 0, Duration::default()),
+        });
+
+        ranked.into_iter().map(|(node, _)| node).collect()
+    }
+
+    /// Send the request to the nodes in order until one succeeds, or only to the pinned
+    /// node. A failed node is unavailable until the next health check.
+    async fn call<'a, T, F, Fut>(&'a self, request: F) -> Result<T>
+    where
+        F: Fn(&'a CkbRpcClient) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut last_error = anyhow!("no ckb node available");
+        let nodes = match self.pinned {
+            Some(index) => vec![&self.nodes[index]],
+            None => self.ranked_nodes(),
+        };
+
+        for node in nodes {
+            match request(&node.client).await {
+                Ok(res) => return Ok(res),
+                Err(err) => {
+                    log::warn!("request to ckb node {} failed, error: {}", node.uri, err);
+                    node.health.write().tip = None;
+                    last_error = err;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_health(pool: &CkbRpcClientPool, index: usize, tip: Option<u64>, latency_ms: u64) {
+        let mut health = pool.nodes[index].health.write();
+        health.tip = tip;
+        health.latency = Duration::from_millis(latency_ms);
+    }
+
+    #[test]
+    fn test_ranked_nodes() {
+        let uris = (0..5).map(|i| format!("http://node{}", i)).collect();
+        let pool = CkbRpcClientPool::new(uris);
+        set_health(&pool, 0, None, 0);
+        set_health(&pool, 1, Some(100), 50);
+        set_health(&pool, 2, Some(99), 10);
+        set_health(&pool, 3, Some(90), 1);
+        set_health(&pool, 4, Some(100), 1);
+        pool.nodes[4].health.write().refused = true;
+
+        let ranked = pool
+            .ranked_nodes()
+            .iter()
+            .map(|node| node.uri.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranked,
+            vec![
+                "http://node2",
+                "http://node1",
+                "http://node3",
+                "http://node0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_refuse_other_chain() {
+        let pool = CkbRpcClientPool::new(vec!["http://node0".into(), "http://node1".into()]);
+        pool.set_genesis_hash(H256::default());
+
+        pool.update_health(0, Ok((10, Duration::from_millis(1), H256([1u8; 32]))));
+        pool.update_health(1, Ok((10, Duration::from_millis(5), H256::default())));
+        assert!(pool.nodes[0].health.read().refused);
+        assert_eq!(pool.ranked_nodes().len(), 1);
+        assert_eq!(pool.ranked_nodes()[0].uri, "http://node1");
+    }
+
+    // fail the requests to the first node
+    async fn request(pool: &CkbRpcClientPool, client: &CkbRpcClient) -> Result<u64> {
+        if std::ptr::eq(client, &pool.nodes[0].client) {
+            Err(anyhow!("connection refused"))
+        } else {
+            Ok(10)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_failover() {
+        let pool = CkbRpcClientPool::new(vec!["http://node0".into(), "http://node1".into()]);
+        set_health(&pool, 0, Some(10), 1);
+        set_health(&pool, 1, Some(10), 5);
+
+        let res = pool.call(|client| request(&pool, client)).await.unwrap();
+        assert_eq!(res, 10);
+        // the failed node is ranked last until the next health check
+        assert!(pool.nodes[0].health.read().tip.is_none());
+        assert_eq!(pool.ranked_nodes()[0].uri, "http://node1");
+    }
+
+    #[tokio::test]
+    async fn test_call_pinned() {
+        let pool = CkbRpcClientPool::new(vec!["http://node0".into(), "http://node1".into()]);
+        set_health(&pool, 0, Some(10), 1);
+        set_health(&pool, 1, Some(10), 5);
+
+        let pinned = pool.pinned();
+        assert_eq!(pinned.pinned, Some(0));
+        // no failover to the other node
+        assert!(pinned.call(|client| request(&pool, client)).await.is_err());
+        assert!(pool.nodes[0].health.read().tip.is_none());
+
+        // the next round is pinned to the healthy node
+        let pinned = pool.pinned();
+        assert_eq!(pinned.pinned, Some(1));
+        let res = pinned.call(|client| request(&pool, client)).await.unwrap();
+        assert_eq!(res, 10);
+    }
+}
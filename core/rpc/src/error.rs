@@ -137,8 +137,29 @@ pub enum RpcErrorMessage {
     #[display(fmt = "Invalid outpoint")]
     InvalidOutPoint,
 
+    #[display(fmt = "Cannot find cheque cell")]
+    CannotFindChequeCell,
+
+    #[display(fmt = "The inputs are reserved by another transaction")]
+    InputsReserved,
+
+    #[display(fmt = "Cannot fit the transaction in the max block cycles or size")]
+    ExceedMaxBlockLimit,
+
+    #[display(fmt = "Cannot bump the fee, {}", _0)]
+    CannotBumpFee(String),
+
+    #[display(fmt = "Invalid partially signed transaction, {}", _0)]
+    InvalidPartiallySignedTransaction(String),
+
     #[display(fmt = "Overflow")]
     Overflow,
+
+    #[display(fmt = "Missing multisig script of {}", _0)]
+    MissingMultisigScript(String),
+
+    #[display(fmt = "Unsupport identity flag {}", _0)]
+    UnsupportIdentityFlag(u8),
 }
 
 impl std::error::Error for RpcErrorMessage {}
@@ -167,6 +188,8 @@ impl RpcErrorMessage {
             RpcErrorMessage::MissingScriptInfo(_) => -11020,
             RpcErrorMessage::InvalidScriptHash(_) => -11021,
             RpcErrorMessage::ParseAddressError(_) => -11022,
+            RpcErrorMessage::MissingMultisigScript(_) => -11023,
+            RpcErrorMessage::UnsupportIdentityFlag(_) => -11024,
 
             RpcErrorMessage::MissingConsumedInfo => -11020,
 
@@ -188,6 +211,15 @@ impl RpcErrorMessage {
 
             RpcErrorMessage::CannotFindUnlockedWithdrawingCell => -10110,
             RpcErrorMessage::InvalidOutPoint => -10111,
+
+            RpcErrorMessage::CannotFindChequeCell => -10120,
+
+            RpcErrorMessage::InputsReserved => -10130,
+
+            RpcErrorMessage::ExceedMaxBlockLimit => -10140,
+            RpcErrorMessage::CannotBumpFee(_) => -10150,
+
+            RpcErrorMessage::InvalidPartiallySignedTransaction(_) => -10160,
         }
     }
 }
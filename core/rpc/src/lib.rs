@@ -1,6 +1,7 @@
 #![allow(clippy::mutable_key_type, clippy::upper_case_acronyms)]
 
 pub mod ckb_client;
+pub mod ckb_client_pool;
 pub mod rpc_impl;
 pub mod types;
 
@@ -10,22 +11,32 @@ mod tests;
 
 use error::RpcResult;
 use types::{
-    indexer, indexer_legacy, AdjustAccountPayload, BlockInfo, DaoClaimPayload, DaoDepositPayload,
-    DaoWithdrawPayload, GetBalancePayload, GetBalanceResponse, GetBlockInfoPayload,
-    GetSpentTransactionPayload, GetTransactionInfoResponse, MercuryInfo, QueryTransactionsPayload,
-    SmartTransferPayload, TransactionCompletionResponse, TransferPayload, TxView,
+    indexer, indexer_legacy, AdjustAccountPayload, AdvanceQueryPayload, BatchTransferPayload,
+    BlockInfo, BumpFeePayload, ChequeClaimPayload, ChequeWithdrawPayload, ConsolidatePayload,
+    DaoClaimPayload, DaoDepositPayload, DaoWithdrawPayload, EstimateFeeRatePayload,
+    EstimateFeeRateResponse, GetBalancePayload, GetBalanceResponse, GetBlockInfoPayload,
+    GetSpentTransactionPayload, GetTransactionInfoResponse, JsonItem, MercuryInfo,
+    MergeSignaturesPayload, MultiAssetTransferPayload, PartiallySignedTransaction,
+    PreviewTransactionResponse, QueryResponse, QueryTransactionsPayload, RollbackInfo,
+    SmartTransferPayload, TransactionCompletionResponse, TransactionInfo, TransferPayload,
+    TxPoolInfo, TxView, VerifyTransactionResponse,
 };
 
 pub use ckb_client::CkbRpcClient;
-pub use rpc_impl::{MercuryRpcImpl, CURRENT_BLOCK_NUMBER, CURRENT_EPOCH_NUMBER, TX_POOL_CACHE};
+pub use ckb_client_pool::CkbRpcClientPool;
+pub use rpc_impl::{
+    notify_chain_event, resolve_pending_transactions, ChainEvent, MercuryRpcImpl, PendingTxCache,
+    CURRENT_BLOCK_NUMBER, CURRENT_EPOCH_NUMBER, PENDING_TX_CACHE, TX_POOL_CACHE,
+};
 
 use common::{PaginationResponse, Result};
 use core_storage::DBInfo;
-use core_synchronization::SyncAdapter;
+use core_synchronization::{SyncAdapter, SyncState};
 
 use async_trait::async_trait;
 use ckb_jsonrpc_types::{
-    BlockView, EpochView, LocalNode, RawTxPool, TransactionWithStatus, Uint64,
+    BlockView, Consensus, EpochView, LocalNode, RawTxPool, Transaction, TransactionView,
+    TransactionWithStatus, Uint64,
 };
 use ckb_types::{bytes::Bytes, core, core::BlockNumber, H160, H256};
 use jsonrpsee_proc_macros::rpc;
@@ -47,6 +58,12 @@ pub trait MercuryRpc {
         payload: QueryTransactionsPayload,
     ) -> RpcResult<PaginationResponse<TxView>>;
 
+    #[method(name = "advance_query")]
+    async fn advance_query(
+        &self,
+        payload: AdvanceQueryPayload,
+    ) -> RpcResult<PaginationResponse<QueryResponse>>;
+
     #[method(name = "build_adjust_account_transaction")]
     async fn build_adjust_account_transaction(
         &self,
@@ -65,6 +82,66 @@ pub trait MercuryRpc {
         payload: SmartTransferPayload,
     ) -> RpcResult<TransactionCompletionResponse>;
 
+    #[method(name = "build_multi_asset_transfer_transaction")]
+    async fn build_multi_asset_transfer_transaction(
+        &self,
+        payload: MultiAssetTransferPayload,
+    ) -> RpcResult<TransactionCompletionResponse>;
+
+    #[method(name = "build_batch_transfer_transactions")]
+    async fn build_batch_transfer_transactions(
+        &self,
+        payload: BatchTransferPayload,
+    ) -> RpcResult<Vec<TransactionCompletionResponse>>;
+
+    #[method(name = "build_consolidate_transaction")]
+    async fn build_consolidate_transaction(
+        &self,
+        payload: ConsolidatePayload,
+    ) -> RpcResult<Option<TransactionCompletionResponse>>;
+
+    #[method(name = "build_bump_fee_transaction")]
+    async fn build_bump_fee_transaction(
+        &self,
+        payload: BumpFeePayload,
+    ) -> RpcResult<TransactionCompletionResponse>;
+
+    #[method(name = "estimate_fee_rate")]
+    async fn estimate_fee_rate(
+        &self,
+        payload: EstimateFeeRatePayload,
+    ) -> RpcResult<EstimateFeeRateResponse>;
+
+    #[method(name = "verify_transaction")]
+    async fn verify_transaction(
+        &self,
+        transaction: Transaction,
+    ) -> RpcResult<VerifyTransactionResponse>;
+
+    #[method(name = "preview_transaction")]
+    async fn preview_transaction(
+        &self,
+        transaction: TransactionView,
+    ) -> RpcResult<PreviewTransactionResponse>;
+
+    #[method(name = "build_partially_signed_transaction")]
+    async fn build_partially_signed_transaction(
+        &self,
+        payload: TransactionCompletionResponse,
+    ) -> RpcResult<PartiallySignedTransaction>;
+
+    #[method(name = "merge_signatures")]
+    async fn merge_signatures(
+        &self,
+        payload: MergeSignaturesPayload,
+    ) -> RpcResult<PartiallySignedTransaction>;
+
+    #[method(name = "finalize_transaction")]
+    async fn finalize_transaction(
+        &self,
+        transaction: PartiallySignedTransaction,
+    ) -> RpcResult<Transaction>;
+
     #[method(name = "register_address")]
     async fn register_addresses(&self, addresses: Vec<String>) -> RpcResult<Vec<H160>>;
 
@@ -74,6 +151,12 @@ pub trait MercuryRpc {
     #[method(name = "get_db_info")]
     fn get_db_info(&self) -> RpcResult<DBInfo>;
 
+    #[method(name = "get_sync_state")]
+    fn get_sync_state(&self) -> RpcResult<SyncState>;
+
+    #[method(name = "release_reserved_inputs")]
+    fn release_reserved_inputs(&self, tx_hash: H256) -> RpcResult<u64>;
+
     #[method(name = "build_dao_deposit_transaction")]
     async fn build_dao_deposit_transaction(
         &self,
@@ -92,6 +175,18 @@ pub trait MercuryRpc {
         payload: DaoClaimPayload,
     ) -> RpcResult<TransactionCompletionResponse>;
 
+    #[method(name = "build_cheque_claim_transaction")]
+    async fn build_cheque_claim_transaction(
+        &self,
+        payload: ChequeClaimPayload,
+    ) -> RpcResult<TransactionCompletionResponse>;
+
+    #[method(name = "build_cheque_withdraw_transaction")]
+    async fn build_cheque_withdraw_transaction(
+        &self,
+        payload: ChequeWithdrawPayload,
+    ) -> RpcResult<TransactionCompletionResponse>;
+
     #[method(name = "get_spent_transaction")]
     async fn get_spent_transaction(&self, payload: GetSpentTransactionPayload)
         -> RpcResult<TxView>;
@@ -151,6 +246,30 @@ pub trait MercuryRpc {
     ) -> RpcResult<Vec<indexer_legacy::CellTransaction>>;
 }
 
+#[rpc(server)]
+pub trait MercurySubscription {
+    #[subscription(
+        name = "subscribe_new_tip",
+        unsub = "unsubscribe_new_tip",
+        item = BlockInfo
+    )]
+    fn subscribe_new_tip(&self);
+
+    #[subscription(
+        name = "subscribe_rollback",
+        unsub = "unsubscribe_rollback",
+        item = RollbackInfo
+    )]
+    fn subscribe_rollback(&self);
+
+    #[subscription(
+        name = "subscribe_records",
+        unsub = "unsubscribe_records",
+        item = TransactionInfo
+    )]
+    fn subscribe_records(&self, item: JsonItem);
+}
+
 #[async_trait]
 pub trait CkbRpc: Sync + Send + 'static {
     async fn local_node_info(&self) -> Result<LocalNode>;
@@ -159,6 +278,8 @@ pub trait CkbRpc: Sync + Send + 'static {
 
     async fn get_raw_tx_pool(&self, verbose: Option<bool>) -> Result<RawTxPool>;
 
+    async fn get_tx_pool_info(&self) -> Result<TxPoolInfo>;
+
     async fn get_transactions(
         &self,
         hashes: Vec<H256>,
@@ -169,11 +290,15 @@ pub trait CkbRpc: Sync + Send + 'static {
         block_number: Vec<BlockNumber>,
     ) -> Result<Vec<Option<BlockView>>>;
 
+    async fn get_block_hash(&self, block_number: BlockNumber) -> Result<Option<H256>>;
+
     async fn get_epoch_by_number(&self, epoch_number: Uint64) -> Result<EpochView>;
 
     async fn get_current_epoch(&self) -> Result<EpochView>;
 
     async fn get_block(&self, block_hash: H256, use_hex_format: bool) -> Result<Option<BlockView>>;
+
+    async fn get_consensus(&self) -> Result<Consensus>;
 }
 
 #[async_trait]
@@ -1,56 +1,89 @@
 mod adjust_account;
+pub(crate) mod batch_transfer;
 mod build_tx;
+pub(crate) mod bump_fee;
+pub(crate) mod coin_selector;
+mod consolidate;
 mod consts;
+pub(crate) mod fee_rate;
 mod operation;
+pub(crate) mod partial_sign;
+pub(crate) mod preview;
 mod query;
+mod reservation;
+mod subscription;
+mod tx_pool;
 pub(crate) mod utils;
+mod verify;
 
 pub use crate::rpc_impl::consts::{
-    ckb, BYTE_SHANNONS, CHEQUE_CELL_CAPACITY, DEFAULT_FEE_RATE, INIT_ESTIMATE_FEE, MAX_ITEM_NUM,
-    MIN_CKB_CAPACITY, MIN_DAO_CAPACITY, MIN_DAO_LOCK_PERIOD, STANDARD_SUDT_CAPACITY,
-    WITHDRAWING_DAO_CELL_OCCUPIED_CAPACITY,
+    ckb, BLOCK_RESERVED_BYTES, BYTE_SHANNONS, CHAIN_EVENT_CHANNEL_SIZE, CHEQUE_CELL_CAPACITY,
+    DEFAULT_FEE_RATE, ESTIMATED_LOCK_CYCLES, ESTIMATED_UDT_CELL_CYCLES, ESTIMATED_UDT_CYCLES,
+    FEE_RATE_ESTIMATE_BLOCKS, FEE_RATE_PAGE_LIMIT, INIT_ESTIMATE_FEE, MAX_FEE_RATE_ESTIMATE_BLOCKS,
+    MAX_ITEM_NUM, MAX_RESERVE_RETRY, MIN_CKB_CAPACITY, MIN_DAO_CAPACITY, MIN_DAO_LOCK_PERIOD,
+    STANDARD_SUDT_CAPACITY, WITHDRAWING_DAO_CELL_OCCUPIED_CAPACITY,
 };
+pub use crate::rpc_impl::fee_rate::FeeRateCache;
+pub use crate::rpc_impl::reservation::InputReservations;
+pub use crate::rpc_impl::subscription::{notify_chain_event, ChainEvent, SubscriptionEvent};
+pub use crate::rpc_impl::tx_pool::{resolve_pending_transactions, PendingTxCache};
 
 use crate::error::{RpcError, RpcErrorMessage, RpcResult};
-use crate::rpc_impl::build_tx::calculate_tx_size;
+pub(crate) use crate::rpc_impl::build_tx::{
+    calculate_fee, calculate_tx_size, cheque_withdraw_fee_index,
+};
+use crate::rpc_impl::verify::load_consensus;
 use crate::types::{
-    indexer, indexer_legacy, AdjustAccountPayload, BlockInfo, DaoClaimPayload, DaoDepositPayload,
-    DaoWithdrawPayload, GetBalancePayload, GetBalanceResponse, GetBlockInfoPayload,
-    GetSpentTransactionPayload, GetTransactionInfoResponse, MercuryInfo, QueryTransactionsPayload,
-    SmartTransferPayload, TransactionCompletionResponse, TransferPayload, TxView,
+    indexer, indexer_legacy, AdjustAccountPayload, AdvanceQueryPayload, BatchTransferPayload,
+    BlockInfo, BumpFeePayload, ChequeClaimPayload, ChequeWithdrawPayload, CoinSelection,
+    ConsolidatePayload, DaoClaimPayload, DaoDepositPayload, DaoWithdrawPayload,
+    EstimateFeeRatePayload, EstimateFeeRateResponse, GetBalancePayload, GetBalanceResponse,
+    GetBlockInfoPayload, GetSpentTransactionPayload, GetTransactionInfoResponse, MercuryInfo,
+    MergeSignaturesPayload, MultiAssetTransferPayload, PartiallySignedTransaction,
+    PreviewTransactionResponse, QueryResponse, QueryTransactionsPayload, SmartTransferPayload,
+    TransactionCompletionResponse, TransferPayload, TxView, VerifyTransactionResponse,
 };
 use crate::{CkbRpc, MercuryRpcServer};
 
 use common::utils::{parse_address, ScriptInfo};
 use common::{
     anyhow, hash::blake2b_160, Address, AddressPayload, CodeHashIndex, Context, NetworkType,
-    PaginationResponse, Result, ACP, CHEQUE, DAO, SECP256K1, SUDT,
+    PaginationResponse, Result, ACP, CHEQUE, DAO, MULTISIG, OMNI_LOCK, SECP256K1, SUDT, XUDT,
 };
-use core_storage::{DBInfo, RelationalStorage};
+use core_storage::{DBInfo, Storage};
+use core_synchronization::{sync_state, SyncState};
 
 use arc_swap::ArcSwap;
 use async_trait::async_trait;
-use ckb_jsonrpc_types::Uint64;
+use ckb_chain_spec::consensus::Consensus;
+use ckb_jsonrpc_types::{Transaction, TransactionView, Uint64};
 use ckb_types::core::{BlockNumber, RationalU256};
 use ckb_types::{bytes::Bytes, packed, prelude::*, H160, H256};
 use clap::crate_version;
-use dashmap::DashMap;
 use jsonrpsee_http_server::types::Error;
 use parking_lot::RwLock;
+use tokio::sync::broadcast;
 
 use std::collections::{HashMap, HashSet};
-use std::{str::FromStr, sync::Arc, thread::ThreadId};
+use std::time::Duration;
+use std::{str::FromStr, sync::Arc};
 
 lazy_static::lazy_static! {
     pub static ref TX_POOL_CACHE: RwLock<HashSet<packed::OutPoint>> = RwLock::new(HashSet::new());
+    pub static ref PENDING_TX_CACHE: RwLock<PendingTxCache> = RwLock::new(PendingTxCache::default());
     pub static ref CURRENT_BLOCK_NUMBER: ArcSwap<BlockNumber> = ArcSwap::from_pointee(0u64);
     pub static ref CURRENT_EPOCH_NUMBER: ArcSwap<RationalU256> = ArcSwap::from_pointee(RationalU256::zero());
-    pub static ref ACP_USED_CACHE: DashMap<ThreadId, Vec<packed::OutPoint>> = DashMap::new();
+    pub static ref RESERVED_INPUTS: RwLock<InputReservations> = RwLock::new(InputReservations::default());
     pub static ref SECP256K1_CODE_HASH: ArcSwap<H256> = ArcSwap::from_pointee(H256::default());
+    pub static ref MULTISIG_CODE_HASH: ArcSwap<H256> = ArcSwap::from_pointee(H256::default());
     pub static ref SUDT_CODE_HASH: ArcSwap<H256> = ArcSwap::from_pointee(H256::default());
+    pub static ref XUDT_CODE_HASH: ArcSwap<H256> = ArcSwap::from_pointee(H256::default());
+    pub static ref OMNI_LOCK_CODE_HASH: ArcSwap<H256> = ArcSwap::from_pointee(H256::default());
     pub static ref ACP_CODE_HASH: ArcSwap<H256> = ArcSwap::from_pointee(H256::default());
     pub static ref CHEQUE_CODE_HASH: ArcSwap<H256> = ArcSwap::from_pointee(H256::default());
     pub static ref DAO_CODE_HASH: ArcSwap<H256> = ArcSwap::from_pointee(H256::default());
+    pub static ref CHAIN_EVENT_SENDER: broadcast::Sender<ChainEvent> = broadcast::channel(CHAIN_EVENT_CHANNEL_SIZE).0;
+    pub static ref SUBSCRIPTION_EVENT_SENDER: broadcast::Sender<SubscriptionEvent> = broadcast::channel(CHAIN_EVENT_CHANNEL_SIZE).0;
 }
 
 macro_rules! rpc_impl {
@@ -65,13 +98,18 @@ macro_rules! rpc_impl {
     }};
 }
 
+#[derive(Clone)]
 pub struct MercuryRpcImpl<C> {
-    storage: RelationalStorage,
+    storage: Arc<dyn Storage + Send + Sync>,
     builtin_scripts: HashMap<String, ScriptInfo>,
     ckb_client: C,
     network_type: NetworkType,
     cheque_timeout: RationalU256,
     cellbase_maturity: RationalU256,
+    coin_selection: CoinSelection,
+    reservation_ttl: Duration,
+    fee_rate_cache: Arc<RwLock<FeeRateCache>>,
+    consensus: Arc<Consensus>,
 }
 
 #[async_trait]
@@ -95,6 +133,13 @@ impl<C: CkbRpc> MercuryRpcServer for MercuryRpcImpl<C> {
         rpc_impl!(self, inner_query_transactions, payload)
     }
 
+    async fn advance_query(
+        &self,
+        payload: AdvanceQueryPayload,
+    ) -> RpcResult<PaginationResponse<QueryResponse>> {
+        rpc_impl!(self, inner_advance_query, payload)
+    }
+
     async fn build_adjust_account_transaction(
         &self,
         payload: AdjustAccountPayload,
@@ -116,6 +161,76 @@ impl<C: CkbRpc> MercuryRpcServer for MercuryRpcImpl<C> {
         rpc_impl!(self, inner_build_smart_transfer_transaction, payload)
     }
 
+    async fn build_multi_asset_transfer_transaction(
+        &self,
+        payload: MultiAssetTransferPayload,
+    ) -> RpcResult<TransactionCompletionResponse> {
+        rpc_impl!(self, inner_build_multi_asset_transfer_transaction, payload)
+    }
+
+    async fn build_batch_transfer_transactions(
+        &self,
+        payload: BatchTransferPayload,
+    ) -> RpcResult<Vec<TransactionCompletionResponse>> {
+        rpc_impl!(self, inner_build_batch_transfer_transactions, payload)
+    }
+
+    async fn build_consolidate_transaction(
+        &self,
+        payload: ConsolidatePayload,
+    ) -> RpcResult<Option<TransactionCompletionResponse>> {
+        rpc_impl!(self, inner_build_consolidate_transaction, payload)
+    }
+
+    async fn build_bump_fee_transaction(
+        &self,
+        payload: BumpFeePayload,
+    ) -> RpcResult<TransactionCompletionResponse> {
+        rpc_impl!(self, inner_build_bump_fee_transaction, payload)
+    }
+
+    async fn estimate_fee_rate(
+        &self,
+        payload: EstimateFeeRatePayload,
+    ) -> RpcResult<EstimateFeeRateResponse> {
+        rpc_impl!(self, inner_estimate_fee_rate, payload)
+    }
+
+    async fn verify_transaction(
+        &self,
+        transaction: Transaction,
+    ) -> RpcResult<VerifyTransactionResponse> {
+        rpc_impl!(self, inner_verify_transaction, transaction)
+    }
+
+    async fn preview_transaction(
+        &self,
+        transaction: TransactionView,
+    ) -> RpcResult<PreviewTransactionResponse> {
+        rpc_impl!(self, inner_preview_transaction, transaction)
+    }
+
+    async fn build_partially_signed_transaction(
+        &self,
+        payload: TransactionCompletionResponse,
+    ) -> RpcResult<PartiallySignedTransaction> {
+        rpc_impl!(self, inner_build_partially_signed_transaction, payload)
+    }
+
+    async fn merge_signatures(
+        &self,
+        payload: MergeSignaturesPayload,
+    ) -> RpcResult<PartiallySignedTransaction> {
+        rpc_impl!(self, inner_merge_signatures, payload)
+    }
+
+    async fn finalize_transaction(
+        &self,
+        transaction: PartiallySignedTransaction,
+    ) -> RpcResult<Transaction> {
+        rpc_impl!(self, inner_finalize_transaction, transaction)
+    }
+
     async fn register_addresses(&self, addresses: Vec<String>) -> RpcResult<Vec<H160>> {
         let mut inputs: Vec<(H160, String)> = vec![];
         for addr_str in addresses {
@@ -149,6 +264,15 @@ impl<C: CkbRpc> MercuryRpcServer for MercuryRpcImpl<C> {
             .map_err(|err| Error::from(RpcError::from(err)))
     }
 
+    fn get_sync_state(&self) -> RpcResult<SyncState> {
+        Ok(sync_state())
+    }
+
+    fn release_reserved_inputs(&self, tx_hash: H256) -> RpcResult<u64> {
+        self.inner_release_reserved_inputs(tx_hash)
+            .map_err(|err| Error::from(RpcError::from(err)))
+    }
+
     async fn build_dao_deposit_transaction(
         &self,
         payload: DaoDepositPayload,
@@ -170,6 +294,20 @@ impl<C: CkbRpc> MercuryRpcServer for MercuryRpcImpl<C> {
         rpc_impl!(self, inner_build_dao_claim_transaction, payload)
     }
 
+    async fn build_cheque_claim_transaction(
+        &self,
+        payload: ChequeClaimPayload,
+    ) -> RpcResult<TransactionCompletionResponse> {
+        rpc_impl!(self, inner_build_cheque_claim_transaction, payload)
+    }
+
+    async fn build_cheque_withdraw_transaction(
+        &self,
+        payload: ChequeWithdrawPayload,
+    ) -> RpcResult<TransactionCompletionResponse> {
+        rpc_impl!(self, inner_build_cheque_withdraw_transaction, payload)
+    }
+
     async fn get_spent_transaction(
         &self,
         payload: GetSpentTransactionPayload,
@@ -275,13 +413,15 @@ impl<C: CkbRpc> MercuryRpcServer for MercuryRpcImpl<C> {
 }
 
 impl<C: CkbRpc> MercuryRpcImpl<C> {
-    pub fn new(
-        storage: RelationalStorage,
+    pub fn new<S: Storage + Send + Sync + 'static>(
+        storage: S,
         builtin_scripts: HashMap<String, ScriptInfo>,
         ckb_client: C,
         network_type: NetworkType,
         cheque_timeout: RationalU256,
         cellbase_maturity: RationalU256,
+        coin_selection: CoinSelection,
+        reservation_ttl: Duration,
     ) -> Self {
         SECP256K1_CODE_HASH.swap(Arc::new(
             builtin_scripts
@@ -301,6 +441,17 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 .code_hash()
                 .unpack(),
         ));
+        // xUDT, multisig and omni-lock are optional, they are enabled only if configured in
+        // the builtin scripts.
+        if let Some(xudt) = builtin_scripts.get(XUDT) {
+            XUDT_CODE_HASH.swap(Arc::new(xudt.script.code_hash().unpack()));
+        }
+        if let Some(multisig) = builtin_scripts.get(MULTISIG) {
+            MULTISIG_CODE_HASH.swap(Arc::new(multisig.script.code_hash().unpack()));
+        }
+        if let Some(omni_lock) = builtin_scripts.get(OMNI_LOCK) {
+            OMNI_LOCK_CODE_HASH.swap(Arc::new(omni_lock.script.code_hash().unpack()));
+        }
         ACP_CODE_HASH.swap(Arc::new(
             builtin_scripts
                 .get(ACP)
@@ -330,12 +481,16 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         ));
 
         MercuryRpcImpl {
-            storage,
+            storage: Arc::new(storage),
             builtin_scripts,
             ckb_client,
             network_type,
             cheque_timeout,
             cellbase_maturity,
+            coin_selection,
+            reservation_ttl,
+            fee_rate_cache: Arc::new(RwLock::new(FeeRateCache::default())),
+            consensus: Arc::new(load_consensus(network_type)),
         }
     }
 }
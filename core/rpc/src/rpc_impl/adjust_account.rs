@@ -1,7 +1,7 @@
 use crate::error::{InnerResult, RpcErrorMessage};
-use crate::rpc_impl::{calculate_tx_size, ckb, utils, INIT_ESTIMATE_FEE};
+use crate::rpc_impl::{calculate_fee, calculate_tx_size, ckb, utils, INIT_ESTIMATE_FEE};
 use crate::rpc_impl::{
-    ACP_CODE_HASH, BYTE_SHANNONS, DEFAULT_FEE_RATE, MIN_CKB_CAPACITY, SECP256K1_CODE_HASH,
+    ACP_CODE_HASH, BYTE_SHANNONS, MAX_RESERVE_RETRY, MIN_CKB_CAPACITY, SECP256K1_CODE_HASH,
     STANDARD_SUDT_CAPACITY,
 };
 use crate::types::{
@@ -11,8 +11,8 @@ use crate::types::{
 use crate::{CkbRpc, MercuryRpcImpl};
 
 use common::hash::blake2b_256_to_160;
-use common::utils::{decode_udt_amount, encode_udt_amount};
-use common::{Address, AddressPayload, Context, DetailedCell, ACP, SECP256K1, SUDT};
+use common::utils::{decode_udt_amount, encode_udt_amount, update_udt_amount};
+use common::{Address, AddressPayload, Context, DetailedCell, ACP, SECP256K1};
 use common_logger::tracing_async;
 
 use ckb_types::core::TransactionView;
@@ -34,7 +34,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
 
         let account_number = payload.account_number.unwrap_or(1) as usize;
         let extra_ckb = payload.extra_ckb.unwrap_or_else(|| ckb(1));
-        let fee_rate = payload.fee_rate.unwrap_or(DEFAULT_FEE_RATE);
+        let fee_rate = self.resolve_fee_rate(ctx.clone(), payload.fee_rate).await?;
         let item: Item = payload.item.clone().try_into()?;
         let from = parse_from(payload.from.clone())?;
 
@@ -54,6 +54,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             )
             .await?;
         let live_acps_len = live_acps.len();
+        let udt_script_name = utils::udt_script_name(&payload.asset_info);
 
         if live_acps_len == account_number {
             return Ok(None);
@@ -67,6 +68,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             .await?;
 
         if live_acps_len < account_number {
+            let mut reserve_retry = 0;
             loop {
                 let (tx_view, signature_actions, change_cell_index) = self
                     .build_create_acp_transaction_fixed_fee(
@@ -74,16 +76,14 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         from.clone(),
                         account_number - live_acps_len,
                         sudt_type_script.clone(),
+                        udt_script_name,
                         item.clone(),
                         extra_ckb,
                         estimate_fee,
                     )
                     .await?;
                 let tx_size = calculate_tx_size(tx_view.clone()) as u64;
-                let mut actual_fee = fee_rate.saturating_mul(tx_size) / 1000;
-                if actual_fee * 1000 < fee_rate.saturating_mul(tx_size) {
-                    actual_fee += 1;
-                }
+                let actual_fee = calculate_fee(fee_rate, tx_size);
                 if estimate_fee < actual_fee {
                     estimate_fee += BYTE_SHANNONS;
                     continue;
@@ -96,19 +96,56 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     )?;
                     let adjust_response =
                         TransactionCompletionResponse::new(tx_view, signature_actions);
+                    if payload.dry_run {
+                        return self
+                            .dry_run_transaction(ctx.clone(), adjust_response, &[])
+                            .await
+                            .map(Some);
+                    }
+                    if !self.reserve_inputs(&adjust_response) {
+                        reserve_retry += 1;
+                        if reserve_retry > MAX_RESERVE_RETRY {
+                            return Err(RpcErrorMessage::InputsReserved);
+                        }
+                        continue;
+                    }
                     return Ok(Some(adjust_response));
                 }
             }
         } else {
-            let res = self
-                .build_collect_asset_transaction_fixed_fee(
-                    live_acps,
-                    live_acps_len - account_number,
-                    fee_rate,
-                )
-                .await?;
-
-            Ok(Some(TransactionCompletionResponse::new(res.0, res.1)))
+            let mut reserve_retry = 0;
+            loop {
+                // the acp cells reserved by a concurrent build are not collected
+                let acp_cells = live_acps
+                    .iter()
+                    .filter(|cell| !self.is_in_cache(&cell.out_point))
+                    .cloned()
+                    .collect();
+                let res = self
+                    .build_collect_asset_transaction_fixed_fee(
+                        acp_cells,
+                        live_acps_len - account_number,
+                        udt_script_name,
+                        fee_rate,
+                    )
+                    .await?;
+
+                let adjust_response = TransactionCompletionResponse::new(res.0, res.1);
+                if payload.dry_run {
+                    return self
+                        .dry_run_transaction(ctx.clone(), adjust_response, &[])
+                        .await
+                        .map(Some);
+                }
+                if !self.reserve_inputs(&adjust_response) {
+                    reserve_retry += 1;
+                    if reserve_retry > MAX_RESERVE_RETRY {
+                        return Err(RpcErrorMessage::InputsReserved);
+                    }
+                    continue;
+                }
+                return Ok(Some(adjust_response));
+            }
         }
     }
 
@@ -119,6 +156,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         from: Vec<Item>,
         acp_need_count: usize,
         sudt_type_script: packed::Script,
+        udt_script_name: &str,
         item: Item,
         extra_ckb: u64,
         fee: u64,
@@ -158,11 +196,12 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             &mut script_set,
             &mut signature_actions,
             &mut input_index,
+            self.coin_selection,
         )
         .await?;
 
         script_set.insert(ACP.to_string());
-        script_set.insert(SUDT.to_string());
+        script_set.insert(udt_script_name.to_string());
 
         let change_cell = {
             let lock_args = self.get_secp_lock_args_by_item(from[0].clone())?;
@@ -233,6 +272,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         &self,
         mut acp_cells: Vec<DetailedCell>,
         acp_consume_count: usize,
+        udt_script_name: &str,
         fee_rate: u64,
     ) -> InnerResult<(ckb_jsonrpc_types::TransactionView, Vec<SignatureAction>)> {
         let acp_need = acp_consume_count + 1;
@@ -272,16 +312,16 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             input_udt_sum += amount;
         }
 
-        let output = output
+        let output_data = Bytes::from(update_udt_amount(&output.cell_data, input_udt_sum));
+        let output_cell = output
             .cell_output
             .as_builder()
             .capacity((input_capacity_sum).pack())
             .build();
-        let output_data = Bytes::from(input_udt_sum.to_le_bytes().to_vec());
 
         let mut script_set = HashSet::new();
         script_set.insert(SECP256K1.to_string());
-        script_set.insert(SUDT.to_string());
+        script_set.insert(udt_script_name.to_string());
         script_set.insert(ACP.to_string());
 
         let address = Address::new(
@@ -314,7 +354,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
 
         let (tx_view, signature_actions) = self.prebuild_tx_complete(
             inputs,
-            vec![output],
+            vec![output_cell],
             vec![output_data.pack()],
             script_set,
             vec![],
@@ -0,0 +1,521 @@
+use crate::error::{InnerResult, RpcErrorMessage};
+use crate::rpc_impl::utils::{add_signature_action, address_to_identity};
+use crate::rpc_impl::{
+    calculate_fee, calculate_tx_size, utils, BLOCK_RESERVED_BYTES, CHEQUE_CELL_CAPACITY,
+    ESTIMATED_LOCK_CYCLES, ESTIMATED_UDT_CELL_CYCLES, ESTIMATED_UDT_CYCLES, INIT_ESTIMATE_FEE,
+    MAX_ITEM_NUM, MAX_RESERVE_RETRY, MIN_CKB_CAPACITY, STANDARD_SUDT_CAPACITY,
+};
+use crate::types::{
+    AssetInfo, AssetType, BatchTransferPayload, HashAlgorithm, Item, SignAlgorithm,
+    SignatureAction, Source, TransactionCompletionResponse,
+};
+use crate::{CkbRpc, MercuryRpcImpl};
+
+use common::hash::blake2b_256_to_160;
+use common::{Address, Context, CHEQUE, SECP256K1};
+use common_logger::tracing_async;
+
+use ckb_types::core::{ScriptHashType, TransactionView};
+use ckb_types::{packed, prelude::*};
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// An output paying to a recipient of a batch transfer.
+pub(crate) struct BatchRecipient {
+    output: packed::CellOutput,
+    data: packed::Bytes,
+    capacity: u64,
+    udt_amount: u128,
+}
+
+impl BatchRecipient {
+    /// The bytes that the output adds to a transaction, including the offsets of the
+    /// outputs and the outputs data.
+    fn tx_size(&self) -> u64 {
+        (self.output.as_slice().len() + self.data.as_slice().len() + 8) as u64
+    }
+}
+
+pub(crate) struct BlockLimit {
+    pub(crate) max_bytes: u64,
+    pub(crate) max_cycles: u64,
+}
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    #[tracing_async]
+    pub(crate) async fn inner_build_batch_transfer_transactions(
+        &self,
+        ctx: Context,
+        payload: BatchTransferPayload,
+    ) -> InnerResult<Vec<TransactionCompletionResponse>> {
+        if payload.from.is_empty() || payload.to.is_empty() {
+            return Err(RpcErrorMessage::NeedAtLeastOneFromAndOneTo);
+        }
+        if payload.from.len() > MAX_ITEM_NUM {
+            return Err(RpcErrorMessage::ExceedMaxItemNum);
+        }
+
+        let consensus = self
+            .ckb_client
+            .get_consensus()
+            .await
+            .map_err(|err| RpcErrorMessage::CkbClientError(err.to_string()))?;
+        let max_block_bytes: u64 = consensus.max_block_bytes.into();
+        let limit = BlockLimit {
+            max_bytes: max_block_bytes.saturating_sub(BLOCK_RESERVED_BYTES),
+            max_cycles: consensus.max_block_cycles.into(),
+        };
+        let recipients = self.build_batch_recipients(ctx.clone(), &payload).await?;
+
+        let fee_rate = self.resolve_fee_rate(ctx.clone(), payload.fee_rate).await?;
+        let mut estimate_fee = INIT_ESTIMATE_FEE;
+        let mut reserve_retry = 0;
+
+        loop {
+            let (responses, actual_fee) = self
+                .prebuild_batch_transfer_transactions(
+                    ctx.clone(),
+                    &payload,
+                    &recipients,
+                    &limit,
+                    fee_rate,
+                    estimate_fee,
+                )
+                .await?;
+
+            // the pooled capacity cannot afford the fee of the whole chain, pool again
+            if estimate_fee < actual_fee {
+                estimate_fee = actual_fee + INIT_ESTIMATE_FEE;
+                continue;
+            }
+
+            if payload.dry_run {
+                let mut prev_txs = vec![];
+                let mut verified = vec![];
+                for response in responses.into_iter() {
+                    let tx_view =
+                        packed::Transaction::from(response.tx_view.inner.clone()).into_view();
+                    verified.push(
+                        self.dry_run_transaction(ctx.clone(), response, &prev_txs)
+                            .await?,
+                    );
+                    prev_txs.push(tx_view);
+                }
+                return Ok(verified);
+            }
+
+            // only the first transaction spends the live cells, the following ones spend the
+            // change of the previous one
+            if !self.reserve_inputs(&responses[0]) {
+                reserve_retry += 1;
+                if reserve_retry > MAX_RESERVE_RETRY {
+                    return Err(RpcErrorMessage::InputsReserved);
+                }
+                continue;
+            }
+            return Ok(responses);
+        }
+    }
+
+    pub(crate) async fn build_batch_recipients(
+        &self,
+        ctx: Context,
+        payload: &BatchTransferPayload,
+    ) -> InnerResult<Vec<BatchRecipient>> {
+        let (udt_type_script, sender_address) = if payload.asset_info.asset_type == AssetType::CKB {
+            (None, None)
+        } else {
+            let type_script = self
+                .build_sudt_type_script(
+                    ctx.clone(),
+                    blake2b_256_to_160(&payload.asset_info.udt_hash),
+                )
+                .await?;
+            let item = Item::Identity(address_to_identity(&payload.from[0])?);
+            (
+                Some(type_script),
+                Some(self.get_secp_address_by_item(item)?),
+            )
+        };
+
+        let mut recipients = vec![];
+        for to in &payload.to {
+            let amount = to.amount.parse::<u128>().map_err(|_| {
+                RpcErrorMessage::InvalidRpcParams(
+                    "To amount should be a valid u128 number".to_string(),
+                )
+            })?;
+            if amount == 0 {
+                return Err(RpcErrorMessage::TransferAmountMustPositive);
+            }
+
+            let (mut outputs, mut cells_data) = (vec![], vec![]);
+            let (capacity, udt_amount) = match (&udt_type_script, &sender_address) {
+                (Some(type_script), Some(sender_address)) => {
+                    let receiver_address = Address::from_str(&to.address)
+                        .map_err(RpcErrorMessage::InvalidRpcParams)?;
+                    if !receiver_address.is_secp256k1() {
+                        return Err(RpcErrorMessage::InvalidRpcParams(
+                            "Every to address should be secp/256k1 address".to_string(),
+                        ));
+                    }
+                    let cheque_args =
+                        utils::build_cheque_args(receiver_address, sender_address.clone());
+                    let cheque_lock = self
+                        .get_script_builder(CHEQUE)?
+                        .args(cheque_args)
+                        .hash_type(ScriptHashType::Type.into())
+                        .build();
+                    self.build_cell_for_output(
+                        CHEQUE_CELL_CAPACITY,
+                        cheque_lock,
+                        Some(type_script.clone()),
+                        Some(amount),
+                        &mut outputs,
+                        &mut cells_data,
+                    )?;
+                    (CHEQUE_CELL_CAPACITY, amount)
+                }
+                _ => {
+                    let capacity = u64::try_from(amount).map_err(|_| RpcErrorMessage::Overflow)?;
+                    if capacity < MIN_CKB_CAPACITY {
+                        return Err(RpcErrorMessage::RequiredCKBLessThanMin);
+                    }
+                    let secp_address =
+                        self.get_secp_address_by_item(Item::Address(to.address.to_owned()))?;
+                    self.build_cell_for_output(
+                        capacity,
+                        secp_address.payload().into(),
+                        None,
+                        None,
+                        &mut outputs,
+                        &mut cells_data,
+                    )?;
+                    (capacity, 0)
+                }
+            };
+
+            recipients.push(BatchRecipient {
+                output: outputs.remove(0),
+                data: cells_data.remove(0),
+                capacity,
+                udt_amount,
+            });
+        }
+
+        Ok(recipients)
+    }
+
+    /// Build the chain of the batch transactions, and return them with the sum of their fee.
+    /// The first transaction pools the live cells for all the recipients and the fee of
+    /// the whole chain, the change of each transaction is spent by the next one.
+    pub(crate) async fn prebuild_batch_transfer_transactions(
+        &self,
+        ctx: Context,
+        payload: &BatchTransferPayload,
+        recipients: &[BatchRecipient],
+        limit: &BlockLimit,
+        fee_rate: u64,
+        estimate_fee: u64,
+    ) -> InnerResult<(Vec<TransactionCompletionResponse>, u64)> {
+        let coin_selection = payload.coin_selection.unwrap_or(self.coin_selection);
+        let is_udt = payload.asset_info.asset_type != AssetType::CKB;
+        let required_ckb = recipients
+            .iter()
+            .try_fold(0u64, |acc, r| acc.checked_add(r.capacity))
+            .ok_or(RpcErrorMessage::Overflow)?;
+        let required_udt = recipients
+            .iter()
+            .try_fold(0u128, |acc, r| acc.checked_add(r.udt_amount))
+            .ok_or(RpcErrorMessage::Overflow)?;
+        let udt_change_capacity = if is_udt { STANDARD_SUDT_CAPACITY } else { 0 };
+
+        let mut from_items = vec![];
+        for address in &payload.from {
+            from_items.push(Item::Identity(address_to_identity(address)?));
+        }
+
+        let mut script_set = HashSet::new();
+        let (mut outputs, mut cells_data) = (vec![], vec![]);
+        let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
+        let mut input_index = 0;
+        let mut inputs = vec![];
+
+        // pool the live cells for the whole chain
+        let mut udt_remaining = 0u128;
+        let udt_type_script = if is_udt {
+            script_set.insert(utils::udt_script_name(&payload.asset_info).to_string());
+            script_set.insert(CHEQUE.to_string());
+            self.build_required_udt_tx_part(
+                ctx.clone(),
+                from_items.clone(),
+                Some(Source::Free),
+                payload.asset_info.udt_hash.clone(),
+                required_udt,
+                &mut udt_remaining,
+                &mut inputs,
+                &mut script_set,
+                &mut signature_actions,
+                &mut outputs,
+                &mut cells_data,
+                &mut input_index,
+                coin_selection,
+            )
+            .await?;
+            Some(
+                self.build_sudt_type_script(
+                    ctx.clone(),
+                    blake2b_256_to_160(&payload.asset_info.udt_hash),
+                )
+                .await?,
+            )
+        } else {
+            None
+        };
+
+        // the change cell is built again after the recipients of the transaction are decided
+        let required_ckb = required_ckb + estimate_fee + udt_change_capacity;
+        let change_index = self
+            .build_required_ckb_and_change_tx_part(
+                ctx.clone(),
+                from_items,
+                Some(Source::Free),
+                required_ckb,
+                payload.change.clone(),
+                None,
+                &mut inputs,
+                &mut script_set,
+                &mut signature_actions,
+                &mut outputs,
+                &mut cells_data,
+                &mut input_index,
+                coin_selection,
+            )
+            .await?;
+        let change_cell = outputs.remove(change_index);
+        cells_data.remove(change_index);
+        let change_lock = change_cell.lock();
+        if !self.is_script(&change_lock, SECP256K1)? {
+            return Err(RpcErrorMessage::InvalidRpcParams(
+                "The change address should be secp/256k1 address".to_string(),
+            ));
+        }
+        let change_capacity: u64 = change_cell.capacity().unpack();
+        let mut available_ckb = change_capacity + required_ckb;
+
+        let mut tx_inputs = self.build_tx_cell_inputs(&inputs, None, Source::Free)?;
+        let (mut base_outputs, mut base_cells_data) = (outputs, cells_data);
+        let mut start = 0;
+        let mut actual_fee = 0;
+        let mut responses = vec![];
+        let mut prev_txs: Vec<TransactionView> = vec![];
+
+        while start < recipients.len() {
+            // measure the transaction without recipients, then take the recipients as many
+            // as the block limit allows
+            let (mut outputs, mut cells_data) = (base_outputs.clone(), base_cells_data.clone());
+            if let Some(type_script) = &udt_type_script {
+                self.build_cell_for_output(
+                    STANDARD_SUDT_CAPACITY,
+                    change_lock.clone(),
+                    Some(type_script.clone()),
+                    Some(udt_remaining),
+                    &mut outputs,
+                    &mut cells_data,
+                )?;
+            }
+            self.build_cell_for_output(
+                0,
+                change_lock.clone(),
+                None,
+                None,
+                &mut outputs,
+                &mut cells_data,
+            )?;
+            let (skeleton, _) = self.prebuild_tx_complete(
+                tx_inputs.clone(),
+                outputs.clone(),
+                cells_data,
+                script_set.clone(),
+                vec![],
+                signature_actions.clone(),
+                HashMap::new(),
+            )?;
+
+            let mut tx_size = calculate_tx_size(skeleton) as u64;
+            let mut cycles = ESTIMATED_LOCK_CYCLES * signature_actions.len() as u64;
+            if is_udt {
+                cycles += ESTIMATED_UDT_CYCLES
+                    + ESTIMATED_UDT_CELL_CYCLES * (tx_inputs.len() + outputs.len()) as u64;
+            }
+            let recipient_cycles = if is_udt { ESTIMATED_UDT_CELL_CYCLES } else { 0 };
+
+            let mut end = start;
+            while end < recipients.len() && end - start < MAX_ITEM_NUM {
+                let recipient_size = recipients[end].tx_size();
+                if tx_size + recipient_size > limit.max_bytes
+                    || cycles + recipient_cycles * (end - start + 1) as u64 > limit.max_cycles
+                {
+                    break;
+                }
+                tx_size += recipient_size;
+                end += 1;
+            }
+            if end == start {
+                return Err(RpcErrorMessage::ExceedMaxBlockLimit);
+            }
+
+            // build the transaction paying to recipients[start..end], the recipients are
+            // halved if the scripts take more cycles than estimated
+            let (tx_view, tx_signature_actions, change_index, udt_change_index, paid_ckb) = loop {
+                let (mut outputs, mut cells_data) = (vec![], vec![]);
+                let mut paid_ckb = 0;
+                let mut chunk_udt_remaining = udt_remaining;
+                for recipient in &recipients[start..end] {
+                    outputs.push(recipient.output.clone());
+                    cells_data.push(recipient.data.clone());
+                    paid_ckb += recipient.capacity;
+                    chunk_udt_remaining -= recipient.udt_amount;
+                }
+                outputs.extend(base_outputs.iter().cloned());
+                cells_data.extend(base_cells_data.iter().cloned());
+
+                let mut udt_change_index = None;
+                if let Some(type_script) = &udt_type_script {
+                    if chunk_udt_remaining != 0 {
+                        udt_change_index = Some(self.build_cell_for_output(
+                            STANDARD_SUDT_CAPACITY,
+                            change_lock.clone(),
+                            Some(type_script.clone()),
+                            Some(chunk_udt_remaining),
+                            &mut outputs,
+                            &mut cells_data,
+                        )?);
+                        paid_ckb += STANDARD_SUDT_CAPACITY;
+                    }
+                }
+                let change_index = self.build_cell_for_output(
+                    0,
+                    change_lock.clone(),
+                    None,
+                    None,
+                    &mut outputs,
+                    &mut cells_data,
+                )?;
+
+                let (tx_view, tx_signature_actions) = self.prebuild_tx_complete(
+                    tx_inputs.clone(),
+                    outputs,
+                    cells_data,
+                    script_set.clone(),
+                    vec![],
+                    signature_actions.clone(),
+                    HashMap::new(),
+                )?;
+                let estimated_cycles = cycles + recipient_cycles * (end - start) as u64;
+                let chunk_cycles = self
+                    .calculate_chunk_cycles(ctx.clone(), tx_view.clone(), &prev_txs)
+                    .await?
+                    .unwrap_or(estimated_cycles);
+                if chunk_cycles > limit.max_cycles {
+                    if end - start == 1 {
+                        return Err(RpcErrorMessage::ExceedMaxBlockLimit);
+                    }
+                    end = start + (end - start) / 2;
+                    continue;
+                }
+
+                udt_remaining = chunk_udt_remaining;
+                break (
+                    tx_view,
+                    tx_signature_actions,
+                    change_index,
+                    udt_change_index,
+                    paid_ckb,
+                );
+            };
+            base_outputs.clear();
+            base_cells_data.clear();
+
+            let tx_size = calculate_tx_size(tx_view.clone()) as u64;
+            let fee = calculate_fee(fee_rate, tx_size);
+            actual_fee += fee;
+
+            let change_capacity = match available_ckb.checked_sub(paid_ckb + fee) {
+                Some(change_capacity) => change_capacity,
+                // the fee is under estimated, the caller pools again with the actual fee
+                None if actual_fee > estimate_fee => return Ok((responses, actual_fee)),
+                None => {
+                    return Err(RpcErrorMessage::TokenIsNotEnough(
+                        AssetInfo::new_ckb().to_string(),
+                    ))
+                }
+            };
+            let tx_view = self.update_tx_view_change_cell_by_index(
+                tx_view.into(),
+                change_index,
+                change_capacity,
+                0,
+            )?;
+
+            // the next transaction spends the change cells
+            let tx_hash = tx_view.hash.pack();
+            let change_address = self.script_to_address(&change_lock).to_string();
+            let change_lock_hash = change_lock.calc_script_hash().to_string();
+            tx_inputs = vec![];
+            signature_actions = HashMap::new();
+            for index in [Some(change_index), udt_change_index].iter().flatten() {
+                add_signature_action(
+                    change_address.clone(),
+                    change_lock_hash.clone(),
+                    SignAlgorithm::Secp256k1,
+                    HashAlgorithm::Blake2b,
+                    &mut signature_actions,
+                    tx_inputs.len(),
+                );
+                tx_inputs.push(
+                    packed::CellInputBuilder::default()
+                        .previous_output(packed::OutPoint::new(tx_hash.clone(), *index as u32))
+                        .build(),
+                );
+            }
+            script_set.insert(SECP256K1.to_string());
+            available_ckb =
+                change_capacity + udt_change_index.map_or(0, |_| STANDARD_SUDT_CAPACITY);
+
+            prev_txs.push(packed::Transaction::from(tx_view.inner.clone()).into_view());
+            responses.push(TransactionCompletionResponse::new(
+                tx_view,
+                tx_signature_actions,
+            ));
+            start = end;
+        }
+
+        Ok((responses, actual_fee))
+    }
+
+    /// The cycles of a batch transaction, in which the type scripts are run and the lock
+    /// groups are estimated, since the locks cannot be run before signing. None if the
+    /// transaction cannot be resolved, such as the cell deps are not indexed yet.
+    async fn calculate_chunk_cycles(
+        &self,
+        ctx: Context,
+        tx_view: TransactionView,
+        prev_txs: &[TransactionView],
+    ) -> InnerResult<Option<u64>> {
+        let verification = self.verify_transaction_view(ctx, tx_view, prev_txs).await?;
+        if !verification.errors.is_empty() {
+            return Ok(None);
+        }
+
+        let skipped_locks = verification
+            .script_results
+            .iter()
+            .filter(|result| result.skipped)
+            .count() as u64;
+        Ok(Some(
+            verification.cycles + ESTIMATED_LOCK_CYCLES * skipped_locks,
+        ))
+    }
+}
@@ -2,12 +2,13 @@ use crate::error::{InnerResult, RpcErrorMessage};
 use crate::rpc_impl::utils::address_to_identity;
 use crate::rpc_impl::{
     address_to_script, utils, ACP_CODE_HASH, BYTE_SHANNONS, CHEQUE_CELL_CAPACITY, CHEQUE_CODE_HASH,
-    CURRENT_EPOCH_NUMBER, DEFAULT_FEE_RATE, INIT_ESTIMATE_FEE, MAX_ITEM_NUM, MIN_CKB_CAPACITY,
+    CURRENT_EPOCH_NUMBER, INIT_ESTIMATE_FEE, MAX_ITEM_NUM, MAX_RESERVE_RETRY, MIN_CKB_CAPACITY,
     MIN_DAO_CAPACITY, STANDARD_SUDT_CAPACITY,
 };
 use crate::types::{
-    AddressOrLockHash, AssetInfo, AssetType, DaoClaimPayload, DaoDepositPayload,
-    DaoWithdrawPayload, ExtraType, From, GetBalancePayload, HashAlgorithm, Item, JsonItem, Mode,
+    AddressOrLockHash, AssetInfo, AssetType, ChequeClaimPayload, ChequeWithdrawPayload,
+    CoinSelection, DaoClaimPayload, DaoDepositPayload, DaoWithdrawPayload, ExtraType, FeeRate,
+    From, GetBalancePayload, HashAlgorithm, Item, JsonItem, Mode, MultiAssetTransferPayload,
     RequiredUDT, SignAlgorithm, SignatureAction, SinceConfig, SinceFlag, SinceType,
     SmartTransferPayload, Source, To, ToInfo, TransactionCompletionResponse, TransferPayload,
     UDTInfo,
@@ -16,8 +17,8 @@ use crate::{CkbRpc, MercuryRpcImpl};
 
 use ckb_types::packed::BytesOpt;
 use common::hash::blake2b_256_to_160;
-use common::utils::{decode_udt_amount, encode_udt_amount};
-use common::{Address, Context, DetailedCell, ACP, CHEQUE, DAO, SECP256K1, SUDT};
+use common::utils::{decode_udt_amount, encode_udt_amount, update_udt_amount};
+use common::{Address, Context, DetailedCell, ACP, CHEQUE, DAO, SECP256K1, XUDT};
 use common_logger::tracing_async;
 use core_storage::Storage;
 
@@ -61,6 +62,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             ctx.clone(),
             payload.clone(),
             payload.fee_rate,
+            payload.dry_run,
         )
         .await
     }
@@ -72,6 +74,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         payload: DaoDepositPayload,
         fixed_fee: u64,
     ) -> InnerResult<(TransactionView, Vec<SignatureAction>, usize)> {
+        let coin_selection = payload.coin_selection.unwrap_or(self.coin_selection);
         let mut inputs = Vec::new();
         let (mut outputs, mut cells_data) = (vec![], vec![]);
         let mut script_set = HashSet::new();
@@ -98,6 +101,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 &mut outputs,
                 &mut cells_data,
                 &mut input_index,
+                coin_selection,
             )
             .await?;
 
@@ -148,6 +152,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             ctx.clone(),
             payload.clone(),
             payload.fee_rate,
+            payload.dry_run,
         )
         .await
     }
@@ -182,6 +187,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             &mut script_set,
             &mut signature_actions,
             &mut input_index,
+            self.coin_selection,
         )
         .await?;
 
@@ -315,6 +321,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             ctx,
             payload.clone(),
             payload.fee_rate,
+            payload.dry_run,
         )
         .await
     }
@@ -502,6 +509,397 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         .map(|(tx_view, signature_actions)| (tx_view, signature_actions, change_cell_index))
     }
 
+    #[tracing_async]
+    pub(crate) async fn inner_build_cheque_claim_transaction(
+        &self,
+        ctx: Context,
+        payload: ChequeClaimPayload,
+    ) -> InnerResult<TransactionCompletionResponse> {
+        self.build_transaction_with_adjusted_fee(
+            Self::prebuild_cheque_claim_transaction,
+            ctx,
+            payload.clone(),
+            payload.fee_rate,
+            payload.dry_run,
+        )
+        .await
+    }
+
+    /// The receiver claims all the cheque cells addressed to it. The udt goes into the ACP
+    /// cell of the receiver if there is one, otherwise into a new cell of `to`. The capacity
+    /// of the cheque cells is returned to the senders, so the fee is paid by the receiver.
+    #[tracing_async]
+    async fn prebuild_cheque_claim_transaction(
+        &self,
+        ctx: Context,
+        payload: ChequeClaimPayload,
+        fixed_fee: u64,
+    ) -> InnerResult<(TransactionView, Vec<SignatureAction>, usize)> {
+        let item = Item::try_from(payload.from.clone())?;
+        let receiver_address = self.get_secp_address_by_item(item.clone())?;
+        let to_address = match payload.to {
+            Some(address) => {
+                Address::from_str(&address).map_err(RpcErrorMessage::InvalidRpcParams)?
+            }
+            None => receiver_address.clone(),
+        };
+        if !to_address.is_secp256k1() {
+            return Err(RpcErrorMessage::InvalidRpcParams(
+                "Every to address should be secp/256k1 address".to_string(),
+            ));
+        }
+
+        // the timed out cheque cells are filtered out when getting the live cells
+        let receiver_lock_hash = self.get_secp_lock_hash_by_item(item.clone())?;
+        let cheque_cells = self
+            .get_live_cells_by_item(
+                ctx.clone(),
+                item.clone(),
+                payload.asset_infos.clone(),
+                None,
+                None,
+                Some((**CHEQUE_CODE_HASH.load()).clone()),
+                None,
+                false,
+            )
+            .await?
+            .into_iter()
+            .filter(|cell| {
+                cell.cell_output.lock().args().raw_data().get(0..20)
+                    == Some(&receiver_lock_hash.0[..])
+            })
+            .collect::<Vec<_>>();
+        if cheque_cells.is_empty() {
+            return Err(RpcErrorMessage::CannotFindChequeCell);
+        }
+
+        let mut script_set = HashSet::new();
+        let (mut outputs, mut cells_data) = (vec![], vec![]);
+        let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
+        let mut input_index = 0;
+        script_set.insert(CHEQUE.to_string());
+
+        // tx part I: build cheque inputs and return the capacity to the senders
+        let mut inputs_part_1 = vec![];
+        let mut claimed_udts: Vec<(packed::Script, u128)> = vec![];
+
+        for cell in cheque_cells.iter() {
+            let type_script = match cell.cell_output.type_().to_opt() {
+                Some(script) => script,
+                None => continue,
+            };
+            script_set.insert(utils::udt_script_name_by_type(&type_script).to_string());
+
+            let udt_amount = decode_udt_amount(&cell.cell_data);
+            match claimed_udts
+                .iter_mut()
+                .find(|(script, _)| script.as_slice() == type_script.as_slice())
+            {
+                Some((_, amount)) => {
+                    *amount = amount
+                        .checked_add(udt_amount)
+                        .ok_or(RpcErrorMessage::Overflow)?
+                }
+                None => claimed_udts.push((type_script, udt_amount)),
+            }
+
+            let sender_address = match self
+                .generate_ckb_address_or_lock_hash(ctx.clone(), cell)
+                .await?
+            {
+                AddressOrLockHash::Address(address) => address,
+                AddressOrLockHash::LockHash(_) => {
+                    return Err(RpcErrorMessage::CannotFindAddressByH160)
+                }
+            };
+            let sender_address =
+                Address::from_str(&sender_address).map_err(RpcErrorMessage::InvalidRpcParams)?;
+            self.build_cell_for_output(
+                cell.cell_output.capacity().unpack(),
+                address_to_script(sender_address.payload()),
+                None,
+                None,
+                &mut outputs,
+                &mut cells_data,
+            )?;
+
+            inputs_part_1.push(cell.clone());
+            utils::add_signature_action(
+                receiver_address.to_string(),
+                cell.cell_output.calc_lock_hash().to_string(),
+                SignAlgorithm::Secp256k1,
+                HashAlgorithm::Blake2b,
+                &mut signature_actions,
+                input_index,
+            );
+            input_index += 1;
+        }
+
+        // tx part II: put the claimed udt into the acp cells or the new udt cells
+        let mut inputs_part_2 = vec![];
+        let mut required_ckb = fixed_fee;
+
+        for (type_script, amount) in claimed_udts {
+            let udt_hash: H256 = type_script.calc_script_hash().unpack();
+            let asset_info = if utils::udt_script_name_by_type(&type_script) == XUDT {
+                AssetInfo::new_xudt(udt_hash)
+            } else {
+                AssetInfo::new_udt(udt_hash)
+            };
+            let mut asset_set = HashSet::new();
+            asset_set.insert(asset_info);
+            let live_acps = self
+                .get_live_cells_by_item(
+                    ctx.clone(),
+                    item.clone(),
+                    asset_set,
+                    None,
+                    None,
+                    Some((**ACP_CODE_HASH.load()).clone()),
+                    None,
+                    false,
+                )
+                .await?;
+
+            if let Some(acp_cell) = live_acps
+                .iter()
+                .find(|cell| !self.is_in_cache(&cell.out_point))
+            {
+                let existing_udt_amount = decode_udt_amount(&acp_cell.cell_data);
+                inputs_part_2.push(acp_cell.clone());
+                input_index += 1;
+                script_set.insert(ACP.to_string());
+                self.build_udt_cell_for_output_from(
+                    acp_cell,
+                    existing_udt_amount
+                        .checked_add(amount)
+                        .ok_or(RpcErrorMessage::Overflow)?,
+                    &mut outputs,
+                    &mut cells_data,
+                )?;
+            } else {
+                self.build_cell_for_output(
+                    STANDARD_SUDT_CAPACITY,
+                    to_address.payload().into(),
+                    Some(type_script),
+                    Some(amount),
+                    &mut outputs,
+                    &mut cells_data,
+                )?;
+                required_ckb += STANDARD_SUDT_CAPACITY;
+            }
+        }
+
+        // tx part III: pool ckb for the fee and the new udt cells, and build change cell
+        let mut inputs_part_3 = vec![];
+        let change_cell_index = self
+            .build_required_ckb_and_change_tx_part(
+                ctx.clone(),
+                vec![item],
+                Some(Source::Free),
+                required_ckb,
+                None,
+                None,
+                &mut inputs_part_3,
+                &mut script_set,
+                &mut signature_actions,
+                &mut outputs,
+                &mut cells_data,
+                &mut input_index,
+                self.coin_selection,
+            )
+            .await?;
+
+        // build tx, only the cheque inputs are claimed, the others are free cells of the
+        // receiver
+        let mut other_inputs = vec![];
+        other_inputs.append(&mut inputs_part_2);
+        other_inputs.append(&mut inputs_part_3);
+        let mut inputs = self.build_tx_cell_inputs(&inputs_part_1, None, Source::Claimable)?;
+        inputs.extend(self.build_tx_cell_inputs(&other_inputs, None, Source::Free)?);
+        self.prebuild_tx_complete(
+            inputs,
+            outputs,
+            cells_data,
+            script_set,
+            vec![],
+            signature_actions,
+            HashMap::new(),
+        )
+        .map(|(tx_view, signature_actions)| (tx_view, signature_actions, change_cell_index))
+    }
+
+    #[tracing_async]
+    pub(crate) async fn inner_build_cheque_withdraw_transaction(
+        &self,
+        ctx: Context,
+        payload: ChequeWithdrawPayload,
+    ) -> InnerResult<TransactionCompletionResponse> {
+        self.build_transaction_with_adjusted_fee(
+            Self::prebuild_cheque_withdraw_transaction,
+            ctx,
+            payload.clone(),
+            payload.fee_rate,
+            payload.dry_run,
+        )
+        .await
+    }
+
+    /// The sender withdraws the cheque cells which are not claimed in `cheque_timeout`
+    /// epochs. The cheque cells of the same udt are merged into one cell of `to`. The fee is
+    /// taken from the merged cell with the most spare capacity, or pooled from the free ckb
+    /// of the sender if none of them affords it.
+    #[tracing_async]
+    async fn prebuild_cheque_withdraw_transaction(
+        &self,
+        ctx: Context,
+        payload: ChequeWithdrawPayload,
+        fixed_fee: u64,
+    ) -> InnerResult<(TransactionView, Vec<SignatureAction>, usize)> {
+        let item = Item::try_from(payload.from.clone())?;
+        let sender_address = self.get_secp_address_by_item(item.clone())?;
+        let to_address = match payload.to {
+            Some(address) => {
+                Address::from_str(&address).map_err(RpcErrorMessage::InvalidRpcParams)?
+            }
+            None => sender_address.clone(),
+        };
+        if !to_address.is_secp256k1() {
+            return Err(RpcErrorMessage::InvalidRpcParams(
+                "Every to address should be secp/256k1 address".to_string(),
+            ));
+        }
+
+        let sender_lock_hash = self.get_secp_lock_hash_by_item(item.clone())?;
+        let cheque_cells = self
+            .get_live_cells_by_item(
+                ctx.clone(),
+                item.clone(),
+                payload.asset_infos.clone(),
+                None,
+                None,
+                Some((**CHEQUE_CODE_HASH.load()).clone()),
+                None,
+                false,
+            )
+            .await?
+            .into_iter()
+            .filter(|cell| {
+                cell.cell_output.lock().args().raw_data().get(20..40)
+                    == Some(&sender_lock_hash.0[..])
+                    && cell.cell_output.type_().to_opt().is_some()
+                    && self.is_unlock(
+                        EpochNumberWithFraction::from_full_value(cell.epoch_number).to_rational(),
+                        None,
+                        self.cheque_timeout.clone(),
+                    )
+            })
+            .collect::<Vec<_>>();
+        if cheque_cells.is_empty() {
+            return Err(RpcErrorMessage::CannotFindChequeCell);
+        }
+
+        let mut script_set = HashSet::new();
+        let (mut outputs, mut cells_data) = (vec![], vec![]);
+        let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
+        script_set.insert(CHEQUE.to_string());
+
+        // the udt amount and the capacity of the cheque cells grouped by type script
+        let mut withdrawn_udts: Vec<(packed::Script, u128, u64)> = vec![];
+        for (index, cell) in cheque_cells.iter().enumerate() {
+            let type_script = cell.cell_output.type_().to_opt().unwrap();
+            script_set.insert(utils::udt_script_name_by_type(&type_script).to_string());
+
+            let udt_amount = decode_udt_amount(&cell.cell_data);
+            let capacity: u64 = cell.cell_output.capacity().unpack();
+            match withdrawn_udts
+                .iter_mut()
+                .find(|(script, _, _)| script.as_slice() == type_script.as_slice())
+            {
+                Some((_, amount, total_capacity)) => {
+                    *amount = amount
+                        .checked_add(udt_amount)
+                        .ok_or(RpcErrorMessage::Overflow)?;
+                    *total_capacity += capacity;
+                }
+                None => withdrawn_udts.push((type_script, udt_amount, capacity)),
+            }
+
+            utils::add_signature_action(
+                sender_address.to_string(),
+                cell.cell_output.calc_lock_hash().to_string(),
+                SignAlgorithm::Secp256k1,
+                HashAlgorithm::Blake2b,
+                &mut signature_actions,
+                index,
+            );
+        }
+
+        let fee_index = cheque_withdraw_fee_index(
+            &withdrawn_udts
+                .iter()
+                .map(|(_, _, capacity)| *capacity)
+                .collect::<Vec<_>>(),
+            fixed_fee,
+        );
+        for (index, (type_script, amount, capacity)) in withdrawn_udts.into_iter().enumerate() {
+            let capacity = if fee_index == Some(index) {
+                capacity - fixed_fee
+            } else {
+                capacity
+            };
+            self.build_cell_for_output(
+                capacity,
+                to_address.payload().into(),
+                Some(type_script),
+                Some(amount),
+                &mut outputs,
+                &mut cells_data,
+            )?;
+        }
+
+        let mut inputs = cheque_cells;
+        let change_cell_index = match fee_index {
+            Some(index) => index,
+            None => {
+                let mut fee_inputs = vec![];
+                let mut input_index = inputs.len();
+                let change_cell_index = self
+                    .build_required_ckb_and_change_tx_part(
+                        ctx.clone(),
+                        vec![item],
+                        Some(Source::Free),
+                        fixed_fee,
+                        None,
+                        None,
+                        &mut fee_inputs,
+                        &mut script_set,
+                        &mut signature_actions,
+                        &mut outputs,
+                        &mut cells_data,
+                        &mut input_index,
+                        self.coin_selection,
+                    )
+                    .await?;
+                inputs.append(&mut fee_inputs);
+                change_cell_index
+            }
+        };
+
+        // the since of the cheque inputs is set for the sender
+        let inputs = self.build_tx_cell_inputs(&inputs, None, Source::Free)?;
+        self.prebuild_tx_complete(
+            inputs,
+            outputs,
+            cells_data,
+            script_set,
+            vec![],
+            signature_actions,
+            HashMap::new(),
+        )
+        .map(|(tx_view, signature_actions)| (tx_view, signature_actions, change_cell_index))
+    }
+
     #[tracing_async]
     pub(crate) async fn inner_build_transfer_transaction(
         &self,
@@ -535,6 +933,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             ctx.clone(),
             payload.clone(),
             payload.fee_rate,
+            payload.dry_run,
         )
         .await
     }
@@ -555,11 +954,11 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 self.prebuild_acp_transfer_transaction_with_ckb(ctx.clone(), payload, fixed_fee)
                     .await
             }
-            (AssetType::UDT, Mode::HoldByFrom) => {
+            (AssetType::UDT, Mode::HoldByFrom) | (AssetType::XUDT, Mode::HoldByFrom) => {
                 self.prebuild_cheque_transfer_transaction(ctx.clone(), payload, fixed_fee)
                     .await
             }
-            (AssetType::UDT, Mode::HoldByTo) => {
+            (AssetType::UDT, Mode::HoldByTo) | (AssetType::XUDT, Mode::HoldByTo) => {
                 self.prebuild_acp_transfer_transaction_with_udt(ctx.clone(), payload, fixed_fee)
                     .await
             }
@@ -573,6 +972,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         payload: TransferPayload,
         fixed_fee: u64,
     ) -> InnerResult<(TransactionView, Vec<SignatureAction>, usize)> {
+        let coin_selection = payload.coin_selection.unwrap_or(self.coin_selection);
         let mut script_set = HashSet::new();
         let (mut outputs, mut cells_data) = (vec![], vec![]);
         let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
@@ -600,6 +1000,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     &mut outputs,
                     &mut cells_data,
                     &mut input_index,
+                    coin_selection,
                 )
                 .await?;
         }
@@ -651,6 +1052,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     &mut outputs,
                     &mut cells_data,
                     &mut input_index,
+                    coin_selection,
                 )
                 .await?;
         } else {
@@ -667,6 +1069,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 &mut outputs,
                 &mut cells_data,
                 &mut input_index,
+                coin_selection,
             )
             .await?;
         };
@@ -677,6 +1080,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         inputs.append(&mut inputs_part_2);
         let inputs =
             self.build_tx_cell_inputs(&inputs, payload.since.clone(), payload.from.source.clone())?;
+        self.fill_multisig_signature_actions(&payload.multisig_scripts, &mut signature_actions)?;
         self.prebuild_tx_complete(
             inputs,
             outputs,
@@ -696,6 +1100,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         payload: TransferPayload,
         fixed_fee: u64,
     ) -> InnerResult<(TransactionView, Vec<SignatureAction>, usize)> {
+        let coin_selection = payload.coin_selection.unwrap_or(self.coin_selection);
         let mut script_set = HashSet::new();
         let (mut outputs, mut cells_data) = (vec![], vec![]);
         let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
@@ -723,6 +1128,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     &mut outputs,
                     &mut cells_data,
                     &mut input_index,
+                    coin_selection,
                 )
                 .await?;
         }
@@ -748,7 +1154,10 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     None,
                     false,
                 )
-                .await?;
+                .await?
+                .into_iter()
+                .filter(|cell| !self.is_in_cache(&cell.out_point))
+                .collect::<Vec<_>>();
             if live_acps.is_empty() {
                 return Err(RpcErrorMessage::CannotFindACPCell);
             }
@@ -798,6 +1207,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     &mut outputs,
                     &mut cells_data,
                     &mut input_index,
+                    coin_selection,
                 )
                 .await?;
         } else {
@@ -814,6 +1224,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 &mut outputs,
                 &mut cells_data,
                 &mut input_index,
+                coin_selection,
             )
             .await?;
         };
@@ -825,6 +1236,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         inputs.append(&mut inputs_part_3);
         let inputs =
             self.build_tx_cell_inputs(&inputs, payload.since.clone(), payload.from.source.clone())?;
+        self.fill_multisig_signature_actions(&payload.multisig_scripts, &mut signature_actions)?;
         self.prebuild_tx_complete(
             inputs,
             outputs,
@@ -844,12 +1256,13 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         payload: TransferPayload,
         fixed_fee: u64,
     ) -> InnerResult<(TransactionView, Vec<SignatureAction>, usize)> {
+        let coin_selection = payload.coin_selection.unwrap_or(self.coin_selection);
         let mut script_set = HashSet::new();
         let (mut outputs, mut cells_data) = (vec![], vec![]);
         let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
         let mut change_fee_cell_index = 0usize;
         let mut input_index = 0;
-        script_set.insert(SUDT.to_string());
+        script_set.insert(utils::udt_script_name(&payload.asset_info).to_string());
 
         // tx part I: build pay fee input and change output
         let mut inputs_part_1 = vec![];
@@ -872,6 +1285,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     &mut outputs,
                     &mut cells_data,
                     &mut input_index,
+                    coin_selection,
                 )
                 .await?;
         }
@@ -948,6 +1362,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             &mut outputs,
             &mut cells_data,
             &mut input_index,
+            coin_selection,
         )
         .await?;
 
@@ -971,6 +1386,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     &mut outputs,
                     &mut cells_data,
                     &mut input_index,
+                    coin_selection,
                 )
                 .await?;
         } else {
@@ -990,6 +1406,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 &mut outputs,
                 &mut cells_data,
                 &mut input_index,
+                coin_selection,
             )
             .await?;
         };
@@ -1002,6 +1419,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         inputs.append(&mut inputs_part_4);
         let inputs =
             self.build_tx_cell_inputs(&inputs, payload.since.clone(), payload.from.source.clone())?;
+        self.fill_multisig_signature_actions(&payload.multisig_scripts, &mut signature_actions)?;
         self.prebuild_tx_complete(
             inputs,
             outputs,
@@ -1021,12 +1439,13 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         payload: TransferPayload,
         fixed_fee: u64,
     ) -> InnerResult<(TransactionView, Vec<SignatureAction>, usize)> {
+        let coin_selection = payload.coin_selection.unwrap_or(self.coin_selection);
         let mut script_set = HashSet::new();
         let (mut outputs, mut cells_data) = (vec![], vec![]);
         let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
         let mut change_fee_cell_index = 0;
         let mut input_index = 0;
-        script_set.insert(SUDT.to_string());
+        script_set.insert(utils::udt_script_name(&payload.asset_info).to_string());
 
         // tx part I: build pay fee input and change output
         let mut inputs_part_1 = vec![];
@@ -1049,6 +1468,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     &mut outputs,
                     &mut cells_data,
                     &mut input_index,
+                    coin_selection,
                 )
                 .await?;
         }
@@ -1074,7 +1494,10 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     None,
                     false,
                 )
-                .await?;
+                .await?
+                .into_iter()
+                .filter(|cell| !self.is_in_cache(&cell.out_point))
+                .collect::<Vec<_>>();
             if live_acps.is_empty() {
                 return Err(RpcErrorMessage::CannotFindACPCell);
             }
@@ -1088,11 +1511,9 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 .amount
                 .parse::<u128>()
                 .map_err(|err| RpcErrorMessage::InvalidRpcParams(err.to_string()))?;
-            self.build_cell_for_output(
-                live_acps[0].cell_output.capacity().unpack(),
-                live_acps[0].cell_output.lock(),
-                live_acps[0].cell_output.type_().to_opt(),
-                Some(existing_udt_amount + to_udt_amount),
+            self.build_udt_cell_for_output_from(
+                &live_acps[0],
+                existing_udt_amount + to_udt_amount,
                 &mut outputs,
                 &mut cells_data,
             )?;
@@ -1124,6 +1545,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             &mut script_set,
             &mut signature_actions,
             &mut input_index,
+            coin_selection,
         )
         .await?;
 
@@ -1154,14 +1576,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     &mut cells_data,
                 )?;
             } else if code_hash == **ACP_CODE_HASH.load() {
-                self.build_cell_for_output(
-                    cell.cell_output.capacity().unpack(),
-                    cell.cell_output.lock(),
-                    cell.cell_output.type_().to_opt(),
-                    Some(0),
-                    &mut outputs,
-                    &mut cells_data,
-                )?;
+                self.build_udt_cell_for_output_from(cell, 0, &mut outputs, &mut cells_data)?;
             } else {
                 self.build_cell_for_output(
                     cell.cell_output.capacity().unpack(),
@@ -1198,6 +1613,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     &mut outputs,
                     &mut cells_data,
                     &mut input_index,
+                    coin_selection,
                 )
                 .await?;
         } else {
@@ -1217,6 +1633,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 &mut outputs,
                 &mut cells_data,
                 &mut input_index,
+                coin_selection,
             )
             .await?;
         };
@@ -1229,6 +1646,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         inputs.append(&mut inputs_part_4);
         let inputs =
             self.build_tx_cell_inputs(&inputs, payload.since.clone(), payload.from.source.clone())?;
+        self.fill_multisig_signature_actions(&payload.multisig_scripts, &mut signature_actions)?;
         self.prebuild_tx_complete(
             inputs,
             outputs,
@@ -1259,6 +1677,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             ctx.clone(),
             payload.clone(),
             payload.fee_rate,
+            payload.dry_run,
         )
         .await
     }
@@ -1305,12 +1724,15 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     change: payload.change,
                     fee_rate: payload.fee_rate,
                     since: payload.since,
+                    multisig_scripts: payload.multisig_scripts.clone(),
+                    coin_selection: payload.coin_selection,
+                    dry_run: false,
                 };
                 self.prebuild_secp_transfer_transaction(ctx.clone(), transfer_payload, fixed_fee)
                     .await
             }
 
-            AssetType::UDT => {
+            AssetType::UDT | AssetType::XUDT => {
                 let mut asset_infos = HashSet::new();
                 asset_infos.insert(payload.asset_info.clone());
                 let mode = self
@@ -1333,6 +1755,9 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     change: payload.change,
                     fee_rate: payload.fee_rate,
                     since: payload.since,
+                    multisig_scripts: payload.multisig_scripts.clone(),
+                    coin_selection: payload.coin_selection,
+                    dry_run: false,
                 };
                 match mode {
                     Mode::HoldByFrom => {
@@ -1360,12 +1785,336 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
     }
 
     #[tracing_async]
-    async fn build_transaction_with_adjusted_fee<'a, F, Fut, T>(
+    pub(crate) async fn inner_build_multi_asset_transfer_transaction(
+        &self,
+        ctx: Context,
+        payload: MultiAssetTransferPayload,
+    ) -> InnerResult<TransactionCompletionResponse> {
+        if payload.from.items.is_empty()
+            || payload.to.is_empty()
+            || payload
+                .to
+                .iter()
+                .any(|transfer| transfer.to_infos.is_empty())
+        {
+            return Err(RpcErrorMessage::NeedAtLeastOneFromAndOneTo);
+        }
+        let to_num: usize = payload
+            .to
+            .iter()
+            .map(|transfer| transfer.to_infos.len())
+            .sum();
+        if payload.from.items.len() > MAX_ITEM_NUM || to_num > MAX_ITEM_NUM {
+            return Err(RpcErrorMessage::ExceedMaxItemNum);
+        }
+
+        let mut asset_infos = HashSet::new();
+        for transfer in &payload.to {
+            if !asset_infos.insert(transfer.asset_info.clone()) {
+                return Err(RpcErrorMessage::InvalidRpcParams(format!(
+                    "Duplicate asset {} in to",
+                    transfer.asset_info
+                )));
+            }
+            for to_info in &transfer.to_infos {
+                match u128::from_str(&to_info.amount) {
+                    Ok(amount) => {
+                        if amount == 0u128 {
+                            return Err(RpcErrorMessage::TransferAmountMustPositive);
+                        }
+                    }
+                    Err(_) => {
+                        return Err(RpcErrorMessage::InvalidRpcParams(
+                            "To amount should be a valid u128 number".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        self.build_transaction_with_adjusted_fee(
+            Self::prebuild_multi_asset_transfer_transaction,
+            ctx.clone(),
+            payload.clone(),
+            payload.fee_rate,
+            payload.dry_run,
+        )
+        .await
+    }
+
+    /// Each UDT is balanced by its own inputs and change cell, and all the CKB required by
+    /// the outputs and the fee is pooled at last, which also builds the only CKB change cell.
+    #[tracing_async]
+    async fn prebuild_multi_asset_transfer_transaction(
+        &self,
+        ctx: Context,
+        payload: MultiAssetTransferPayload,
+        fixed_fee: u64,
+    ) -> InnerResult<(TransactionView, Vec<SignatureAction>, usize)> {
+        let coin_selection = payload.coin_selection.unwrap_or(self.coin_selection);
+        let mut script_set = HashSet::new();
+        let (mut outputs, mut cells_data) = (vec![], vec![]);
+        let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
+        let mut input_index = 0;
+        let mut inputs = vec![];
+        let mut required_ckb = 0;
+
+        let mut from_items = vec![];
+        for json_item in &payload.from.items {
+            let item = Item::try_from(json_item.to_owned())?;
+            from_items.push(item)
+        }
+        let sender_address = self.get_secp_address_by_item(from_items[0].clone())?;
+        let change_lock: packed::Script = match &payload.change {
+            Some(address) => {
+                let address =
+                    Address::from_str(address).map_err(RpcErrorMessage::InvalidRpcParams)?;
+                address_to_script(address.payload())
+            }
+            None => sender_address.payload().into(),
+        };
+
+        for transfer in &payload.to {
+            match (&transfer.asset_info.asset_type, &transfer.mode) {
+                (AssetType::CKB, Mode::HoldByFrom) => {
+                    for to in &transfer.to_infos {
+                        let capacity = to
+                            .amount
+                            .parse::<u64>()
+                            .map_err(|err| RpcErrorMessage::InvalidRpcParams(err.to_string()))?;
+                        if capacity < MIN_CKB_CAPACITY {
+                            return Err(RpcErrorMessage::RequiredCKBLessThanMin);
+                        }
+                        let address = Address::from_str(&to.address)
+                            .map_err(RpcErrorMessage::InvalidRpcParams)?;
+                        required_ckb += capacity;
+                        self.build_cell_for_output(
+                            capacity,
+                            address_to_script(address.payload()),
+                            None,
+                            None,
+                            &mut outputs,
+                            &mut cells_data,
+                        )?;
+                    }
+                    continue;
+                }
+                (AssetType::CKB, Mode::HoldByTo) => {
+                    for to in &transfer.to_infos {
+                        let capacity = to
+                            .amount
+                            .parse::<u64>()
+                            .map_err(|err| RpcErrorMessage::InvalidRpcParams(err.to_string()))?;
+                        let live_acp = self
+                            .get_recipient_acp_cell(
+                                ctx.clone(),
+                                &to.address,
+                                transfer.asset_info.clone(),
+                                &inputs,
+                            )
+                            .await?;
+                        let current_capacity: u64 = live_acp.cell_output.capacity().unpack();
+                        self.build_cell_for_output(
+                            current_capacity + capacity,
+                            live_acp.cell_output.lock(),
+                            live_acp.cell_output.type_().to_opt(),
+                            None,
+                            &mut outputs,
+                            &mut cells_data,
+                        )?;
+                        inputs.push(live_acp);
+                        input_index += 1;
+                        script_set.insert(ACP.to_string());
+
+                        required_ckb += capacity;
+                    }
+                    continue;
+                }
+                _ => (),
+            }
+
+            script_set.insert(utils::udt_script_name(&transfer.asset_info).to_string());
+            let sudt_type_script = self
+                .build_sudt_type_script(
+                    ctx.clone(),
+                    blake2b_256_to_160(&transfer.asset_info.udt_hash),
+                )
+                .await?;
+            let mut required_udt = 0;
+            for to in &transfer.to_infos {
+                let to_udt_amount = to
+                    .amount
+                    .parse::<u128>()
+                    .map_err(|err| RpcErrorMessage::InvalidRpcParams(err.to_string()))?;
+                required_udt += to_udt_amount;
+
+                if transfer.mode == Mode::HoldByTo {
+                    // pay the udt into the acp cell of the recipient
+                    let live_acp = self
+                        .get_recipient_acp_cell(
+                            ctx.clone(),
+                            &to.address,
+                            transfer.asset_info.clone(),
+                            &inputs,
+                        )
+                        .await?;
+                    let existing_udt_amount = decode_udt_amount(&live_acp.cell_data);
+                    self.build_udt_cell_for_output_from(
+                        &live_acp,
+                        existing_udt_amount + to_udt_amount,
+                        &mut outputs,
+                        &mut cells_data,
+                    )?;
+                    inputs.push(live_acp);
+                    input_index += 1;
+                    script_set.insert(ACP.to_string());
+                    continue;
+                }
+
+                // build the cheque output of the recipient
+                let receiver_address =
+                    Address::from_str(&to.address).map_err(RpcErrorMessage::InvalidRpcParams)?;
+                if !receiver_address.is_secp256k1() {
+                    return Err(RpcErrorMessage::InvalidRpcParams(
+                        "Every to address should be secp/256k1 address".to_string(),
+                    ));
+                }
+                let cheque_args =
+                    utils::build_cheque_args(receiver_address, sender_address.clone());
+                let cheque_lock = self
+                    .get_script_builder(CHEQUE)?
+                    .args(cheque_args)
+                    .hash_type(ScriptHashType::Type.into())
+                    .build();
+                self.build_cell_for_output(
+                    CHEQUE_CELL_CAPACITY,
+                    cheque_lock,
+                    Some(sudt_type_script.clone()),
+                    Some(to_udt_amount),
+                    &mut outputs,
+                    &mut cells_data,
+                )?;
+                script_set.insert(CHEQUE.to_string());
+
+                required_ckb += CHEQUE_CELL_CAPACITY;
+            }
+
+            // pool the udt and build the udt change cell
+            let mut pool_udt_amount: u128 = 0;
+            let mut udt_inputs = vec![];
+            self.build_required_udt_tx_part(
+                ctx.clone(),
+                from_items.clone(),
+                Some(payload.from.source.clone()),
+                transfer.asset_info.udt_hash.clone(),
+                required_udt,
+                &mut pool_udt_amount,
+                &mut udt_inputs,
+                &mut script_set,
+                &mut signature_actions,
+                &mut outputs,
+                &mut cells_data,
+                &mut input_index,
+                coin_selection,
+            )
+            .await?;
+            inputs.append(&mut udt_inputs);
+
+            if pool_udt_amount > required_udt {
+                self.build_cell_for_output(
+                    STANDARD_SUDT_CAPACITY,
+                    change_lock.clone(),
+                    Some(sudt_type_script),
+                    Some(pool_udt_amount - required_udt),
+                    &mut outputs,
+                    &mut cells_data,
+                )?;
+                required_ckb += STANDARD_SUDT_CAPACITY;
+            }
+        }
+
+        // pool ckb for all the outputs and the fee and build the ckb change cell
+        let mut ckb_inputs = vec![];
+        self.pool_live_cells_by_items(
+            ctx.clone(),
+            from_items,
+            required_ckb + fixed_fee + MIN_CKB_CAPACITY,
+            vec![],
+            Some(payload.from.source.clone()),
+            &mut 0,
+            &mut ckb_inputs,
+            &mut script_set,
+            &mut signature_actions,
+            &mut input_index,
+            coin_selection,
+        )
+        .await?;
+        let pool_capacity = get_pool_capacity(&ckb_inputs)?;
+        inputs.append(&mut ckb_inputs);
+        let change_fee_cell_index = self.build_cell_for_output(
+            pool_capacity - required_ckb - fixed_fee,
+            change_lock,
+            None,
+            None,
+            &mut outputs,
+            &mut cells_data,
+        )?;
+
+        // build resp
+        let inputs =
+            self.build_tx_cell_inputs(&inputs, payload.since.clone(), payload.from.source.clone())?;
+        self.fill_multisig_signature_actions(&payload.multisig_scripts, &mut signature_actions)?;
+        self.prebuild_tx_complete(
+            inputs,
+            outputs,
+            cells_data,
+            script_set,
+            vec![],
+            signature_actions,
+            HashMap::new(),
+        )
+        .map(|(tx_view, signature_actions)| (tx_view, signature_actions, change_fee_cell_index))
+    }
+
+    /// The live acp cell of the recipient holding the asset, which is not spent by
+    /// the inputs yet.
+    async fn get_recipient_acp_cell(
+        &self,
+        ctx: Context,
+        address: &str,
+        asset_info: AssetInfo,
+        inputs: &[DetailedCell],
+    ) -> InnerResult<DetailedCell> {
+        let item = Item::Identity(address_to_identity(address)?);
+        let mut asset_set = HashSet::new();
+        asset_set.insert(asset_info);
+        self.get_live_cells_by_item(
+            ctx.clone(),
+            item,
+            asset_set,
+            None,
+            None,
+            Some((**ACP_CODE_HASH.load()).clone()),
+            None,
+            false,
+        )
+        .await?
+        .into_iter()
+        .find(|cell| {
+            !self.is_in_cache(&cell.out_point)
+                && inputs.iter().all(|input| input.out_point != cell.out_point)
+        })
+        .ok_or(RpcErrorMessage::CannotFindACPCell)
+    }
+
+    #[tracing_async]
+    pub(crate) async fn build_transaction_with_adjusted_fee<'a, F, Fut, T>(
         &'a self,
         prebuild: F,
         ctx: Context,
         payload: T,
-        fee_rate: Option<u64>,
+        fee_rate: Option<FeeRate>,
+        dry_run: bool,
     ) -> InnerResult<TransactionCompletionResponse>
     where
         F: Fn(&'a MercuryRpcImpl<C>, Context, T, u64) -> Fut + Copy,
@@ -1375,16 +2124,14 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         T: Clone,
     {
         let mut estimate_fee = INIT_ESTIMATE_FEE;
-        let fee_rate = fee_rate.unwrap_or(DEFAULT_FEE_RATE);
+        let fee_rate = self.resolve_fee_rate(ctx.clone(), fee_rate).await?;
+        let mut reserve_retry = 0;
 
         loop {
             let (tx_view, signature_actions, change_cell_index) =
                 prebuild(self, ctx.clone(), payload.clone(), estimate_fee).await?;
             let tx_size = calculate_tx_size(tx_view.clone());
-            let mut actual_fee = fee_rate.saturating_mul(tx_size as u64) / 1000;
-            if actual_fee * 1000 < fee_rate.saturating_mul(tx_size as u64) {
-                actual_fee += 1;
-            }
+            let actual_fee = calculate_fee(fee_rate, tx_size as u64);
 
             if estimate_fee < actual_fee {
                 // increase estimate fee by 1 CKB
@@ -1399,6 +2146,23 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 )?;
                 let adjust_response =
                     TransactionCompletionResponse::new(tx_view, signature_actions);
+
+                // a dry run transaction is never sent, so its inputs are not reserved
+                if dry_run {
+                    return self
+                        .dry_run_transaction(ctx.clone(), adjust_response, &[])
+                        .await;
+                }
+
+                // the inputs are reserved by a concurrent build meanwhile, build again
+                // without them
+                if !self.reserve_inputs(&adjust_response) {
+                    reserve_retry += 1;
+                    if reserve_retry > MAX_RESERVE_RETRY {
+                        return Err(RpcErrorMessage::InputsReserved);
+                    }
+                    continue;
+                }
                 return Ok(adjust_response);
             }
         }
@@ -1479,7 +2243,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         }
     }
 
-    fn build_cell_for_output(
+    pub(crate) fn build_cell_for_output(
         &self,
         capacity: u64,
         lock_script: packed::Script,
@@ -1507,6 +2271,22 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         Ok(cell_index)
     }
 
+    /// Rebuild an existing udt cell with the updated amount, the lock, type and the xUDT
+    /// extension data of it are kept.
+    fn build_udt_cell_for_output_from(
+        &self,
+        cell: &DetailedCell,
+        udt_amount: u128,
+        outputs: &mut Vec<packed::CellOutput>,
+        cells_data: &mut Vec<packed::Bytes>,
+    ) -> InnerResult<usize> {
+        let cell_index = outputs.len();
+        outputs.push(cell.cell_output.clone());
+        cells_data.push(Bytes::from(update_udt_amount(&cell.cell_data, udt_amount)).pack());
+
+        Ok(cell_index)
+    }
+
     #[tracing_async]
     pub(crate) async fn build_sudt_type_script(
         &self,
@@ -1532,45 +2312,50 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         cells_data: Vec<packed::Bytes>,
         script_set: HashSet<String>,
         header_deps: Vec<packed::Byte32>,
-        signature_actions: HashMap<String, SignatureAction>,
+        mut signature_actions: HashMap<String, SignatureAction>,
         type_witness_args: HashMap<usize, (packed::BytesOpt, packed::BytesOpt)>,
     ) -> InnerResult<(TransactionView, Vec<SignatureAction>)> {
         // build cell deps
         let cell_deps = self.build_cell_deps(script_set)?;
 
+        // the multisig inputs whose script is not provided can not be signed
+        self.fill_multisig_signature_actions(&[], &mut signature_actions)?;
+
         // build witnesses
         let mut witnesses_map = HashMap::new();
         for sig_action in signature_actions.values() {
-            match sig_action.signature_info.algorithm {
-                SignAlgorithm::Secp256k1 => {
-                    let mut witness = packed::WitnessArgs::new_builder()
-                        .lock(Some(Bytes::from(vec![0u8; 65])).pack())
-                        .build();
-                    if let Some((input_type, output_type)) =
-                        type_witness_args.get(&sig_action.signature_location.index)
-                    {
-                        witness = witness
-                            .as_builder()
-                            .input_type(input_type.to_owned())
-                            .output_type(output_type.to_owned())
-                            .build()
-                    };
-                    witnesses_map.insert(sig_action.signature_location.index, witness);
-
-                    for other_index in &sig_action.other_indexes_in_group {
-                        let mut witness = packed::WitnessArgs::new_builder().build();
-                        if let Some((input_type, output_type)) = type_witness_args.get(other_index)
-                        {
-                            witness = witness
-                                .as_builder()
-                                .input_type(input_type.to_owned())
-                                .output_type(output_type.to_owned())
-                                .build()
-                        }
-                        witnesses_map.insert(*other_index, witness);
-                    }
-                }
+            let lock = match &sig_action.signature_info.multisig_script {
+                Some(multisig_script) => multisig_script.witness_placeholder(),
+                None => sig_action
+                    .signature_info
+                    .algorithm
+                    .witness_lock_placeholder(),
+            };
+            let mut witness = packed::WitnessArgs::new_builder()
+                .lock(Some(lock).pack())
+                .build();
+            if let Some((input_type, output_type)) =
+                type_witness_args.get(&sig_action.signature_location.index)
+            {
+                witness = witness
+                    .as_builder()
+                    .input_type(input_type.to_owned())
+                    .output_type(output_type.to_owned())
+                    .build()
             };
+            witnesses_map.insert(sig_action.signature_location.index, witness);
+
+            for other_index in &sig_action.other_indexes_in_group {
+                let mut witness = packed::WitnessArgs::new_builder().build();
+                if let Some((input_type, output_type)) = type_witness_args.get(other_index) {
+                    witness = witness
+                        .as_builder()
+                        .input_type(input_type.to_owned())
+                        .output_type(output_type.to_owned())
+                        .build()
+                }
+                witnesses_map.insert(*other_index, witness);
+            }
         }
         let mut witnesses = vec![];
         for (index, _) in inputs.iter().enumerate() {
@@ -1684,7 +2469,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
     }
 
     #[tracing_async]
-    async fn build_required_ckb_and_change_tx_part(
+    pub(crate) async fn build_required_ckb_and_change_tx_part(
         &self,
         ctx: Context,
         items: Vec<Item>,
@@ -1698,6 +2483,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         outputs: &mut Vec<packed::CellOutput>,
         cells_data: &mut Vec<packed::Bytes>,
         input_index: &mut usize,
+        coin_selection: CoinSelection,
     ) -> InnerResult<usize> {
         let required_ckb = if let Some(udt_info) = &udt_change_info {
             if udt_info.amount != 0 {
@@ -1720,6 +2506,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             script_set,
             signature_actions,
             input_index,
+            coin_selection,
         )
         .await?;
 
@@ -1765,7 +2552,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
     }
 
     #[tracing_async]
-    async fn build_required_udt_tx_part(
+    pub(crate) async fn build_required_udt_tx_part(
         &self,
         ctx: Context,
         from_items: Vec<Item>,
@@ -1779,6 +2566,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         outputs: &mut Vec<packed::CellOutput>,
         cells_data: &mut Vec<packed::Bytes>,
         input_index: &mut usize,
+        coin_selection: CoinSelection,
     ) -> InnerResult<()> {
         self.pool_live_cells_by_items(
             ctx.clone(),
@@ -1794,6 +2582,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             script_set,
             signature_actions,
             input_index,
+            coin_selection,
         )
         .await?;
 
@@ -1824,14 +2613,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     cells_data,
                 )?;
             } else if code_hash == **ACP_CODE_HASH.load() {
-                self.build_cell_for_output(
-                    cell.cell_output.capacity().unpack(),
-                    cell.cell_output.lock(),
-                    cell.cell_output.type_().to_opt(),
-                    Some(0),
-                    outputs,
-                    cells_data,
-                )?;
+                self.build_udt_cell_for_output_from(cell, 0, outputs, cells_data)?;
             } else {
                 self.build_cell_for_output(
                     cell.cell_output.capacity().unpack(),
@@ -1864,3 +2646,25 @@ pub(crate) fn calculate_tx_size(tx_view: TransactionView) -> usize {
     // tx offset bytesize
     tx_size + 4
 }
+
+/// The index of the withdrawn udt cell paying the fee of a cheque withdrawal, which is the
+/// one with the most capacity. None if it cannot afford the fee besides its occupied
+/// capacity.
+pub(crate) fn cheque_withdraw_fee_index(capacities: &[u64], fee: u64) -> Option<usize> {
+    capacities
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, capacity)| **capacity)
+        .filter(|(_, capacity)| **capacity >= STANDARD_SUDT_CAPACITY + fee)
+        .map(|(index, _)| index)
+}
+
+/// The fee rate is in shannons per KB, the fee is rounded up.
+pub(crate) fn calculate_fee(fee_rate: u64, tx_size: u64) -> u64 {
+    let fee = fee_rate.saturating_mul(tx_size) / 1000;
+    if fee * 1000 < fee_rate.saturating_mul(tx_size) {
+        fee + 1
+    } else {
+        fee
+    }
+}
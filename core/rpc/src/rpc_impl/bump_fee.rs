@@ -0,0 +1,320 @@
+use crate::error::{InnerResult, RpcErrorMessage};
+use crate::rpc_impl::utils::add_signature_action;
+use crate::rpc_impl::{address_to_script, calculate_fee, calculate_tx_size, RESERVED_INPUTS};
+use crate::types::{
+    BumpFeePayload, HashAlgorithm, IdentityFlag, MultisigScript, SignAlgorithm, SignatureAction,
+    TransactionCompletionResponse,
+};
+use crate::{CkbRpc, MercuryRpcImpl};
+
+use common::{Address, AddressPayload, Context, DetailedCell, ACP, CHEQUE, SECP256K1};
+use common_logger::tracing_async;
+use core_storage::Storage;
+
+use ckb_jsonrpc_types::Status;
+use ckb_types::core::{Capacity, TransactionView};
+use ckb_types::{packed, prelude::*, H160, H256};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    /// Rebuild a pending transaction spending the same inputs with a higher fee, which is
+    /// taken out of the change output. The new fee is at least the fee of the pending
+    /// transaction plus the `min_rbf_rate` of the node, so that the node accepts the
+    /// replacement.
+    #[tracing_async]
+    pub(crate) async fn inner_build_bump_fee_transaction(
+        &self,
+        ctx: Context,
+        payload: BumpFeePayload,
+    ) -> InnerResult<TransactionCompletionResponse> {
+        let tx = self
+            .ckb_client
+            .get_transactions(vec![payload.tx_hash.clone()])
+            .await
+            .map_err(|e| RpcErrorMessage::CkbClientError(e.to_string()))?
+            .into_iter()
+            .next()
+            .flatten()
+            .ok_or(RpcErrorMessage::CannotFindTransactionByHash)?;
+        // a proposed transaction cannot be replaced any more
+        if !matches!(tx.tx_status.status, Status::Pending) {
+            return Err(RpcErrorMessage::CannotBumpFee(
+                "the transaction is not pending".to_string(),
+            ));
+        }
+
+        let wrapper = self
+            .get_pending_transaction_wrapper(ctx.clone(), tx)
+            .await?;
+        let tx_view = wrapper.transaction_view;
+        let input_capacity = wrapper
+            .input_cells
+            .iter()
+            .map(|cell| Unpack::<u64>::unpack(&cell.cell_output.capacity()))
+            .try_fold(0u64, |acc, capacity| acc.checked_add(capacity))
+            .ok_or(RpcErrorMessage::Overflow)?;
+        let output_capacity = tx_view
+            .outputs_capacity()
+            .map_err(|e| RpcErrorMessage::OccupiedCapacityError(e.to_string()))?
+            .as_u64();
+        let old_fee = input_capacity.checked_sub(output_capacity).ok_or_else(|| {
+            RpcErrorMessage::CannotBumpFee("the outputs exceed the inputs".to_string())
+        })?;
+        let change_index =
+            self.get_bump_fee_change_index(&payload, &tx_view, &wrapper.input_cells)?;
+
+        let (tx_view, signature_actions) = self
+            .rebuild_witnesses(ctx.clone(), &tx_view, &wrapper.input_cells)
+            .await?;
+        let tx_size = calculate_tx_size(tx_view.clone()) as u64;
+        let fee_rate = self.resolve_fee_rate(ctx.clone(), payload.fee_rate).await?;
+        let min_rbf_rate: u64 = self
+            .ckb_client
+            .get_tx_pool_info()
+            .await
+            .map_err(|e| RpcErrorMessage::CkbClientError(e.to_string()))?
+            .min_rbf_rate
+            .ok_or_else(|| {
+                RpcErrorMessage::CannotBumpFee(
+                    "the node does not support replace by fee".to_string(),
+                )
+            })?
+            .into();
+        let new_fee = calculate_bump_fee(old_fee, fee_rate, min_rbf_rate, tx_size)?;
+
+        let change = tx_view
+            .output(change_index)
+            .ok_or(RpcErrorMessage::CannotFindChangeCell)?;
+        let change_data = tx_view.outputs_data().get(change_index).unwrap_or_default();
+        let occupied = Capacity::bytes(change_data.raw_data().len())
+            .and_then(|data_capacity| change.occupied_capacity(data_capacity))
+            .map_err(|e| RpcErrorMessage::OccupiedCapacityError(e.to_string()))?
+            .as_u64();
+        let change_capacity = Unpack::<u64>::unpack(&change.capacity())
+            .checked_sub(new_fee - old_fee)
+            .filter(|capacity| *capacity >= occupied)
+            .ok_or_else(|| {
+                RpcErrorMessage::CannotBumpFee("the change cannot afford the fee".to_string())
+            })?;
+
+        let mut outputs = tx_view.outputs().into_iter().collect::<Vec<_>>();
+        outputs[change_index] = change.as_builder().capacity(change_capacity.pack()).build();
+        let tx_view = tx_view.as_advanced_builder().set_outputs(outputs).build();
+        let response = TransactionCompletionResponse::new(tx_view.into(), signature_actions);
+
+        if payload.dry_run {
+            return self.dry_run_transaction(ctx.clone(), response, &[]).await;
+        }
+
+        // the inputs reserved for the replaced transaction are taken over
+        RESERVED_INPUTS.write().release(&payload.tx_hash);
+        if !self.reserve_inputs(&response) {
+            return Err(RpcErrorMessage::InputsReserved);
+        }
+        Ok(response)
+    }
+
+    /// The change output is the last output of the change address, or the last output
+    /// without type script owned by an input lock if the change address is not given.
+    fn get_bump_fee_change_index(
+        &self,
+        payload: &BumpFeePayload,
+        tx_view: &TransactionView,
+        input_cells: &[DetailedCell],
+    ) -> InnerResult<usize> {
+        let outputs = tx_view
+            .outputs()
+            .into_iter()
+            .enumerate()
+            .collect::<Vec<_>>();
+        let index = match &payload.change {
+            Some(address) => {
+                let address =
+                    Address::from_str(address).map_err(RpcErrorMessage::InvalidRpcParams)?;
+                let change_lock = address_to_script(address.payload());
+                outputs
+                    .iter()
+                    .rev()
+                    .find(|(_, output)| output.lock() == change_lock)
+                    .map(|(index, _)| *index)
+            }
+            None => outputs
+                .iter()
+                .rev()
+                .find(|(_, output)| {
+                    output.type_().to_opt().is_none()
+                        && input_cells
+                            .iter()
+                            .any(|cell| cell.cell_output.lock() == output.lock())
+                })
+                .map(|(index, _)| *index),
+        };
+
+        index.ok_or(RpcErrorMessage::CannotFindChangeCell)
+    }
+
+    /// Fill the signature placeholders in the witnesses which were signed, and build the
+    /// signature actions of them. The other witnesses are kept.
+    async fn rebuild_witnesses(
+        &self,
+        ctx: Context,
+        tx_view: &TransactionView,
+        input_cells: &[DetailedCell],
+    ) -> InnerResult<(TransactionView, Vec<SignatureAction>)> {
+        let mut witnesses = tx_view.witnesses().into_iter().collect::<Vec<_>>();
+        let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
+        let mut multisig_scripts = vec![];
+
+        for (index, cell) in input_cells.iter().enumerate() {
+            let lock_hash = cell.cell_output.calc_lock_hash().to_string();
+            if let Some(action) = signature_actions.get_mut(&lock_hash) {
+                action.add_group(index);
+                continue;
+            }
+
+            let witness_args = witnesses
+                .get(index)
+                .and_then(|witness| packed::WitnessArgs::from_slice(&witness.raw_data()).ok());
+            let witness_args = match witness_args {
+                Some(witness_args) if witness_args.lock().to_opt().is_some() => witness_args,
+                // the lock is unlocked without signature, such as an acp cell receiving
+                _ => continue,
+            };
+
+            let since: u64 = tx_view
+                .inputs()
+                .get(index)
+                .map(|input| input.since().unpack())
+                .unwrap_or_default();
+            let witness_lock = witness_args.lock().to_opt().unwrap_or_default().raw_data();
+            let (address, sign_algorithm, multisig_script) = self
+                .get_signer(ctx.clone(), cell, since, &witness_lock)
+                .await?;
+            let placeholder = match multisig_script {
+                Some(multisig_script) => {
+                    let placeholder = multisig_script.witness_placeholder();
+                    multisig_scripts.push(multisig_script);
+                    placeholder
+                }
+                None => sign_algorithm.witness_lock_placeholder(),
+            };
+            add_signature_action(
+                address,
+                lock_hash,
+                sign_algorithm,
+                HashAlgorithm::Blake2b,
+                &mut signature_actions,
+                index,
+            );
+            witnesses[index] = witness_args
+                .as_builder()
+                .lock(Some(placeholder).pack())
+                .build()
+                .as_bytes()
+                .pack();
+        }
+        self.fill_multisig_signature_actions(&multisig_scripts, &mut signature_actions)?;
+
+        let mut signature_actions: Vec<SignatureAction> =
+            signature_actions.into_iter().map(|(_, s)| s).collect();
+        signature_actions.sort_unstable();
+        let tx_view = tx_view
+            .as_advanced_builder()
+            .set_witnesses(witnesses)
+            .build();
+        Ok((tx_view, signature_actions))
+    }
+
+    /// The address and the sign algorithm of the signer of an input. A cheque cell is
+    /// signed by the sender when it is withdrawn with since, otherwise by the receiver. The
+    /// multisig script of a multisig input is parsed from the witness lock signed before.
+    pub(crate) async fn get_signer(
+        &self,
+        ctx: Context,
+        cell: &DetailedCell,
+        since: u64,
+        witness_lock: &[u8],
+    ) -> InnerResult<(String, SignAlgorithm, Option<MultisigScript>)> {
+        let lock = cell.cell_output.lock();
+        let args = lock.args().raw_data();
+        let code_hash: H256 = lock.code_hash().unpack();
+        let invalid_args =
+            || RpcErrorMessage::CannotBumpFee(format!("invalid args of lock {}", code_hash));
+
+        if self.is_script(&lock, SECP256K1)? {
+            return Ok((
+                self.script_to_address(&lock).to_string(),
+                SignAlgorithm::Secp256k1,
+                None,
+            ));
+        }
+        if self.is_multisig_script(&lock) {
+            let multisig_script = MultisigScript::from_witness_lock(witness_lock)
+                .filter(|script| args.len() >= 20 && script.lock_args().as_bytes() == &args[0..20])
+                .ok_or_else(|| {
+                    RpcErrorMessage::CannotBumpFee(
+                        "the multisig script is not found in the witness".to_string(),
+                    )
+                })?;
+            return Ok((
+                self.script_to_address(&lock).to_string(),
+                SignAlgorithm::Secp256k1,
+                Some(multisig_script),
+            ));
+        }
+        if self.is_omni_lock_script(&lock) {
+            let flag = args.get(0).ok_or_else(invalid_args)?;
+            let sign_algorithm = IdentityFlag::try_from(*flag)?.omni_lock_sign_algorithm()?;
+            return Ok((
+                self.script_to_address(&lock).to_string(),
+                sign_algorithm,
+                None,
+            ));
+        }
+        if self.is_script(&lock, ACP)? {
+            let pubkey_hash = H160::from_slice(args.get(0..20).ok_or_else(invalid_args)?)
+                .map_err(|_| invalid_args())?;
+            let address = Address::new(
+                self.network_type,
+                AddressPayload::from_pubkey_hash(self.network_type, pubkey_hash),
+                true,
+            );
+            return Ok((address.to_string(), SignAlgorithm::Secp256k1, None));
+        }
+        if self.is_script(&lock, CHEQUE)? {
+            let range = if since != 0 { 20..40 } else { 0..20 };
+            let lock_hash = H160::from_slice(args.get(range).ok_or_else(invalid_args)?)
+                .map_err(|_| invalid_args())?;
+            let scripts = self
+                .storage
+                .get_scripts(ctx, vec![lock_hash], vec![], None, vec![])
+                .await
+                .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?;
+            let address = scripts
+                .get(0)
+                .map(|script| self.script_to_address(script).to_string())
+                .ok_or(RpcErrorMessage::CannotFindAddressByH160)?;
+            // a cheque cell is owned by secp256k1 addresses only
+            return Ok((address, SignAlgorithm::Secp256k1, None));
+        }
+
+        Err(RpcErrorMessage::UnsupportLockScript(code_hash.to_string()))
+    }
+}
+
+/// The fee of a replacing transaction is the fee at `fee_rate`, and no less than the fee of
+/// the replaced one plus the fee at `min_rbf_rate`.
+pub(crate) fn calculate_bump_fee(
+    old_fee: u64,
+    fee_rate: u64,
+    min_rbf_rate: u64,
+    tx_size: u64,
+) -> InnerResult<u64> {
+    let min_fee = old_fee
+        .checked_add(calculate_fee(min_rbf_rate, tx_size))
+        .ok_or(RpcErrorMessage::Overflow)?;
+    Ok(calculate_fee(fee_rate, tx_size).max(min_fee))
+}
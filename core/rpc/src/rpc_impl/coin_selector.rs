@@ -0,0 +1,156 @@
+use crate::types::CoinSelection;
+
+use common::DetailedCell;
+
+/// The maximum number of the search steps of branch and bound, the selection falls back to
+/// largest first when no exact match is found in time.
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// A cell that can be pooled and the amount of the required asset it provides.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub cell: DetailedCell,
+    pub amount: u128,
+}
+
+/// A coin selector decides which candidate cells are pooled and in which order. The
+/// returned cells are pooled one by one until the required amount is satisfied.
+pub trait CoinSelector {
+    fn select(&self, candidates: Vec<Candidate>, required: u128) -> Vec<Candidate>;
+}
+
+/// Keep the order that the cells are returned by the storage.
+pub struct Sequential;
+
+impl CoinSelector for Sequential {
+    fn select(&self, candidates: Vec<Candidate>, _required: u128) -> Vec<Candidate> {
+        candidates
+    }
+}
+
+pub struct LargestFirst;
+
+impl CoinSelector for LargestFirst {
+    fn select(&self, mut candidates: Vec<Candidate>, _required: u128) -> Vec<Candidate> {
+        candidates.sort_by(|a, b| b.amount.cmp(&a.amount));
+        candidates
+    }
+}
+
+/// Pool the small cells first, which cleans up the dust cells of an address.
+pub struct SmallestFirst;
+
+impl CoinSelector for SmallestFirst {
+    fn select(&self, mut candidates: Vec<Candidate>, _required: u128) -> Vec<Candidate> {
+        candidates.sort_by(|a, b| a.amount.cmp(&b.amount));
+        candidates
+    }
+}
+
+/// Use the smallest cell that covers the required amount alone, otherwise the largest
+/// cells first.
+pub struct MinimizeInputs;
+
+impl CoinSelector for MinimizeInputs {
+    fn select(&self, candidates: Vec<Candidate>, required: u128) -> Vec<Candidate> {
+        let mut candidates = LargestFirst.select(candidates, required);
+        if let Some(index) = candidates
+            .iter()
+            .rposition(|candidate| candidate.amount >= required)
+        {
+            let candidate = candidates.remove(index);
+            candidates.insert(0, candidate);
+        }
+        candidates
+    }
+}
+
+/// Search the cells whose sum is exactly the required amount, so that no change is left.
+pub struct BranchAndBound {
+    pub max_tries: usize,
+}
+
+impl CoinSelector for BranchAndBound {
+    fn select(&self, candidates: Vec<Candidate>, required: u128) -> Vec<Candidate> {
+        let candidates = LargestFirst.select(candidates, required);
+
+        // the sum of the amount from each index to the end bounds the search
+        let mut remaining_sum = vec![0u128; candidates.len() + 1];
+        for (i, candidate) in candidates.iter().enumerate().rev() {
+            remaining_sum[i] = remaining_sum[i + 1].saturating_add(candidate.amount);
+        }
+
+        let mut picked = vec![];
+        let mut tries = 0;
+        if self.search(
+            &candidates,
+            &remaining_sum,
+            0,
+            required,
+            &mut picked,
+            &mut tries,
+        ) {
+            return picked.into_iter().map(|i| candidates[i].clone()).collect();
+        }
+
+        candidates
+    }
+}
+
+impl BranchAndBound {
+    fn search(
+        &self,
+        candidates: &[Candidate],
+        remaining_sum: &[u128],
+        index: usize,
+        required: u128,
+        picked: &mut Vec<usize>,
+        tries: &mut usize,
+    ) -> bool {
+        if required == 0 {
+            return true;
+        }
+        if index == candidates.len() || remaining_sum[index] < required || *tries >= self.max_tries
+        {
+            return false;
+        }
+        *tries += 1;
+
+        let amount = candidates[index].amount;
+        if amount <= required {
+            picked.push(index);
+            if self.search(
+                candidates,
+                remaining_sum,
+                index + 1,
+                required - amount,
+                picked,
+                tries,
+            ) {
+                return true;
+            }
+            picked.pop();
+        }
+
+        self.search(
+            candidates,
+            remaining_sum,
+            index + 1,
+            required,
+            picked,
+            tries,
+        )
+    }
+}
+
+pub fn coin_selector(selection: CoinSelection) -> Box<dyn CoinSelector> {
+    match selection {
+        CoinSelection::Sequential => Box::new(Sequential),
+        CoinSelection::LargestFirst => Box::new(LargestFirst),
+        CoinSelection::SmallestFirst => Box::new(SmallestFirst),
+        CoinSelection::MinimizeInputs => Box::new(MinimizeInputs),
+        CoinSelection::BranchAndBound => Box::new(BranchAndBound {
+            max_tries: BNB_MAX_TRIES,
+        }),
+    }
+}
@@ -0,0 +1,280 @@
+use crate::error::{InnerResult, RpcErrorMessage};
+use crate::rpc_impl::utils::add_signature_action;
+use crate::rpc_impl::{
+    address_to_script, utils, CHEQUE_CODE_HASH, MAX_ITEM_NUM, MIN_CKB_CAPACITY,
+    SECP256K1_CODE_HASH, STANDARD_SUDT_CAPACITY,
+};
+use crate::types::{
+    AddressOrLockHash, AssetInfo, AssetType, ConsolidatePayload, HashAlgorithm, Item,
+    SignAlgorithm, SignatureAction, Source, TransactionCompletionResponse,
+};
+use crate::{CkbRpc, MercuryRpcImpl};
+
+use common::utils::decode_udt_amount;
+use common::{Address, Context, DetailedCell, CHEQUE, SECP256K1};
+use common_logger::tracing_async;
+
+use ckb_types::core::TransactionView;
+use ckb_types::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// The default and the least minimum number of the live cells to consolidate.
+const DEFAULT_CONSOLIDATE_THRESHOLD: u64 = 2;
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    #[tracing_async]
+    pub(crate) async fn inner_build_consolidate_transaction(
+        &self,
+        ctx: Context,
+        payload: ConsolidatePayload,
+    ) -> InnerResult<Option<TransactionCompletionResponse>> {
+        if payload.max_input_num == Some(0) {
+            return Err(RpcErrorMessage::InvalidRpcParams(
+                "max_input_num should be positive".to_string(),
+            ));
+        }
+
+        let threshold = payload.threshold.unwrap_or(DEFAULT_CONSOLIDATE_THRESHOLD);
+        if threshold < DEFAULT_CONSOLIDATE_THRESHOLD {
+            return Err(RpcErrorMessage::InvalidRpcParams(format!(
+                "threshold should be at least {}",
+                DEFAULT_CONSOLIDATE_THRESHOLD
+            )));
+        }
+
+        let item = Item::try_from(payload.item.clone())?;
+        let cells = self
+            .get_consolidate_cells(ctx.clone(), item, &payload.asset_info)
+            .await?;
+        if (cells.len() as u64) < threshold {
+            return Ok(None);
+        }
+
+        self.build_transaction_with_adjusted_fee(
+            Self::prebuild_consolidate_transaction,
+            ctx.clone(),
+            payload.clone(),
+            payload.fee_rate,
+            payload.dry_run,
+        )
+        .await
+        .map(Some)
+    }
+
+    async fn prebuild_consolidate_transaction(
+        &self,
+        ctx: Context,
+        payload: ConsolidatePayload,
+        fixed_fee: u64,
+    ) -> InnerResult<(TransactionView, Vec<SignatureAction>, usize)> {
+        let item = Item::try_from(payload.item.clone())?;
+        let owner_address = self.get_secp_address_by_item(item.clone())?;
+        let to_lock = match &payload.to {
+            Some(address) => {
+                let address = self.get_secp_address_by_item(Item::Address(address.clone()))?;
+                address_to_script(address.payload())
+            }
+            None => address_to_script(owner_address.payload()),
+        };
+        let max_input_num = payload
+            .max_input_num
+            .map(|num| num as usize)
+            .unwrap_or(MAX_ITEM_NUM)
+            .min(MAX_ITEM_NUM);
+
+        let mut cells = self
+            .get_consolidate_cells(ctx.clone(), item.clone(), &payload.asset_info)
+            .await?;
+        cells.truncate(max_input_num);
+        if cells.is_empty() {
+            return Err(RpcErrorMessage::TokenIsNotEnough(
+                payload.asset_info.to_string(),
+            ));
+        }
+
+        let mut script_set = HashSet::new();
+        let (mut outputs, mut cells_data) = (vec![], vec![]);
+        let mut signature_actions: HashMap<String, SignatureAction> = HashMap::new();
+        let mut swept_capacity = 0u64;
+        let mut swept_udt_amount = 0u128;
+        let mut type_script = None;
+
+        for (input_index, cell) in cells.iter().enumerate() {
+            let lock = cell.cell_output.lock();
+            let address = if self.is_script(&lock, SECP256K1)? {
+                script_set.insert(SECP256K1.to_string());
+                swept_capacity += Unpack::<u64>::unpack(&cell.cell_output.capacity());
+                self.script_to_address(&lock).to_string()
+            } else {
+                // the capacity of a cheque cell is returned to the sender
+                script_set.insert(CHEQUE.to_string());
+                let sender_address = match self
+                    .generate_ckb_address_or_lock_hash(ctx.clone(), cell)
+                    .await?
+                {
+                    AddressOrLockHash::Address(address) => address,
+                    AddressOrLockHash::LockHash(_) => {
+                        return Err(RpcErrorMessage::CannotFindAddressByH160)
+                    }
+                };
+                let sender_address = Address::from_str(&sender_address)
+                    .map_err(RpcErrorMessage::InvalidRpcParams)?;
+                self.build_cell_for_output(
+                    cell.cell_output.capacity().unpack(),
+                    address_to_script(sender_address.payload()),
+                    None,
+                    None,
+                    &mut outputs,
+                    &mut cells_data,
+                )?;
+                owner_address.to_string()
+            };
+
+            if let Some(script) = cell.cell_output.type_().to_opt() {
+                script_set.insert(utils::udt_script_name_by_type(&script).to_string());
+                swept_udt_amount = swept_udt_amount
+                    .checked_add(decode_udt_amount(&cell.cell_data))
+                    .ok_or(RpcErrorMessage::Overflow)?;
+                type_script = Some(script);
+            }
+
+            add_signature_action(
+                address,
+                lock.calc_script_hash().to_string(),
+                SignAlgorithm::Secp256k1,
+                HashAlgorithm::Blake2b,
+                &mut signature_actions,
+                input_index,
+            );
+        }
+
+        // the fee is paid by the swept capacity. The capacity of the swept cheque cells is
+        // returned to the senders, so the udt cell and the fee may be paid by the free ckb
+        // of the item, with a change cell
+        let (min_capacity, pool_ckb) = if type_script.is_some() {
+            (STANDARD_SUDT_CAPACITY, true)
+        } else {
+            // the free ckb cells are the swept ones
+            (MIN_CKB_CAPACITY, false)
+        };
+        let capacity = swept_capacity
+            .checked_sub(fixed_fee)
+            .filter(|capacity| *capacity >= min_capacity);
+        let udt_amount = type_script.as_ref().map(|_| swept_udt_amount);
+        let consolidated_index = self.build_cell_for_output(
+            capacity.unwrap_or_else(|| swept_capacity.max(min_capacity)),
+            to_lock,
+            type_script,
+            udt_amount,
+            &mut outputs,
+            &mut cells_data,
+        )?;
+
+        let mut inputs = self.build_tx_cell_inputs(&cells, None, Source::Claimable)?;
+        let change_cell_index = match capacity {
+            Some(_) => consolidated_index,
+            None if pool_ckb => {
+                let required_ckb = min_capacity.saturating_sub(swept_capacity) + fixed_fee;
+                let mut fee_inputs = vec![];
+                let mut input_index = cells.len();
+                let change_cell_index = self
+                    .build_required_ckb_and_change_tx_part(
+                        ctx.clone(),
+                        vec![item],
+                        Some(Source::Free),
+                        required_ckb,
+                        None,
+                        None,
+                        &mut fee_inputs,
+                        &mut script_set,
+                        &mut signature_actions,
+                        &mut outputs,
+                        &mut cells_data,
+                        &mut input_index,
+                        self.coin_selection,
+                    )
+                    .await?;
+                inputs.extend(self.build_tx_cell_inputs(&fee_inputs, None, Source::Free)?);
+                change_cell_index
+            }
+            None => return Err(RpcErrorMessage::RequiredCKBLessThanMin),
+        };
+
+        self.prebuild_tx_complete(
+            inputs,
+            outputs,
+            cells_data,
+            script_set,
+            vec![],
+            signature_actions,
+            HashMap::new(),
+        )
+        .map(|(tx_view, signature_actions)| (tx_view, signature_actions, change_cell_index))
+    }
+
+    /// The live cells of an item which can be consolidated, that are the free secp256k1 cells
+    /// of CKB, or the secp256k1 cells and the claimable cheque cells of a UDT. The small
+    /// cells are consolidated first.
+    async fn get_consolidate_cells(
+        &self,
+        ctx: Context,
+        item: Item,
+        asset_info: &AssetInfo,
+    ) -> InnerResult<Vec<DetailedCell>> {
+        let mut asset_set = HashSet::new();
+        asset_set.insert(asset_info.clone());
+
+        let mut cells = self
+            .get_live_cells_by_item(
+                ctx.clone(),
+                item.clone(),
+                asset_set.clone(),
+                None,
+                None,
+                Some((**SECP256K1_CODE_HASH.load()).clone()),
+                None,
+                false,
+            )
+            .await?;
+
+        if asset_info.asset_type == AssetType::CKB {
+            cells.retain(|cell| {
+                cell.cell_data.is_empty()
+                    && cell.cell_output.type_().to_opt().is_none()
+                    && (cell.tx_index != 0 || self.is_cellbase_mature(cell))
+            });
+        } else {
+            let receiver_lock_hash = self.get_secp_lock_hash_by_item(item.clone())?;
+            let cheque_cells = self
+                .get_live_cells_by_item(
+                    ctx.clone(),
+                    item,
+                    asset_set,
+                    None,
+                    None,
+                    Some((**CHEQUE_CODE_HASH.load()).clone()),
+                    None,
+                    false,
+                )
+                .await?
+                .into_iter()
+                .filter(|cell| {
+                    cell.cell_output.lock().args().raw_data().get(0..20)
+                        == Some(&receiver_lock_hash.0[..])
+                });
+            cells.extend(cheque_cells);
+        }
+
+        cells.retain(|cell| !self.is_in_cache(&cell.out_point));
+        if asset_info.asset_type == AssetType::CKB {
+            cells.sort_by_key(|cell| Unpack::<u64>::unpack(&cell.cell_output.capacity()));
+        } else {
+            cells.sort_by_key(|cell| decode_udt_amount(&cell.cell_data));
+        }
+
+        Ok(cells)
+    }
+}
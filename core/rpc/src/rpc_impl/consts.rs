@@ -8,6 +8,20 @@ pub const DEFAULT_FEE_RATE: u64 = 1000;
 pub const MAX_ITEM_NUM: usize = 1000;
 pub const MIN_DAO_CAPACITY: u64 = 200 * BYTE_SHANNONS;
 pub const MIN_DAO_LOCK_PERIOD: u64 = 180;
+pub const CHAIN_EVENT_CHANNEL_SIZE: usize = 1024;
+pub const MAX_RESERVE_RETRY: usize = 3;
+pub const FEE_RATE_ESTIMATE_BLOCKS: u64 = 100;
+pub const MAX_FEE_RATE_ESTIMATE_BLOCKS: u64 = 1000;
+pub const FEE_RATE_PAGE_LIMIT: u64 = 500;
+
+// The cycles are estimated before the transactions are signed, so that a batch transfer
+// is split into the transactions fitting in a block. The type scripts of each transaction
+// are then run to check the estimation, the locks cannot be run before signing.
+pub const ESTIMATED_LOCK_CYCLES: u64 = 2_000_000;
+pub const ESTIMATED_UDT_CYCLES: u64 = 500_000;
+pub const ESTIMATED_UDT_CELL_CYCLES: u64 = 20_000;
+// The block header and the cellbase also take the space of a block.
+pub const BLOCK_RESERVED_BYTES: u64 = 1_000;
 
 pub const fn ckb(num: u64) -> u64 {
     num * BYTE_SHANNONS
@@ -0,0 +1,298 @@
+use crate::error::{InnerResult, RpcErrorMessage};
+use crate::rpc_impl::{
+    calculate_tx_size, DEFAULT_FEE_RATE, FEE_RATE_ESTIMATE_BLOCKS, FEE_RATE_PAGE_LIMIT,
+    MAX_FEE_RATE_ESTIMATE_BLOCKS,
+};
+use crate::types::{EstimateFeeRatePayload, EstimateFeeRateResponse, FeeRate, FeeRateTarget};
+use crate::{CkbRpc, MercuryRpcImpl};
+
+use common::{Context, Order, PaginationRequest, Range};
+use common_logger::tracing_async;
+use core_storage::Storage;
+use protocol::TransactionWrapper;
+
+use ckb_jsonrpc_types::RawTxPool;
+use ckb_types::{core::BlockNumber, prelude::*, H256};
+
+use std::collections::BTreeMap;
+
+// The percentiles of the sampled fee rates that the targets are resolved to.
+const FAST_PERCENTILE: usize = 80;
+const NORMAL_PERCENTILE: usize = 50;
+const SLOW_PERCENTILE: usize = 20;
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    #[tracing_async]
+    pub(crate) async fn inner_estimate_fee_rate(
+        &self,
+        ctx: Context,
+        payload: EstimateFeeRatePayload,
+    ) -> InnerResult<EstimateFeeRateResponse> {
+        let block_count = payload.block_count.unwrap_or(FEE_RATE_ESTIMATE_BLOCKS);
+        if block_count == 0 || block_count > MAX_FEE_RATE_ESTIMATE_BLOCKS {
+            return Err(RpcErrorMessage::InvalidRpcParams(format!(
+                "block_count should be between 1 and {}",
+                MAX_FEE_RATE_ESTIMATE_BLOCKS
+            )));
+        }
+
+        let committed_rates = self
+            .get_committed_fee_rates(ctx.clone(), block_count)
+            .await?;
+        let pending_rates = self.get_pending_fee_rates().await?;
+
+        Ok(estimate_fee_rates(committed_rates, pending_rates))
+    }
+
+    /// Resolve the fee rate of a build payload, a target is resolved by the fee rates
+    /// estimated from the recent blocks and the tx pool. The tx pool is skipped if it
+    /// cannot be queried, so that the build does not depend on the node.
+    pub(crate) async fn resolve_fee_rate(
+        &self,
+        ctx: Context,
+        fee_rate: Option<FeeRate>,
+    ) -> InnerResult<u64> {
+        let target = match fee_rate {
+            None => return Ok(DEFAULT_FEE_RATE),
+            Some(FeeRate::Value(value)) => return Ok(value),
+            Some(FeeRate::Target(target)) => target,
+        };
+
+        let committed_rates = self
+            .get_committed_fee_rates(ctx, FEE_RATE_ESTIMATE_BLOCKS)
+            .await?;
+        let pending_rates = self.get_pending_fee_rates().await.unwrap_or_else(|e| {
+            log::warn!("[fee rate] estimate without the tx pool: {}", e);
+            vec![]
+        });
+
+        let estimated = estimate_fee_rates(committed_rates, pending_rates);
+        Ok(match target {
+            FeeRateTarget::Fast => estimated.fast,
+            FeeRateTarget::Normal => estimated.normal,
+            FeeRateTarget::Slow => estimated.slow,
+        })
+    }
+
+    /// The fee rates of the transactions committed in the recent blocks. The fee rates of a
+    /// block are cached, so that only the blocks appended since the last estimation are
+    /// loaded.
+    pub(crate) async fn get_committed_fee_rates(
+        &self,
+        ctx: Context,
+        block_count: u64,
+    ) -> InnerResult<Vec<u64>> {
+        let tip_number = match self
+            .storage
+            .get_tip(ctx.clone())
+            .await
+            .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?
+        {
+            Some((number, _)) => number,
+            None => return Ok(vec![]),
+        };
+
+        // the cached blocks are contiguous, so they are all on the canonical chain if the
+        // highest one is, otherwise the chain is reorganized and the cache is dropped
+        let cached_tip = self.fee_rate_cache.read().tip();
+        if let Some((number, hash)) = cached_tip {
+            let canonical_hash = if number <= tip_number {
+                self.storage
+                    .get_canonical_block_hash(ctx.clone(), number)
+                    .await
+                    .ok()
+            } else {
+                None
+            };
+            if canonical_hash != Some(hash) {
+                self.fee_rate_cache.write().clear();
+            }
+        }
+
+        let from = tip_number.saturating_sub(block_count - 1);
+        let uncached_ranges = self.fee_rate_cache.read().uncached_ranges(from, tip_number);
+        for range in uncached_ranges {
+            let blocks = self.get_block_fee_rates(ctx.clone(), range).await?;
+            let mut cache = self.fee_rate_cache.write();
+            for (number, (hash, rates)) in blocks {
+                cache.insert(number, hash, rates);
+            }
+        }
+
+        let mut cache = self.fee_rate_cache.write();
+        cache.prune(tip_number.saturating_sub(MAX_FEE_RATE_ESTIMATE_BLOCKS - 1));
+        Ok(cache.rates(from, tip_number))
+    }
+
+    /// Load the transactions of the blocks in pages, and sample the fee rates of them by
+    /// block.
+    async fn get_block_fee_rates(
+        &self,
+        ctx: Context,
+        range: Range,
+    ) -> InnerResult<BTreeMap<BlockNumber, (H256, Vec<u64>)>> {
+        let mut blocks = BTreeMap::new();
+        let mut cursor = None;
+
+        loop {
+            let pagination =
+                PaginationRequest::new(cursor, Order::Asc, Some(FEE_RATE_PAGE_LIMIT), None, false);
+            let page = self
+                .storage
+                .get_transactions(
+                    ctx.clone(),
+                    vec![],
+                    vec![],
+                    vec![],
+                    Some(range.clone()),
+                    pagination,
+                )
+                .await
+                .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?;
+
+            for tx in page.response.iter() {
+                // every block has a cellbase, so that a block without other transactions
+                // is cached too
+                let cell = match tx.output_cells.first() {
+                    Some(cell) => cell,
+                    None => continue,
+                };
+                let (_, rates) = blocks
+                    .entry(cell.block_number)
+                    .or_insert_with(|| (cell.block_hash.clone(), vec![]));
+                if let Some(rate) = committed_fee_rate(tx) {
+                    rates.push(rate);
+                }
+            }
+
+            if page.response.is_empty() || page.next_cursor.is_none() {
+                break;
+            }
+            cursor = page.next_cursor;
+        }
+
+        Ok(blocks)
+    }
+
+    async fn get_pending_fee_rates(&self) -> InnerResult<Vec<u64>> {
+        let raw_pool = self
+            .ckb_client
+            .get_raw_tx_pool(Some(true))
+            .await
+            .map_err(|e| RpcErrorMessage::CkbClientError(e.to_string()))?;
+
+        let entries = match raw_pool {
+            RawTxPool::Verbose(entries) => entries,
+            RawTxPool::Ids(_) => return Ok(vec![]),
+        };
+
+        Ok(entries
+            .pending
+            .values()
+            .chain(entries.proposed.values())
+            .filter(|entry| entry.size.value() > 0)
+            .map(|entry| entry.fee.value().saturating_mul(1000) / entry.size.value())
+            .collect())
+    }
+}
+
+/// The cellbases and the transactions whose outputs exceed the inputs, that are
+/// withdrawing from DAO, are not sampled.
+fn committed_fee_rate(tx: &TransactionWrapper) -> Option<u64> {
+    if tx.is_cellbase {
+        return None;
+    }
+
+    let inputs_capacity: u64 = tx
+        .input_cells
+        .iter()
+        .map(|cell| Unpack::<u64>::unpack(&cell.cell_output.capacity()))
+        .sum();
+    let outputs_capacity: u64 = tx
+        .output_cells
+        .iter()
+        .map(|cell| Unpack::<u64>::unpack(&cell.cell_output.capacity()))
+        .sum();
+    let fee = inputs_capacity.checked_sub(outputs_capacity)?;
+    let tx_size = calculate_tx_size(tx.transaction_view.clone()) as u64;
+    Some(fee.saturating_mul(1000) / tx_size)
+}
+
+fn estimate_fee_rates(
+    committed_rates: Vec<u64>,
+    pending_rates: Vec<u64>,
+) -> EstimateFeeRateResponse {
+    let (committed_tx_count, pending_tx_count) =
+        (committed_rates.len() as u64, pending_rates.len() as u64);
+
+    let mut rates = committed_rates;
+    rates.extend(pending_rates);
+    rates.sort_unstable();
+
+    EstimateFeeRateResponse {
+        fast: percentile(&rates, FAST_PERCENTILE),
+        normal: percentile(&rates, NORMAL_PERCENTILE),
+        slow: percentile(&rates, SLOW_PERCENTILE),
+        committed_tx_count,
+        pending_tx_count,
+    }
+}
+
+/// The nearest rank percentile of the sorted fee rates, which is at least the default fee
+/// rate accepted by the nodes.
+pub(crate) fn percentile(sorted_rates: &[u64], percent: usize) -> u64 {
+    if sorted_rates.is_empty() {
+        return DEFAULT_FEE_RATE;
+    }
+
+    let index = (sorted_rates.len() - 1) * percent / 100;
+    sorted_rates[index].max(DEFAULT_FEE_RATE)
+}
+
+/// The fee rates sampled from the recent blocks by block number. The cached blocks are
+/// contiguous, since they are loaded by ranges ending at the tip.
+#[derive(Default, Debug)]
+pub struct FeeRateCache {
+    blocks: BTreeMap<BlockNumber, (H256, Vec<u64>)>,
+}
+
+impl FeeRateCache {
+    pub fn tip(&self) -> Option<(BlockNumber, H256)> {
+        self.blocks
+            .iter()
+            .next_back()
+            .map(|(number, (hash, _))| (*number, hash.clone()))
+    }
+
+    pub fn insert(&mut self, block_number: BlockNumber, block_hash: H256, rates: Vec<u64>) {
+        self.blocks.insert(block_number, (block_hash, rates));
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// Drop the blocks lower than `from`.
+    pub fn prune(&mut self, from: BlockNumber) {
+        self.blocks = self.blocks.split_off(&from);
+    }
+
+    /// The ranges of the blocks from `from` to `to` which are not cached.
+    pub fn uncached_ranges(&self, from: BlockNumber, to: BlockNumber) -> Vec<Range> {
+        let mut ranges: Vec<Range> = vec![];
+        for number in (from..=to).filter(|number| !self.blocks.contains_key(number)) {
+            match ranges.last_mut() {
+                Some(range) if range.to + 1 == number => range.to = number,
+                _ => ranges.push(Range::new(number, number)),
+            }
+        }
+        ranges
+    }
+
+    pub fn rates(&self, from: BlockNumber, to: BlockNumber) -> Vec<u64> {
+        self.blocks
+            .range(from..=to)
+            .flat_map(|(_, (_, rates))| rates.iter().copied())
+            .collect()
+    }
+}
@@ -0,0 +1,327 @@
+use crate::error::{InnerResult, RpcErrorMessage};
+use crate::rpc_impl::address_to_script;
+use crate::types::{
+    MergeSignaturesPayload, PartiallySignedTransaction, ResolvedInputCell, SignAlgorithm,
+    SignatureAction, TransactionCompletionResponse, SECP256K1_WITNESS_LOCATION,
+};
+use crate::{CkbRpc, MercuryRpcImpl};
+
+use common::hash::{blake2b_160, new_blake2b, Blake2b};
+use common::secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use common::secp256k1::{Message, Secp256k1};
+use common::{utils::parse_address, Context};
+use common_logger::tracing_async;
+
+use ckb_jsonrpc_types::{JsonBytes, Transaction};
+use ckb_types::core::TransactionView;
+use ckb_types::{bytes::Bytes, packed, prelude::*, H160};
+
+use std::ops::Range;
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    #[tracing_async]
+    pub(crate) async fn inner_build_partially_signed_transaction(
+        &self,
+        ctx: Context,
+        payload: TransactionCompletionResponse,
+    ) -> InnerResult<PartiallySignedTransaction> {
+        let tx_view = packed::Transaction::from(payload.tx_view.inner).into_view();
+        let input_cells = self
+            .get_pending_input_cells(ctx, &tx_view)
+            .await?
+            .into_iter()
+            .map(|cell| ResolvedInputCell {
+                out_point: cell.out_point.into(),
+                output: cell.cell_output.into(),
+                output_data: JsonBytes::from_bytes(cell.cell_data),
+            })
+            .collect();
+
+        let witnesses = tx_view.witnesses().into_iter().collect::<Vec<_>>();
+        let mut signature_actions = vec![];
+        for action in payload.signature_actions.into_iter() {
+            if !is_signed(&action, &witnesses)? {
+                signature_actions.push(action);
+            }
+        }
+
+        Ok(PartiallySignedTransaction {
+            tx_view: tx_view.into(),
+            input_cells,
+            signature_actions,
+        })
+    }
+
+    /// Fill the signatures in the witnesses of their signature actions. A signature is
+    /// accepted only if it is signed by the signer of the action over the sighash-all
+    /// message, and a multisig action is fulfilled when all the `threshold` signatures of
+    /// its pubkey hashes are filled.
+    #[tracing_async]
+    pub(crate) async fn inner_merge_signatures(
+        &self,
+        _ctx: Context,
+        payload: MergeSignaturesPayload,
+    ) -> InnerResult<PartiallySignedTransaction> {
+        let tx_view = check_partially_signed_transaction(&payload.transaction)?;
+        let mut witnesses = tx_view.witnesses().into_iter().collect::<Vec<_>>();
+        let signature_actions = payload.transaction.signature_actions;
+
+        for signature in payload.signatures.into_iter() {
+            let signature_bytes = signature.signature.into_bytes();
+            if signature_bytes.len() != SECP256K1_WITNESS_LOCATION.1 {
+                return Err(RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+                    "the signature of {} should be {} bytes",
+                    signature.address, SECP256K1_WITNESS_LOCATION.1
+                )));
+            }
+
+            let action = signature_actions
+                .iter()
+                .find(|action| action.signature_info.address == signature.address)
+                .ok_or_else(|| {
+                    RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+                        "no outstanding signature action of {}",
+                        signature.address
+                    ))
+                })?;
+            let signers = get_signer_pubkey_hashes(action)?;
+            let message = signing_message(&tx_view, action, &witnesses)?;
+            let signer = recover_pubkey_hash(&message, &signature_bytes)
+                .filter(|signer| signers.contains(signer))
+                .ok_or_else(|| {
+                    RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+                        "the signature is not signed by {}",
+                        signature.address
+                    ))
+                })?;
+            let index = action.signature_location.index;
+            let (witness_args, lock, range) = locate_signatures(action, &witnesses)?;
+
+            let mut lock = lock.to_vec();
+            let slots = lock[range.clone()].chunks(SECP256K1_WITNESS_LOCATION.1);
+            // a signer merged twice is ignored
+            if slots
+                .clone()
+                .filter_map(|slot| recover_pubkey_hash(&message, slot))
+                .any(|filled_signer| filled_signer == signer)
+            {
+                continue;
+            }
+            let slot = slots
+                .position(|slot| slot.iter().all(|byte| *byte == 0))
+                .ok_or_else(|| {
+                    RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+                        "the signatures of {} are already filled",
+                        signature.address
+                    ))
+                })?;
+            let start = range.start + slot * SECP256K1_WITNESS_LOCATION.1;
+            lock[start..start + SECP256K1_WITNESS_LOCATION.1].copy_from_slice(&signature_bytes);
+
+            witnesses[index] = witness_args
+                .as_builder()
+                .lock(Some(Bytes::from(lock)).pack())
+                .build()
+                .as_bytes()
+                .pack();
+        }
+
+        let mut outstanding_actions = vec![];
+        for action in signature_actions.into_iter() {
+            if !is_signed(&action, &witnesses)? {
+                outstanding_actions.push(action);
+            }
+        }
+        let tx_view = tx_view
+            .as_advanced_builder()
+            .set_witnesses(witnesses)
+            .build();
+
+        Ok(PartiallySignedTransaction {
+            tx_view: tx_view.into(),
+            input_cells: payload.transaction.input_cells,
+            signature_actions: outstanding_actions,
+        })
+    }
+
+    #[tracing_async]
+    pub(crate) async fn inner_finalize_transaction(
+        &self,
+        _ctx: Context,
+        transaction: PartiallySignedTransaction,
+    ) -> InnerResult<Transaction> {
+        let tx_view = check_partially_signed_transaction(&transaction)?;
+        let witnesses = tx_view.witnesses().into_iter().collect::<Vec<_>>();
+
+        for action in transaction.signature_actions.iter() {
+            if !is_signed(action, &witnesses)? {
+                return Err(RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+                    "the signature of {} is missing",
+                    action.signature_info.address
+                )));
+            }
+        }
+
+        Ok(tx_view.data().into())
+    }
+}
+
+/// The resolved input cells should be the ones spent by the transaction, in order.
+fn check_partially_signed_transaction(
+    transaction: &PartiallySignedTransaction,
+) -> InnerResult<TransactionView> {
+    let tx_view = packed::Transaction::from(transaction.tx_view.inner.clone()).into_view();
+    let matched = tx_view.inputs().len() == transaction.input_cells.len()
+        && tx_view
+            .input_pts_iter()
+            .zip(transaction.input_cells.iter())
+            .all(|(out_point, cell)| out_point == packed::OutPoint::from(cell.out_point.clone()));
+    if !matched {
+        return Err(RpcErrorMessage::InvalidPartiallySignedTransaction(
+            "the input cells do not match the inputs".to_string(),
+        ));
+    }
+
+    Ok(tx_view)
+}
+
+fn is_signed(action: &SignatureAction, witnesses: &[packed::Bytes]) -> InnerResult<bool> {
+    let (_, lock, range) = locate_signatures(action, witnesses)?;
+    Ok(lock[range]
+        .chunks(SECP256K1_WITNESS_LOCATION.1)
+        .all(|slot| slot.iter().any(|byte| *byte != 0)))
+}
+
+/// The pubkey hashes allowed to sign an action, which are the pubkey hashes of the multisig
+/// script, or the lock args of the address. Only the secp256k1 signatures can be verified.
+fn get_signer_pubkey_hashes(action: &SignatureAction) -> InnerResult<Vec<H160>> {
+    let info = &action.signature_info;
+    if info.algorithm != SignAlgorithm::Secp256k1 {
+        return Err(RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+            "cannot verify the {:?} signature of {}",
+            info.algorithm, info.address
+        )));
+    }
+
+    let address = parse_address(&info.address)
+        .map_err(|e| RpcErrorMessage::ParseAddressError(e.to_string()))?;
+    let args = address_to_script(address.payload()).args().raw_data();
+    if args.len() < 20 {
+        return Err(RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+            "the lock args of {} is too short",
+            info.address
+        )));
+    }
+    let pubkey_hash = H160::from_slice(&args[0..20]).expect("lock args");
+
+    match &info.multisig_script {
+        Some(multisig_script) if multisig_script.lock_args() == pubkey_hash => {
+            Ok(multisig_script.pubkey_hashes.clone())
+        }
+        Some(_) => Err(RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+            "the multisig script of {} mismatches the lock args",
+            info.address
+        ))),
+        None => Ok(vec![pubkey_hash]),
+    }
+}
+
+/// The sighash-all message of an action, which hashes the transaction hash, the first
+/// witness of the group with the signatures zeroed, the other witnesses of the group and
+/// the witnesses without inputs.
+pub(crate) fn signing_message(
+    tx_view: &TransactionView,
+    action: &SignatureAction,
+    witnesses: &[packed::Bytes],
+) -> InnerResult<[u8; 32]> {
+    let (witness_args, lock, range) = locate_signatures(action, witnesses)?;
+    let mut lock = lock.to_vec();
+    lock[range].iter_mut().for_each(|byte| *byte = 0);
+    let first_witness = witness_args
+        .as_builder()
+        .lock(Some(Bytes::from(lock)).pack())
+        .build()
+        .as_bytes();
+
+    let mut blake2b = new_blake2b();
+    blake2b.update(tx_view.hash().as_slice());
+    hash_witness(&mut blake2b, &first_witness);
+    for index in action.other_indexes_in_group.iter() {
+        let witness = witnesses
+            .get(*index)
+            .map(|witness| witness.raw_data())
+            .unwrap_or_default();
+        hash_witness(&mut blake2b, &witness);
+    }
+    for witness in witnesses.iter().skip(tx_view.inputs().len()) {
+        hash_witness(&mut blake2b, &witness.raw_data());
+    }
+
+    let mut message = [0u8; 32];
+    blake2b.finalize(&mut message);
+    Ok(message)
+}
+
+fn hash_witness(blake2b: &mut Blake2b, witness: &[u8]) {
+    blake2b.update(&(witness.len() as u64).to_le_bytes());
+    blake2b.update(witness);
+}
+
+/// Recover the blake160 hash of the compressed pubkey from a recoverable signature.
+fn recover_pubkey_hash(message: &[u8; 32], signature: &[u8]) -> Option<H160> {
+    if signature.len() != SECP256K1_WITNESS_LOCATION.1 {
+        return None;
+    }
+
+    let recovery_id = RecoveryId::from_i32(signature[64] as i32).ok()?;
+    let signature = RecoverableSignature::from_compact(&signature[0..64], recovery_id).ok()?;
+    let message = Message::from_slice(message).ok()?;
+    let pubkey = Secp256k1::verification_only()
+        .recover(&message, &signature)
+        .ok()?;
+    Some(H160(blake2b_160(&pubkey.serialize()[..])))
+}
+
+/// Locate the signatures of an action in the witness lock. The witness lock starts at
+/// `SECP256K1_WITNESS_LOCATION.0` of the witness, so the offset of the action should not
+/// be ahead of it, and the signatures should fit in the witness lock.
+fn locate_signatures(
+    action: &SignatureAction,
+    witnesses: &[packed::Bytes],
+) -> InnerResult<(packed::WitnessArgs, Bytes, Range<usize>)> {
+    let index = action.signature_location.index;
+    let (offset, length) = match &action.signature_info.multisig_script {
+        Some(multisig_script) => multisig_script.get_signature_offset(),
+        None => action.signature_info.algorithm.get_signature_offset(),
+    };
+    if action.signature_location.offset != offset || offset < SECP256K1_WITNESS_LOCATION.0 {
+        return Err(RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+            "the signature offset {} of witness {} should be {}",
+            action.signature_location.offset, index, offset
+        )));
+    }
+
+    let witness_args = witnesses
+        .get(index)
+        .and_then(|witness| packed::WitnessArgs::from_slice(&witness.raw_data()).ok())
+        .ok_or_else(|| {
+            RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+                "witness {} is not a witness args",
+                index
+            ))
+        })?;
+    let lock = witness_args
+        .lock()
+        .to_opt()
+        .map(|lock| lock.raw_data())
+        .unwrap_or_default();
+    let start = offset - SECP256K1_WITNESS_LOCATION.0;
+    if start + length > lock.len() {
+        return Err(RpcErrorMessage::InvalidPartiallySignedTransaction(format!(
+            "the signatures do not fit in the lock of witness {}",
+            index
+        )));
+    }
+
+    Ok((witness_args, lock, start..start + length))
+}
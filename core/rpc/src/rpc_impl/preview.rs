@@ -0,0 +1,176 @@
+use crate::error::{InnerResult, RpcErrorMessage};
+use crate::rpc_impl::tx_pool::build_pending_cells;
+use crate::rpc_impl::utils::deduct_balances;
+use crate::rpc_impl::CURRENT_EPOCH_NUMBER;
+use crate::types::{
+    AddressOrLockHash, AssetInfo, Balance, BalanceChange, GetBalancePayload, JsonItem,
+    PreviewTransactionResponse, Record,
+};
+use crate::{CkbRpc, MercuryRpcImpl};
+
+use common::Context;
+use common_logger::tracing_async;
+use core_storage::Storage;
+
+use ckb_jsonrpc_types::{TransactionView, TransactionWithStatus};
+use ckb_types::{packed, prelude::*};
+use num_bigint::BigInt;
+use num_traits::Zero;
+use protocol::TransactionWrapper;
+
+use std::collections::{HashMap, HashSet};
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    /// Preview the balance changes of a transaction which is not sent yet. The outputs are
+    /// treated as if the transaction were committed in the block after the tip.
+    #[tracing_async]
+    pub(crate) async fn inner_preview_transaction(
+        &self,
+        ctx: Context,
+        transaction: TransactionView,
+    ) -> InnerResult<PreviewTransactionResponse> {
+        let tx_view = packed::Transaction::from(transaction.inner).into_view();
+        let tip = self
+            .storage
+            .get_block_header(ctx.clone(), None, None)
+            .await
+            .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?;
+
+        let tx_with_status = TransactionWithStatus::with_pending(tx_view.clone());
+        let output_cells = build_pending_cells(&tx_view, tip.number(), tip.epoch().full_value());
+        let input_cells = self.get_pending_input_cells(ctx.clone(), &tx_view).await?;
+
+        let wrapper = TransactionWrapper {
+            transaction_with_status: tx_with_status,
+            transaction_view: tx_view,
+            input_cells,
+            output_cells,
+            is_cellbase: false,
+        };
+        let transaction = self.query_transaction_info(ctx.clone(), &wrapper).await?;
+        let balance_changes = get_balance_changes(&transaction.records)?;
+        let balances = self
+            .get_previewed_balances(ctx, &transaction.records, &balance_changes)
+            .await?;
+
+        Ok(PreviewTransactionResponse {
+            transaction,
+            balance_changes,
+            balances,
+        })
+    }
+
+    /// The balances after the transaction is committed. The output records are accumulated
+    /// to the current balances and the input records are deducted from them. The lock
+    /// hashes without address are skipped, since their balances cannot be queried.
+    async fn get_previewed_balances(
+        &self,
+        ctx: Context,
+        records: &[Record],
+        balance_changes: &[BalanceChange],
+    ) -> InnerResult<Vec<Balance>> {
+        let mut asset_infos_map: HashMap<String, HashSet<AssetInfo>> = HashMap::new();
+        for change in balance_changes {
+            if let AddressOrLockHash::Address(address) = &change.address_or_lock_hash {
+                asset_infos_map
+                    .entry(address.clone())
+                    .or_insert_with(HashSet::new)
+                    .insert(change.asset_info.clone());
+            }
+        }
+
+        let mut balances_map: HashMap<(AddressOrLockHash, AssetInfo), Balance> = HashMap::new();
+        for (address, asset_infos) in asset_infos_map.into_iter() {
+            let payload = GetBalancePayload {
+                item: JsonItem::Address(address),
+                asset_infos,
+                tip_block_number: None,
+            };
+            let balances = self.inner_get_balance(ctx.clone(), payload).await?.balances;
+            for balance in balances.into_iter() {
+                let key = (
+                    balance.address_or_lock_hash.clone(),
+                    balance.asset_info.clone(),
+                );
+                balances_map.insert(key, balance);
+            }
+        }
+
+        // the amount of an input record is negative
+        let (input_records, output_records): (Vec<Record>, Vec<Record>) = records
+            .iter()
+            .cloned()
+            .partition(|record| record.amount.starts_with('-'));
+        let spent_records = input_records
+            .into_iter()
+            .map(|mut record| {
+                record.amount = record.amount.trim_start_matches('-').to_string();
+                record
+            })
+            .collect::<Vec<_>>();
+
+        let tip_epoch_number = (**CURRENT_EPOCH_NUMBER.load()).clone();
+        let mut spent_map: HashMap<(AddressOrLockHash, AssetInfo), Balance> = HashMap::new();
+        self.accumulate_balance_from_records(
+            ctx.clone(),
+            &mut spent_map,
+            &spent_records,
+            Some(tip_epoch_number.clone()),
+        )
+        .await?;
+        self.accumulate_balance_from_records(
+            ctx,
+            &mut balances_map,
+            &output_records,
+            Some(tip_epoch_number),
+        )
+        .await?;
+        deduct_balances(&mut balances_map, spent_map)?;
+
+        Ok(balance_changes
+            .iter()
+            .filter(|change| matches!(change.address_or_lock_hash, AddressOrLockHash::Address(_)))
+            .filter_map(|change| {
+                balances_map
+                    .get(&(
+                        change.address_or_lock_hash.clone(),
+                        change.asset_info.clone(),
+                    ))
+                    .cloned()
+            })
+            .collect())
+    }
+}
+
+/// Sum the amounts of the records by owner and asset, in the order they first appear.
+pub(crate) fn get_balance_changes(records: &[Record]) -> InnerResult<Vec<BalanceChange>> {
+    let mut changes: Vec<(AddressOrLockHash, AssetInfo, BigInt)> = vec![];
+    for record in records {
+        let amount = record.amount.parse::<BigInt>().map_err(|e| {
+            RpcErrorMessage::InvalidRpcParams(format!(
+                "invalid record amount {}: {}",
+                record.amount, e
+            ))
+        })?;
+        match changes.iter_mut().find(|(owner, asset_info, _)| {
+            *owner == record.address_or_lock_hash && *asset_info == record.asset_info
+        }) {
+            Some((_, _, total)) => *total += amount,
+            None => changes.push((
+                record.address_or_lock_hash.clone(),
+                record.asset_info.clone(),
+                amount,
+            )),
+        }
+    }
+
+    Ok(changes
+        .into_iter()
+        .filter(|(_, _, amount)| !amount.is_zero())
+        .map(|(address_or_lock_hash, asset_info, amount)| BalanceChange {
+            address_or_lock_hash,
+            asset_info,
+            amount: amount.to_string(),
+        })
+        .collect())
+}
@@ -1,17 +1,23 @@
 use crate::error::{InnerResult, RpcErrorMessage};
-use crate::rpc_impl::{address_to_script, CURRENT_BLOCK_NUMBER, CURRENT_EPOCH_NUMBER};
+use crate::rpc_impl::tx_pool::to_transaction_status;
+use crate::rpc_impl::utils::deduct_balances;
+use crate::rpc_impl::{
+    CHEQUE_CODE_HASH, CURRENT_BLOCK_NUMBER, CURRENT_EPOCH_NUMBER, PENDING_TX_CACHE, SUDT_CODE_HASH,
+    XUDT_CODE_HASH,
+};
 use crate::types::{
-    indexer, indexer_legacy, AddressOrLockHash, AssetInfo, Balance, BlockInfo, BurnInfo,
-    GetBalancePayload, GetBalanceResponse, GetBlockInfoPayload, GetSpentTransactionPayload,
-    GetTransactionInfoResponse, IOType, Item, QueryTransactionsPayload, Record, StructureType,
+    indexer, indexer_legacy, AddressOrLockHash, AdvanceQueryPayload, AssetInfo, Balance, BlockInfo,
+    BurnInfo, GetBalancePayload, GetBalanceResponse, GetBlockInfoPayload,
+    GetSpentTransactionPayload, GetTransactionInfoResponse, IOType, Item, QueryResponse,
+    QueryTransactionsPayload, QueryType, Record, ScriptWrapper, Status, StructureType,
     TransactionInfo, TransactionStatus, TxView,
 };
 use crate::{CkbRpc, MercuryRpcImpl};
 
-use common::utils::parse_address;
-use common::{Context, Order, PaginationRequest, PaginationResponse, Range, SECP256K1};
+use common::utils::{decode_udt_amount, u256_low_u64};
+use common::{Context, DetailedCell, Order, PaginationRequest, PaginationResponse, Range, DAO};
 use common_logger::tracing_async;
-use core_storage::{DBInfo, Storage};
+use core_storage::{CellFilter, CellIOType, DBInfo, ScriptBalance, Storage};
 
 use ckb_jsonrpc_types::{self, Capacity, Script, Uint64};
 use ckb_types::{bytes::Bytes, packed, prelude::*, H160, H256};
@@ -20,7 +26,7 @@ use num_traits::{ToPrimitive, Zero};
 
 use protocol::TransactionWrapper;
 use std::collections::{HashMap, HashSet};
-use std::{convert::TryInto, iter::Iterator, str::FromStr};
+use std::{convert::TryInto, iter::Iterator};
 
 impl<C: CkbRpc> MercuryRpcImpl<C> {
     pub(crate) fn inner_get_db_info(&self, ctx: Context) -> InnerResult<DBInfo> {
@@ -51,22 +57,36 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         } else {
             payload.asset_infos.clone()
         };
-        let live_cells = self
-            .get_live_cells_by_item(
-                ctx.clone(),
-                item.clone(),
-                asset_infos,
-                payload.tip_block_number,
-                tip_epoch_number.clone(),
-                None,
-                None,
-                true,
-            )
-            .await?;
-
         let mut balances_map: HashMap<(AddressOrLockHash, AssetInfo), Balance> = HashMap::new();
 
-        let secp_lock_hash = self.get_secp_lock_hash_by_item(item)?;
+        let secp_lock_hash = self.get_secp_lock_hash_by_item(item.clone())?;
+
+        // the balance table only knows the live cells of the tip
+        let live_cells = match (&item, payload.tip_block_number) {
+            (Item::Identity(_), None) | (Item::Address(_), None) => {
+                self.accumulate_balance_from_table(
+                    ctx.clone(),
+                    item.clone(),
+                    &payload.asset_infos,
+                    &secp_lock_hash,
+                    &mut balances_map,
+                )
+                .await?
+            }
+            _ => {
+                self.get_live_cells_by_item(
+                    ctx.clone(),
+                    item.clone(),
+                    asset_infos,
+                    payload.tip_block_number,
+                    tip_epoch_number.clone(),
+                    None,
+                    None,
+                    true,
+                )
+                .await?
+            }
+        };
 
         for cell in live_cells {
             let records = self
@@ -82,31 +102,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             // filter record, remain the one that owned by item.
             let records: Vec<Record> = records
                 .into_iter()
-                .filter(|record| {
-                    match &record.address_or_lock_hash {
-                        AddressOrLockHash::Address(address) => {
-                            // unwrap here is ok, because if this address is invalid, it will throw error for more earlier.
-                            let address = parse_address(address).unwrap();
-                            let args: Bytes = address_to_script(address.payload()).args().unpack();
-                            let lock_hash: H256 = self
-                                .get_script_builder(SECP256K1)
-                                .unwrap()
-                                .args(Bytes::from((&args[0..20]).to_vec()).pack())
-                                .build()
-                                .calc_script_hash()
-                                .unpack();
-                            secp_lock_hash == H160::from_slice(&lock_hash.0[0..20]).unwrap()
-                        }
-                        AddressOrLockHash::LockHash(lock_hash) => {
-                            secp_lock_hash
-                                == H160::from_str(lock_hash)
-                                    .map_err(|_| {
-                                        RpcErrorMessage::InvalidScriptHash(lock_hash.clone())
-                                    })
-                                    .unwrap()
-                        }
-                    }
-                })
+                .filter(|record| self.is_record_owned_by(record, &secp_lock_hash))
                 .filter(|record| {
                     payload.asset_infos.contains(&record.asset_info)
                         || payload.asset_infos.is_empty()
@@ -122,6 +118,64 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             .await?;
         }
 
+        // the committed cells consumed by the tx pool are deducted, and the outputs of the
+        // transactions in the tx pool are counted as pending
+        if payload.tip_block_number.is_none() {
+            let lock_hashes = self.get_lock_hashes_by_item(ctx.clone(), item).await?;
+            let (spent_cells, pending_cells) = {
+                let cache = PENDING_TX_CACHE.read();
+                (
+                    cache.get_spent_cells_by_lock_hashes(&lock_hashes),
+                    cache.get_cells_by_lock_hashes(&lock_hashes),
+                )
+            };
+
+            let mut spent_balances_map = HashMap::new();
+            for cell in spent_cells {
+                let records = self
+                    .to_record(ctx.clone(), &cell, IOType::Output, None, None)
+                    .await?
+                    .into_iter()
+                    .filter(|record| self.is_record_owned_by(record, &secp_lock_hash))
+                    .filter(|record| {
+                        payload.asset_infos.contains(&record.asset_info)
+                            || payload.asset_infos.is_empty()
+                    })
+                    .collect::<Vec<_>>();
+                self.accumulate_balance_from_records(
+                    ctx.clone(),
+                    &mut spent_balances_map,
+                    &records,
+                    None,
+                )
+                .await?;
+            }
+            deduct_balances(&mut balances_map, spent_balances_map)?;
+
+            for cell in pending_cells {
+                let records = match self
+                    .to_record(ctx.clone(), &cell, IOType::Output, None, None)
+                    .await
+                {
+                    Ok(records) => records,
+                    Err(e) => {
+                        log::warn!("[get balance] skip pending cell {}", e);
+                        continue;
+                    }
+                };
+                let records: Vec<Record> = records
+                    .into_iter()
+                    .filter(|record| self.is_record_owned_by(record, &secp_lock_hash))
+                    .filter(|record| {
+                        payload.asset_infos.contains(&record.asset_info)
+                            || payload.asset_infos.is_empty()
+                    })
+                    .collect();
+
+                self.accumulate_pending_balance_from_records(&mut balances_map, &records);
+            }
+        }
+
         let balances = balances_map
             .into_iter()
             .map(|(_, balance)| balance)
@@ -135,6 +189,220 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         })
     }
 
+    /// Accumulate the balances of the item from the balance table, which sums up the
+    /// live cells by lock script and type script. The balance of a DAO cell, an immature
+    /// cellbase cell or a cheque cell depends on the tip, so these cells are subtracted
+    /// from the sums and returned to be accumulated from their records.
+    #[tracing_async]
+    async fn accumulate_balance_from_table(
+        &self,
+        ctx: Context,
+        item: Item,
+        asset_infos: &HashSet<AssetInfo>,
+        secp_lock_hash: &H160,
+        balances_map: &mut HashMap<(AddressOrLockHash, AssetInfo), Balance>,
+    ) -> InnerResult<Vec<DetailedCell>> {
+        let ckb_asset_info = AssetInfo::new_ckb();
+        let cheque_code_hash = (**CHEQUE_CODE_HASH.load()).clone();
+        let lock_scripts: HashMap<H256, packed::Script> = self
+            .get_lock_scripts_by_item(ctx.clone(), item.clone())
+            .await?
+            .into_iter()
+            .filter(|script| {
+                let code_hash: H256 = script.code_hash().unpack();
+                code_hash != cheque_code_hash
+            })
+            .map(|script| (script.calc_script_hash().unpack(), script))
+            .collect();
+        let lock_hashes = lock_scripts.keys().cloned().collect::<Vec<_>>();
+
+        let cheque_asset_infos = if asset_infos.contains(&ckb_asset_info) {
+            HashSet::new()
+        } else {
+            asset_infos.clone()
+        };
+        let mut special_cells = self
+            .get_live_cells_by_item(
+                ctx.clone(),
+                item,
+                cheque_asset_infos,
+                None,
+                None,
+                Some(cheque_code_hash),
+                None,
+                true,
+            )
+            .await?;
+        if lock_hashes.is_empty() {
+            return Ok(special_cells);
+        }
+
+        if asset_infos.is_empty() || asset_infos.contains(&ckb_asset_info) {
+            let dao_type_hash: H256 = self
+                .builtin_scripts
+                .get(DAO)
+                .cloned()
+                .ok_or_else(|| RpcErrorMessage::MissingScriptInfo(DAO.to_string()))?
+                .script
+                .calc_script_hash()
+                .unpack();
+            let dao_cells = self
+                .get_live_cells(
+                    ctx.clone(),
+                    None,
+                    lock_hashes.clone(),
+                    vec![dao_type_hash],
+                    None,
+                    None,
+                    PaginationRequest::default(),
+                )
+                .await?;
+            special_cells.extend(dao_cells.response);
+
+            // a cellbase cell older than the maturity plus one epoch is always unlocked
+            let maturity_epochs = u256_low_u64(self.cellbase_maturity.clone().into_u256()) + 1;
+            let tip_block_number = **CURRENT_BLOCK_NUMBER.load();
+            let recent_range = Range::new(
+                self.get_epoch_start_number(ctx.clone(), tip_block_number, maturity_epochs)
+                    .await?,
+                tip_block_number,
+            );
+            let recent_cells = self
+                .get_live_cells(
+                    ctx.clone(),
+                    None,
+                    lock_hashes.clone(),
+                    vec![],
+                    None,
+                    Some(recent_range),
+                    PaginationRequest::default(),
+                )
+                .await?;
+            special_cells.extend(
+                recent_cells
+                    .response
+                    .into_iter()
+                    .filter(|cell| cell.tx_index == 0 && cell.cell_output.type_().is_none()),
+            );
+        }
+
+        let mut balances: HashMap<(H256, Option<H256>), ScriptBalance> = self
+            .storage
+            .get_balances(ctx.clone(), lock_hashes)
+            .await
+            .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?
+            .into_iter()
+            .map(|balance| {
+                (
+                    (balance.lock_hash.clone(), balance.type_hash.clone()),
+                    balance,
+                )
+            })
+            .collect();
+        for cell in special_cells.iter() {
+            let lock_hash: H256 = cell.cell_output.lock().calc_script_hash().unpack();
+            let type_hash: Option<H256> = cell
+                .cell_output
+                .type_()
+                .to_opt()
+                .map(|script| script.calc_script_hash().unpack());
+            let balance = match balances.get_mut(&(lock_hash, type_hash.clone())) {
+                Some(balance) => balance,
+                None => continue,
+            };
+            let capacity: u64 = cell.cell_output.capacity().unpack();
+            let occupied = cell
+                .cell_output
+                .occupied_capacity(
+                    ckb_types::core::Capacity::bytes(cell.cell_data.len())
+                        .map_err(|e| RpcErrorMessage::OccupiedCapacityError(e.to_string()))?,
+                )
+                .map_err(|e| RpcErrorMessage::OccupiedCapacityError(e.to_string()))?;
+            balance.capacity = balance.capacity.saturating_sub(capacity);
+            balance.occupied_capacity = balance.occupied_capacity.saturating_sub(occupied.as_u64());
+            if type_hash.is_some() {
+                balance.udt_amount = balance
+                    .udt_amount
+                    .wrapping_sub(decode_udt_amount(&cell.cell_data));
+            }
+            balance.cell_count = balance.cell_count.saturating_sub(1);
+        }
+
+        let type_hashes = balances
+            .keys()
+            .filter_map(|(_, type_hash)| type_hash.as_ref())
+            .map(|type_hash| H160::from_slice(&type_hash.0[0..20]).unwrap())
+            .collect::<HashSet<_>>();
+        let udt_code_hashes: HashMap<H256, H256> = if type_hashes.is_empty() {
+            HashMap::new()
+        } else {
+            self.storage
+                .get_scripts(
+                    ctx.clone(),
+                    type_hashes.into_iter().collect(),
+                    vec![
+                        (**SUDT_CODE_HASH.load()).clone(),
+                        (**XUDT_CODE_HASH.load()).clone(),
+                    ],
+                    None,
+                    vec![],
+                )
+                .await
+                .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?
+                .into_iter()
+                .map(|script| {
+                    (
+                        script.calc_script_hash().unpack(),
+                        script.code_hash().unpack(),
+                    )
+                })
+                .collect()
+        };
+
+        let mut records = Vec::new();
+        for balance in balances.into_values().filter(|b| b.cell_count > 0) {
+            let lock_script = match lock_scripts.get(&balance.lock_hash) {
+                Some(script) => script,
+                None => continue,
+            };
+            let address_or_lock_hash =
+                AddressOrLockHash::Address(self.script_to_address(lock_script).to_string());
+
+            if let Some(type_hash) = balance.type_hash.clone() {
+                if let Some(code_hash) = udt_code_hashes.get(&type_hash) {
+                    let asset_info = if *code_hash == **XUDT_CODE_HASH.load() {
+                        AssetInfo::new_xudt(type_hash)
+                    } else {
+                        AssetInfo::new_udt(type_hash)
+                    };
+                    records.push(table_record(
+                        address_or_lock_hash.clone(),
+                        asset_info,
+                        balance.udt_amount,
+                        0,
+                    ));
+                }
+            }
+
+            records.push(table_record(
+                address_or_lock_hash,
+                ckb_asset_info.clone(),
+                balance.capacity as u128,
+                balance.occupied_capacity,
+            ));
+        }
+
+        let records: Vec<Record> = records
+            .into_iter()
+            .filter(|record| self.is_record_owned_by(record, secp_lock_hash))
+            .filter(|record| asset_infos.contains(&record.asset_info) || asset_infos.is_empty())
+            .collect();
+        self.accumulate_balance_from_records(ctx, balances_map, &records, None)
+            .await?;
+
+        Ok(special_cells)
+    }
+
     #[tracing_async]
     pub(crate) async fn inner_get_block_info(
         &self,
@@ -174,16 +442,41 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         ctx: Context,
         payload: QueryTransactionsPayload,
     ) -> InnerResult<PaginationResponse<TxView>> {
-        let pagination_ret = self
+        let item: Item = payload.item.try_into()?;
+        // the pending transactions are put in front of the first page
+        let mut pending_txs =
+            if payload.include_pending.unwrap_or(false) && payload.pagination.cursor.is_none() {
+                self.get_pending_transactions_by_item(ctx.clone(), item.clone())
+                    .await?
+            } else {
+                vec![]
+            };
+        let pending_count = pending_txs.len() as u64;
+        let mut pagination = payload.pagination;
+        if let Some(limit) = pagination.limit {
+            // at least one committed transaction is on the page, so that the cursor to the
+            // next page is kept
+            pending_txs.truncate(limit.saturating_sub(1) as usize);
+            pagination.limit = Some(limit - pending_txs.len() as u64);
+        }
+        let mut pagination_ret = self
             .get_transactions_by_item(
                 ctx.clone(),
-                payload.item.try_into()?,
+                item,
                 payload.asset_infos,
                 payload.extra,
                 payload.block_range,
-                payload.pagination,
+                pagination,
             )
             .await?;
+        if !pending_txs.is_empty() {
+            pagination_ret.count = pagination_ret.count.map(|count| count + pending_count);
+            pagination_ret.response = pending_txs
+                .into_iter()
+                .chain(pagination_ret.response.into_iter())
+                .collect();
+        }
+
         match &payload.structure_type {
             StructureType::Native => Ok(PaginationResponse {
                 response: pagination_ret
@@ -212,6 +505,133 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         }
     }
 
+    #[tracing_async]
+    pub(crate) async fn inner_advance_query(
+        &self,
+        ctx: Context,
+        payload: AdvanceQueryPayload,
+    ) -> InnerResult<PaginationResponse<QueryResponse>> {
+        if payload.lock.is_none() && payload.type_.is_none() && payload.block_range.is_none() {
+            return Err(RpcErrorMessage::InvalidRpcParams(
+                "need at least one of lock, type and block range".to_string(),
+            ));
+        }
+
+        let io_type = match (
+            payload.lock.as_ref().and_then(|w| w.io_type.clone()),
+            payload.type_.as_ref().and_then(|w| w.io_type.clone()),
+        ) {
+            (Some(lock_io), Some(type_io)) if lock_io != type_io => {
+                return Err(RpcErrorMessage::InvalidRpcParams(
+                    "conflicting io type of lock and type".to_string(),
+                ))
+            }
+            (lock_io, type_io) => lock_io.or(type_io),
+        };
+
+        // The `args_len` of the payload is the prefix length of the lock args, the same as
+        // the one of the lock script wrapper.
+        let mut lock_wrapper = payload.lock.clone();
+        if let Some(args_len) = payload.args_len {
+            match lock_wrapper.as_mut() {
+                Some(wrapper) if wrapper.script.is_some() => {
+                    wrapper.args_len = wrapper.args_len.or(Some(args_len))
+                }
+                _ => {
+                    return Err(RpcErrorMessage::InvalidRpcParams(
+                        "args len requires a lock script".to_string(),
+                    ))
+                }
+            }
+        }
+
+        let lock_hashes = self
+            .get_script_hashes_by_wrapper(ctx.clone(), lock_wrapper.as_ref())
+            .await?;
+        let type_hashes = self
+            .get_script_hashes_by_wrapper(ctx.clone(), payload.type_.as_ref())
+            .await?;
+        let (lock_hashes, type_hashes) = match (lock_hashes, type_hashes) {
+            (Some(lock_hashes), Some(type_hashes)) => (lock_hashes, type_hashes),
+            // A script is specified but no script in the database matches it.
+            _ => {
+                return Ok(PaginationResponse {
+                    response: vec![],
+                    next_cursor: None,
+                    count: Some(0),
+                })
+            }
+        };
+
+        let data_prefix = if let Some(data) = &payload.data {
+            let data = data.strip_prefix("0x").unwrap_or(data);
+            Some(Bytes::from(hex::decode(data).map_err(|e| {
+                RpcErrorMessage::DecodeHexError(e.to_string())
+            })?))
+        } else {
+            None
+        };
+        let filter = CellFilter {
+            io_type: io_type.map(|io_type| match io_type {
+                IOType::Input => CellIOType::Input,
+                IOType::Output => CellIOType::Output,
+            }),
+            data_prefix,
+        };
+
+        match payload.query_type {
+            QueryType::Cell => {
+                let db_response = self
+                    .storage
+                    .get_cells_by_filter(
+                        ctx.clone(),
+                        lock_hashes,
+                        type_hashes,
+                        payload.block_range.clone(),
+                        filter,
+                        payload.pagination.clone(),
+                    )
+                    .await
+                    .map_err(|error| RpcErrorMessage::DBError(error.to_string()))?;
+
+                Ok(PaginationResponse {
+                    response: db_response
+                        .response
+                        .into_iter()
+                        .map(|cell| QueryResponse::Cell(cell.into()))
+                        .collect(),
+                    next_cursor: db_response.next_cursor,
+                    count: db_response.count,
+                })
+            }
+
+            QueryType::Transaction => {
+                let db_response = self
+                    .storage
+                    .get_transactions_by_filter(
+                        ctx.clone(),
+                        lock_hashes,
+                        type_hashes,
+                        payload.block_range.clone(),
+                        filter,
+                        payload.pagination.clone(),
+                    )
+                    .await
+                    .map_err(|error| RpcErrorMessage::DBError(error.to_string()))?;
+
+                Ok(PaginationResponse {
+                    response: db_response
+                        .response
+                        .into_iter()
+                        .map(|tx| QueryResponse::Transaction(tx.transaction_with_status))
+                        .collect(),
+                    next_cursor: db_response.next_cursor,
+                    count: db_response.count,
+                })
+            }
+        }
+    }
+
     #[tracing_async]
     pub(crate) async fn inner_get_tip(&self, ctx: Context) -> InnerResult<Option<indexer::Tip>> {
         let block = self
@@ -589,9 +1009,18 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         ctx: Context,
         tx_hash: H256,
     ) -> InnerResult<GetTransactionInfoResponse> {
-        let tx = self
-            .inner_get_transaction_with_status(ctx.clone(), tx_hash)
-            .await?;
+        let tx = match self
+            .inner_get_transaction_with_status(ctx.clone(), tx_hash.clone())
+            .await
+        {
+            Ok(tx) => tx,
+            Err(RpcErrorMessage::CannotFindTransactionByHash) => {
+                return self
+                    .get_pending_transaction_info(ctx.clone(), tx_hash)
+                    .await;
+            }
+            Err(e) => return Err(e),
+        };
         let transaction = self.query_transaction_info(ctx.clone(), &tx).await?;
 
         Ok(GetTransactionInfoResponse {
@@ -602,7 +1031,25 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
     }
 
     #[tracing_async]
-    async fn query_transaction_info(
+    async fn get_pending_transaction_info(
+        &self,
+        ctx: Context,
+        tx_hash: H256,
+    ) -> InnerResult<GetTransactionInfoResponse> {
+        let tx = self
+            .get_pending_transaction_by_hash(&tx_hash)
+            .ok_or(RpcErrorMessage::CannotFindTransactionByHash)?;
+        let transaction = self.query_transaction_info(ctx.clone(), &tx).await?;
+
+        Ok(GetTransactionInfoResponse {
+            transaction: Some(transaction),
+            status: to_transaction_status(&tx.transaction_with_status.tx_status.status),
+            reject_reason: None,
+        })
+    }
+
+    #[tracing_async]
+    pub(crate) async fn query_transaction_info(
         &self,
         ctx: Context,
         tx_wrapper: &TransactionWrapper,
@@ -677,6 +1124,57 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         })
     }
 
+    /// Resolve the script hashes matched by a script wrapper. When `args_len` is set,
+    /// only the first `args_len` bytes of the args are compared. Return `None` if a
+    /// script is specified but nothing matches it, and an empty vec if no script is specified.
+    #[tracing_async]
+    async fn get_script_hashes_by_wrapper(
+        &self,
+        ctx: Context,
+        wrapper: Option<&ScriptWrapper>,
+    ) -> InnerResult<Option<Vec<H256>>> {
+        let script: packed::Script = match wrapper.and_then(|w| w.script.clone()) {
+            Some(script) => script.into(),
+            None => return Ok(Some(vec![])),
+        };
+
+        let args_len = match wrapper.and_then(|w| w.args_len) {
+            Some(len) => len as usize,
+            None => {
+                return Ok(Some(vec![script.calc_script_hash().unpack()]));
+            }
+        };
+
+        let args = script.args().raw_data();
+        if args_len > args.len() {
+            return Err(RpcErrorMessage::InvalidRpcParams(
+                "args len is greater than the length of script args".to_string(),
+            ));
+        }
+
+        let scripts = self
+            .storage
+            .get_scripts_by_partial_arg(
+                ctx.clone(),
+                script.code_hash().unpack(),
+                args.slice(0..args_len),
+                (0, args_len as u32),
+            )
+            .await
+            .map_err(|error| RpcErrorMessage::DBError(error.to_string()))?;
+        let hashes: Vec<H256> = scripts
+            .into_iter()
+            .filter(|s| s.hash_type() == script.hash_type())
+            .map(|s| s.calc_script_hash().unpack())
+            .collect();
+
+        if hashes.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(hashes))
+        }
+    }
+
     #[tracing_async]
     async fn get_cells_by_search_key(
         &self,
@@ -774,3 +1272,23 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         Ok(db_response)
     }
 }
+
+/// The record of the plain cells summed up in the balance table.
+fn table_record(
+    address_or_lock_hash: AddressOrLockHash,
+    asset_info: AssetInfo,
+    amount: u128,
+    occupied: u64,
+) -> Record {
+    Record {
+        id: String::new(),
+        address_or_lock_hash,
+        asset_info,
+        amount: amount.to_string(),
+        occupied,
+        status: Status::Fixed(0),
+        extra: None,
+        block_number: 0,
+        epoch_number: 0,
+    }
+}
@@ -0,0 +1,104 @@
+use crate::error::InnerResult;
+use crate::rpc_impl::RESERVED_INPUTS;
+use crate::types::TransactionCompletionResponse;
+use crate::{CkbRpc, MercuryRpcImpl};
+
+use ckb_types::{packed, H256};
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// The input cells of the built transactions, which are reserved until the transactions
+/// are released or the reservations expire. The input pooling skips the reserved cells, so
+/// that the concurrent builds never choose the same cells.
+#[derive(Default, Clone, Debug)]
+pub struct InputReservations {
+    cells: HashMap<packed::OutPoint, (H256, Instant)>,
+    transactions: HashMap<H256, Vec<packed::OutPoint>>,
+}
+
+impl InputReservations {
+    pub fn is_reserved(&self, out_point: &packed::OutPoint) -> bool {
+        self.cells
+            .get(out_point)
+            .map(|(_, expire_at)| *expire_at > Instant::now())
+            .unwrap_or(false)
+    }
+
+    /// Reserve the inputs of a transaction. Nothing is reserved if any of the inputs is
+    /// reserved by another transaction, and the reservations of the same transaction are
+    /// replaced by the new ones.
+    pub fn reserve(
+        &mut self,
+        tx_hash: H256,
+        out_points: Vec<packed::OutPoint>,
+        ttl: Duration,
+    ) -> bool {
+        self.remove_expired();
+
+        if out_points.iter().any(|out_point| {
+            self.cells
+                .get(out_point)
+                .map(|(hash, _)| *hash != tx_hash)
+                .unwrap_or(false)
+        }) {
+            return false;
+        }
+
+        self.release(&tx_hash);
+        let expire_at = Instant::now() + ttl;
+        for out_point in out_points.iter() {
+            self.cells
+                .insert(out_point.clone(), (tx_hash.clone(), expire_at));
+        }
+        self.transactions.insert(tx_hash, out_points);
+        true
+    }
+
+    /// Release the inputs reserved by a transaction, and return the number of them.
+    pub fn release(&mut self, tx_hash: &H256) -> usize {
+        let out_points = self.transactions.remove(tx_hash).unwrap_or_default();
+        for out_point in out_points.iter() {
+            self.cells.remove(out_point);
+        }
+        out_points.len()
+    }
+
+    fn remove_expired(&mut self) {
+        let now = Instant::now();
+        let expired = self
+            .cells
+            .iter()
+            .filter(|(_, (_, expire_at))| *expire_at <= now)
+            .map(|(_, (tx_hash, _))| tx_hash.clone())
+            .collect::<Vec<_>>();
+        for tx_hash in expired.iter() {
+            self.release(tx_hash);
+        }
+    }
+}
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    pub(crate) fn inner_release_reserved_inputs(&self, tx_hash: H256) -> InnerResult<u64> {
+        let released = RESERVED_INPUTS.write().release(&tx_hash);
+        Ok(released as u64)
+    }
+
+    /// Reserve the inputs of a built transaction for `reservation_ttl`, return false if
+    /// some of them have been reserved by a concurrent build.
+    pub(crate) fn reserve_inputs(&self, response: &TransactionCompletionResponse) -> bool {
+        let out_points = response
+            .tx_view
+            .inner
+            .inputs
+            .iter()
+            .map(|input| input.previous_output.clone().into())
+            .collect::<Vec<packed::OutPoint>>();
+
+        RESERVED_INPUTS.write().reserve(
+            response.tx_view.hash.clone(),
+            out_points,
+            self.reservation_ttl,
+        )
+    }
+}
@@ -0,0 +1,186 @@
+use crate::error::InnerResult;
+use crate::rpc_impl::{CHAIN_EVENT_SENDER, SUBSCRIPTION_EVENT_SENDER};
+use crate::types::{BlockInfo, GetBlockInfoPayload, Item, JsonItem, RollbackInfo, TransactionInfo};
+use crate::{CkbRpc, MercuryRpcImpl, MercurySubscriptionServer};
+
+use common::Context;
+
+use ckb_types::{core::BlockNumber, H160, H256};
+use jsonrpsee_ws_server::SubscriptionSink;
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+
+use std::convert::TryInto;
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChainEvent {
+    NewTip(BlockNumber, H256),
+    Rollback(BlockNumber, H256),
+}
+
+/// A chain event with the block info fetched once for all the subscribers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubscriptionEvent {
+    NewTip(Arc<BlockInfo>),
+    Rollback(RollbackInfo),
+}
+
+/// Broadcast a chain event to all the subscribers.
+pub fn notify_chain_event(event: ChainEvent) {
+    // an error means that there is no subscriber at present
+    let _ = CHAIN_EVENT_SENDER.send(event);
+}
+
+impl<C: CkbRpc + Clone> MercurySubscriptionServer for MercuryRpcImpl<C> {
+    fn subscribe_new_tip(&self, sink: SubscriptionSink) {
+        spawn_subscription(sink, |event| match event {
+            SubscriptionEvent::NewTip(block_info) => vec![block_info.as_ref().clone()],
+            SubscriptionEvent::Rollback(_) => vec![],
+        });
+    }
+
+    fn subscribe_rollback(&self, sink: SubscriptionSink) {
+        spawn_subscription(sink, |event| match event {
+            SubscriptionEvent::Rollback(rollback_info) => vec![rollback_info.clone()],
+            SubscriptionEvent::NewTip(_) => vec![],
+        });
+    }
+
+    fn subscribe_records(&self, mut sink: SubscriptionSink, item: JsonItem) {
+        let secp_lock_hash = match self.get_record_subscription_lock_hash(item) {
+            Ok(hash) => hash,
+            Err(e) => {
+                log::warn!("[subscription] invalid record subscription item {}", e);
+                sink.close(format!("invalid record subscription item: {}", e));
+                return;
+            }
+        };
+        let rpc = self.clone();
+
+        spawn_subscription(sink, move |event| match event {
+            SubscriptionEvent::NewTip(block_info) => {
+                rpc.filter_records(block_info, &secp_lock_hash)
+            }
+            SubscriptionEvent::Rollback(_) => vec![],
+        });
+    }
+}
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    /// Fetch the block info of each new tip once and forward the chain events to the
+    /// subscribers, so that the subscriptions don't query the storage on their own.
+    pub async fn dispatch_chain_events(&self) {
+        let mut receiver = CHAIN_EVENT_SENDER.subscribe();
+
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(num)) => {
+                    log::warn!("[subscription] dispatcher lagged behind {} events", num);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+
+            if let Some(event) = self.to_subscription_event(event).await {
+                // an error means that there is no subscriber at present
+                let _ = SUBSCRIPTION_EVENT_SENDER.send(event);
+            }
+        }
+    }
+
+    pub(crate) async fn to_subscription_event(
+        &self,
+        event: ChainEvent,
+    ) -> Option<SubscriptionEvent> {
+        match event {
+            ChainEvent::NewTip(block_number, block_hash) => {
+                match self
+                    .inner_get_block_info(
+                        Context::new(),
+                        GetBlockInfoPayload {
+                            block_number: Some(block_number),
+                            block_hash: Some(block_hash),
+                        },
+                    )
+                    .await
+                {
+                    Ok(block_info) => Some(SubscriptionEvent::NewTip(Arc::new(block_info))),
+                    Err(e) => {
+                        log::error!("[subscription] get block info {} error {}", block_number, e);
+                        None
+                    }
+                }
+            }
+            ChainEvent::Rollback(block_number, block_hash) => {
+                Some(SubscriptionEvent::Rollback(RollbackInfo {
+                    block_number,
+                    block_hash,
+                }))
+            }
+        }
+    }
+
+    pub(crate) fn get_record_subscription_lock_hash(&self, item: JsonItem) -> InnerResult<H160> {
+        item.try_into()
+            .and_then(|item: Item| self.get_secp_lock_hash_by_item(item))
+    }
+
+    /// Keep the transactions of a block with the records owned by the secp lock hash.
+    pub(crate) fn filter_records(
+        &self,
+        block_info: &BlockInfo,
+        secp_lock_hash: &H160,
+    ) -> Vec<TransactionInfo> {
+        block_info
+            .transactions
+            .iter()
+            .filter_map(|tx_info| {
+                let records = tx_info
+                    .records
+                    .iter()
+                    .filter(|record| self.is_record_owned_by(record, secp_lock_hash))
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                if records.is_empty() {
+                    None
+                } else {
+                    Some(TransactionInfo {
+                        records,
+                        ..tx_info.clone()
+                    })
+                }
+            })
+            .collect()
+    }
+}
+
+fn spawn_subscription<F, T>(mut sink: SubscriptionSink, handler: F)
+where
+    F: Fn(&SubscriptionEvent) -> Vec<T> + Send + 'static,
+    T: Serialize + Send,
+{
+    let mut receiver = SUBSCRIPTION_EVENT_SENDER.subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(num)) => {
+                    log::warn!("[subscription] subscriber lagged behind {} events", num);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+
+            for item in handler(&event).iter() {
+                // the subscriber has gone away
+                if sink.send(item).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,369 @@
+use crate::error::{InnerResult, RpcErrorMessage};
+use crate::rpc_impl::{address_to_script, PENDING_TX_CACHE};
+use crate::types::{decode_record_id, AddressOrLockHash, Item, TransactionStatus};
+use crate::{CkbRpc, MercuryRpcImpl};
+
+use common::utils::parse_address;
+use common::{Context, DetailedCell};
+use common_logger::tracing_async;
+use core_storage::Storage;
+
+use ckb_jsonrpc_types::{Status, TransactionWithStatus};
+use ckb_types::core::{BlockNumber, TransactionView};
+use ckb_types::{packed, prelude::*, H256};
+use protocol::TransactionWrapper;
+
+use std::collections::{HashMap, HashSet};
+
+/// The pending and proposed transactions in the tx pool with their inputs resolved. The
+/// outputs, the committed cells consumed and the transactions are indexed by lock hash,
+/// so that the pending assets and transactions of an item are found without scanning
+/// the pool.
+#[derive(Default, Clone)]
+pub struct PendingTxCache {
+    pub transactions: HashMap<H256, TransactionWrapper>,
+    pub outputs: HashMap<packed::OutPoint, DetailedCell>,
+    /// The outputs not consumed by other transactions in the pool.
+    pub lock_index: HashMap<H256, Vec<packed::OutPoint>>,
+    /// The committed cells consumed by the transactions in the pool.
+    pub spent_index: HashMap<H256, Vec<DetailedCell>>,
+    pub tx_index: HashMap<H256, Vec<H256>>,
+}
+
+impl PendingTxCache {
+    pub fn new(transactions: Vec<TransactionWrapper>) -> Self {
+        let mut cache = PendingTxCache::default();
+        let consumed = transactions
+            .iter()
+            .flat_map(|tx| tx.transaction_view.input_pts_iter())
+            .collect::<HashSet<_>>();
+        for cell in transactions.iter().flat_map(|tx| tx.output_cells.iter()) {
+            cache.outputs.insert(cell.out_point.clone(), cell.clone());
+        }
+
+        for tx in transactions.into_iter() {
+            let tx_hash: H256 = tx.transaction_view.hash().unpack();
+            for cell in tx.input_cells.iter().chain(tx.output_cells.iter()) {
+                let tx_hashes = cache
+                    .tx_index
+                    .entry(lock_hash(cell))
+                    .or_insert_with(Vec::new);
+                if !tx_hashes.contains(&tx_hash) {
+                    tx_hashes.push(tx_hash.clone());
+                }
+            }
+            for cell in tx.input_cells.iter() {
+                if !cache.outputs.contains_key(&cell.out_point) {
+                    cache
+                        .spent_index
+                        .entry(lock_hash(cell))
+                        .or_insert_with(Vec::new)
+                        .push(cell.clone());
+                }
+            }
+            for cell in tx.output_cells.iter() {
+                if !consumed.contains(&cell.out_point) {
+                    cache
+                        .lock_index
+                        .entry(lock_hash(cell))
+                        .or_insert_with(Vec::new)
+                        .push(cell.out_point.clone());
+                }
+            }
+
+            cache.transactions.insert(tx_hash, tx);
+        }
+
+        cache
+    }
+
+    pub fn get_cells_by_lock_hashes(&self, lock_hashes: &[H256]) -> Vec<DetailedCell> {
+        lock_hashes
+            .iter()
+            .filter_map(|lock_hash| self.lock_index.get(lock_hash))
+            .flatten()
+            .filter_map(|out_point| self.outputs.get(out_point).cloned())
+            .collect()
+    }
+
+    pub fn get_spent_cells_by_lock_hashes(&self, lock_hashes: &[H256]) -> Vec<DetailedCell> {
+        lock_hashes
+            .iter()
+            .filter_map(|lock_hash| self.spent_index.get(lock_hash))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_transactions_by_lock_hashes(&self, lock_hashes: &[H256]) -> Vec<TransactionWrapper> {
+        let mut tx_hashes = Vec::new();
+        for tx_hash in lock_hashes
+            .iter()
+            .filter_map(|lock_hash| self.tx_index.get(lock_hash))
+            .flatten()
+        {
+            if !tx_hashes.contains(tx_hash) {
+                tx_hashes.push(tx_hash.clone());
+            }
+        }
+
+        tx_hashes
+            .iter()
+            .filter_map(|tx_hash| self.transactions.get(tx_hash).cloned())
+            .collect()
+    }
+
+    /// The input cells of the transactions, which are reused by the next flush.
+    pub fn input_cells(&self) -> HashMap<H256, Vec<DetailedCell>> {
+        self.transactions
+            .iter()
+            .map(|(tx_hash, tx)| (tx_hash.clone(), tx.input_cells.clone()))
+            .collect()
+    }
+}
+
+/// The outputs of a transaction which is not committed are treated as if they were
+/// committed in the block after the tip.
+pub fn build_pending_cells(
+    tx: &TransactionView,
+    tip_block_number: BlockNumber,
+    tip_epoch_number: u64,
+) -> Vec<DetailedCell> {
+    tx.outputs_with_data_iter()
+        .zip(tx.output_pts_iter())
+        .map(|((cell_output, cell_data), out_point)| DetailedCell {
+            epoch_number: tip_epoch_number,
+            block_number: tip_block_number + 1,
+            block_hash: H256::default(),
+            // a transaction in the pool is never a cellbase
+            tx_index: 1,
+            out_point,
+            cell_output,
+            cell_data,
+            consumed_block_number: None,
+            consumed_block_hash: None,
+            consumed_tx_hash: None,
+            consumed_tx_index: None,
+            consumed_input_index: None,
+            since: None,
+        })
+        .collect()
+}
+
+/// Resolve the inputs of the transactions in the tx pool. The outputs of the pool are
+/// looked up first, then the inputs resolved by the previous flush, and the storage at
+/// last, so that the storage is only queried for the transactions newly entering the
+/// pool. A transaction of which the inputs are not synchronized yet is skipped.
+pub async fn resolve_pending_transactions<S: Storage + Sync + ?Sized>(
+    ctx: Context,
+    storage: &S,
+    transactions: Vec<TransactionWithStatus>,
+    previous_inputs: &HashMap<H256, Vec<DetailedCell>>,
+    tip_block_number: BlockNumber,
+    tip_epoch_number: u64,
+) -> Vec<TransactionWrapper> {
+    let transactions = transactions
+        .into_iter()
+        .filter_map(|tx| {
+            let tx_view = packed::Transaction::from(tx.transaction.clone()?.inner).into_view();
+            Some((tx, tx_view))
+        })
+        .collect::<Vec<_>>();
+    let outputs = transactions
+        .iter()
+        .flat_map(|(_, tx_view)| build_pending_cells(tx_view, tip_block_number, tip_epoch_number))
+        .map(|cell| (cell.out_point.clone(), cell))
+        .collect::<HashMap<_, _>>();
+
+    let mut ret = Vec::new();
+    'tx: for (tx, tx_view) in transactions.into_iter() {
+        let tx_hash: H256 = tx_view.hash().unpack();
+        let input_cells = match previous_inputs.get(&tx_hash) {
+            Some(cells) => cells.clone(),
+            None => {
+                let mut cells = Vec::new();
+                for out_point in tx_view.input_pts_iter() {
+                    let cell = match outputs.get(&out_point) {
+                        Some(cell) => Some(cell.clone()),
+                        None => storage
+                            .get_cells(
+                                ctx.clone(),
+                                Some(out_point),
+                                vec![],
+                                vec![],
+                                None,
+                                Default::default(),
+                            )
+                            .await
+                            .ok()
+                            .and_then(|res| res.response.into_iter().next()),
+                    };
+                    match cell {
+                        Some(cell) => cells.push(cell),
+                        None => continue 'tx,
+                    }
+                }
+                cells
+            }
+        };
+        let output_cells = tx_view
+            .output_pts_iter()
+            .filter_map(|out_point| outputs.get(&out_point).cloned())
+            .collect();
+
+        ret.push(TransactionWrapper {
+            transaction_with_status: tx,
+            transaction_view: tx_view,
+            input_cells,
+            output_cells,
+            is_cellbase: false,
+        });
+    }
+
+    ret
+}
+
+fn lock_hash(cell: &DetailedCell) -> H256 {
+    cell.cell_output.lock().calc_script_hash().unpack()
+}
+
+pub fn to_transaction_status(status: &Status) -> TransactionStatus {
+    match status {
+        Status::Proposed => TransactionStatus::proposed,
+        Status::Committed => TransactionStatus::committed,
+        Status::Unknown => TransactionStatus::unknown,
+        _ => TransactionStatus::pending,
+    }
+}
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    #[tracing_async]
+    pub(crate) async fn get_lock_hashes_by_item(
+        &self,
+        ctx: Context,
+        item: Item,
+    ) -> InnerResult<Vec<H256>> {
+        Ok(self
+            .get_lock_scripts_by_item(ctx, item)
+            .await?
+            .iter()
+            .map(|script| script.calc_script_hash().unpack())
+            .collect())
+    }
+
+    #[tracing_async]
+    pub(crate) async fn get_lock_scripts_by_item(
+        &self,
+        ctx: Context,
+        item: Item,
+    ) -> InnerResult<Vec<packed::Script>> {
+        let scripts = match item {
+            Item::Identity(ident) => self.get_scripts_by_identity(ctx, ident, None).await?,
+            Item::Address(address) => {
+                let address = parse_address(&address)
+                    .map_err(|e| RpcErrorMessage::CommonError(e.to_string()))?;
+                self.get_scripts_by_address(ctx, &address, None).await?
+            }
+            Item::Record(id) => match decode_record_id(id)?.1 {
+                AddressOrLockHash::Address(address) => {
+                    let address = parse_address(&address)
+                        .map_err(|e| RpcErrorMessage::CommonError(e.to_string()))?;
+                    vec![address_to_script(address.payload())]
+                }
+                AddressOrLockHash::LockHash(_) => vec![],
+            },
+        };
+
+        Ok(scripts)
+    }
+
+    #[tracing_async]
+    pub(crate) async fn get_pending_transactions_by_item(
+        &self,
+        ctx: Context,
+        item: Item,
+    ) -> InnerResult<Vec<TransactionWrapper>> {
+        let lock_hashes = self.get_lock_hashes_by_item(ctx, item).await?;
+        Ok(PENDING_TX_CACHE
+            .read()
+            .get_transactions_by_lock_hashes(&lock_hashes))
+    }
+
+    pub(crate) fn get_pending_transaction_by_hash(
+        &self,
+        tx_hash: &H256,
+    ) -> Option<TransactionWrapper> {
+        PENDING_TX_CACHE.read().transactions.get(tx_hash).cloned()
+    }
+
+    /// The input cells of a pending transaction are either committed or the outputs of
+    /// other pending transactions.
+    #[tracing_async]
+    pub(crate) async fn get_pending_transaction_wrapper(
+        &self,
+        ctx: Context,
+        tx: TransactionWithStatus,
+    ) -> InnerResult<TransactionWrapper> {
+        let transaction_view = packed::Transaction::from(
+            tx.transaction
+                .clone()
+                .ok_or(RpcErrorMessage::CannotFindTransactionByHash)?
+                .inner,
+        )
+        .into_view();
+        let input_cells = self
+            .get_pending_input_cells(ctx.clone(), &transaction_view)
+            .await?;
+
+        let output_cells = {
+            let cache = PENDING_TX_CACHE.read();
+            transaction_view
+                .output_pts_iter()
+                .filter_map(|out_point| cache.outputs.get(&out_point).cloned())
+                .collect()
+        };
+
+        Ok(TransactionWrapper {
+            transaction_with_status: tx,
+            transaction_view,
+            input_cells,
+            output_cells,
+            is_cellbase: false,
+        })
+    }
+
+    /// Resolve the input cells of a transaction which is not committed, the outputs of
+    /// the pending transactions are looked up before the storage.
+    pub(crate) async fn get_pending_input_cells(
+        &self,
+        ctx: Context,
+        transaction_view: &TransactionView,
+    ) -> InnerResult<Vec<DetailedCell>> {
+        let mut input_cells = Vec::new();
+        for out_point in transaction_view.input_pts_iter() {
+            let cell = PENDING_TX_CACHE.read().outputs.get(&out_point).cloned();
+            let cell = match cell {
+                Some(cell) => cell,
+                None => self
+                    .storage
+                    .get_cells(
+                        ctx.clone(),
+                        Some(out_point),
+                        vec![],
+                        vec![],
+                        None,
+                        Default::default(),
+                    )
+                    .await
+                    .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?
+                    .response
+                    .get(0)
+                    .cloned()
+                    .ok_or(RpcErrorMessage::CannotFindDetailedCellByOutPoint)?,
+            };
+            input_cells.push(cell);
+        }
+
+        Ok(input_cells)
+    }
+}
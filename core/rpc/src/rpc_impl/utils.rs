@@ -1,14 +1,16 @@
 use crate::error::{InnerResult, RpcErrorMessage};
+use crate::rpc_impl::coin_selector::{coin_selector, Candidate};
 use crate::rpc_impl::{
     address_to_script, ACP_CODE_HASH, CHEQUE_CODE_HASH, CURRENT_BLOCK_NUMBER, CURRENT_EPOCH_NUMBER,
-    DAO_CODE_HASH, MIN_DAO_LOCK_PERIOD, SECP256K1_CODE_HASH, SUDT_CODE_HASH, TX_POOL_CACHE,
-    WITHDRAWING_DAO_CELL_OCCUPIED_CAPACITY,
+    DAO_CODE_HASH, MIN_DAO_LOCK_PERIOD, MULTISIG_CODE_HASH, OMNI_LOCK_CODE_HASH, RESERVED_INPUTS,
+    SECP256K1_CODE_HASH, SUDT_CODE_HASH, TX_POOL_CACHE, WITHDRAWING_DAO_CELL_OCCUPIED_CAPACITY,
+    XUDT_CODE_HASH,
 };
 use crate::types::{
-    decode_record_id, encode_record_id, AddressOrLockHash, AssetInfo, AssetType, Balance, DaoInfo,
-    DaoState, ExtraFilter, ExtraType, HashAlgorithm, IOType, Identity, IdentityFlag, Item, Record,
-    RequiredUDT, SignAlgorithm, SignatureAction, SignatureInfo, SignatureLocation, SinceConfig,
-    SinceFlag, SinceType, Source, Status,
+    decode_record_id, encode_record_id, AddressOrLockHash, AssetInfo, AssetType, Balance,
+    CoinSelection, DaoInfo, DaoState, ExtraFilter, ExtraType, HashAlgorithm, IOType, Identity,
+    IdentityFlag, Item, MultisigScript, Record, RequiredUDT, SignAlgorithm, SignatureAction,
+    SignatureInfo, SignatureLocation, SinceConfig, SinceFlag, SinceType, Source, Status,
 };
 use crate::{CkbRpc, MercuryRpcImpl};
 
@@ -16,7 +18,7 @@ use common::hash::blake2b_160;
 use common::utils::{decode_dao_block_number, decode_udt_amount, parse_address, u256_low_u64};
 use common::{
     Address, AddressPayload, Context, DetailedCell, PaginationRequest, PaginationResponse, Range,
-    ACP, CHEQUE, DAO, SECP256K1,
+    ACP, CHEQUE, DAO, MULTISIG, OMNI_LOCK, SECP256K1, SUDT, XUDT,
 };
 use common_logger::tracing_async;
 use core_storage::Storage;
@@ -25,10 +27,11 @@ use ckb_dao_utils::extract_dao_data;
 use ckb_types::core::{BlockNumber, Capacity, EpochNumberWithFraction, RationalU256};
 use ckb_types::{bytes::Bytes, packed, prelude::*, H160, H256};
 use num_bigint::{BigInt, BigUint};
+use num_traits::ToPrimitive;
 use protocol::TransactionWrapper;
 
 use std::collections::{HashMap, HashSet};
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::str::FromStr;
 
 impl<C: CkbRpc> MercuryRpcImpl<C> {
@@ -55,7 +58,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
     ) -> InnerResult<Vec<packed::Script>> {
         let mut scripts = Vec::new();
 
-        let (flag, pubkey_hash) = ident.parse();
+        let (flag, pubkey_hash) = ident.parse()?;
         match flag {
             IdentityFlag::Ckb => {
                 if lock_filter.is_none()
@@ -117,9 +120,15 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     scripts.append(&mut sender_cheque);
                 }
             }
-            _ => {
-                unreachable!();
+            IdentityFlag::Ethereum
+            | IdentityFlag::Tron
+            | IdentityFlag::Bitcoin
+            | IdentityFlag::Dogecoin => {
+                if lock_filter.is_none() || lock_filter.unwrap() == **OMNI_LOCK_CODE_HASH.load() {
+                    scripts.push(self.build_omni_lock_script(&ident)?);
+                }
             }
+            _ => return Err(RpcErrorMessage::UnsupportIdentityFlag(flag as u8)),
         }
 
         Ok(scripts)
@@ -132,6 +141,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         addr: &Address,
         lock_filter: Option<H256>,
     ) -> InnerResult<Vec<packed::Script>> {
+        self.check_multisig_address(addr)?;
         let mut ret = Vec::new();
         let script = address_to_script(addr.payload());
 
@@ -141,12 +151,24 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             ret.push(script.clone());
         }
 
+        if (lock_filter.is_none() || lock_filter.clone().unwrap() == **MULTISIG_CODE_HASH.load())
+            && self.is_multisig_script(&script)
+        {
+            ret.push(script.clone());
+        }
+
         if (lock_filter.is_none() || lock_filter.clone().unwrap() == **ACP_CODE_HASH.load())
             && self.is_script(&script, ACP)?
         {
             ret.push(script.clone());
         }
 
+        if (lock_filter.is_none() || lock_filter.clone().unwrap() == **OMNI_LOCK_CODE_HASH.load())
+            && self.is_omni_lock_script(&script)
+        {
+            ret.push(script.clone());
+        }
+
         if (lock_filter.is_none() || lock_filter.unwrap() == **CHEQUE_CODE_HASH.load())
             && self.is_script(&script, CHEQUE)?
         {
@@ -185,8 +207,12 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             Item::Address(address) => {
                 let address = parse_address(&address)
                     .map_err(|err| RpcErrorMessage::InvalidRpcParams(err.to_string()))?;
+                self.check_multisig_address(&address)?;
                 let script = address_to_script(address.payload());
-                if self.is_script(&script, SECP256K1)? {
+                if self.is_script(&script, SECP256K1)?
+                    || self.is_multisig_script(&script)
+                    || self.is_omni_lock_script(&script)
+                {
                     Ok(address)
                 } else if self.is_script(&script, ACP)? {
                     let args: Bytes = address_to_script(address.payload()).args().unpack();
@@ -196,24 +222,29 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         .build();
                     Ok(self.script_to_address(&secp_script))
                 } else {
-                    // todo, return error in the future
-                    unreachable!()
+                    Err(RpcErrorMessage::UnsupportLockScript(hex::encode(
+                        script.code_hash().as_slice(),
+                    )))
                 }
             }
-            Item::Identity(identity) => {
-                match identity.flag() {
-                    IdentityFlag::Ckb => {
-                        let pubkey_hash = identity.hash();
-                        let secp_script = self
-                            .get_script_builder(SECP256K1)?
-                            .args(Bytes::from(pubkey_hash.0.to_vec()).pack())
-                            .build();
-                        Ok(self.script_to_address(&secp_script))
-                    }
-                    // todo, return error in the future
-                    _ => unreachable!(),
+            Item::Identity(identity) => match identity.flag()? {
+                IdentityFlag::Ckb => {
+                    let pubkey_hash = identity.hash();
+                    let secp_script = self
+                        .get_script_builder(SECP256K1)?
+                        .args(Bytes::from(pubkey_hash.0.to_vec()).pack())
+                        .build();
+                    Ok(self.script_to_address(&secp_script))
                 }
-            }
+                IdentityFlag::Ethereum
+                | IdentityFlag::Tron
+                | IdentityFlag::Bitcoin
+                | IdentityFlag::Dogecoin => {
+                    let omni_lock_script = self.build_omni_lock_script(&identity)?;
+                    Ok(self.script_to_address(&omni_lock_script))
+                }
+                flag => Err(RpcErrorMessage::UnsupportIdentityFlag(flag as u8)),
+            },
             Item::Record(id) => {
                 let (_out_point, address_or_lock_hash) = decode_record_id(id)?;
                 match address_or_lock_hash {
@@ -221,8 +252,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         self.get_secp_address_by_item(Item::Address(address))
                     }
                     AddressOrLockHash::LockHash(_lock_hash) => {
-                        // todo, return error in the future
-                        unreachable!()
+                        Err(RpcErrorMessage::CannotFindAddressByH160)
                     }
                 }
             }
@@ -255,7 +285,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         .unpack(),
                     _ => H256::default(),
                 },
-                AssetType::UDT => asset_info.udt_hash,
+                AssetType::UDT | AssetType::XUDT => asset_info.udt_hash,
             })
             .collect();
 
@@ -283,7 +313,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     )
                     .await
                     .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?;
-                let (_flag, pubkey_hash) = ident.parse();
+                let pubkey_hash = ident.hash();
                 let secp_lock_hash: H256 = self
                     .get_script_builder(SECP256K1)?
                     .args(Bytes::from(pubkey_hash.0.to_vec()).pack())
@@ -425,6 +455,8 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         }
                     } else if code_hash == **SECP256K1_CODE_HASH.load()
                         || code_hash == **ACP_CODE_HASH.load()
+                        || code_hash == **MULTISIG_CODE_HASH.load()
+                        || code_hash == **OMNI_LOCK_CODE_HASH.load()
                     {
                         let record_address = match address_or_lock_hash {
                             AddressOrLockHash::Address(address) => address,
@@ -456,7 +488,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
     }
 
     #[tracing_async]
-    async fn get_live_cells(
+    pub(crate) async fn get_live_cells(
         &self,
         ctx: Context,
         out_point: Option<packed::OutPoint>,
@@ -519,7 +551,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         .unpack(),
                     _ => H256::default(),
                 },
-                AssetType::UDT => asset_info.udt_hash,
+                AssetType::UDT | AssetType::XUDT => asset_info.udt_hash,
             })
             .collect();
 
@@ -608,26 +640,36 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         signature_actions: &mut HashMap<String, SignatureAction>,
         script_type: AssetScriptType,
         input_index: &mut usize,
+        coin_selection: CoinSelection,
     ) -> bool {
         let zero = BigInt::from(0);
-        for cell in resource_cells.iter() {
-            if *amount_required <= zero {
-                return true;
-            }
-
-            if self.is_in_cache(&cell.out_point) {
-                continue;
-            }
+        if *amount_required <= zero {
+            return true;
+        }
 
-            let amount = if is_ckb {
-                let capacity: u64 = cell.cell_output.capacity().unpack();
-                capacity as u128
-            } else {
-                decode_udt_amount(&cell.cell_data)
-            };
+        let sign_algorithm = match script_type {
+            AssetScriptType::OmniLock(ref sign_algorithm) => sign_algorithm.clone(),
+            _ => SignAlgorithm::Secp256k1,
+        };
+        let candidates = resource_cells
+            .into_iter()
+            .filter(|cell| !self.is_in_cache(&cell.out_point))
+            .filter_map(|cell| {
+                let amount = if is_ckb {
+                    let capacity: u64 = cell.cell_output.capacity().unpack();
+                    capacity as u128
+                } else {
+                    decode_udt_amount(&cell.cell_data)
+                };
+                (amount != 0).then(|| Candidate { cell, amount })
+            })
+            .collect::<Vec<_>>();
+        let required = amount_required.to_u128().unwrap_or(u128::MAX);
 
-            if amount == 0 {
-                continue;
+        for Candidate { cell, amount } in coin_selector(coin_selection).select(candidates, required)
+        {
+            if *amount_required <= zero {
+                return true;
             }
 
             *amount_required -= amount;
@@ -642,6 +684,14 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     )
                     .to_string()
                 }
+                AssetScriptType::Multisig => {
+                    script_set.insert(MULTISIG.to_string());
+                    self.script_to_address(&cell.cell_output.lock()).to_string()
+                }
+                AssetScriptType::OmniLock(_) => {
+                    script_set.insert(OMNI_LOCK.to_string());
+                    self.script_to_address(&cell.cell_output.lock()).to_string()
+                }
                 AssetScriptType::ACP => {
                     script_set.insert(ACP.to_string());
                     Address::new(
@@ -684,7 +734,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
             add_signature_action(
                 addr,
                 cell.cell_output.calc_lock_hash().to_string(),
-                SignAlgorithm::Secp256k1,
+                sign_algorithm.clone(),
                 HashAlgorithm::Blake2b,
                 signature_actions,
                 *input_index,
@@ -698,24 +748,30 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
     pub(crate) fn get_secp_lock_hash_by_item(&self, item: Item) -> InnerResult<H160> {
         match item {
             Item::Identity(ident) => {
-                let (flag, pubkey_hash) = ident.parse();
-                match flag {
-                    IdentityFlag::Ckb => {
-                        let lock_hash: H256 = self
-                            .get_script_builder(SECP256K1)?
-                            .args(Bytes::from(pubkey_hash.0.to_vec()).pack())
-                            .build()
-                            .calc_script_hash()
-                            .unpack();
-                        Ok(H160::from_slice(&lock_hash.0[0..20]).unwrap())
-                    }
-                    _ => unreachable!(),
-                }
+                let (flag, pubkey_hash) = ident.parse()?;
+                let lock_hash: H256 = match flag {
+                    IdentityFlag::Ckb => self
+                        .get_script_builder(SECP256K1)?
+                        .args(Bytes::from(pubkey_hash.0.to_vec()).pack())
+                        .build()
+                        .calc_script_hash()
+                        .unpack(),
+                    IdentityFlag::Ethereum
+                    | IdentityFlag::Tron
+                    | IdentityFlag::Bitcoin
+                    | IdentityFlag::Dogecoin => self
+                        .build_omni_lock_script(&ident)?
+                        .calc_script_hash()
+                        .unpack(),
+                    _ => return Err(RpcErrorMessage::UnsupportIdentityFlag(flag as u8)),
+                };
+                Ok(H160::from_slice(&lock_hash.0[0..20]).unwrap())
             }
 
             Item::Address(addr) => {
                 let addr = parse_address(&addr)
                     .map_err(|e| RpcErrorMessage::CommonError(e.to_string()))?;
+                self.check_multisig_address(&addr)?;
                 let script = address_to_script(addr.payload());
                 if self.is_script(&script, SECP256K1)? || self.is_script(&script, ACP)? {
                     let lock_hash: H256 = self
@@ -725,8 +781,13 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         .calc_script_hash()
                         .unpack();
                     Ok(H160::from_slice(&lock_hash.0[0..20]).unwrap())
+                } else if self.is_multisig_script(&script) || self.is_omni_lock_script(&script) {
+                    let lock_hash: H256 = script.calc_script_hash().unpack();
+                    Ok(H160::from_slice(&lock_hash.0[0..20]).unwrap())
                 } else {
-                    unreachable!();
+                    Err(RpcErrorMessage::UnsupportLockScript(hex::encode(
+                        script.code_hash().as_slice(),
+                    )))
                 }
             }
 
@@ -746,22 +807,28 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
     pub(crate) fn get_secp_lock_args_by_item(&self, item: Item) -> InnerResult<H160> {
         match item {
             Item::Identity(ident) => {
-                let (flag, pubkey_hash) = ident.parse();
+                let (flag, pubkey_hash) = ident.parse()?;
                 match flag {
                     IdentityFlag::Ckb => Ok(pubkey_hash),
-                    _ => unreachable!(),
+                    _ => Err(RpcErrorMessage::UnsupportIdentityFlag(flag as u8)),
                 }
             }
 
             Item::Address(addr) => {
                 let addr = parse_address(&addr)
                     .map_err(|e| RpcErrorMessage::CommonError(e.to_string()))?;
+                self.check_multisig_address(&addr)?;
                 let script = address_to_script(addr.payload());
-                if self.is_script(&script, SECP256K1)? || self.is_script(&script, ACP)? {
+                if self.is_script(&script, SECP256K1)?
+                    || self.is_script(&script, ACP)?
+                    || self.is_multisig_script(&script)
+                {
                     let lock_args = script.args().raw_data();
                     Ok(H160::from_slice(&lock_args[0..20]).unwrap())
                 } else {
-                    unreachable!();
+                    Err(RpcErrorMessage::UnsupportLockScript(hex::encode(
+                        script.code_hash().as_slice(),
+                    )))
                 }
             }
 
@@ -778,9 +845,22 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         }
     }
 
-    fn is_in_cache(&self, cell: &packed::OutPoint) -> bool {
-        let cache = TX_POOL_CACHE.read();
-        cache.contains(cell)
+    pub(crate) fn is_record_owned_by(&self, record: &Record, secp_lock_hash: &H160) -> bool {
+        match &record.address_or_lock_hash {
+            AddressOrLockHash::Address(address) => self
+                .get_secp_lock_hash_by_item(Item::Address(address.to_owned()))
+                .map(|hash| *secp_lock_hash == hash)
+                .unwrap_or(false),
+            AddressOrLockHash::LockHash(lock_hash) => H160::from_str(lock_hash)
+                .map(|hash| *secp_lock_hash == hash)
+                .unwrap_or(false),
+        }
+    }
+
+    /// A cell is unavailable if it is spent by a transaction in the tx pool, or reserved
+    /// by another built transaction.
+    pub(crate) fn is_in_cache(&self, cell: &packed::OutPoint) -> bool {
+        TX_POOL_CACHE.read().contains(cell) || RESERVED_INPUTS.read().is_reserved(cell)
     }
 
     #[allow(clippy::unnecessary_unwrap)]
@@ -800,7 +880,9 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         let udt_record = if let Some(type_script) = cell.cell_output.type_().to_opt() {
             let type_code_hash: H256 = type_script.code_hash().unpack();
 
-            if type_code_hash == **SUDT_CODE_HASH.load() {
+            if type_code_hash == **SUDT_CODE_HASH.load()
+                || type_code_hash == **XUDT_CODE_HASH.load()
+            {
                 let address_or_lock_hash = self
                     .generate_udt_address_or_lock_hash(
                         ctx.clone(),
@@ -810,7 +892,11 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     )
                     .await?;
                 let id = encode_record_id(cell.out_point.clone(), address_or_lock_hash.clone());
-                let asset_info = AssetInfo::new_udt(type_script.calc_script_hash().unpack());
+                let asset_info = if type_code_hash == **XUDT_CODE_HASH.load() {
+                    AssetInfo::new_xudt(type_script.calc_script_hash().unpack())
+                } else {
+                    AssetInfo::new_udt(type_script.calc_script_hash().unpack())
+                };
                 let status = self
                     .generate_udt_status(ctx.clone(), cell, &io_type, tip_epoch_number.clone())
                     .await?;
@@ -1199,6 +1285,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         script_set: &mut HashSet<String>,
         signature_actions: &mut HashMap<String, SignatureAction>,
         input_index: &mut usize,
+        coin_selection: CoinSelection,
     ) -> InnerResult<()> {
         let zero = BigInt::from(0);
         let mut asset_ckb_set = HashSet::new();
@@ -1217,6 +1304,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                     script_set,
                     signature_actions,
                     input_index,
+                    coin_selection,
                 )
                 .await?;
             }
@@ -1291,6 +1379,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 signature_actions,
                 AssetScriptType::Secp256k1,
                 input_index,
+                coin_selection,
             ) {
                 return Ok(());
             }
@@ -1322,10 +1411,92 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                 signature_actions,
                 AssetScriptType::Secp256k1,
                 input_index,
+                coin_selection,
             ) {
                 return Ok(());
             }
 
+            let multisig_cells = self
+                .get_live_cells_by_item(
+                    ctx.clone(),
+                    item.clone(),
+                    asset_ckb_set.clone(),
+                    None,
+                    None,
+                    Some((**MULTISIG_CODE_HASH.load()).clone()),
+                    None,
+                    false,
+                )
+                .await?;
+            // the multisig cells locked with since args are not pooled
+            let multisig_cells = multisig_cells
+                .into_iter()
+                .filter(|cell| {
+                    cell.cell_data.is_empty()
+                        && cell.cell_output.lock().args().raw_data().len() == 20
+                })
+                .collect::<Vec<_>>();
+
+            if self.pool_asset(
+                pool_cells,
+                &mut required_ckb,
+                multisig_cells,
+                true,
+                input_capacity_sum,
+                script_set,
+                signature_actions,
+                AssetScriptType::Multisig,
+                input_index,
+                coin_selection,
+            ) {
+                return Ok(());
+            }
+
+            if self.builtin_scripts.contains_key(OMNI_LOCK) {
+                let omni_lock_cells = self
+                    .get_live_cells_by_item(
+                        ctx.clone(),
+                        item.clone(),
+                        asset_ckb_set.clone(),
+                        None,
+                        None,
+                        Some((**OMNI_LOCK_CODE_HASH.load()).clone()),
+                        None,
+                        false,
+                    )
+                    .await?;
+                // only the omni-lock cells without any mode enabled are pooled
+                let omni_lock_cells = omni_lock_cells
+                    .into_iter()
+                    .filter(|cell| {
+                        let args = cell.cell_output.lock().args().raw_data();
+                        cell.cell_data.is_empty() && args.len() == 22 && args[21] == 0
+                    })
+                    .collect::<Vec<_>>();
+
+                // all the omni-lock cells of an item share the same lock script
+                if let Some(cell) = omni_lock_cells.get(0) {
+                    let sign_algorithm =
+                        IdentityFlag::try_from(cell.cell_output.lock().args().raw_data()[0])?
+                            .omni_lock_sign_algorithm()?;
+
+                    if self.pool_asset(
+                        pool_cells,
+                        &mut required_ckb,
+                        omni_lock_cells,
+                        true,
+                        input_capacity_sum,
+                        script_set,
+                        signature_actions,
+                        AssetScriptType::OmniLock(sign_algorithm),
+                        input_index,
+                        coin_selection,
+                    ) {
+                        return Ok(());
+                    }
+                }
+            }
+
             if required_ckb > zero {
                 return Err(RpcErrorMessage::TokenIsNotEnough(
                     AssetInfo::new_ckb().to_string(),
@@ -1419,6 +1590,30 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         Ok(())
     }
 
+    /// The records of the pending cells are accumulated to the pending amount only.
+    pub(crate) fn accumulate_pending_balance_from_records(
+        &self,
+        balances_map: &mut HashMap<(AddressOrLockHash, AssetInfo), Balance>,
+        records: &[Record],
+    ) {
+        for record in records {
+            let key = (
+                record.address_or_lock_hash.clone(),
+                record.asset_info.clone(),
+            );
+            let balance = balances_map.entry(key).or_insert_with(|| {
+                Balance::new(
+                    record.address_or_lock_hash.clone(),
+                    record.asset_info.clone(),
+                )
+            });
+
+            let amount = u128::from_str(&record.amount).unwrap();
+            let accumulate_pending = amount + u128::from_str(&balance.pending).unwrap();
+            balance.pending = accumulate_pending.to_string();
+        }
+    }
+
     #[tracing_async]
     pub(crate) async fn get_epoch_by_number(
         &self,
@@ -1433,6 +1628,36 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         Ok(header.epoch().to_rational())
     }
 
+    /// Get the first block number of the epoch which is `epochs` epochs before the epoch
+    /// of the block, walking back by the real length of each epoch.
+    #[tracing_async]
+    pub(crate) async fn get_epoch_start_number(
+        &self,
+        ctx: Context,
+        block_number: BlockNumber,
+        epochs: u64,
+    ) -> InnerResult<BlockNumber> {
+        let mut number = block_number;
+        for _ in 0..=epochs {
+            if number == 0 {
+                return Ok(0);
+            }
+
+            let header = self
+                .storage
+                .get_block_header(ctx.clone(), None, Some(number))
+                .await
+                .map_err(|_| RpcErrorMessage::GetEpochFromNumberError(number))?;
+            let start_number = number - header.epoch().index();
+            if start_number == 0 {
+                return Ok(0);
+            }
+            number = start_number - 1;
+        }
+
+        Ok(number + 1)
+    }
+
     #[tracing_async]
     async fn pool_udt(
         &self,
@@ -1446,6 +1671,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         script_set: &mut HashSet<String>,
         signature_action: &mut HashMap<String, SignatureAction>,
         input_index: &mut usize,
+        coin_selection: CoinSelection,
     ) -> InnerResult<()> {
         let zero = BigInt::from(0);
         for required_udt in required_udts.iter() {
@@ -1492,6 +1718,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         signature_action,
                         AssetScriptType::ChequeReceiver(receiver_addr),
                         input_index,
+                        coin_selection,
                     ) {
                         break;
                     }
@@ -1522,6 +1749,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         signature_action,
                         AssetScriptType::ChequeSender(sender_addr),
                         input_index,
+                        coin_selection,
                     ) {
                         break;
                     }
@@ -1551,6 +1779,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         signature_action,
                         AssetScriptType::Secp256k1,
                         input_index,
+                        coin_selection,
                     )
                 {
                     break;
@@ -1580,6 +1809,7 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
                         signature_action,
                         AssetScriptType::ACP,
                         input_index,
+                        coin_selection,
                     )
                 {
                     break;
@@ -1632,6 +1862,38 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         Ok(script.code_hash() == s.code_hash() && script.hash_type() == s.hash_type())
     }
 
+    /// Omni-lock is optional, so a script is never an omni-lock one if it is not configured.
+    pub(crate) fn is_omni_lock_script(&self, script: &packed::Script) -> bool {
+        self.builtin_scripts.contains_key(OMNI_LOCK)
+            && self.is_script(script, OMNI_LOCK).unwrap_or(false)
+    }
+
+    /// Multisig is optional, so a script is never a multisig one if it is not configured.
+    pub(crate) fn is_multisig_script(&self, script: &packed::Script) -> bool {
+        self.builtin_scripts.contains_key(MULTISIG)
+            && self.is_script(script, MULTISIG).unwrap_or(false)
+    }
+
+    /// Reject a multisig address if multisig is not configured, instead of treating it as
+    /// an unknown lock.
+    pub(crate) fn check_multisig_address(&self, address: &Address) -> InnerResult<()> {
+        if address.is_multisig() && !self.builtin_scripts.contains_key(MULTISIG) {
+            return Err(RpcErrorMessage::MissingScriptInfo(MULTISIG.to_string()));
+        }
+        Ok(())
+    }
+
+    /// The omni-lock args is `<21 bytes identity> <1 byte omni-lock flags>`, and no
+    /// omni-lock mode is enabled here.
+    pub(crate) fn build_omni_lock_script(&self, ident: &Identity) -> InnerResult<packed::Script> {
+        let mut args = ident.0.to_vec();
+        args.push(0u8);
+        Ok(self
+            .get_script_builder(OMNI_LOCK)?
+            .args(Bytes::from(args).pack())
+            .build())
+    }
+
     pub(crate) fn is_unlock(
         &self,
         from: RationalU256,
@@ -1650,13 +1912,75 @@ impl<C: CkbRpc> MercuryRpcImpl<C> {
         Address::new(self.network_type, payload, true)
     }
 
-    fn is_cellbase_mature(&self, cell: &DetailedCell) -> bool {
+    /// Attach the multisig script to the signature actions of multisig inputs, the
+    /// signature offset is moved behind the multisig script in witness lock.
+    pub(crate) fn fill_multisig_signature_actions(
+        &self,
+        multisig_scripts: &[MultisigScript],
+        signature_actions: &mut HashMap<String, SignatureAction>,
+    ) -> InnerResult<()> {
+        for action in signature_actions.values_mut() {
+            if action.signature_info.multisig_script.is_some() {
+                continue;
+            }
+
+            let address = parse_address(&action.signature_info.address)
+                .map_err(|e| RpcErrorMessage::ParseAddressError(e.to_string()))?;
+            let script = address_to_script(address.payload());
+            if !self.is_multisig_script(&script) {
+                continue;
+            }
+
+            let lock_args = script.args().raw_data();
+            let multisig_script = multisig_scripts
+                .iter()
+                .find(|s| s.lock_args().as_bytes() == &lock_args[0..20])
+                .cloned()
+                .ok_or_else(|| RpcErrorMessage::MissingMultisigScript(address.to_string()))?;
+            action.signature_location.offset = multisig_script.get_signature_offset().0;
+            action.signature_info.multisig_script = Some(multisig_script);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn is_cellbase_mature(&self, cell: &DetailedCell) -> bool {
         (**CURRENT_EPOCH_NUMBER.load()).clone().saturating_sub(
             EpochNumberWithFraction::from_full_value(cell.epoch_number).to_rational(),
         ) > self.cellbase_maturity
     }
 }
 
+/// Deduct the balances of the cells which are not available any more, every amount is
+/// floored at zero.
+pub(crate) fn deduct_balances(
+    balances_map: &mut HashMap<(AddressOrLockHash, AssetInfo), Balance>,
+    deducted_map: HashMap<(AddressOrLockHash, AssetInfo), Balance>,
+) -> InnerResult<()> {
+    let deduct = |amount: &mut String, deducted: &str| -> InnerResult<()> {
+        let ret = parse_balance_amount(amount)?.saturating_sub(parse_balance_amount(deducted)?);
+        *amount = ret.to_string();
+        Ok(())
+    };
+
+    for (key, deducted) in deducted_map.into_iter() {
+        if let Some(balance) = balances_map.get_mut(&key) {
+            deduct(&mut balance.free, &deducted.free)?;
+            deduct(&mut balance.occupied, &deducted.occupied)?;
+            deduct(&mut balance.freezed, &deducted.freezed)?;
+            deduct(&mut balance.claimable, &deducted.claimable)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_balance_amount(amount: &str) -> InnerResult<u128> {
+    u128::from_str(amount).map_err(|e| {
+        RpcErrorMessage::InvalidRpcParams(format!("invalid balance amount {}: {}", amount, e))
+    })
+}
+
 pub(crate) fn is_dao_withdraw_unlock(
     deposit_epoch: RationalU256,
     withdraw_epoch: RationalU256,
@@ -1732,6 +2056,7 @@ pub fn add_signature_action(
                 signature_info: SignatureInfo {
                     algorithm: sign_algorithm,
                     address,
+                    multisig_script: None,
                 },
                 hash_algorithm,
                 other_indexes_in_group: vec![],
@@ -1740,6 +2065,23 @@ pub fn add_signature_action(
     }
 }
 
+pub fn udt_script_name(asset_info: &AssetInfo) -> &'static str {
+    match asset_info.asset_type {
+        AssetType::XUDT => XUDT,
+        _ => SUDT,
+    }
+}
+
+/// The script name of a udt cell deduced from the code hash of its type script.
+pub fn udt_script_name_by_type(type_script: &packed::Script) -> &'static str {
+    let code_hash: H256 = type_script.code_hash().unpack();
+    if code_hash == **XUDT_CODE_HASH.load() {
+        XUDT
+    } else {
+        SUDT
+    }
+}
+
 pub fn to_since(config: SinceConfig) -> InnerResult<u64> {
     let since = match (config.flag, config.type_) {
         (SinceFlag::Absolute, SinceType::BlockNumber) => 0b0000_0000u64,
@@ -1766,6 +2108,8 @@ pub fn build_cheque_args(receiver_address: Address, sender_address: Address) ->
 
 pub enum AssetScriptType {
     Secp256k1,
+    Multisig,
+    OmniLock(SignAlgorithm),
     ACP,
     ChequeSender(String),
     ChequeReceiver(String),
@@ -1775,8 +2119,13 @@ pub enum AssetScriptType {
 pub fn address_to_identity(address: &str) -> InnerResult<Identity> {
     let address = Address::from_str(address).map_err(RpcErrorMessage::CommonError)?;
     let script = address_to_script(address.payload());
+    let code_hash: H256 = script.code_hash().unpack();
     let pub_key_hash = if address.is_secp256k1() || address.is_acp() {
         script.args().as_slice()[4..24].to_vec()
+    } else if code_hash == **OMNI_LOCK_CODE_HASH.load() && script.args().raw_data().len() >= 21 {
+        let args = script.args().raw_data();
+        let flag = IdentityFlag::try_from(args[0])?;
+        return Ok(Identity::new(flag, H160::from_slice(&args[1..21]).unwrap()));
     } else {
         return Err(RpcErrorMessage::UnsupportLockScript(hex::encode(
             script.code_hash().as_slice(),
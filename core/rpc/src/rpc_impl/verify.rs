@@ -0,0 +1,425 @@
+use crate::error::{InnerResult, RpcErrorMessage};
+use crate::rpc_impl::PENDING_TX_CACHE;
+use crate::types::{
+    ScriptGroupType, ScriptVerifyResult, TransactionCompletionResponse, VerifyTransactionResponse,
+    SECP256K1_WITNESS_LOCATION,
+};
+use crate::{CkbRpc, MercuryRpcImpl};
+
+use common::{Context, DetailedCell, NetworkType, DAO};
+use common_logger::tracing_async;
+use core_storage::Storage;
+
+use ckb_chain_spec::consensus::{Consensus, ConsensusBuilder};
+use ckb_chain_spec::ChainSpec;
+use ckb_jsonrpc_types::Transaction;
+use ckb_resource::Resource;
+use ckb_script::{ScriptGroupType as VmScriptGroupType, TransactionScriptsVerifier, TxVerifyEnv};
+use ckb_traits::{CellDataProvider, HeaderProvider};
+use ckb_types::core::cell::{CellMeta, CellMetaBuilder, ResolvedTransaction};
+use ckb_types::core::{
+    Capacity, DepType, EpochNumberWithFraction, HeaderView, TransactionInfo, TransactionView,
+};
+use ckb_types::{bytes::Bytes, packed, prelude::*, H256};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// Provides the data of the resolved cells and the header deps to the script VM.
+struct VerifyDataLoader {
+    cell_data: HashMap<packed::OutPoint, Bytes>,
+    headers: HashMap<packed::Byte32, HeaderView>,
+}
+
+impl CellDataProvider for VerifyDataLoader {
+    fn get_cell_data(&self, out_point: &packed::OutPoint) -> Option<Bytes> {
+        self.cell_data.get(out_point).cloned()
+    }
+
+    fn get_cell_data_hash(&self, out_point: &packed::OutPoint) -> Option<packed::Byte32> {
+        self.cell_data
+            .get(out_point)
+            .map(|data| packed::CellOutput::calc_data_hash(data))
+    }
+}
+
+impl HeaderProvider for VerifyDataLoader {
+    fn get_header(&self, hash: &packed::Byte32) -> Option<HeaderView> {
+        self.headers.get(hash).cloned()
+    }
+}
+
+impl<C: CkbRpc> MercuryRpcImpl<C> {
+    #[tracing_async]
+    pub(crate) async fn inner_verify_transaction(
+        &self,
+        ctx: Context,
+        transaction: Transaction,
+    ) -> InnerResult<VerifyTransactionResponse> {
+        let tx_view = packed::Transaction::from(transaction).into_view();
+        self.verify_transaction_view(ctx, tx_view, &[]).await
+    }
+
+    /// Verify a built transaction and attach the result to the response. A dry run
+    /// transaction is never sent, the previous transactions of a batch are passed so that
+    /// the change they spend can be resolved.
+    pub(crate) async fn dry_run_transaction(
+        &self,
+        ctx: Context,
+        mut response: TransactionCompletionResponse,
+        prev_txs: &[TransactionView],
+    ) -> InnerResult<TransactionCompletionResponse> {
+        let tx_view = packed::Transaction::from(response.tx_view.inner.clone()).into_view();
+        let verification = self.verify_transaction_view(ctx, tx_view, prev_txs).await?;
+        response.verification = Some(verification);
+        Ok(response)
+    }
+
+    /// Resolve the inputs, the cell deps and the header deps of a transaction from the
+    /// storage, and run the scripts in the VM. The lock groups still holding the zero
+    /// signature placeholder are skipped, since they are not signed yet.
+    pub(crate) async fn verify_transaction_view(
+        &self,
+        ctx: Context,
+        tx_view: TransactionView,
+        prev_txs: &[TransactionView],
+    ) -> InnerResult<VerifyTransactionResponse> {
+        let mut errors = vec![];
+
+        let mut resolved_inputs = vec![];
+        for out_point in tx_view.input_pts_iter() {
+            match self
+                .resolve_cell_meta(ctx.clone(), &out_point, prev_txs)
+                .await?
+            {
+                Some(cell) => resolved_inputs.push(cell),
+                None => errors.push(format!(
+                    "Input {} is dead or unknown",
+                    out_point_to_string(&out_point)
+                )),
+            }
+        }
+
+        let (mut resolved_cell_deps, mut resolved_dep_groups) = (vec![], vec![]);
+        for cell_dep in tx_view.cell_deps_iter() {
+            let out_point = cell_dep.out_point();
+            let cell = match self
+                .resolve_cell_meta(ctx.clone(), &out_point, prev_txs)
+                .await?
+            {
+                Some(cell) => cell,
+                None => {
+                    errors.push(format!(
+                        "Cell dep {} is dead or unknown",
+                        out_point_to_string(&out_point)
+                    ));
+                    continue;
+                }
+            };
+
+            if !matches!(
+                DepType::try_from(cell_dep.dep_type()),
+                Ok(DepType::DepGroup)
+            ) {
+                resolved_cell_deps.push(cell);
+                continue;
+            }
+
+            let data = cell.mem_cell_data.clone().unwrap_or_default();
+            match packed::OutPointVec::from_slice(&data) {
+                Ok(sub_out_points) => {
+                    for sub_out_point in sub_out_points.into_iter() {
+                        match self
+                            .resolve_cell_meta(ctx.clone(), &sub_out_point, prev_txs)
+                            .await?
+                        {
+                            Some(sub_cell) => resolved_cell_deps.push(sub_cell),
+                            None => errors.push(format!(
+                                "Cell dep {} in dep group is dead or unknown",
+                                out_point_to_string(&sub_out_point)
+                            )),
+                        }
+                    }
+                }
+                Err(_) => errors.push(format!(
+                    "Invalid dep group {}",
+                    out_point_to_string(&out_point)
+                )),
+            }
+            resolved_dep_groups.push(cell);
+        }
+
+        let mut headers = HashMap::new();
+        for block_hash in tx_view.header_deps_iter() {
+            match self
+                .storage
+                .get_block_header(ctx.clone(), Some(block_hash.unpack()), None)
+                .await
+            {
+                Ok(header) => {
+                    headers.insert(block_hash, header);
+                }
+                Err(_) => errors.push(format!("Header dep {} is unknown", block_hash)),
+            }
+        }
+
+        // the scripts cannot be run without the complete resolved transaction
+        if !errors.is_empty() {
+            return Ok(VerifyTransactionResponse {
+                cycles: 0,
+                script_results: vec![],
+                errors,
+            });
+        }
+
+        errors.extend(self.check_capacity(&tx_view, &resolved_inputs)?);
+
+        let cell_data = resolved_inputs
+            .iter()
+            .chain(resolved_cell_deps.iter())
+            .chain(resolved_dep_groups.iter())
+            .filter_map(|cell| {
+                cell.mem_cell_data
+                    .clone()
+                    .map(|data| (cell.out_point.clone(), data))
+            })
+            .collect();
+        let loader = VerifyDataLoader { cell_data, headers };
+        let groups = script_groups(&tx_view, &resolved_inputs);
+        let rtx = ResolvedTransaction {
+            transaction: tx_view,
+            resolved_cell_deps,
+            resolved_inputs,
+            resolved_dep_groups,
+        };
+
+        let tip_header = self
+            .storage
+            .get_block_header(ctx.clone(), None, None)
+            .await
+            .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?;
+        let tx_env = TxVerifyEnv::new_submit(&tip_header);
+        let verifier = TransactionScriptsVerifier::new(&rtx, &self.consensus, &loader, &tx_env);
+
+        let max_cycles = self.consensus.max_block_cycles();
+        let mut cycles = 0u64;
+        let mut script_results = vec![];
+        for (group_type, script_hash, skipped) in groups {
+            let mut result = ScriptVerifyResult {
+                script_hash: script_hash.unpack(),
+                group_type,
+                cycles: None,
+                error: None,
+                skipped,
+            };
+            if !skipped {
+                let vm_group_type = match group_type {
+                    ScriptGroupType::Lock => VmScriptGroupType::Lock,
+                    ScriptGroupType::Type => VmScriptGroupType::Type,
+                };
+                match verifier.verify_single(
+                    vm_group_type,
+                    &script_hash,
+                    max_cycles.saturating_sub(cycles),
+                ) {
+                    Ok(used) => {
+                        cycles = cycles.saturating_add(used);
+                        result.cycles = Some(used);
+                    }
+                    Err(e) => result.error = Some(e.to_string()),
+                }
+            }
+            script_results.push(result);
+        }
+
+        Ok(VerifyTransactionResponse {
+            cycles,
+            script_results,
+            errors,
+        })
+    }
+
+    /// Resolve a cell from the previous transactions of a batch, the pending transactions
+    /// and the live cells in the storage in turn.
+    async fn resolve_cell_meta(
+        &self,
+        ctx: Context,
+        out_point: &packed::OutPoint,
+        prev_txs: &[TransactionView],
+    ) -> InnerResult<Option<CellMeta>> {
+        let index: u32 = out_point.index().unpack();
+        if let Some(tx) = prev_txs.iter().find(|tx| tx.hash() == out_point.tx_hash()) {
+            return Ok(tx.output_with_data(index as usize).map(|(output, data)| {
+                CellMetaBuilder::from_cell_output(output, data)
+                    .out_point(out_point.clone())
+                    .build()
+            }));
+        }
+
+        let pending_cell = PENDING_TX_CACHE.read().outputs.get(out_point).cloned();
+        if let Some(cell) = pending_cell {
+            return Ok(Some(
+                CellMetaBuilder::from_cell_output(cell.cell_output, cell.cell_data)
+                    .out_point(out_point.clone())
+                    .build(),
+            ));
+        }
+
+        let cell = self
+            .storage
+            .get_live_cells(
+                ctx,
+                Some(out_point.clone()),
+                vec![],
+                vec![],
+                None,
+                Default::default(),
+            )
+            .await
+            .map_err(|e| RpcErrorMessage::DBError(e.to_string()))?
+            .response
+            .get(0)
+            .cloned();
+
+        Ok(cell.map(committed_cell_meta))
+    }
+
+    /// The outputs must hold their occupied capacity, and cannot spend more capacity than
+    /// the inputs except withdrawing from DAO, whose interest is checked by the DAO script.
+    fn check_capacity(
+        &self,
+        tx_view: &TransactionView,
+        resolved_inputs: &[CellMeta],
+    ) -> InnerResult<Vec<String>> {
+        let mut errors = vec![];
+
+        for (index, (output, data)) in tx_view.outputs_with_data_iter().enumerate() {
+            match Capacity::bytes(data.len()).and_then(|cap| output.is_lack_of_capacity(cap)) {
+                Ok(false) => (),
+                Ok(true) => errors.push(format!("Output {} is lack of capacity", index)),
+                Err(e) => errors.push(format!("Output {} capacity error {}", index, e)),
+            }
+        }
+
+        let mut withdraw_dao = false;
+        for cell in resolved_inputs.iter() {
+            if let Some(type_script) = cell.cell_output.type_().to_opt() {
+                withdraw_dao |= self.is_script(&type_script, DAO)?;
+            }
+        }
+        if !withdraw_dao {
+            let inputs_capacity = resolved_inputs
+                .iter()
+                .map(|cell| Unpack::<u64>::unpack(&cell.cell_output.capacity()))
+                .try_fold(0u64, |acc, cap| acc.checked_add(cap))
+                .ok_or(RpcErrorMessage::Overflow)?;
+            match tx_view.outputs_capacity() {
+                Ok(outputs_capacity) if outputs_capacity.as_u64() > inputs_capacity => {
+                    errors.push(format!(
+                        "Outputs capacity {} exceeds inputs capacity {}",
+                        outputs_capacity.as_u64(),
+                        inputs_capacity
+                    ))
+                }
+                Ok(_) => (),
+                Err(e) => errors.push(format!("Outputs capacity error {}", e)),
+            }
+        }
+
+        Ok(errors)
+    }
+}
+
+/// The consensus of the network is loaded from the bundled chain spec, which is built
+/// once when the rpc is created.
+pub(crate) fn load_consensus(network_type: NetworkType) -> Consensus {
+    let spec_name = match network_type {
+        NetworkType::Mainnet => "mainnet",
+        NetworkType::Testnet | NetworkType::Staging => "testnet",
+        NetworkType::Dev => return ConsensusBuilder::default().build(),
+    };
+
+    ChainSpec::load_from(&Resource::bundled(format!("specs/{}.toml", spec_name)))
+        .and_then(|spec| spec.build_consensus())
+        .expect("load bundled chain spec")
+}
+
+fn committed_cell_meta(cell: DetailedCell) -> CellMeta {
+    let block_hash: packed::Byte32 = cell.block_hash.pack();
+    CellMetaBuilder::from_cell_output(cell.cell_output, cell.cell_data)
+        .out_point(cell.out_point)
+        .transaction_info(TransactionInfo::new(
+            cell.block_number,
+            EpochNumberWithFraction::from_full_value(cell.epoch_number),
+            block_hash,
+            cell.tx_index as usize,
+        ))
+        .build()
+}
+
+/// The lock groups of the inputs followed by the type groups of the inputs and outputs,
+/// and whether each group is skipped for the unsigned placeholder.
+fn script_groups(
+    tx_view: &TransactionView,
+    resolved_inputs: &[CellMeta],
+) -> Vec<(ScriptGroupType, packed::Byte32, bool)> {
+    let mut groups: Vec<(ScriptGroupType, packed::Byte32, bool)> = vec![];
+    // a lock script and a type script of the same hash are different groups
+    let contains = |groups: &[(ScriptGroupType, packed::Byte32, bool)],
+                    group_type: ScriptGroupType,
+                    script_hash: &packed::Byte32| {
+        groups
+            .iter()
+            .any(|(t, hash, _)| *t == group_type && hash == script_hash)
+    };
+
+    for (index, cell) in resolved_inputs.iter().enumerate() {
+        let lock_hash = cell.cell_output.lock().calc_script_hash();
+        if !contains(&groups, ScriptGroupType::Lock, &lock_hash) {
+            groups.push((
+                ScriptGroupType::Lock,
+                lock_hash,
+                has_signature_placeholder(tx_view, index),
+            ));
+        }
+    }
+
+    let type_scripts = resolved_inputs
+        .iter()
+        .map(|cell| cell.cell_output.type_())
+        .chain(tx_view.outputs().into_iter().map(|output| output.type_()))
+        .filter_map(|script| script.to_opt());
+    for type_script in type_scripts {
+        let type_hash = type_script.calc_script_hash();
+        if !contains(&groups, ScriptGroupType::Type, &type_hash) {
+            groups.push((ScriptGroupType::Type, type_hash, false));
+        }
+    }
+
+    groups
+}
+
+/// Whether the witness lock of the first input of a group ends with the zero signature
+/// placeholder filled by the builders.
+fn has_signature_placeholder(tx_view: &TransactionView, index: usize) -> bool {
+    let lock = tx_view
+        .witnesses()
+        .get(index)
+        .and_then(|witness| packed::WitnessArgs::from_slice(&witness.raw_data()).ok())
+        .and_then(|witness_args| witness_args.lock().to_opt())
+        .map(|lock| lock.raw_data());
+
+    match lock {
+        Some(lock) if lock.len() >= SECP256K1_WITNESS_LOCATION.1 => lock
+            [lock.len() - SECP256K1_WITNESS_LOCATION.1..]
+            .iter()
+            .all(|byte| *byte == 0),
+        _ => false,
+    }
+}
+
+fn out_point_to_string(out_point: &packed::OutPoint) -> String {
+    let tx_hash: H256 = out_point.tx_hash().unpack();
+    let index: u32 = out_point.index().unpack();
+    format!("{}-{}", tx_hash, index)
+}
@@ -0,0 +1,179 @@
+use super::*;
+use crate::error::RpcErrorMessage;
+use crate::rpc_impl::batch_transfer::BlockLimit;
+use crate::rpc_impl::INIT_ESTIMATE_FEE;
+use crate::types::{AssetInfo, BatchTransferPayload, ToInfo};
+
+const FROM_ADDRESS: &str = "ckt1qyqr79tnk3pp34xp92gerxjc4p3mus2690psf0dd70";
+const TO_ADDRESS: &str = "ckt1qyq2y6jdkynen2vx946tnsdw2dgucvv7ph0s8n4kfd";
+
+fn ckb_batch_payload(recipient_number: usize, amount: u64) -> BatchTransferPayload {
+    BatchTransferPayload {
+        asset_info: AssetInfo::new_ckb(),
+        from: vec![FROM_ADDRESS.to_string()],
+        to: (0..recipient_number)
+            .map(|_| ToInfo {
+                address: TO_ADDRESS.to_string(),
+                amount: (amount * BYTE_SHANNONS).to_string(),
+            })
+            .collect(),
+        change: None,
+        fee_rate: None,
+        coin_selection: None,
+        dry_run: false,
+    }
+}
+
+fn output_capacity(response: &TransactionCompletionResponse, index: usize) -> u64 {
+    response.tx_view.inner.outputs[index].capacity.into()
+}
+
+#[tokio::test]
+async fn test_batch_transfer_chunks_and_chains_change() {
+    let engine =
+        RpcTestEngine::init_data(vec![AddressData::new(FROM_ADDRESS, 500_000, 0, 0, 0)]).await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let payload = ckb_batch_payload(5, 100);
+    let recipients = rpc
+        .build_batch_recipients(Context::new(), &payload)
+        .await
+        .unwrap();
+
+    // all the recipients fit in one transaction
+    let unlimited = BlockLimit {
+        max_bytes: u64::MAX,
+        max_cycles: u64::MAX,
+    };
+    let (responses, _) = rpc
+        .prebuild_batch_transfer_transactions(
+            Context::new(),
+            &payload,
+            &recipients,
+            &unlimited,
+            1000,
+            INIT_ESTIMATE_FEE,
+        )
+        .await
+        .unwrap();
+    assert_eq!(responses.len(), 1);
+    assert_eq!(responses[0].tx_view.inner.outputs.len(), 6);
+
+    // the block fits two recipients besides the input and the change
+    let tx_view = packed::Transaction::from(responses[0].tx_view.inner.clone()).into_view();
+    let skeleton_size =
+        crate::rpc_impl::calculate_tx_size(tx_view) as u64 - 5 * recipients[0].tx_size();
+    let limit = BlockLimit {
+        max_bytes: skeleton_size + 2 * recipients[0].tx_size(),
+        max_cycles: u64::MAX,
+    };
+    let (responses, actual_fee) = rpc
+        .prebuild_batch_transfer_transactions(
+            Context::new(),
+            &payload,
+            &recipients,
+            &limit,
+            1000,
+            INIT_ESTIMATE_FEE,
+        )
+        .await
+        .unwrap();
+    assert_eq!(responses.len(), 3);
+    assert!(actual_fee <= INIT_ESTIMATE_FEE);
+
+    let paid_numbers = responses
+        .iter()
+        .map(|response| response.tx_view.inner.outputs.len() - 1)
+        .collect::<Vec<_>>();
+    assert_eq!(paid_numbers, vec![2, 2, 1]);
+
+    // each transaction spends the change of the previous one, which is the last output
+    let mut input_capacity = 500_000 * BYTE_SHANNONS;
+    for (index, response) in responses.iter().enumerate() {
+        let outputs_len = response.tx_view.inner.outputs.len();
+        if index > 0 {
+            let prev = &responses[index - 1];
+            let inputs = &response.tx_view.inner.inputs;
+            assert_eq!(inputs.len(), 1);
+            assert_eq!(inputs[0].previous_output.tx_hash, prev.tx_view.hash);
+            let prev_change_index: u32 = inputs[0].previous_output.index.into();
+            assert_eq!(
+                prev_change_index as usize,
+                prev.tx_view.inner.outputs.len() - 1
+            );
+        }
+
+        let paid: u64 = (0..outputs_len - 1)
+            .map(|i| output_capacity(response, i))
+            .sum();
+        let change = output_capacity(response, outputs_len - 1);
+        let tx_view = packed::Transaction::from(response.tx_view.inner.clone()).into_view();
+        let fee = crate::rpc_impl::calculate_fee(
+            1000,
+            crate::rpc_impl::calculate_tx_size(tx_view) as u64,
+        );
+        assert_eq!(paid, (outputs_len - 1) as u64 * 100 * BYTE_SHANNONS);
+        assert_eq!(change, input_capacity - paid - fee);
+        input_capacity = change;
+    }
+    assert_eq!(input_capacity, (500_000 - 500) * BYTE_SHANNONS - actual_fee);
+}
+
+#[tokio::test]
+async fn test_batch_transfer_exceed_block_limit() {
+    let engine =
+        RpcTestEngine::init_data(vec![AddressData::new(FROM_ADDRESS, 500_000, 0, 0, 0)]).await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let payload = ckb_batch_payload(2, 100);
+    let recipients = rpc
+        .build_batch_recipients(Context::new(), &payload)
+        .await
+        .unwrap();
+
+    // not even one recipient fits in the block
+    let limit = BlockLimit {
+        max_bytes: 100,
+        max_cycles: u64::MAX,
+    };
+    let res = rpc
+        .prebuild_batch_transfer_transactions(
+            Context::new(),
+            &payload,
+            &recipients,
+            &limit,
+            1000,
+            INIT_ESTIMATE_FEE,
+        )
+        .await;
+    assert_eq!(res.err(), Some(RpcErrorMessage::ExceedMaxBlockLimit));
+}
+
+#[tokio::test]
+async fn test_batch_transfer_required_amount_overflow() {
+    let engine =
+        RpcTestEngine::init_data(vec![AddressData::new(FROM_ADDRESS, 500_000, 0, 0, 0)]).await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let mut payload = ckb_batch_payload(2, 100);
+    for to in payload.to.iter_mut() {
+        to.amount = (u64::MAX / 2 + 1).to_string();
+    }
+    let recipients = rpc
+        .build_batch_recipients(Context::new(), &payload)
+        .await
+        .unwrap();
+
+    let limit = BlockLimit {
+        max_bytes: u64::MAX,
+        max_cycles: u64::MAX,
+    };
+    let res = rpc
+        .prebuild_batch_transfer_transactions(
+            Context::new(),
+            &payload,
+            &recipients,
+            &limit,
+            1000,
+            INIT_ESTIMATE_FEE,
+        )
+        .await;
+    assert_eq!(res.err(), Some(RpcErrorMessage::Overflow));
+}
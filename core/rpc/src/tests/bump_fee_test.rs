@@ -0,0 +1,137 @@
+use super::*;
+use crate::error::RpcErrorMessage;
+use crate::rpc_impl::bump_fee::calculate_bump_fee;
+use crate::types::{MultisigScript, SignAlgorithm};
+
+use common::MULTISIG;
+
+fn new_multisig_script() -> MultisigScript {
+    MultisigScript {
+        require_first_n: 0,
+        threshold: 2,
+        pubkey_hashes: vec![H160(random()), H160(random()), H160(random())],
+    }
+}
+
+#[test]
+fn test_calculate_bump_fee() {
+    // the fee at the fee rate is enough for the replacement
+    assert_eq!(calculate_bump_fee(500, 3000, 1000, 1000), Ok(3000));
+    // the fee is raised to the old fee plus the fee at min_rbf_rate
+    assert_eq!(calculate_bump_fee(2000, 1000, 1500, 1000), Ok(3500));
+    // the fees are rounded up
+    assert_eq!(calculate_bump_fee(0, 1000, 1000, 1001), Ok(1001));
+    assert_eq!(calculate_bump_fee(10, 1, 1, 1), Ok(11));
+    assert_eq!(
+        calculate_bump_fee(u64::MAX, 1000, 1000, 1000),
+        Err(RpcErrorMessage::Overflow)
+    );
+}
+
+#[test]
+fn test_multisig_script_from_witness_lock() {
+    let multisig_script = new_multisig_script();
+    let witness_lock = multisig_script.witness_placeholder();
+    assert_eq!(
+        MultisigScript::from_witness_lock(&witness_lock),
+        Some(multisig_script.clone())
+    );
+
+    let bytes = multisig_script.to_bytes();
+    assert!(MultisigScript::from_witness_lock(&bytes[0..bytes.len() - 1]).is_none());
+    assert!(MultisigScript::from_witness_lock(&[0u8; 65]).is_some());
+    assert!(MultisigScript::from_witness_lock(&[1u8; 65]).is_none());
+    assert!(MultisigScript::from_witness_lock(&[]).is_none());
+}
+
+#[tokio::test]
+async fn test_get_signer_of_multisig_input() {
+    let engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let multisig_script = new_multisig_script();
+    let lock = engine
+        .script_map
+        .get(MULTISIG)
+        .cloned()
+        .unwrap()
+        .script
+        .as_builder()
+        .args(Bytes::from(multisig_script.lock_args().0.to_vec()).pack())
+        .build();
+    let cell = new_cell(lock.clone());
+
+    let (address, algorithm, script) = rpc
+        .get_signer(
+            Context::new(),
+            &cell,
+            0,
+            &multisig_script.witness_placeholder(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(address, rpc.script_to_address(&lock).to_string());
+    assert_eq!(algorithm, SignAlgorithm::Secp256k1);
+    assert_eq!(script, Some(multisig_script));
+
+    // the witness lock of another multisig script does not unlock the input
+    let res = rpc
+        .get_signer(
+            Context::new(),
+            &cell,
+            0,
+            &new_multisig_script().witness_placeholder(),
+        )
+        .await;
+    assert!(matches!(res, Err(RpcErrorMessage::CannotBumpFee(_))));
+}
+
+#[tokio::test]
+async fn test_get_signer_of_short_args() {
+    let engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+
+    for script_name in [ACP, CHEQUE] {
+        let lock = engine
+            .script_map
+            .get(script_name)
+            .cloned()
+            .unwrap()
+            .script
+            .as_builder()
+            .args(rand_bytes(10).pack())
+            .build();
+        let res = rpc
+            .get_signer(Context::new(), &new_cell(lock), 0, &[])
+            .await;
+        assert!(matches!(res, Err(RpcErrorMessage::CannotBumpFee(_))));
+    }
+}
+
+#[tokio::test]
+async fn test_get_signer_of_acp_input() {
+    let engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let pubkey_hash = H160(random());
+    let lock = engine
+        .script_map
+        .get(ACP)
+        .cloned()
+        .unwrap()
+        .script
+        .as_builder()
+        .args(Bytes::from(pubkey_hash.0.to_vec()).pack())
+        .build();
+
+    let (address, algorithm, script) = rpc
+        .get_signer(Context::new(), &new_cell(lock), 0, &[])
+        .await
+        .unwrap();
+    let expected = Address::new(
+        NETWORK_TYPE,
+        AddressPayload::from_pubkey_hash(NETWORK_TYPE, pubkey_hash),
+        true,
+    );
+    assert_eq!(address, expected.to_string());
+    assert_eq!(algorithm, SignAlgorithm::Secp256k1);
+    assert!(script.is_none());
+}
@@ -0,0 +1,109 @@
+use super::*;
+use crate::rpc_impl::cheque_withdraw_fee_index;
+use crate::types::{AssetInfo, ChequeClaimPayload, JsonItem, Source};
+
+const RECEIVER_ADDRESS: &str = "ckt1qyqr79tnk3pp34xp92gerxjc4p3mus2690psf0dd70";
+// the sender of the cheque cells built by `RpcTestEngine::init_data`
+const SENDER_ADDRESS: &str = "ckt1qyqd5eyygtdmwdr7ge736zw6z0ju6wsw7rssu8fcve";
+
+#[test]
+fn test_cheque_withdraw_fee_index() {
+    let fee = 1000;
+    // the fee is paid by the cell with the most capacity
+    assert_eq!(
+        cheque_withdraw_fee_index(
+            &[
+                CHEQUE_CELL_CAPACITY,
+                2 * CHEQUE_CELL_CAPACITY,
+                CHEQUE_CELL_CAPACITY
+            ],
+            fee
+        ),
+        Some(1)
+    );
+    assert_eq!(
+        cheque_withdraw_fee_index(&[STANDARD_SUDT_CAPACITY + fee], fee),
+        Some(0)
+    );
+    // the fee is pooled from the free cells of the sender
+    assert_eq!(
+        cheque_withdraw_fee_index(&[STANDARD_SUDT_CAPACITY + fee - 1], fee),
+        None
+    );
+    assert_eq!(cheque_withdraw_fee_index(&[], fee), None);
+}
+
+#[tokio::test]
+async fn test_cheque_input_since() {
+    let engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let cheque_cell = new_cell(engine.cheque_builder().args(rand_bytes(40).pack()).build());
+    let secp_cell = new_cell(parse_address(RECEIVER_ADDRESS).unwrap().payload().into());
+    let cells = vec![cheque_cell, secp_cell];
+
+    // the sender withdraws the cheque cell after 6 epochs
+    let inputs = rpc
+        .build_tx_cell_inputs(&cells, None, Source::Free)
+        .unwrap();
+    let since: u64 = inputs[0].since().unpack();
+    assert_eq!(since, 0xA000_0000_0000_0006);
+    let since: u64 = inputs[1].since().unpack();
+    assert_eq!(since, 0);
+
+    // the receiver claims the cheque cell at any time
+    let inputs = rpc
+        .build_tx_cell_inputs(&cells, None, Source::Claimable)
+        .unwrap();
+    assert!(inputs.iter().all(|input| {
+        let since: u64 = input.since().unpack();
+        since == 0
+    }));
+}
+
+#[tokio::test]
+async fn test_cheque_claim() {
+    let engine = RpcTestEngine::init_data(vec![
+        AddressData::new(RECEIVER_ADDRESS, 1000, 0, 0, 100),
+        AddressData::new(SENDER_ADDRESS, 1000, 0, 0, 0),
+    ])
+    .await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let udt_hash: H256 = engine.sudt_script.calc_script_hash().unpack();
+    let payload = ChequeClaimPayload {
+        from: JsonItem::Address(RECEIVER_ADDRESS.to_string()),
+        asset_infos: hashset![AssetInfo::new_udt(udt_hash)],
+        to: None,
+        fee_rate: None,
+        dry_run: false,
+    };
+
+    let response = rpc
+        .inner_build_cheque_claim_transaction(Context::new(), payload)
+        .await
+        .unwrap();
+    let tx = packed::Transaction::from(response.tx_view.inner).into_view();
+
+    // the cheque cell and the free cell paying the fee
+    assert_eq!(tx.inputs().len(), 2);
+    assert!(tx.inputs().into_iter().all(|input| {
+        let since: u64 = input.since().unpack();
+        since == 0
+    }));
+
+    // the capacity of the cheque cell goes back to the sender
+    let sender_lock: packed::Script = parse_address(SENDER_ADDRESS).unwrap().payload().into();
+    let output = tx.output(0).unwrap();
+    assert_eq!(output.lock(), sender_lock);
+    let capacity: u64 = output.capacity().unpack();
+    assert_eq!(capacity, CHEQUE_CELL_CAPACITY);
+
+    // the udt goes into a new cell of the receiver
+    let receiver_lock: packed::Script = parse_address(RECEIVER_ADDRESS).unwrap().payload().into();
+    let output = tx.output(1).unwrap();
+    assert_eq!(output.lock(), receiver_lock);
+    assert_eq!(output.type_().to_opt(), Some(engine.sudt_script.clone()));
+    assert_eq!(
+        decode_udt_amount(&tx.outputs_data().get(1).unwrap().raw_data()),
+        100
+    );
+}
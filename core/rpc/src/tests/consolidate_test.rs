@@ -0,0 +1,86 @@
+use super::*;
+use crate::error::RpcErrorMessage;
+use crate::types::{AssetInfo, ConsolidatePayload, JsonItem};
+
+const OWNER_ADDRESS: &str = "ckt1qyqr79tnk3pp34xp92gerxjc4p3mus2690psf0dd70";
+// the sender of the cheque cells built by `RpcTestEngine::init_data`
+const SENDER_ADDRESS: &str = "ckt1qyqd5eyygtdmwdr7ge736zw6z0ju6wsw7rssu8fcve";
+
+fn consolidate_payload(asset_info: AssetInfo, threshold: Option<u64>) -> ConsolidatePayload {
+    ConsolidatePayload {
+        item: JsonItem::Address(OWNER_ADDRESS.to_string()),
+        asset_info,
+        max_input_num: None,
+        threshold,
+        to: None,
+        fee_rate: None,
+        dry_run: false,
+    }
+}
+
+#[tokio::test]
+async fn test_consolidate_threshold_out_of_range() {
+    let engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+
+    for threshold in [0, 1] {
+        let res = rpc
+            .inner_build_consolidate_transaction(
+                Context::new(),
+                consolidate_payload(AssetInfo::new_ckb(), Some(threshold)),
+            )
+            .await;
+        assert!(matches!(res, Err(RpcErrorMessage::InvalidRpcParams(_))));
+    }
+}
+
+#[tokio::test]
+async fn test_consolidate_cheque_cells() {
+    let engine = RpcTestEngine::init_data(vec![
+        AddressData::new(OWNER_ADDRESS, 1000, 0, 0, 50),
+        AddressData::new(OWNER_ADDRESS, 0, 0, 0, 100),
+        AddressData::new(SENDER_ADDRESS, 1000, 0, 0, 0),
+    ])
+    .await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let udt_hash: H256 = engine.sudt_script.calc_script_hash().unpack();
+
+    // the udt is held only in the cheque cells, whose capacity goes back to the sender
+    let response = rpc
+        .inner_build_consolidate_transaction(
+            Context::new(),
+            consolidate_payload(AssetInfo::new_udt(udt_hash), None),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    let tx = packed::Transaction::from(response.tx_view.inner).into_view();
+
+    // the two cheque cells and the free cell paying the udt cell and the fee
+    assert_eq!(tx.inputs().len(), 3);
+    assert_eq!(tx.outputs().len(), 4);
+
+    let sender_lock: packed::Script = parse_address(SENDER_ADDRESS).unwrap().payload().into();
+    for index in 0..2 {
+        let output = tx.output(index).unwrap();
+        let capacity: u64 = output.capacity().unpack();
+        assert_eq!(output.lock(), sender_lock);
+        assert_eq!(capacity, CHEQUE_CELL_CAPACITY);
+    }
+
+    let owner_lock: packed::Script = parse_address(OWNER_ADDRESS).unwrap().payload().into();
+    let consolidated = tx.output(2).unwrap();
+    let capacity: u64 = consolidated.capacity().unpack();
+    assert_eq!(consolidated.lock(), owner_lock);
+    assert_eq!(capacity, STANDARD_SUDT_CAPACITY);
+    assert_eq!(
+        decode_udt_amount(&tx.outputs_data().get(2).unwrap().raw_data()),
+        150
+    );
+
+    let change = tx.output(3).unwrap();
+    let capacity: u64 = change.capacity().unpack();
+    assert_eq!(change.lock(), owner_lock);
+    assert!(change.type_().to_opt().is_none());
+    assert!(capacity < 1000 * BYTE_SHANNONS - STANDARD_SUDT_CAPACITY);
+}
@@ -0,0 +1,179 @@
+use super::*;
+use crate::rpc_impl::fee_rate::percentile;
+use crate::rpc_impl::{calculate_tx_size, FeeRateCache, DEFAULT_FEE_RATE};
+use crate::types::{EstimateFeeRatePayload, FeeRate, FeeRateTarget};
+
+use common::Range;
+
+fn new_cellbase(number: u64, capacity: u64) -> TransactionView {
+    TransactionBuilder::default()
+        .input(packed::CellInput::new_cellbase_input(number))
+        .witness(packed::Script::default().into_witness())
+        .output(
+            packed::CellOutputBuilder::default()
+                .capacity(capacity.pack())
+                .lock(CELLBASE_ADDRESS.clone().payload().into())
+                .build(),
+        )
+        .output_data(Default::default())
+        .build()
+}
+
+fn spend(input: &TransactionView, fee: u64) -> TransactionView {
+    let capacity: u64 = input.output(0).unwrap().capacity().unpack();
+    TransactionBuilder::default()
+        .input(packed::CellInput::new(
+            packed::OutPoint::new(input.hash(), 0),
+            0,
+        ))
+        .output(
+            packed::CellOutputBuilder::default()
+                .capacity((capacity - fee).pack())
+                .lock(CELLBASE_ADDRESS.clone().payload().into())
+                .build(),
+        )
+        .output_data(Default::default())
+        .build()
+}
+
+fn fee_rate(tx: &TransactionView, fee: u64) -> u64 {
+    fee * 1000 / calculate_tx_size(tx.clone()) as u64
+}
+
+#[test]
+fn test_percentile() {
+    assert_eq!(percentile(&[], 50), DEFAULT_FEE_RATE);
+
+    let rates = (1..=10).map(|i| i * 1000).collect::<Vec<_>>();
+    assert_eq!(percentile(&rates, 80), 8000);
+    assert_eq!(percentile(&rates, 50), 5000);
+    assert_eq!(percentile(&rates, 20), 2000);
+    assert_eq!(percentile(&rates, 100), 10000);
+
+    // the percentile is no less than the default fee rate
+    assert_eq!(percentile(&[10, 20, 30], 50), DEFAULT_FEE_RATE);
+    assert_eq!(percentile(&[5000], 20), 5000);
+}
+
+#[test]
+fn test_fee_rate_cache() {
+    let mut cache = FeeRateCache::default();
+    assert!(cache.tip().is_none());
+    assert_eq!(cache.uncached_ranges(0, 3), vec![Range::new(0, 3)]);
+
+    cache.insert(1, h256!("0x1"), vec![1000]);
+    cache.insert(2, h256!("0x2"), vec![]);
+    cache.insert(4, h256!("0x4"), vec![3000, 2000]);
+    assert_eq!(cache.tip(), Some((4, h256!("0x4"))));
+    assert_eq!(
+        cache.uncached_ranges(0, 6),
+        vec![Range::new(0, 0), Range::new(3, 3), Range::new(5, 6)]
+    );
+    assert_eq!(cache.rates(0, 6), vec![1000, 3000, 2000]);
+    assert_eq!(cache.rates(2, 3), Vec::<u64>::new());
+
+    cache.prune(2);
+    assert_eq!(cache.rates(0, 6), vec![3000, 2000]);
+    assert_eq!(cache.uncached_ranges(1, 2), vec![Range::new(1, 1)]);
+
+    cache.clear();
+    assert!(cache.tip().is_none());
+}
+
+#[tokio::test]
+async fn test_get_committed_fee_rates() {
+    let mut engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+
+    let cellbase_0 = new_cellbase(0, 1000 * BYTE_SHANNONS);
+    engine
+        .append(RpcTestEngine::new_block(vec![cellbase_0.clone()], 0, 0))
+        .await;
+    // the cellbases are not sampled
+    assert!(rpc
+        .get_committed_fee_rates(Context::new(), 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let tx_1 = spend(&cellbase_0, 10_000);
+    engine
+        .append(RpcTestEngine::new_block(
+            vec![new_cellbase(1, 1000 * BYTE_SHANNONS), tx_1.clone()],
+            1,
+            0,
+        ))
+        .await;
+    let rates = rpc
+        .get_committed_fee_rates(Context::new(), 10)
+        .await
+        .unwrap();
+    assert_eq!(rates, vec![fee_rate(&tx_1, 10_000)]);
+
+    // the block appended after the last estimation is loaded
+    let tx_2 = spend(&tx_1, 50_000);
+    engine
+        .append(RpcTestEngine::new_block(
+            vec![new_cellbase(2, 1000 * BYTE_SHANNONS), tx_2.clone()],
+            2,
+            0,
+        ))
+        .await;
+    let rates = rpc
+        .get_committed_fee_rates(Context::new(), 10)
+        .await
+        .unwrap();
+    assert_eq!(
+        rates,
+        vec![fee_rate(&tx_1, 10_000), fee_rate(&tx_2, 50_000)]
+    );
+
+    // only the recent blocks are sampled
+    let rates = rpc
+        .get_committed_fee_rates(Context::new(), 1)
+        .await
+        .unwrap();
+    assert_eq!(rates, vec![fee_rate(&tx_2, 50_000)]);
+}
+
+#[tokio::test]
+async fn test_resolve_fee_rate_without_tx_pool() {
+    let mut engine = RpcTestEngine::new().await;
+    // the ckb client of the test engine cannot query the tx pool
+    let rpc = engine.rpc(NETWORK_TYPE);
+
+    let cellbase_0 = new_cellbase(0, 1000 * BYTE_SHANNONS);
+    engine
+        .append(RpcTestEngine::new_block(vec![cellbase_0.clone()], 0, 0))
+        .await;
+    assert_eq!(
+        rpc.resolve_fee_rate(Context::new(), Some(FeeRate::Target(FeeRateTarget::Fast)))
+            .await,
+        Ok(DEFAULT_FEE_RATE)
+    );
+
+    let tx_1 = spend(&cellbase_0, 100_000);
+    engine
+        .append(RpcTestEngine::new_block(
+            vec![new_cellbase(1, 1000 * BYTE_SHANNONS), tx_1.clone()],
+            1,
+            0,
+        ))
+        .await;
+    assert!(rpc
+        .inner_estimate_fee_rate(Context::new(), EstimateFeeRatePayload { block_count: None })
+        .await
+        .is_err());
+
+    // the target is resolved by the committed fee rates
+    assert_eq!(
+        rpc.resolve_fee_rate(Context::new(), Some(FeeRate::Target(FeeRateTarget::Fast)))
+            .await,
+        Ok(fee_rate(&tx_1, 100_000).max(DEFAULT_FEE_RATE))
+    );
+    assert_eq!(
+        rpc.resolve_fee_rate(Context::new(), Some(FeeRate::Value(3000)))
+            .await,
+        Ok(3000)
+    );
+}
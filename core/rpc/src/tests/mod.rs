@@ -1,29 +1,39 @@
 #![allow(dead_code, unused_imports)]
 
+mod batch_transfer_test;
+mod bump_fee_test;
+mod cheque_test;
+mod consolidate_test;
+mod fee_rate_test;
+mod multi_asset_transfer_test;
 mod operation_test;
+mod partial_sign_test;
+mod preview_test;
 mod query_test;
 // mod transfer_completion_test;
 mod rpc_test;
 mod sqlite;
+mod subscription_test;
 mod utils_test;
+mod verify_test;
 
 use crate::rpc_impl::{
     address_to_script, ACP_CODE_HASH, BYTE_SHANNONS, CHEQUE_CELL_CAPACITY, CHEQUE_CODE_HASH,
     DAO_CODE_HASH, SECP256K1_CODE_HASH, STANDARD_SUDT_CAPACITY, SUDT_CODE_HASH,
 };
 use crate::types::{
-    AdjustAccountPayload, AdvanceQueryPayload, BlockInfo, DaoDepositPayload, DaoWithdrawPayload,
-    GetBalancePayload, GetBalanceResponse, GetBlockInfoPayload, GetSpentTransactionPayload,
-    GetTransactionInfoResponse, MercuryInfo, QueryResponse, QueryTransactionsPayload,
-    SmartTransferPayload, StructureType, TransactionCompletionResponse, TransactionStatus,
-    TransferPayload, TxView,
+    AdjustAccountPayload, AdvanceQueryPayload, BlockInfo, CoinSelection, DaoDepositPayload,
+    DaoWithdrawPayload, GetBalancePayload, GetBalanceResponse, GetBlockInfoPayload,
+    GetSpentTransactionPayload, GetTransactionInfoResponse, MercuryInfo, QueryResponse,
+    QueryTransactionsPayload, SmartTransferPayload, StructureType, TransactionCompletionResponse,
+    TransactionStatus, TransferPayload, TxView,
 };
 use crate::{CkbRpcClient, MercuryRpcImpl, MercuryRpcServer};
 
 use common::utils::{decode_udt_amount, parse_address, ScriptInfo};
 use common::{
-    async_trait, hash::blake2b_160, Address, AddressPayload, Context, NetworkType, Result, ACP,
-    CHEQUE, DAO, SECP256K1, SUDT,
+    async_trait, hash::blake2b_160, Address, AddressPayload, Context, DetailedCell, NetworkType,
+    Result, ACP, CHEQUE, DAO, SECP256K1, SUDT,
 };
 use core_cli::config::{parse, MercuryConfig};
 use core_storage::{DBDriver, RelationalStorage, Storage};
@@ -38,6 +48,7 @@ use parking_lot::RwLock;
 use rand::random;
 
 use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use std::{str::FromStr, sync::Arc};
 
 const CONFIG_PATH: &str = "../../devtools/config/testnet_config.toml";
@@ -92,8 +103,7 @@ impl RpcTestEngine {
             .await
             .unwrap();
 
-        let mut tx = store.pool.transaction().await.unwrap();
-        sqlite::create_tables(&mut tx).await.unwrap();
+        store.migrate(DBDriver::SQLite).await.unwrap();
 
         let config: MercuryConfig = parse(CONFIG_PATH).unwrap();
         let script_map = config.to_script_map();
@@ -344,6 +354,8 @@ impl RpcTestEngine {
             net_ty,
             RationalU256::from_u256(6u64.into()),
             RationalU256::from_u256(6u64.into()),
+            CoinSelection::default(),
+            Duration::from_secs(120),
         )
     }
 
@@ -417,6 +429,27 @@ pub fn rand_h256() -> H256 {
     H256::from_slice(&rand_bytes(32)).unwrap()
 }
 
+pub fn new_cell(lock: packed::Script) -> DetailedCell {
+    DetailedCell {
+        epoch_number: 0,
+        block_number: 0,
+        block_hash: H256::default(),
+        tx_index: 0,
+        out_point: packed::OutPoint::new(rand_h256().pack(), 0),
+        cell_output: packed::CellOutputBuilder::default()
+            .capacity(CHEQUE_CELL_CAPACITY.pack())
+            .lock(lock)
+            .build(),
+        cell_data: Bytes::new(),
+        consumed_block_number: None,
+        consumed_block_hash: None,
+        consumed_tx_hash: None,
+        consumed_tx_index: None,
+        consumed_input_index: None,
+        since: None,
+    }
+}
+
 fn write_file(data: String) {
     std::fs::write(OUTPUT_FILE, data).unwrap();
 }
@@ -0,0 +1,173 @@
+use super::*;
+use crate::error::RpcErrorMessage;
+use crate::types::{
+    AssetInfo, AssetTransfer, From, JsonItem, Mode, MultiAssetTransferPayload, Source, ToInfo,
+};
+
+const FROM_ADDRESS: &str = "ckt1qyqr79tnk3pp34xp92gerxjc4p3mus2690psf0dd70";
+const TO_ADDRESS: &str = "ckt1qyq2y6jdkynen2vx946tnsdw2dgucvv7ph0s8n4kfd";
+
+fn multi_asset_payload(
+    to: Vec<AssetTransfer>,
+    change: Option<String>,
+) -> MultiAssetTransferPayload {
+    MultiAssetTransferPayload {
+        from: From {
+            items: vec![JsonItem::Address(FROM_ADDRESS.to_string())],
+            source: Source::Free,
+        },
+        to,
+        change,
+        fee_rate: None,
+        since: None,
+        multisig_scripts: vec![],
+        coin_selection: None,
+        dry_run: false,
+    }
+}
+
+fn asset_transfer(asset_info: AssetInfo, address: &str, amount: u128, mode: Mode) -> AssetTransfer {
+    AssetTransfer {
+        asset_info,
+        to_infos: vec![ToInfo {
+            address: address.to_string(),
+            amount: amount.to_string(),
+        }],
+        mode,
+    }
+}
+
+fn acp_lock(engine: &RpcTestEngine, address: &str) -> packed::Script {
+    let address = parse_address(address).unwrap();
+    engine
+        .acp_builder()
+        .args(address.payload().args().pack())
+        .build()
+}
+
+#[tokio::test]
+async fn test_multi_asset_transfer_keeps_address_locks() {
+    let engine =
+        RpcTestEngine::init_data(vec![AddressData::new(FROM_ADDRESS, 1000, 100, 0, 0)]).await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let udt_hash: H256 = engine.sudt_script.calc_script_hash().unpack();
+    let to_acp_lock = acp_lock(&engine, TO_ADDRESS);
+    let to_acp_address = Address::new(NETWORK_TYPE, to_acp_lock.clone().into(), true);
+    let change_lock = acp_lock(&engine, FROM_ADDRESS);
+    let change_address = Address::new(NETWORK_TYPE, change_lock.clone().into(), true);
+    let payload = multi_asset_payload(
+        vec![
+            asset_transfer(
+                AssetInfo::new_ckb(),
+                &to_acp_address.to_string(),
+                100 * BYTE_SHANNONS as u128,
+                Mode::HoldByFrom,
+            ),
+            asset_transfer(
+                AssetInfo::new_udt(udt_hash),
+                TO_ADDRESS,
+                20,
+                Mode::HoldByFrom,
+            ),
+        ],
+        Some(change_address.to_string()),
+    );
+
+    let response = rpc
+        .inner_build_multi_asset_transfer_transaction(Context::new(), payload)
+        .await
+        .unwrap();
+    let tx = packed::Transaction::from(response.tx_view.inner).into_view();
+
+    // the udt cell and the ckb cell of the sender
+    assert_eq!(tx.inputs().len(), 2);
+    assert_eq!(tx.outputs().len(), 5);
+
+    // the ckb recipient gets a cell locked by the acp address as given
+    let output = tx.output(0).unwrap();
+    let capacity: u64 = output.capacity().unpack();
+    assert_eq!(output.lock(), to_acp_lock);
+    assert_eq!(capacity, 100 * BYTE_SHANNONS);
+
+    // the udt recipient gets a cheque cell
+    let output = tx.output(1).unwrap();
+    let code_hash: H256 = output.lock().code_hash().unpack();
+    assert_eq!(code_hash, **CHEQUE_CODE_HASH.load());
+    assert_eq!(
+        decode_udt_amount(&tx.outputs_data().get(1).unwrap().raw_data()),
+        20
+    );
+
+    // both the udt change and the ckb change are locked by the change address
+    let output = tx.output(3).unwrap();
+    assert_eq!(output.lock(), change_lock);
+    assert_eq!(output.type_().to_opt(), Some(engine.sudt_script.clone()));
+    assert_eq!(
+        decode_udt_amount(&tx.outputs_data().get(3).unwrap().raw_data()),
+        80
+    );
+    let output = tx.output(4).unwrap();
+    assert_eq!(output.lock(), change_lock);
+    assert!(output.type_().to_opt().is_none());
+}
+
+#[tokio::test]
+async fn test_multi_asset_transfer_into_acp_cell() {
+    let engine = RpcTestEngine::init_data(vec![
+        AddressData::new(FROM_ADDRESS, 1000, 100, 0, 0),
+        AddressData::new(TO_ADDRESS, 0, 0, 10, 0),
+    ])
+    .await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let udt_hash: H256 = engine.sudt_script.calc_script_hash().unpack();
+    let payload = multi_asset_payload(
+        vec![asset_transfer(
+            AssetInfo::new_udt(udt_hash),
+            TO_ADDRESS,
+            20,
+            Mode::HoldByTo,
+        )],
+        None,
+    );
+
+    let response = rpc
+        .inner_build_multi_asset_transfer_transaction(Context::new(), payload)
+        .await
+        .unwrap();
+    let tx = packed::Transaction::from(response.tx_view.inner).into_view();
+
+    // the acp cell of the recipient, the udt cell and the ckb cell of the sender
+    assert_eq!(tx.inputs().len(), 3);
+
+    let output = tx.output(0).unwrap();
+    let capacity: u64 = output.capacity().unpack();
+    assert_eq!(output.lock(), acp_lock(&engine, TO_ADDRESS));
+    assert_eq!(capacity, STANDARD_SUDT_CAPACITY);
+    assert_eq!(
+        decode_udt_amount(&tx.outputs_data().get(0).unwrap().raw_data()),
+        30
+    );
+}
+
+#[tokio::test]
+async fn test_multi_asset_transfer_without_acp_cell() {
+    let engine =
+        RpcTestEngine::init_data(vec![AddressData::new(FROM_ADDRESS, 1000, 100, 0, 0)]).await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let udt_hash: H256 = engine.sudt_script.calc_script_hash().unpack();
+
+    // the udt is not paid by a cheque cell when the recipient has no acp cell
+    let payload = multi_asset_payload(
+        vec![asset_transfer(
+            AssetInfo::new_udt(udt_hash),
+            TO_ADDRESS,
+            20,
+            Mode::HoldByTo,
+        )],
+        None,
+    );
+    let res = rpc
+        .inner_build_multi_asset_transfer_transaction(Context::new(), payload)
+        .await;
+    assert_eq!(res.err(), Some(RpcErrorMessage::CannotFindACPCell));
+}
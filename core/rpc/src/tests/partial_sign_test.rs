@@ -0,0 +1,209 @@
+use super::*;
+use crate::rpc_impl::partial_sign::signing_message;
+use crate::types::{
+    HashAlgorithm, MergeSignaturesPayload, MultisigScript, PartiallySignedTransaction,
+    ResolvedInputCell, SignAlgorithm, SignatureAction, SignatureInfo, SignatureLocation,
+    WitnessSignature, SECP256K1_WITNESS_LOCATION,
+};
+
+use common::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use common::CodeHashIndex;
+
+use ckb_jsonrpc_types::JsonBytes;
+
+fn random_key() -> (SecretKey, H160) {
+    let secret_key = SecretKey::from_slice(&random::<[u8; 32]>()).unwrap();
+    let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+    (secret_key, H160(blake2b_160(&pubkey.serialize()[..])))
+}
+
+fn sign(secret_key: &SecretKey, message: &[u8; 32]) -> JsonBytes {
+    let (recovery_id, data) = Secp256k1::new()
+        .sign_recoverable(&Message::from_slice(message).unwrap(), secret_key)
+        .serialize_compact();
+    let mut signature = data.to_vec();
+    signature.push(recovery_id.to_i32() as u8);
+    JsonBytes::from_vec(signature)
+}
+
+/// A transaction spending one cell of the address, with an empty witness lock.
+fn build_partially_signed_tx(
+    address: &Address,
+    multisig_script: Option<MultisigScript>,
+) -> PartiallySignedTransaction {
+    let out_point = packed::OutPoint::new(H256(random()).pack(), 0);
+    let output = packed::CellOutputBuilder::default()
+        .capacity(capacity_bytes!(100).pack())
+        .lock(address_to_script(address.payload()))
+        .build();
+    let (offset, placeholder) = match multisig_script.as_ref() {
+        Some(script) => (
+            script.get_signature_offset().0,
+            script.witness_placeholder(),
+        ),
+        None => (
+            SECP256K1_WITNESS_LOCATION.0,
+            Bytes::from(vec![0u8; SECP256K1_WITNESS_LOCATION.1]),
+        ),
+    };
+    let witness = packed::WitnessArgs::new_builder()
+        .lock(Some(placeholder).pack())
+        .build();
+    let tx_view = TransactionBuilder::default()
+        .input(packed::CellInput::new(out_point.clone(), 0))
+        .output(output.clone())
+        .output_data(Default::default())
+        .witness(witness.as_bytes().pack())
+        .build();
+
+    PartiallySignedTransaction {
+        tx_view: tx_view.into(),
+        input_cells: vec![ResolvedInputCell {
+            out_point: out_point.into(),
+            output: output.into(),
+            output_data: JsonBytes::default(),
+        }],
+        signature_actions: vec![SignatureAction {
+            signature_location: SignatureLocation { index: 0, offset },
+            signature_info: SignatureInfo {
+                algorithm: SignAlgorithm::Secp256k1,
+                address: address.to_string(),
+                multisig_script,
+            },
+            hash_algorithm: HashAlgorithm::Blake2b,
+            other_indexes_in_group: vec![],
+        }],
+    }
+}
+
+fn get_message(transaction: &PartiallySignedTransaction) -> [u8; 32] {
+    let tx_view = packed::Transaction::from(transaction.tx_view.inner.clone()).into_view();
+    let witnesses = tx_view.witnesses().into_iter().collect::<Vec<_>>();
+    signing_message(&tx_view, &transaction.signature_actions[0], &witnesses).unwrap()
+}
+
+fn merge_payload(
+    transaction: &PartiallySignedTransaction,
+    address: &Address,
+    signature: JsonBytes,
+) -> MergeSignaturesPayload {
+    MergeSignaturesPayload {
+        transaction: transaction.clone(),
+        signatures: vec![WitnessSignature {
+            address: address.to_string(),
+            signature,
+        }],
+    }
+}
+
+#[tokio::test]
+async fn test_merge_signatures() {
+    let rpc = RpcTestEngine::new().await.rpc(NETWORK_TYPE);
+    let (secret_key, pubkey_hash) = random_key();
+    let address = Address::new(
+        NETWORK_TYPE,
+        AddressPayload::from_pubkey_hash(NETWORK_TYPE, pubkey_hash),
+        true,
+    );
+    let transaction = build_partially_signed_tx(&address, None);
+    let signature = sign(&secret_key, &get_message(&transaction));
+
+    let merged = rpc
+        .inner_merge_signatures(
+            Context::new(),
+            merge_payload(&transaction, &address, signature),
+        )
+        .await
+        .unwrap();
+    assert!(merged.signature_actions.is_empty());
+    assert!(rpc
+        .inner_finalize_transaction(Context::new(), merged)
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn test_merge_multisig_signature_twice() {
+    let rpc = RpcTestEngine::new().await.rpc(NETWORK_TYPE);
+    let keys = (0..3).map(|_| random_key()).collect::<Vec<_>>();
+    let multisig_script = MultisigScript {
+        require_first_n: 0,
+        threshold: 2,
+        pubkey_hashes: keys.iter().map(|(_, hash)| hash.clone()).collect(),
+    };
+    let address = Address::new(
+        NETWORK_TYPE,
+        AddressPayload::new_short(
+            NETWORK_TYPE,
+            CodeHashIndex::Multisig,
+            multisig_script.lock_args(),
+        ),
+        true,
+    );
+    let transaction = build_partially_signed_tx(&address, Some(multisig_script));
+    let message = get_message(&transaction);
+    let signature_0 = sign(&keys[0].0, &message);
+
+    let merged = rpc
+        .inner_merge_signatures(
+            Context::new(),
+            merge_payload(&transaction, &address, signature_0.clone()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(merged.signature_actions.len(), 1);
+
+    // the second merge of the same signer does not fill another slot
+    let merged = rpc
+        .inner_merge_signatures(
+            Context::new(),
+            merge_payload(&merged, &address, signature_0),
+        )
+        .await
+        .unwrap();
+    assert_eq!(merged.signature_actions.len(), 1);
+    assert!(rpc
+        .inner_finalize_transaction(Context::new(), merged.clone())
+        .await
+        .is_err());
+
+    let merged = rpc
+        .inner_merge_signatures(
+            Context::new(),
+            merge_payload(&merged, &address, sign(&keys[2].0, &message)),
+        )
+        .await
+        .unwrap();
+    assert!(merged.signature_actions.is_empty());
+}
+
+#[tokio::test]
+async fn test_merge_signature_of_wrong_signer() {
+    let rpc = RpcTestEngine::new().await.rpc(NETWORK_TYPE);
+    let (_, pubkey_hash) = random_key();
+    let (other_secret_key, _) = random_key();
+    let address = Address::new(
+        NETWORK_TYPE,
+        AddressPayload::from_pubkey_hash(NETWORK_TYPE, pubkey_hash),
+        true,
+    );
+    let transaction = build_partially_signed_tx(&address, None);
+
+    let signature = sign(&other_secret_key, &get_message(&transaction));
+    assert!(rpc
+        .inner_merge_signatures(
+            Context::new(),
+            merge_payload(&transaction, &address, signature),
+        )
+        .await
+        .is_err());
+
+    let garbage = JsonBytes::from_vec(vec![1u8; SECP256K1_WITNESS_LOCATION.1]);
+    assert!(rpc
+        .inner_merge_signatures(
+            Context::new(),
+            merge_payload(&transaction, &address, garbage),
+        )
+        .await
+        .is_err());
+}
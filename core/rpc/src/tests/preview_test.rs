@@ -0,0 +1,76 @@
+use super::*;
+use crate::error::RpcErrorMessage;
+use crate::rpc_impl::preview::get_balance_changes;
+use crate::types::{AddressOrLockHash, AssetInfo, Record, Status};
+
+const SENDER_ADDRESS: &str = "ckt1qyqr79tnk3pp34xp92gerxjc4p3mus2690psf0dd70";
+const RECEIVER_ADDRESS: &str = "ckt1qyq2y6jdkynen2vx946tnsdw2dgucvv7ph0s8n4kfd";
+
+fn new_record(address: &str, asset_info: AssetInfo, amount: &str) -> Record {
+    Record {
+        id: String::new(),
+        address_or_lock_hash: AddressOrLockHash::Address(address.to_string()),
+        amount: amount.to_string(),
+        occupied: 0,
+        asset_info,
+        status: Status::Fixed(0),
+        extra: None,
+        block_number: 0,
+        epoch_number: 0,
+    }
+}
+
+#[test]
+fn test_get_balance_changes() {
+    let udt_info = AssetInfo::new_udt(rand_h256());
+    let records = vec![
+        new_record(SENDER_ADDRESS, AssetInfo::new_ckb(), "-1000"),
+        new_record(SENDER_ADDRESS, udt_info.clone(), "-100"),
+        new_record(RECEIVER_ADDRESS, udt_info.clone(), "100"),
+        new_record(SENDER_ADDRESS, AssetInfo::new_ckb(), "900"),
+        new_record(RECEIVER_ADDRESS, AssetInfo::new_ckb(), "50"),
+        new_record(RECEIVER_ADDRESS, AssetInfo::new_ckb(), "-50"),
+    ];
+
+    // the changes are summed up by owner and asset, and the zero changes are dropped
+    let changes = get_balance_changes(&records)
+        .unwrap()
+        .into_iter()
+        .map(|change| {
+            (
+                change.address_or_lock_hash,
+                change.asset_info,
+                change.amount,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        vec![
+            (
+                AddressOrLockHash::Address(SENDER_ADDRESS.to_string()),
+                AssetInfo::new_ckb(),
+                "-100".to_string()
+            ),
+            (
+                AddressOrLockHash::Address(SENDER_ADDRESS.to_string()),
+                udt_info.clone(),
+                "-100".to_string()
+            ),
+            (
+                AddressOrLockHash::Address(RECEIVER_ADDRESS.to_string()),
+                udt_info,
+                "100".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_get_balance_changes_invalid_amount() {
+    let records = vec![new_record(SENDER_ADDRESS, AssetInfo::new_ckb(), "0x10")];
+    assert!(matches!(
+        get_balance_changes(&records),
+        Err(RpcErrorMessage::InvalidRpcParams(_))
+    ));
+}
@@ -1,6 +1,8 @@
 use super::*;
 
-use crate::types::StructureType;
+use crate::error::RpcErrorMessage;
+use crate::types::{QueryType, StructureType};
+use common::{PaginationRequest, Range};
 use tokio::test;
 
 #[test]
@@ -33,6 +35,42 @@ async fn test_get_spent_transaction() {
     assert!(res.unwrap_err().to_string().contains("10090"))
 }
 
+#[test]
+async fn test_advance_query_without_filter() {
+    let engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NetworkType::Testnet);
+
+    let payload = AdvanceQueryPayload {
+        lock: None,
+        type_: None,
+        data: None,
+        args_len: None,
+        block_range: None,
+        pagination: PaginationRequest::default(),
+        query_type: QueryType::Cell,
+    };
+    let res = rpc.advance_query(payload).await;
+    assert!(res.is_err());
+}
+
+#[test]
+async fn test_advance_query_args_len_without_lock() {
+    let engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NetworkType::Testnet);
+
+    let payload = AdvanceQueryPayload {
+        lock: None,
+        type_: None,
+        data: None,
+        args_len: Some(20),
+        block_range: Some(Range::new(0, 1)),
+        pagination: PaginationRequest::default(),
+        query_type: QueryType::Cell,
+    };
+    let res = rpc.inner_advance_query(Context::new(), payload).await;
+    assert!(matches!(res, Err(RpcErrorMessage::InvalidRpcParams(_))));
+}
+
 // fn query_test(
 //     rpc: &MercuryRpcImpl<MemoryDB, CkbRpcClient>,
 //     addr: &str,
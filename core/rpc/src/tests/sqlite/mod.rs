@@ -16,19 +16,7 @@ pub async fn delete_all_data(tx: &mut RBatisTxExecutor<'_>) -> Result<()> {
     delete_uncle_relationship_table_data(tx).await?;
     delete_canonical_chain_table_data(tx).await?;
     delete_registered_address_table_data(tx).await?;
-    tx.commit().await?;
-    Ok(())
-}
-
-pub async fn create_tables(tx: &mut RBatisTxExecutor<'_>) -> Result<()> {
-    create_block_table(tx).await?;
-    create_transaction_table(tx).await?;
-    create_cell_table(tx).await?;
-    create_live_cell_table(tx).await?;
-    create_script_table(tx).await?;
-    create_uncle_relationship_table(tx).await?;
-    create_canonical_chain_table(tx).await?;
-    create_registered_address_table(tx).await?;
+    delete_balance_table_data(tx).await?;
     tx.commit().await?;
     Ok(())
 }
@@ -0,0 +1,104 @@
+use super::*;
+use crate::error::RpcErrorMessage;
+use crate::rpc_impl::{ChainEvent, SubscriptionEvent};
+use crate::types::{JsonItem, RollbackInfo};
+
+const MINER_ADDRESS: &str = "ckt1qyqd5eyygtdmwdr7ge736zw6z0ju6wsw7rssu8fcve";
+const OWNER_ADDRESS: &str = "ckt1qyqr79tnk3pp34xp92gerxjc4p3mus2690psf0dd70";
+const OTHER_ADDRESS: &str = "ckt1qyq2y6jdkynen2vx946tnsdw2dgucvv7ph0s8n4kfd";
+
+fn pay_to(address: &str, capacity: u64) -> TransactionView {
+    let address = parse_address(address).unwrap();
+    TransactionBuilder::default()
+        .output(
+            packed::CellOutputBuilder::default()
+                .capacity((capacity * BYTE_SHANNONS).pack())
+                .lock(address.payload().into())
+                .build(),
+        )
+        .output_data(Default::default())
+        .build()
+}
+
+async fn new_tip_block_info(engine: &mut RpcTestEngine) -> Arc<BlockInfo> {
+    let block = RpcTestEngine::new_block(
+        vec![
+            RpcTestEngine::build_cellbase_tx(MINER_ADDRESS, 1000),
+            pay_to(OWNER_ADDRESS, 100),
+            pay_to(OTHER_ADDRESS, 200),
+        ],
+        0,
+        0,
+    );
+    engine.append(block.clone()).await;
+
+    let event = engine
+        .rpc(NETWORK_TYPE)
+        .to_subscription_event(ChainEvent::NewTip(block.number(), block.hash().unpack()))
+        .await;
+    match event {
+        Some(SubscriptionEvent::NewTip(block_info)) => block_info,
+        _ => panic!("expect a new tip event"),
+    }
+}
+
+#[tokio::test]
+async fn test_subscription_event() {
+    let mut engine = RpcTestEngine::new().await;
+    let block_info = new_tip_block_info(&mut engine).await;
+    assert_eq!(block_info.block_number, 0);
+    assert_eq!(block_info.transactions.len(), 3);
+
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let block_hash = rand_h256();
+    assert_eq!(
+        rpc.to_subscription_event(ChainEvent::Rollback(1, block_hash.clone()))
+            .await,
+        Some(SubscriptionEvent::Rollback(RollbackInfo {
+            block_number: 1,
+            block_hash: block_hash.clone(),
+        }))
+    );
+
+    // the block info of an unknown block is not sent to the subscribers
+    assert_eq!(
+        rpc.to_subscription_event(ChainEvent::NewTip(1, block_hash))
+            .await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_subscription_filter_records() {
+    let mut engine = RpcTestEngine::new().await;
+    let block_info = new_tip_block_info(&mut engine).await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let secp_lock_hash = rpc
+        .get_record_subscription_lock_hash(JsonItem::Address(OWNER_ADDRESS.to_string()))
+        .unwrap();
+
+    let tx_infos = rpc.filter_records(&block_info, &secp_lock_hash);
+    assert_eq!(tx_infos.len(), 1);
+    assert_eq!(tx_infos[0].tx_hash, block_info.transactions[1].tx_hash);
+    assert!(tx_infos[0]
+        .records
+        .iter()
+        .all(|record| rpc.is_record_owned_by(record, &secp_lock_hash)));
+}
+
+#[tokio::test]
+async fn test_subscription_invalid_item() {
+    let engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+
+    assert!(rpc
+        .get_record_subscription_lock_hash(JsonItem::Address(OWNER_ADDRESS.to_string()))
+        .is_ok());
+    assert!(rpc
+        .get_record_subscription_lock_hash(JsonItem::Address("ckt1invalid".to_string()))
+        .is_err());
+    assert!(matches!(
+        rpc.get_record_subscription_lock_hash(JsonItem::Identity("0x00".to_string())),
+        Err(RpcErrorMessage::DecodeJson(_))
+    ));
+}
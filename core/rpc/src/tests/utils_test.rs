@@ -1,8 +1,17 @@
 use super::*;
-use crate::rpc_impl::utils;
-use crate::types::{SinceConfig, SinceFlag, SinceType};
+use crate::error::RpcErrorMessage;
+use crate::rpc_impl::coin_selector::{coin_selector, Candidate};
+use crate::rpc_impl::{resolve_pending_transactions, utils, InputReservations, PendingTxCache};
+use crate::types::{
+    AddressOrLockHash, AssetInfo, Balance, CoinSelection, Identity, IdentityFlag, Item,
+    MultisigScript, SignAlgorithm, SinceConfig, SinceFlag, SinceType,
+};
 
+use std::convert::TryFrom;
+
+use ckb_jsonrpc_types::TransactionWithStatus;
 use ckb_types::core::EpochNumberWithFraction;
+use common::DetailedCell;
 
 #[tokio::test]
 async fn test_is_dao_withdraw_unlock() {
@@ -121,3 +130,383 @@ async fn test_to_since() {
     });
     assert_eq!(Ok(0x20068d02880000b6u64), since)
 }
+
+#[tokio::test]
+async fn test_multisig_script() {
+    let multisig_script = MultisigScript {
+        require_first_n: 0,
+        threshold: 2,
+        pubkey_hashes: vec![
+            h160!("0x13e41d6f9292555916f17b4882a5477c01270142"),
+            h160!("0x5ca7c0d8f0a0c1e8b3a7d0bd8c0b0e2e4a1ba5b3"),
+            h160!("0xe2fa82e70b062c8644b80ad7ecf6e015e5f352f6"),
+        ],
+    };
+    let bytes = multisig_script.to_bytes();
+    assert_eq!(bytes.len(), 4 + 20 * 3);
+    assert_eq!(&bytes[0..4], &[0u8, 0, 2, 3]);
+    assert_eq!(
+        multisig_script.lock_args().as_bytes(),
+        &common::hash::blake2b_160(&bytes)
+    );
+    assert_eq!(multisig_script.get_signature_offset(), (20 + 64, 65 * 2));
+    assert_eq!(multisig_script.witness_placeholder().len(), 64 + 65 * 2);
+}
+
+#[tokio::test]
+async fn test_multisig_not_configured() {
+    let mut engine = RpcTestEngine::new().await;
+    engine.script_map.remove(common::MULTISIG);
+    let rpc = engine.rpc(NETWORK_TYPE);
+
+    let address = Address::new(
+        NETWORK_TYPE,
+        AddressPayload::new_short(
+            NETWORK_TYPE,
+            common::CodeHashIndex::Multisig,
+            H160(rand::random()),
+        ),
+        true,
+    );
+    assert!(address.is_multisig());
+    assert!(!rpc.is_multisig_script(&address_to_script(address.payload())));
+    assert_eq!(
+        rpc.get_secp_address_by_item(Item::Address(address.to_string())),
+        Err(RpcErrorMessage::MissingScriptInfo(
+            common::MULTISIG.to_string()
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_omni_lock_witness_placeholder() {
+    let algorithm = IdentityFlag::Ethereum.omni_lock_sign_algorithm().unwrap();
+    assert_eq!(algorithm, SignAlgorithm::EthereumPersonal);
+
+    let placeholder = algorithm.witness_lock_placeholder();
+    let witness = packed::WitnessArgs::new_builder()
+        .lock(Some(placeholder).pack())
+        .build();
+    let (offset, len) = algorithm.get_signature_offset();
+    assert_eq!(witness.as_bytes().len(), offset + len);
+
+    assert!(IdentityFlag::Ckb.omni_lock_sign_algorithm().is_err());
+    assert!(IdentityFlag::try_from(0x10).is_err());
+}
+
+#[tokio::test]
+async fn test_unsupported_identity_flag() {
+    let engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+
+    for flag in [
+        IdentityFlag::Eos,
+        IdentityFlag::OwnerLock,
+        IdentityFlag::Exec,
+        IdentityFlag::DI,
+    ] {
+        let item = Item::Identity(Identity::new(flag.clone(), H160(rand::random())));
+        let err = RpcErrorMessage::UnsupportIdentityFlag(flag as u8);
+        assert_eq!(rpc.get_secp_address_by_item(item.clone()), Err(err.clone()));
+        assert_eq!(rpc.get_secp_lock_hash_by_item(item), Err(err));
+    }
+}
+
+#[tokio::test]
+async fn test_get_epoch_start_number() {
+    let mut engine = RpcTestEngine::new().await;
+    // the epochs 0, 1 and 2 have 2, 3 and 4 blocks
+    let epochs = [(0, 2), (1, 3), (2, 4)];
+    let mut number = 0;
+    for (epoch, length) in epochs {
+        for index in 0..length {
+            let cellbase = RpcTestEngine::build_cellbase_tx(
+                "ckt1qyqr79tnk3pp34xp92gerxjc4p3mus2690psf0dd70",
+                1000 + number,
+            );
+            let epoch = EpochNumberWithFraction::new(epoch, index, length).full_value();
+            engine
+                .append(RpcTestEngine::new_block(vec![cellbase], number, epoch))
+                .await;
+            number += 1;
+        }
+    }
+    let rpc = engine.rpc(NETWORK_TYPE);
+
+    let start_number =
+        |block_number, epochs| rpc.get_epoch_start_number(Context::new(), block_number, epochs);
+    assert_eq!(start_number(7, 0).await, Ok(5));
+    assert_eq!(start_number(5, 0).await, Ok(5));
+    assert_eq!(start_number(7, 1).await, Ok(2));
+    assert_eq!(start_number(4, 1).await, Ok(0));
+    assert_eq!(start_number(8, 2).await, Ok(0));
+    assert_eq!(start_number(8, 6).await, Ok(0));
+    assert_eq!(start_number(0, 1).await, Ok(0));
+    assert_eq!(
+        start_number(9, 0).await,
+        Err(RpcErrorMessage::GetEpochFromNumberError(9))
+    );
+}
+
+fn new_pending_tx(input: packed::OutPoint, lock: &packed::Script) -> TransactionView {
+    TransactionBuilder::default()
+        .input(packed::CellInput::new(input, 0))
+        .output(
+            packed::CellOutputBuilder::default()
+                .capacity(capacity_bytes!(100).pack())
+                .lock(lock.clone())
+                .build(),
+        )
+        .output_data(Default::default())
+        .build()
+}
+
+fn new_lock() -> packed::Script {
+    packed::ScriptBuilder::default()
+        .args(rand_bytes(20).pack())
+        .build()
+}
+
+#[tokio::test]
+async fn test_pending_tx_cache() {
+    let mut engine = RpcTestEngine::new().await;
+    let (lock_a, lock_b, lock_c) = (new_lock(), new_lock(), new_lock());
+    let committed_tx = TransactionBuilder::default()
+        .output(
+            packed::CellOutputBuilder::default()
+                .capacity(capacity_bytes!(100).pack())
+                .lock(lock_a.clone())
+                .build(),
+        )
+        .output_data(Default::default())
+        .build();
+    let cellbase = TransactionBuilder::default()
+        .input(packed::CellInput::new_cellbase_input(0))
+        .output(
+            packed::CellOutputBuilder::default()
+                .capacity(capacity_bytes!(1000).pack())
+                .lock(CELLBASE_ADDRESS.clone().payload().into())
+                .build(),
+        )
+        .output_data(Default::default())
+        .build();
+    engine
+        .append(RpcTestEngine::new_block(
+            vec![cellbase, committed_tx.clone()],
+            0,
+            0,
+        ))
+        .await;
+
+    // tx_1 spends the committed cell, and tx_2 spends the output of tx_1
+    let tx_1 = new_pending_tx(packed::OutPoint::new(committed_tx.hash(), 0), &lock_b);
+    let tx_2 = new_pending_tx(packed::OutPoint::new(tx_1.hash(), 0), &lock_c);
+    let unknown = new_pending_tx(packed::OutPoint::new(H256(random()).pack(), 0), &lock_c);
+    let pool = vec![
+        TransactionWithStatus::with_pending(tx_1.clone()),
+        TransactionWithStatus::with_pending(tx_2.clone()),
+        TransactionWithStatus::with_pending(unknown.clone()),
+    ];
+    let txs = resolve_pending_transactions(
+        Context::new(),
+        &engine.store,
+        pool.clone(),
+        &HashMap::new(),
+        10,
+        0,
+    )
+    .await;
+    // the transaction of which the inputs are not synchronized is skipped
+    assert_eq!(txs.len(), 2);
+    let cache = PendingTxCache::new(txs);
+
+    let lock_hash = |lock: &packed::Script| -> H256 { lock.calc_script_hash().unpack() };
+    let spent = cache.get_spent_cells_by_lock_hashes(&[lock_hash(&lock_a), lock_hash(&lock_b)]);
+    assert_eq!(spent.len(), 1);
+    assert_eq!(
+        spent[0].out_point,
+        packed::OutPoint::new(committed_tx.hash(), 0)
+    );
+    assert!(cache
+        .get_cells_by_lock_hashes(&[lock_hash(&lock_b)])
+        .is_empty());
+    let cells = cache.get_cells_by_lock_hashes(&[lock_hash(&lock_c)]);
+    assert_eq!(cells.len(), 1);
+    assert_eq!(cells[0].out_point, packed::OutPoint::new(tx_2.hash(), 0));
+    assert_eq!(cells[0].block_number, 11);
+
+    let txs = cache.get_transactions_by_lock_hashes(&[lock_hash(&lock_b), lock_hash(&lock_c)]);
+    assert_eq!(txs.len(), 2);
+    let txs = cache.get_transactions_by_lock_hashes(&[lock_hash(&lock_a)]);
+    assert_eq!(txs.len(), 1);
+    assert_eq!(txs[0].transaction_view.hash(), tx_1.hash());
+
+    // the inputs resolved by the previous flush are reused
+    let mut previous_inputs = cache.input_cells();
+    previous_inputs.insert(unknown.hash().unpack(), spent);
+    let txs =
+        resolve_pending_transactions(Context::new(), &engine.store, pool, &previous_inputs, 10, 0)
+            .await;
+    assert_eq!(txs.len(), 3);
+}
+
+#[test]
+fn test_deduct_balances() {
+    let key = (
+        AddressOrLockHash::LockHash(H256::default().to_string()),
+        AssetInfo::new_ckb(),
+    );
+    let mut balance = Balance::new(key.0.clone(), key.1.clone());
+    balance.free = "100".to_string();
+    balance.occupied = "61".to_string();
+    let mut deducted = Balance::new(key.0.clone(), key.1.clone());
+    deducted.free = "30".to_string();
+    deducted.occupied = "70".to_string();
+
+    let mut balances_map = HashMap::new();
+    balances_map.insert(key.clone(), balance);
+    let mut deducted_map = HashMap::new();
+    deducted_map.insert(key.clone(), deducted);
+    utils::deduct_balances(&mut balances_map, deducted_map).unwrap();
+
+    assert_eq!(balances_map[&key].free, "70");
+    assert_eq!(balances_map[&key].occupied, "0");
+    assert_eq!(balances_map[&key].freezed, "0");
+
+    // the deducted balance of another key is skipped
+    let other_key = (key.0.clone(), AssetInfo::new_udt(rand_h256()));
+    let mut deducted = Balance::new(other_key.0.clone(), other_key.1.clone());
+    deducted.free = "10".to_string();
+    let mut deducted_map = HashMap::new();
+    deducted_map.insert(other_key, deducted);
+    utils::deduct_balances(&mut balances_map, deducted_map).unwrap();
+    assert_eq!(balances_map[&key].free, "70");
+}
+
+#[test]
+fn test_deduct_balances_invalid_amount() {
+    let key = (
+        AddressOrLockHash::LockHash(H256::default().to_string()),
+        AssetInfo::new_ckb(),
+    );
+    let mut balance = Balance::new(key.0.clone(), key.1.clone());
+    balance.free = "100".to_string();
+    let mut deducted = Balance::new(key.0.clone(), key.1.clone());
+    deducted.free = "-30".to_string();
+
+    let mut balances_map = HashMap::new();
+    balances_map.insert(key.clone(), balance);
+    let mut deducted_map = HashMap::new();
+    deducted_map.insert(key.clone(), deducted);
+    assert!(matches!(
+        utils::deduct_balances(&mut balances_map, deducted_map),
+        Err(RpcErrorMessage::InvalidRpcParams(_))
+    ));
+}
+
+fn new_candidate(amount: u64) -> Candidate {
+    let cell = DetailedCell {
+        epoch_number: 0,
+        block_number: 0,
+        block_hash: H256::default(),
+        tx_index: 0,
+        out_point: packed::OutPoint::default(),
+        cell_output: packed::CellOutputBuilder::default()
+            .capacity(amount.pack())
+            .build(),
+        cell_data: Bytes::new(),
+        consumed_block_number: None,
+        consumed_block_hash: None,
+        consumed_tx_hash: None,
+        consumed_tx_index: None,
+        consumed_input_index: None,
+        since: None,
+    };
+    Candidate {
+        cell,
+        amount: amount as u128,
+    }
+}
+
+fn select_amounts(selection: CoinSelection, amounts: &[u64], required: u128) -> Vec<u128> {
+    let candidates = amounts.iter().map(|a| new_candidate(*a)).collect();
+    coin_selector(selection)
+        .select(candidates, required)
+        .iter()
+        .map(|c| c.amount)
+        .collect()
+}
+
+#[test]
+fn test_coin_selector() {
+    let amounts = [30, 10, 50, 20];
+
+    assert_eq!(
+        select_amounts(CoinSelection::Sequential, &amounts, 40),
+        vec![30, 10, 50, 20]
+    );
+    assert_eq!(
+        select_amounts(CoinSelection::LargestFirst, &amounts, 40),
+        vec![50, 30, 20, 10]
+    );
+    assert_eq!(
+        select_amounts(CoinSelection::SmallestFirst, &amounts, 40),
+        vec![10, 20, 30, 50]
+    );
+    assert_eq!(
+        select_amounts(CoinSelection::MinimizeInputs, &amounts, 25),
+        vec![30, 50, 20, 10]
+    );
+    assert_eq!(
+        select_amounts(CoinSelection::MinimizeInputs, &amounts, 70),
+        vec![50, 30, 20, 10]
+    );
+    assert_eq!(
+        select_amounts(CoinSelection::BranchAndBound, &amounts, 40),
+        vec![30, 10]
+    );
+    assert_eq!(
+        select_amounts(CoinSelection::BranchAndBound, &amounts, 75),
+        vec![50, 30, 20, 10]
+    );
+}
+
+#[test]
+fn test_input_reservations() {
+    let mut reservations = InputReservations::default();
+    let out_point_1 = packed::OutPoint::new(h256!("0x1").pack(), 0);
+    let out_point_2 = packed::OutPoint::new(h256!("0x1").pack(), 1);
+    let tx_hash_1 = h256!("0xa");
+    let tx_hash_2 = h256!("0xb");
+    let ttl = Duration::from_secs(60);
+
+    assert!(reservations.reserve(tx_hash_1.clone(), vec![out_point_1.clone()], ttl));
+    assert!(reservations.is_reserved(&out_point_1));
+    assert!(!reservations.is_reserved(&out_point_2));
+
+    // the same transaction renews the reservation, another one is refused
+    assert!(reservations.reserve(tx_hash_1.clone(), vec![out_point_1.clone()], ttl));
+    assert!(!reservations.reserve(
+        tx_hash_2.clone(),
+        vec![out_point_1.clone(), out_point_2.clone()],
+        ttl
+    ));
+    assert!(!reservations.is_reserved(&out_point_2));
+
+    // the renewed reservation replaces the inputs reserved before
+    assert!(reservations.reserve(tx_hash_1.clone(), vec![out_point_2.clone()], ttl));
+    assert!(!reservations.is_reserved(&out_point_1));
+    assert!(reservations.is_reserved(&out_point_2));
+    assert!(reservations.reserve(tx_hash_1.clone(), vec![out_point_1.clone()], ttl));
+    assert!(!reservations.is_reserved(&out_point_2));
+
+    assert_eq!(reservations.release(&tx_hash_1), 1);
+    assert!(!reservations.is_reserved(&out_point_1));
+    assert!(reservations.reserve(tx_hash_2, vec![out_point_1.clone(), out_point_2], ttl));
+
+    // an expired reservation never blocks other transactions
+    let mut reservations = InputReservations::default();
+    assert!(reservations.reserve(tx_hash_1, vec![out_point_1.clone()], Duration::from_secs(0)));
+    assert!(!reservations.is_reserved(&out_point_1));
+    assert!(reservations.reserve(h256!("0xc"), vec![out_point_1], ttl));
+}
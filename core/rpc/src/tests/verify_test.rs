@@ -0,0 +1,175 @@
+use super::*;
+use crate::types::{ScriptGroupType, SECP256K1_WITNESS_LOCATION};
+
+use common::hash::new_blake2b;
+use common::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+
+use ckb_types::core::DepType;
+
+const BLOCK_DIR: &str = "../../devtools/test_data/blocks/";
+
+/// The secp256k1_blake160_sighash_all lock referenced by data hash, and the dep group of it
+/// in the genesis block of the test data.
+fn genesis_sighash_lock() -> (packed::Byte32, packed::CellDep) {
+    let genesis: BlockView = sqlite::read_block_view(0, BLOCK_DIR.to_string()).into();
+    let system_cells = genesis.transaction(0).unwrap();
+    let sighash_data = system_cells.outputs_data().get(1).unwrap().raw_data();
+    let cell_dep = packed::CellDep::new_builder()
+        .out_point(packed::OutPoint::new(
+            genesis.transaction(1).unwrap().hash(),
+            0,
+        ))
+        .dep_type(DepType::DepGroup.into())
+        .build();
+    (packed::CellOutput::calc_data_hash(&sighash_data), cell_dep)
+}
+
+fn random_key() -> (SecretKey, H160) {
+    let secret_key = SecretKey::from_slice(&random::<[u8; 32]>()).unwrap();
+    let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+    (secret_key, H160(blake2b_160(&pubkey.serialize()[..])))
+}
+
+/// Sign the only input of the transaction with sighash all.
+fn sign_tx(tx: &TransactionView, secret_key: &SecretKey) -> TransactionView {
+    let placeholder = packed::WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(vec![0u8; SECP256K1_WITNESS_LOCATION.1])).pack())
+        .build()
+        .as_bytes();
+    let mut blake2b = new_blake2b();
+    blake2b.update(tx.hash().as_slice());
+    blake2b.update(&(placeholder.len() as u64).to_le_bytes());
+    blake2b.update(&placeholder);
+    let mut message = [0u8; 32];
+    blake2b.finalize(&mut message);
+
+    let (recovery_id, data) = Secp256k1::new()
+        .sign_recoverable(&Message::from_slice(&message).unwrap(), secret_key)
+        .serialize_compact();
+    let mut signature = data.to_vec();
+    signature.push(recovery_id.to_i32() as u8);
+    let witness = packed::WitnessArgs::new_builder()
+        .lock(Some(Bytes::from(signature)).pack())
+        .build();
+    tx.as_advanced_builder()
+        .set_witnesses(vec![witness.as_bytes().pack()])
+        .build()
+}
+
+/// Index the test blocks and a cell of 1000 CKB owned by the key, and build a transaction
+/// spending the cell to the outputs of the capacities.
+async fn build_spending_tx(
+    engine: &mut RpcTestEngine,
+    pubkey_hash: &H160,
+    output_capacities: &[u64],
+) -> TransactionView {
+    sqlite::insert_blocks(engine.store.clone(), BLOCK_DIR).await;
+    let (code_hash, cell_dep) = genesis_sighash_lock();
+    let lock = packed::ScriptBuilder::default()
+        .code_hash(code_hash)
+        .hash_type(ScriptHashType::Data.into())
+        .args(Bytes::from(pubkey_hash.0.to_vec()).pack())
+        .build();
+
+    let cellbase = TransactionBuilder::default()
+        .input(packed::CellInput::new_cellbase_input(10))
+        .output(
+            packed::CellOutputBuilder::default()
+                .capacity(capacity_bytes!(1000).pack())
+                .lock(CELLBASE_ADDRESS.clone().payload().into())
+                .build(),
+        )
+        .output_data(Default::default())
+        .build();
+    let funding_tx = TransactionBuilder::default()
+        .output(
+            packed::CellOutputBuilder::default()
+                .capacity(capacity_bytes!(1000).pack())
+                .lock(lock.clone())
+                .build(),
+        )
+        .output_data(Default::default())
+        .build();
+    engine
+        .append(RpcTestEngine::new_block(
+            vec![cellbase, funding_tx.clone()],
+            10,
+            0,
+        ))
+        .await;
+
+    let mut builder = TransactionBuilder::default()
+        .input(packed::CellInput::new(
+            packed::OutPoint::new(funding_tx.hash(), 0),
+            0,
+        ))
+        .cell_dep(cell_dep);
+    for capacity in output_capacities.iter() {
+        builder = builder
+            .output(
+                packed::CellOutputBuilder::default()
+                    .capacity((capacity * BYTE_SHANNONS).pack())
+                    .lock(lock.clone())
+                    .build(),
+            )
+            .output_data(Default::default());
+    }
+    builder.build()
+}
+
+#[tokio::test]
+async fn test_verify_signed_transaction() {
+    let mut engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let (secret_key, pubkey_hash) = random_key();
+    let tx = build_spending_tx(&mut engine, &pubkey_hash, &[500, 499]).await;
+    let tx = sign_tx(&tx, &secret_key);
+
+    let res = rpc
+        .verify_transaction_view(Context::new(), tx, &[])
+        .await
+        .unwrap();
+    assert!(res.errors.is_empty());
+    assert_eq!(res.script_results.len(), 1);
+    assert_eq!(res.script_results[0].group_type, ScriptGroupType::Lock);
+    assert!(!res.script_results[0].skipped);
+    assert!(res.script_results[0].error.is_none());
+    assert!(res.cycles > 0);
+    assert_eq!(res.script_results[0].cycles, Some(res.cycles));
+}
+
+#[tokio::test]
+async fn test_verify_transaction_with_bad_signature() {
+    let mut engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let (_, pubkey_hash) = random_key();
+    let (other_key, _) = random_key();
+    let tx = build_spending_tx(&mut engine, &pubkey_hash, &[999]).await;
+    let tx = sign_tx(&tx, &other_key);
+
+    let res = rpc
+        .verify_transaction_view(Context::new(), tx, &[])
+        .await
+        .unwrap();
+    assert!(res.errors.is_empty());
+    assert_eq!(res.script_results.len(), 1);
+    assert!(!res.script_results[0].skipped);
+    assert!(res.script_results[0].error.is_some());
+    assert_eq!(res.cycles, 0);
+}
+
+#[tokio::test]
+async fn test_verify_transaction_over_capacity() {
+    let mut engine = RpcTestEngine::new().await;
+    let rpc = engine.rpc(NETWORK_TYPE);
+    let (secret_key, pubkey_hash) = random_key();
+    let tx = build_spending_tx(&mut engine, &pubkey_hash, &[1000, 1]).await;
+    let tx = sign_tx(&tx, &secret_key);
+
+    let res = rpc
+        .verify_transaction_view(Context::new(), tx, &[])
+        .await
+        .unwrap();
+    assert_eq!(res.errors.len(), 1);
+    assert!(res.errors[0].starts_with("Outputs capacity"));
+}
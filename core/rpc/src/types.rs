@@ -3,16 +3,21 @@ pub mod indexer_legacy;
 
 use crate::error::{InnerResult, RpcErrorMessage};
 
-use common::{derive_more::Display, utils::to_fixed_array, NetworkType, PaginationRequest, Range};
+use common::{
+    derive_more::Display, hash::blake2b_160, utils::to_fixed_array, NetworkType, PaginationRequest,
+    Range,
+};
 
 use ckb_jsonrpc_types::{
-    CellDep, CellOutput, OutPoint, Script, TransactionView, TransactionWithStatus,
+    CellDep, CellOutput, JsonBytes, OutPoint, Script, TransactionView, TransactionWithStatus,
+    Uint64,
 };
 use ckb_types::{bytes::Bytes, core::BlockNumber, packed, prelude::*, H160, H256};
 use serde::{Deserialize, Serialize};
 
 use std::cmp::Ordering;
 use std::collections::HashSet;
+use std::str::FromStr;
 
 pub type JsonRecordId = String;
 
@@ -23,6 +28,7 @@ pub type JsonRecordId = String;
 pub type RecordId = Bytes;
 
 pub const SECP256K1_WITNESS_LOCATION: (usize, usize) = (20, 65); // (offset, length)
+pub const OMNI_LOCK_WITNESS_LOCATION: (usize, usize) = (40, 65); // (offset, length)
 
 pub fn encode_record_id(
     out_point: packed::OutPoint,
@@ -57,7 +63,9 @@ pub fn decode_record_id(id: Bytes) -> InnerResult<(packed::OutPoint, AddressOrLo
     match type_ {
         0u8 => Ok((outpoint, AddressOrLockHash::Address(value))),
         1u8 => Ok((outpoint, AddressOrLockHash::LockHash(value))),
-        _ => unreachable!(),
+        _ => Err(RpcErrorMessage::InvalidRpcParams(
+            "invalid record id type".to_string(),
+        )),
     }
 }
 
@@ -73,10 +81,42 @@ pub enum Source {
     Claimable,
 }
 
+/// The strategy of selecting the input cells, see `CoinSelector`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum CoinSelection {
+    Sequential,
+    LargestFirst,
+    SmallestFirst,
+    BranchAndBound,
+    MinimizeInputs,
+}
+
+impl Default for CoinSelection {
+    fn default() -> Self {
+        CoinSelection::Sequential
+    }
+}
+
+impl FromStr for CoinSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Sequential" => Ok(CoinSelection::Sequential),
+            "LargestFirst" => Ok(CoinSelection::LargestFirst),
+            "SmallestFirst" => Ok(CoinSelection::SmallestFirst),
+            "BranchAndBound" => Ok(CoinSelection::BranchAndBound),
+            "MinimizeInputs" => Ok(CoinSelection::MinimizeInputs),
+            _ => Err(format!("invalid coin selection {}", s)),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum AssetType {
     CKB,
     UDT,
+    XUDT,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Display, Hash, PartialEq, Eq)]
@@ -95,6 +135,10 @@ impl AssetInfo {
         AssetInfo::new(AssetType::UDT, udt_hash)
     }
 
+    pub fn new_xudt(udt_hash: H256) -> Self {
+        AssetInfo::new(AssetType::XUDT, udt_hash)
+    }
+
     fn new(asset_type: AssetType, udt_hash: H256) -> Self {
         AssetInfo {
             asset_type,
@@ -172,7 +216,9 @@ impl std::convert::TryFrom<JsonItem> for Item {
                     ));
                 }
 
-                let ident = hex::decode(&s).unwrap();
+                let ident =
+                    hex::decode(&s).map_err(|e| RpcErrorMessage::DecodeHexError(e.to_string()))?;
+                let _ = IdentityFlag::try_from(ident[0])?;
                 Ok(Item::Identity(Identity(to_fixed_array::<21>(&ident))))
             }
             JsonItem::Record(mut s) => {
@@ -239,19 +285,35 @@ pub enum IdentityFlag {
     DI = 0xFE,
 }
 
-impl std::convert::From<u8> for IdentityFlag {
-    fn from(v: u8) -> Self {
+impl std::convert::TryFrom<u8> for IdentityFlag {
+    type Error = RpcErrorMessage;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
         match v {
-            0x0 => IdentityFlag::Ckb,
-            0x1 => IdentityFlag::Ethereum,
-            0x2 => IdentityFlag::Eos,
-            0x3 => IdentityFlag::Tron,
-            0x4 => IdentityFlag::Bitcoin,
-            0x5 => IdentityFlag::Dogecoin,
-            0xFC => IdentityFlag::OwnerLock,
-            0xFD => IdentityFlag::Exec,
-            0xFE => IdentityFlag::DI,
-            _ => unreachable!(),
+            0x0 => Ok(IdentityFlag::Ckb),
+            0x1 => Ok(IdentityFlag::Ethereum),
+            0x2 => Ok(IdentityFlag::Eos),
+            0x3 => Ok(IdentityFlag::Tron),
+            0x4 => Ok(IdentityFlag::Bitcoin),
+            0x5 => Ok(IdentityFlag::Dogecoin),
+            0xFC => Ok(IdentityFlag::OwnerLock),
+            0xFD => Ok(IdentityFlag::Exec),
+            0xFE => Ok(IdentityFlag::DI),
+            _ => Err(RpcErrorMessage::UnsupportIdentityFlag(v)),
+        }
+    }
+}
+
+impl IdentityFlag {
+    /// The identities of other chains are unlocked by omni-lock, the sign algorithm
+    /// is decided by the flag.
+    pub fn omni_lock_sign_algorithm(&self) -> InnerResult<SignAlgorithm> {
+        match self {
+            IdentityFlag::Ethereum => Ok(SignAlgorithm::EthereumPersonal),
+            IdentityFlag::Tron => Ok(SignAlgorithm::Tron),
+            IdentityFlag::Bitcoin => Ok(SignAlgorithm::Bitcoin),
+            IdentityFlag::Dogecoin => Ok(SignAlgorithm::Dogecoin),
+            _ => Err(RpcErrorMessage::UnsupportIdentityFlag(self.clone() as u8)),
         }
     }
 }
@@ -266,12 +328,12 @@ impl Identity {
         Identity(to_fixed_array::<21>(&inner))
     }
 
-    pub fn parse(&self) -> (IdentityFlag, H160) {
-        (self.flag(), self.hash())
+    pub fn parse(&self) -> InnerResult<(IdentityFlag, H160)> {
+        Ok((self.flag()?, self.hash()))
     }
 
-    pub fn flag(&self) -> IdentityFlag {
-        self.0[0].into()
+    pub fn flag(&self) -> InnerResult<IdentityFlag> {
+        IdentityFlag::try_from(self.0[0])
     }
 
     pub fn hash(&self) -> H160 {
@@ -360,6 +422,7 @@ pub struct Balance {
     pub occupied: String,
     pub freezed: String,
     pub claimable: String,
+    pub pending: String,
 }
 
 impl Balance {
@@ -371,6 +434,7 @@ impl Balance {
             occupied: 0u128.to_string(),
             freezed: 0u128.to_string(),
             claimable: 0u128.to_string(),
+            pending: 0u128.to_string(),
         }
     }
 }
@@ -390,6 +454,12 @@ pub struct BlockInfo {
     pub transactions: Vec<TransactionInfo>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct RollbackInfo {
+    pub block_number: BlockNumber,
+    pub block_hash: H256,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 pub struct GetTransactionInfoResponse {
     pub transaction: Option<TransactionInfo>,
@@ -405,6 +475,7 @@ pub struct QueryTransactionsPayload {
     pub block_range: Option<Range>,
     pub pagination: PaginationRequest,
     pub structure_type: StructureType,
+    pub include_pending: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -414,13 +485,17 @@ pub struct AdjustAccountPayload {
     pub asset_info: AssetInfo,
     pub account_number: Option<u32>,
     pub extra_ckb: Option<u64>,
-    pub fee_rate: Option<u64>,
+    pub fee_rate: Option<FeeRate>,
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct TransactionCompletionResponse {
     pub tx_view: TransactionView,
     pub signature_actions: Vec<SignatureAction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<VerifyTransactionResponse>,
 }
 
 impl TransactionCompletionResponse {
@@ -428,10 +503,47 @@ impl TransactionCompletionResponse {
         TransactionCompletionResponse {
             tx_view,
             signature_actions,
+            verification: None,
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct VerifyTransactionResponse {
+    pub cycles: u64,
+    pub script_results: Vec<ScriptVerifyResult>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ScriptVerifyResult {
+    pub script_hash: H256,
+    pub group_type: ScriptGroupType,
+    pub cycles: Option<u64>,
+    pub error: Option<String>,
+    pub skipped: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum ScriptGroupType {
+    Lock,
+    Type,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PreviewTransactionResponse {
+    pub transaction: TransactionInfo,
+    pub balance_changes: Vec<BalanceChange>,
+    pub balances: Vec<Balance>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub address_or_lock_hash: AddressOrLockHash,
+    pub asset_info: AssetInfo,
+    pub amount: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum HashAlgorithm {
     Blake2b,
@@ -440,12 +552,41 @@ pub enum HashAlgorithm {
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum SignAlgorithm {
     Secp256k1,
+    EthereumPersonal,
+    Tron,
+    Bitcoin,
+    Dogecoin,
 }
 
 impl SignAlgorithm {
     pub fn get_signature_offset(&self) -> (usize, usize) {
         match *self {
             SignAlgorithm::Secp256k1 => SECP256K1_WITNESS_LOCATION,
+            SignAlgorithm::EthereumPersonal
+            | SignAlgorithm::Tron
+            | SignAlgorithm::Bitcoin
+            | SignAlgorithm::Dogecoin => OMNI_LOCK_WITNESS_LOCATION,
+        }
+    }
+
+    /// The omni-lock witness lock is an `OmniLockWitnessLock` table, only the
+    /// signature field is filled.
+    pub fn witness_lock_placeholder(&self) -> Bytes {
+        match *self {
+            SignAlgorithm::Secp256k1 => Bytes::from(vec![0u8; SECP256K1_WITNESS_LOCATION.1]),
+            _ => {
+                let signature_len = OMNI_LOCK_WITNESS_LOCATION.1;
+                let header_len = 16usize;
+                let total_len = header_len + 4 + signature_len;
+                let mut ret = Vec::with_capacity(total_len);
+                ret.extend_from_slice(&(total_len as u32).to_le_bytes());
+                ret.extend_from_slice(&(header_len as u32).to_le_bytes());
+                ret.extend_from_slice(&(total_len as u32).to_le_bytes());
+                ret.extend_from_slice(&(total_len as u32).to_le_bytes());
+                ret.extend_from_slice(&(signature_len as u32).to_le_bytes());
+                ret.resize(total_len, 0u8);
+                ret.into()
+            }
         }
     }
 }
@@ -460,6 +601,67 @@ pub struct SignatureLocation {
 pub struct SignatureInfo {
     pub algorithm: SignAlgorithm,
     pub address: String,
+    pub multisig_script: Option<MultisigScript>,
+}
+
+/// The multisig script is serialized as `S | R | M | N | blake160(Pubkey1) | ...`,
+/// and the multisig lock args is the blake160 hash of it.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct MultisigScript {
+    pub require_first_n: u8,
+    pub threshold: u8,
+    pub pubkey_hashes: Vec<H160>,
+}
+
+impl MultisigScript {
+    pub fn to_bytes(&self) -> Bytes {
+        let mut ret = vec![
+            0u8,
+            self.require_first_n,
+            self.threshold,
+            self.pubkey_hashes.len() as u8,
+        ];
+        for pubkey_hash in self.pubkey_hashes.iter() {
+            ret.extend_from_slice(pubkey_hash.as_bytes());
+        }
+        ret.into()
+    }
+
+    pub fn lock_args(&self) -> H160 {
+        H160(blake2b_160(self.to_bytes()))
+    }
+
+    /// Parse the multisig script at the beginning of a multisig witness lock.
+    pub fn from_witness_lock(witness_lock: &[u8]) -> Option<Self> {
+        if witness_lock.len() < 4 || witness_lock[0] != 0 {
+            return None;
+        }
+        let pubkey_hashes_end = 4 + witness_lock[3] as usize * 20;
+        let pubkey_hashes = witness_lock.get(4..pubkey_hashes_end)?;
+        Some(MultisigScript {
+            require_first_n: witness_lock[1],
+            threshold: witness_lock[2],
+            pubkey_hashes: pubkey_hashes
+                .chunks(20)
+                .map(H160::from_slice)
+                .collect::<Result<_, _>>()
+                .ok()?,
+        })
+    }
+
+    /// The witness lock is the multisig script followed by `threshold` signatures.
+    pub fn witness_placeholder(&self) -> Bytes {
+        let mut ret = self.to_bytes().to_vec();
+        ret.resize(self.get_signature_offset().1 + ret.len(), 0u8);
+        ret.into()
+    }
+
+    pub fn get_signature_offset(&self) -> (usize, usize) {
+        (
+            SECP256K1_WITNESS_LOCATION.0 + self.to_bytes().len(),
+            SECP256K1_WITNESS_LOCATION.1 * self.threshold as usize,
+        )
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -499,6 +701,34 @@ impl Ord for SignatureAction {
     }
 }
 
+/// A transaction passed between several signers. The input cells are resolved so that a
+/// signer can check what it signs, and the signature actions are the ones not fulfilled yet.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PartiallySignedTransaction {
+    pub tx_view: TransactionView,
+    pub input_cells: Vec<ResolvedInputCell>,
+    pub signature_actions: Vec<SignatureAction>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ResolvedInputCell {
+    pub out_point: OutPoint,
+    pub output: CellOutput,
+    pub output_data: JsonBytes,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MergeSignaturesPayload {
+    pub transaction: PartiallySignedTransaction,
+    pub signatures: Vec<WitnessSignature>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct WitnessSignature {
+    pub address: String,
+    pub signature: JsonBytes,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 pub struct TransferPayload {
     pub asset_info: AssetInfo,
@@ -506,8 +736,34 @@ pub struct TransferPayload {
     pub to: To,
     pub pay_fee: Option<String>,
     pub change: Option<String>,
-    pub fee_rate: Option<u64>,
+    pub fee_rate: Option<FeeRate>,
     pub since: Option<SinceConfig>,
+    #[serde(default)]
+    pub multisig_scripts: Vec<MultisigScript>,
+    pub coin_selection: Option<CoinSelection>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct MultiAssetTransferPayload {
+    pub from: From,
+    pub to: Vec<AssetTransfer>,
+    pub change: Option<String>,
+    pub fee_rate: Option<FeeRate>,
+    pub since: Option<SinceConfig>,
+    #[serde(default)]
+    pub multisig_scripts: Vec<MultisigScript>,
+    pub coin_selection: Option<CoinSelection>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct AssetTransfer {
+    pub asset_info: AssetInfo,
+    pub to_infos: Vec<ToInfo>,
+    pub mode: Mode,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
@@ -541,8 +797,85 @@ pub struct SmartTransferPayload {
     pub from: Vec<String>,
     pub to: Vec<ToInfo>,
     pub change: Option<String>,
-    pub fee_rate: Option<u64>,
+    pub fee_rate: Option<FeeRate>,
     pub since: Option<SinceConfig>,
+    #[serde(default)]
+    pub multisig_scripts: Vec<MultisigScript>,
+    pub coin_selection: Option<CoinSelection>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct BatchTransferPayload {
+    pub asset_info: AssetInfo,
+    pub from: Vec<String>,
+    pub to: Vec<ToInfo>,
+    pub change: Option<String>,
+    pub fee_rate: Option<FeeRate>,
+    pub coin_selection: Option<CoinSelection>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ConsolidatePayload {
+    pub item: JsonItem,
+    pub asset_info: AssetInfo,
+    pub max_input_num: Option<u64>,
+    pub threshold: Option<u64>,
+    pub to: Option<String>,
+    pub fee_rate: Option<FeeRate>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct BumpFeePayload {
+    pub tx_hash: H256,
+    pub change: Option<String>,
+    pub fee_rate: Option<FeeRate>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// The fee rates of the `tx_pool_info` of a node, the nodes not supporting replace by fee
+/// do not report `min_rbf_rate`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TxPoolInfo {
+    pub min_fee_rate: Uint64,
+    #[serde(default)]
+    pub min_rbf_rate: Option<Uint64>,
+}
+
+/// A fee rate in shannons per KB, or a target resolved by the fee rates estimated from the
+/// recent transactions.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum FeeRate {
+    Value(u64),
+    Target(FeeRateTarget),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum FeeRateTarget {
+    Fast,
+    Normal,
+    Slow,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct EstimateFeeRatePayload {
+    pub block_count: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct EstimateFeeRateResponse {
+    pub fast: u64,
+    pub normal: u64,
+    pub slow: u64,
+    pub committed_tx_count: u64,
+    pub pending_tx_count: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
@@ -565,21 +898,48 @@ pub struct DaoDepositPayload {
     pub from: From,
     pub to: Option<String>,
     pub amount: u64,
-    pub fee_rate: Option<u64>,
+    pub fee_rate: Option<FeeRate>,
+    pub coin_selection: Option<CoinSelection>,
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 pub struct DaoWithdrawPayload {
     pub from: JsonItem,
     pub pay_fee: Option<String>,
-    pub fee_rate: Option<u64>,
+    pub fee_rate: Option<FeeRate>,
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 pub struct DaoClaimPayload {
     pub from: JsonItem,
     pub to: Option<String>,
-    pub fee_rate: Option<u64>,
+    pub fee_rate: Option<FeeRate>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChequeClaimPayload {
+    pub from: JsonItem,
+    pub asset_infos: HashSet<AssetInfo>,
+    pub to: Option<String>,
+    pub fee_rate: Option<FeeRate>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChequeWithdrawPayload {
+    pub from: JsonItem,
+    pub asset_infos: HashSet<AssetInfo>,
+    pub to: Option<String>,
+    pub fee_rate: Option<FeeRate>,
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
@@ -621,6 +981,18 @@ pub struct CellInfo {
     data: Bytes,
 }
 
+impl std::convert::From<common::DetailedCell> for CellInfo {
+    fn from(cell: common::DetailedCell) -> Self {
+        CellInfo {
+            cell_output: cell.cell_output.into(),
+            out_point: cell.out_point.into(),
+            block_hash: cell.block_hash,
+            block_number: cell.block_number,
+            data: cell.cell_data,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 pub struct RequiredUDT {
     pub udt_hash: H256,
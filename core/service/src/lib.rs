@@ -2,39 +2,60 @@
 
 mod middleware;
 
+pub use core_synchronization::BlockDumpFormat;
+
 // use middleware::{CkbRelayMiddleware, RelayMetadata};
 
 use common::{anyhow::anyhow, utils::ScriptInfo, Context, NetworkType, Result};
+use core_rpc::types::CoinSelection;
 use core_rpc::{
-    CkbRpc, CkbRpcClient, MercuryRpcImpl, MercuryRpcServer, CURRENT_BLOCK_NUMBER,
-    CURRENT_EPOCH_NUMBER, TX_POOL_CACHE,
+    notify_chain_event, resolve_pending_transactions, ChainEvent, CkbRpc, CkbRpcClient,
+    CkbRpcClientPool, MercuryRpcImpl, MercuryRpcServer, MercurySubscriptionServer, PendingTxCache,
+    CURRENT_BLOCK_NUMBER, CURRENT_EPOCH_NUMBER, PENDING_TX_CACHE, TX_POOL_CACHE,
 };
-use core_storage::{DBDriver, RelationalStorage, Storage};
-use core_synchronization::Synchronization;
+use core_storage::{DBDriver, KVStorage, RelationalStorage, ReorgLog, Storage};
+use core_synchronization::{BlockDumpWriter, FileSyncAdapter, SyncAdapter, Synchronization};
 
-use ckb_jsonrpc_types::{RawTxPool, TransactionWithStatus};
+use ckb_jsonrpc_types::RawTxPool;
 use ckb_types::core::{BlockNumber, BlockView, EpochNumberWithFraction, RationalU256};
-use ckb_types::{packed, H256};
+use ckb_types::{packed, prelude::*, H256};
 use jsonrpsee_http_server::{HttpServerBuilder, HttpStopHandle};
+use jsonrpsee_ws_server::{WsServerBuilder, WsStopHandle};
 use log::{error, info, warn, LevelFilter};
 use tokio::time::{sleep, Duration};
 
 use std::collections::{HashMap, HashSet};
 use std::net::ToSocketAddrs;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const GENESIS_NUMBER: u64 = 0;
+const EXPORT_BLOCK_BATCH_SIZE: usize = 100;
+const CKB_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_ROLLBACK_RETRY: usize = 3;
 
 #[derive(Clone, Debug)]
 pub struct Service {
     store: RelationalStorage,
-    ckb_client: CkbRpcClient,
+    kv_store: Option<KVStorage>,
+    ckb_client: CkbRpcClientPool,
     poll_interval: Duration,
     rpc_thread_num: usize,
     network_type: NetworkType,
     builtin_scripts: HashMap<String, ScriptInfo>,
     cellbase_maturity: RationalU256,
     cheque_since: RationalU256,
+    coin_selection: CoinSelection,
+    reservation_ttl: Duration,
+    max_reorg_depth: u64,
+}
+
+/// The fork point of a reorg, with the blocks after it to be rolled back from the tip.
+struct ForkPoint {
+    block_number: BlockNumber,
+    new_block_hash: H256,
+    rolled_back_blocks: Vec<(BlockNumber, H256)>,
 }
 
 impl Service {
@@ -47,20 +68,28 @@ impl Service {
         network_ty: &str,
         builtin_scripts: HashMap<String, ScriptInfo>,
         cellbase_maturity: u64,
-        ckb_uri: String,
+        ckb_uris: Vec<String>,
         cheque_since: u64,
+        coin_selection: &str,
+        reservation_ttl: u64,
+        max_reorg_depth: u64,
         log_level: LevelFilter,
+        kv_store_path: Option<String>,
     ) -> Self {
-        let ckb_client = CkbRpcClient::new(ckb_uri);
+        let ckb_client = CkbRpcClientPool::new(ckb_uris);
         let store = RelationalStorage::new(max_connections, center_id, machine_id, log_level);
+        let kv_store = kv_store_path.map(|path| KVStorage::new(&path));
         let network_type = NetworkType::from_raw_str(network_ty).expect("invalid network type");
         let cellbase_maturity = RationalU256::from_u256(cellbase_maturity.into());
         let cheque_since = RationalU256::from_u256(cheque_since.into());
+        let coin_selection =
+            CoinSelection::from_str(coin_selection).expect("invalid coin selection");
 
         info!("Mercury running in CKB {:?}", network_type);
 
         Service {
             store,
+            kv_store,
             ckb_client,
             poll_interval,
             rpc_thread_num,
@@ -68,6 +97,9 @@ impl Service {
             builtin_scripts,
             cellbase_maturity,
             cheque_since,
+            coin_selection,
+            reservation_ttl: Duration::from_secs(reservation_ttl),
+            max_reorg_depth,
         }
     }
 
@@ -81,17 +113,29 @@ impl Service {
         user: String,
         password: String,
     ) -> HttpStopHandle {
-        self.store
-            .connect(
-                DBDriver::from_str(&db_driver),
-                &db_name,
-                &host,
-                port,
-                &user,
-                &password,
-            )
+        if self.kv_store.is_none() {
+            let db_driver = DBDriver::from_str(&db_driver);
+            self.store
+                .connect(db_driver.clone(), &db_name, &host, port, &user, &password)
+                .await
+                .unwrap();
+            self.store
+                .migrate(db_driver)
+                .await
+                .expect("migrate database schema");
+        }
+
+        // the nodes on a chain other than the synchronized one are refused
+        if let Ok(genesis_hash) = self
+            .storage()
+            .get_canonical_block_hash(Context::new(), GENESIS_NUMBER)
             .await
-            .unwrap();
+        {
+            self.ckb_client.set_genesis_hash(genesis_hash);
+        }
+        self.ckb_client.check_health().await;
+        self.ckb_client
+            .spawn_health_check(CKB_HEALTH_CHECK_INTERVAL);
 
         let server = HttpServerBuilder::default()
             .build(
@@ -105,44 +149,137 @@ impl Service {
 
         // let mut io_handler: MetaIoHandler<RelayMetadata, _> =
         //     MetaIoHandler::with_middleware(CkbRelayMiddleware::new(self.ckb_client.clone()));
-        let mercury_rpc_impl = MercuryRpcImpl::new(
-            self.store.clone(),
-            self.builtin_scripts.clone(),
-            self.ckb_client.clone(),
-            self.network_type,
-            self.cheque_since.clone(),
-            self.cellbase_maturity.clone(),
-        );
+        let mercury_rpc_impl = self.build_rpc_impl();
 
         info!("Mercury Running!");
 
         server
-            .start(mercury_rpc_impl.into_rpc())
+            .start(MercuryRpcServer::into_rpc(mercury_rpc_impl))
             .expect("Start jsonrpc http server")
     }
 
-    pub async fn do_sync(&self, sync_task_size: usize, max_task_number: usize) -> Result<()> {
+    /// The websocket server serves the subscriptions together with all the http methods.
+    pub async fn init_ws(&self, listen_address: String) -> WsStopHandle {
+        let server = WsServerBuilder::default()
+            .build(
+                listen_address
+                    .to_socket_addrs()
+                    .expect("config ws_listen_address parsed")
+                    .next()
+                    .expect("ws_listen_address parsed"),
+            )
+            .await
+            .unwrap();
+
+        let mercury_rpc_impl = self.build_rpc_impl();
+        let dispatcher = mercury_rpc_impl.clone();
+        tokio::spawn(async move { dispatcher.dispatch_chain_events().await });
+
+        let mut module = MercuryRpcServer::into_rpc(mercury_rpc_impl.clone());
+        module
+            .merge(MercurySubscriptionServer::into_rpc(mercury_rpc_impl))
+            .expect("Merge subscription methods");
+
+        info!("Mercury websocket listen on {}", listen_address);
+
+        server.start(module).expect("Start jsonrpc ws server")
+    }
+
+    fn build_rpc_impl(&self) -> MercuryRpcImpl<CkbRpcClientPool> {
+        match &self.kv_store {
+            Some(kv_store) => MercuryRpcImpl::new(
+                kv_store.clone(),
+                self.builtin_scripts.clone(),
+                self.ckb_client.clone(),
+                self.network_type,
+                self.cheque_since.clone(),
+                self.cellbase_maturity.clone(),
+                self.coin_selection,
+                self.reservation_ttl,
+            ),
+            None => MercuryRpcImpl::new(
+                self.store.clone(),
+                self.builtin_scripts.clone(),
+                self.ckb_client.clone(),
+                self.network_type,
+                self.cheque_since.clone(),
+                self.cellbase_maturity.clone(),
+                self.coin_selection,
+                self.reservation_ttl,
+            ),
+        }
+    }
+
+    /// The storage that the blocks are appended to, either the KV storage if configured
+    /// or the relational storage.
+    fn storage(&self) -> &(dyn Storage + Send + Sync) {
+        match &self.kv_store {
+            Some(kv_store) => kv_store,
+            None => &self.store,
+        }
+    }
+
+    /// Synchronize the blocks from the node, or from the block dump if its path is given.
+    pub async fn do_sync(
+        &self,
+        sync_task_size: usize,
+        max_task_number: usize,
+        block_dump_path: Option<String>,
+    ) -> Result<()> {
+        // The parallel synchronization writes the relational tables directly, the KV
+        // storage is synchronized block by block instead.
+        if self.kv_store.is_some() {
+            return Ok(());
+        }
+
+        match block_dump_path {
+            Some(path) => {
+                let adapter = FileSyncAdapter::open(&path)?;
+                let dump_tip = adapter
+                    .tip_number()
+                    .ok_or_else(|| anyhow!("no genesis block in block dump {}", path))?;
+                log::info!("sync from block dump {} to {}", path, dump_tip);
+                self.sync_with(Arc::new(adapter), dump_tip, sync_task_size, max_task_number)
+                    .await
+            }
+            None => {
+                // the blocks are pulled from the node the tip is taken from
+                let ckb_client = self.ckb_client.pinned();
+                let node_tip = ckb_client.get_tip_block_number().await?;
+                self.sync_with(
+                    Arc::new(ckb_client),
+                    node_tip,
+                    sync_task_size,
+                    max_task_number,
+                )
+                .await
+            }
+        }
+    }
+
+    async fn sync_with<T: SyncAdapter>(
+        &self,
+        adapter: Arc<T>,
+        chain_tip: BlockNumber,
+        sync_task_size: usize,
+        max_task_number: usize,
+    ) -> Result<()> {
         let db_tip = self
             .store
             .get_tip(Context::new())
             .await?
             .map_or_else(|| 0, |t| t.0);
         let mercury_count = self.store.block_count().await?;
-        let node_tip = self.ckb_client.get_tip_block_number().await?;
 
-        if db_tip > node_tip {
-            return Err(anyhow!("db tip is greater than node tip"));
+        if db_tip > chain_tip {
+            return Err(anyhow!("db tip is greater than chain tip"));
         }
 
-        let sync_handler = Synchronization::new(
-            self.store.inner(),
-            Arc::new(self.ckb_client.clone()),
-            sync_task_size,
-            max_task_number,
-        );
+        let sync_handler =
+            Synchronization::new(self.store.inner(), adapter, sync_task_size, max_task_number);
 
         if (!sync_handler.is_previous_in_update().await?)
-            && node_tip
+            && chain_tip
                 .checked_sub(mercury_count)
                 .ok_or_else(|| anyhow!("chain tip is less than db tip"))?
                 < 1000
@@ -152,50 +289,80 @@ impl Service {
 
         log::info!("start sync");
 
-        sync_handler.do_sync(node_tip).await?;
+        sync_handler.do_sync(chain_tip).await?;
 
         log::info!("finish sync");
 
         Ok(())
     }
 
-    pub async fn start(&self, flush_pool_interval: u64) {
+    pub async fn start(&self, flush_pool_interval: u64) -> Result<()> {
         let client_clone = self.ckb_client.clone();
+        let storage: Arc<dyn Storage + Send + Sync> = match &self.kv_store {
+            Some(kv_store) => Arc::new(kv_store.clone()),
+            None => Arc::new(self.store.clone()),
+        };
 
         tokio::spawn(async move {
-            update_tx_pool_cache(client_clone, flush_pool_interval).await;
+            update_tx_pool_cache(client_clone, storage, flush_pool_interval).await;
         });
 
-        self.run().await;
+        self.run().await
     }
 
-    async fn run(&self) {
+    /// Follow the chain of the node. The service halts when the node reorgs deeper than
+    /// `max_reorg_depth`, which needs an operator to check the node.
+    async fn run(&self) -> Result<()> {
         let mut tip = 0;
 
         loop {
+            // the block and the fork point of a round are taken from the same node
+            let ckb_client = self.ckb_client.pinned();
+
             if let Some((tip_number, tip_hash)) = self
-                .store
+                .storage()
                 .get_tip(Context::new())
                 .await
                 .expect("get tip should be OK")
             {
                 tip = tip_number;
 
-                match self.get_block_by_number(tip_number + 1).await {
+                match self.get_block_by_number(&ckb_client, tip_number + 1).await {
                     Ok(Some(block)) => {
                         if block.parent_hash().raw_data() == tip_hash.0.to_vec() {
                             info!("append {}, {}", block.number(), block.hash());
+                            let new_tip = ChainEvent::NewTip(block.number(), block.hash().unpack());
                             self.change_current_epoch(block.epoch().to_rational());
-                            self.store
+                            self.storage()
                                 .append_block(Context::new(), block)
                                 .await
                                 .unwrap();
+                            notify_chain_event(new_tip);
                         } else {
-                            info!("rollback {}, {}", tip_number, tip_hash);
-                            self.store
-                                .rollback_block(Context::new(), tip_number, tip_hash)
-                                .await
-                                .unwrap();
+                            match self.find_fork_point(&ckb_client, tip_number).await {
+                                Ok(fork_point)
+                                    if fork_point.rolled_back_blocks.len() as u64
+                                        > self.max_reorg_depth =>
+                                {
+                                    error!(
+                                        "[alert] reorg from {} deeper than {} blocks, halt",
+                                        tip_number, self.max_reorg_depth
+                                    );
+                                    return Err(anyhow!(
+                                        "reorg deeper than {} blocks",
+                                        self.max_reorg_depth
+                                    ));
+                                }
+
+                                Ok(fork_point) => {
+                                    self.rollback_with_retry(tip_hash, fork_point).await?
+                                }
+
+                                Err(err) => {
+                                    error!("cannot find the fork point, error: {}", err);
+                                    sleep(self.poll_interval).await;
+                                }
+                            }
                         }
                     }
 
@@ -209,14 +376,16 @@ impl Service {
                     }
                 }
             } else {
-                match self.get_block_by_number(0).await {
+                match self.get_block_by_number(&ckb_client, 0).await {
                     Ok(Some(block)) => {
                         log::info!("append {} block", 0);
+                        let new_tip = ChainEvent::NewTip(block.number(), block.hash().unpack());
                         self.change_current_epoch(block.epoch().to_rational());
-                        self.store
+                        self.storage()
                             .append_block(Context::new(), block)
                             .await
                             .unwrap();
+                        notify_chain_event(new_tip);
                     }
 
                     Ok(None) => {
@@ -234,9 +403,119 @@ impl Service {
         }
     }
 
-    async fn get_block_by_number(&self, block_number: BlockNumber) -> Result<Option<BlockView>> {
-        let ret = self
-            .ckb_client
+    /// Walk back the canonical chain from the tip until the block hash matches the node.
+    /// The walk stops once the reorg is deeper than `max_reorg_depth`.
+    async fn find_fork_point(
+        &self,
+        ckb_client: &CkbRpcClientPool,
+        tip_number: BlockNumber,
+    ) -> Result<ForkPoint> {
+        let mut rolled_back_blocks = vec![];
+        let mut new_block_hash = H256::default();
+        let mut block_number = tip_number;
+
+        loop {
+            let local_hash = self
+                .storage()
+                .get_canonical_block_hash(Context::new(), block_number)
+                .await?;
+            let node_hash = ckb_client.get_block_hash(block_number).await?;
+            if node_hash.as_ref() == Some(&local_hash) {
+                break;
+            }
+
+            rolled_back_blocks.push((block_number, local_hash));
+            new_block_hash = node_hash.unwrap_or_default();
+            if rolled_back_blocks.len() as u64 > self.max_reorg_depth {
+                break;
+            }
+            block_number = block_number
+                .checked_sub(1)
+                .ok_or_else(|| anyhow!("the genesis block mismatches the node"))?;
+        }
+
+        Ok(ForkPoint {
+            block_number,
+            new_block_hash,
+            rolled_back_blocks,
+        })
+    }
+
+    /// Retry the rollback on storage errors, which are mostly transient since the rollback
+    /// is one transaction. The service halts if the rollback still fails.
+    async fn rollback_with_retry(&self, tip_hash: H256, fork_point: ForkPoint) -> Result<()> {
+        let mut retry = 0;
+
+        loop {
+            match self.rollback_to_fork_point(&tip_hash, &fork_point).await {
+                Ok(()) => return Ok(()),
+
+                Err(err) if retry < MAX_ROLLBACK_RETRY => {
+                    retry += 1;
+                    warn!(
+                        "rollback to fork point {} failed, retry {}, error: {}",
+                        fork_point.block_number, retry, err
+                    );
+                    sleep(self.poll_interval).await;
+                }
+
+                Err(err) => {
+                    error!(
+                        "[alert] rollback to fork point {} failed, halt",
+                        fork_point.block_number
+                    );
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Rollback the blocks after the fork point in one transaction, the blocks of the new
+    /// branch are appended by the following loops.
+    async fn rollback_to_fork_point(&self, tip_hash: &H256, fork_point: &ForkPoint) -> Result<()> {
+        let reorg_log = ReorgLog {
+            fork_block_number: fork_point.block_number,
+            depth: fork_point.rolled_back_blocks.len() as u64,
+            old_tip_hash: tip_hash.clone(),
+            old_block_hash: fork_point
+                .rolled_back_blocks
+                .last()
+                .map(|(_, hash)| hash.clone())
+                .unwrap_or_default(),
+            new_block_hash: fork_point.new_block_hash.clone(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("system time before unix epoch")
+                .as_secs(),
+        };
+        warn!(
+            "reorg {} blocks to fork point {}, old block {}, new block {}",
+            reorg_log.depth,
+            reorg_log.fork_block_number,
+            reorg_log.old_block_hash,
+            reorg_log.new_block_hash
+        );
+
+        self.storage()
+            .rollback_reorg(
+                Context::new(),
+                fork_point.rolled_back_blocks.clone(),
+                reorg_log,
+            )
+            .await?;
+        for (block_number, block_hash) in fork_point.rolled_back_blocks.iter() {
+            notify_chain_event(ChainEvent::Rollback(*block_number, block_hash.clone()));
+        }
+
+        Ok(())
+    }
+
+    async fn get_block_by_number(
+        &self,
+        ckb_client: &CkbRpcClientPool,
+        block_number: BlockNumber,
+    ) -> Result<Option<BlockView>> {
+        let ret = ckb_client
             .get_blocks_by_number(vec![block_number])
             .await?
             .get(0)
@@ -248,32 +527,41 @@ impl Service {
 
     pub async fn start_rpc_mode(&self) -> Result<()> {
         loop {
-            let current_epoch = self.ckb_client.get_current_epoch().await?;
-            let tip = self.ckb_client.get_tip_block_number().await?;
+            if let Err(err) = self.update_current_tip().await {
+                error!("cannot get tip from ckb node, error: {}", err);
+            }
 
+            sleep(Duration::from_secs(2)).await;
+        }
+    }
+
+    async fn update_current_tip(&self) -> Result<()> {
+        // the epoch and the tip are taken from the same node
+        let ckb_client = self.ckb_client.pinned();
+        let current_epoch = ckb_client.get_current_epoch().await?;
+        let tip = ckb_client.get_tip_block_number().await?;
+
+        let start_number: u64 = current_epoch.start_number.into();
+        let epoch_length: u64 = current_epoch.length.into();
+        let epoch_number: u64 = current_epoch.number.into();
+        let index = epoch_index(tip, start_number)?;
+
+        let (epoch_number, index, epoch_length) = if index > epoch_length {
+            let current_epoch = ckb_client.get_current_epoch().await?;
             let start_number: u64 = current_epoch.start_number.into();
             let epoch_length: u64 = current_epoch.length.into();
             let epoch_number: u64 = current_epoch.number.into();
-            let index = tip - start_number + 1;
-
-            let (epoch_number, index, epoch_length) = if index > epoch_length {
-                let current_epoch = self.ckb_client.get_current_epoch().await?;
-                let start_number: u64 = current_epoch.start_number.into();
-                let epoch_length: u64 = current_epoch.length.into();
-                let epoch_number: u64 = current_epoch.number.into();
-                let index = tip - start_number + 1;
-                (epoch_number, index, epoch_length)
-            } else {
-                (epoch_number, index, epoch_length)
-            };
-            let current_epoch =
-                EpochNumberWithFraction::new_unchecked(epoch_number, index, epoch_length);
-
-            let _ = *CURRENT_BLOCK_NUMBER.swap(Arc::new(tip));
-            self.change_current_epoch(current_epoch.to_rational());
-
-            sleep(Duration::from_secs(2)).await;
-        }
+            let index = epoch_index(tip, start_number)?;
+            (epoch_number, index, epoch_length)
+        } else {
+            (epoch_number, index, epoch_length)
+        };
+        let current_epoch =
+            EpochNumberWithFraction::new_unchecked(epoch_number, index, epoch_length);
+
+        let _ = *CURRENT_BLOCK_NUMBER.swap(Arc::new(tip));
+        self.change_current_epoch(current_epoch.to_rational());
+        Ok(())
     }
 
     fn change_current_epoch(&self, current_epoch: RationalU256) {
@@ -282,10 +570,47 @@ impl Service {
     }
 }
 
-async fn update_tx_pool_cache(ckb_client: CkbRpcClient, flush_cache_interval: u64) {
+/// Export the blocks from `from` to `to` of the node to a block dump, `to` is the tip of the
+/// node if not given.
+pub async fn export_blocks(
+    ckb_uri: String,
+    path: String,
+    format: BlockDumpFormat,
+    from: BlockNumber,
+    to: Option<BlockNumber>,
+) -> Result<()> {
+    let ckb_client = CkbRpcClient::new(ckb_uri);
+    let to = match to {
+        Some(to) => to,
+        None => ckb_client.get_tip_block_number().await?,
+    };
+    let mut writer = BlockDumpWriter::create(&path, format)?;
+
+    let numbers = (from..=to).collect::<Vec<_>>();
+    for chunk in numbers.chunks(EXPORT_BLOCK_BATCH_SIZE) {
+        let blocks = ckb_client.get_blocks_by_number(chunk.to_vec()).await?;
+        for (number, block) in chunk.iter().zip(blocks.into_iter()) {
+            let block = block.ok_or_else(|| anyhow!("get none block {} from node", number))?;
+            writer.write(&BlockView::from(block))?;
+        }
+
+        writer.flush()?;
+        info!("export blocks to {} of {}", chunk[chunk.len() - 1], to);
+    }
+
+    Ok(())
+}
+
+async fn update_tx_pool_cache(
+    ckb_client: CkbRpcClientPool,
+    storage: Arc<dyn Storage + Send + Sync>,
+    flush_cache_interval: u64,
+) {
     loop {
+        // the transactions and the epoch of a round are taken from the same node
+        let ckb_client = ckb_client.pinned();
         match ckb_client.get_raw_tx_pool(Some(true)).await {
-            Ok(raw_pool) => handle_raw_tx_pool(&ckb_client, raw_pool).await,
+            Ok(raw_pool) => handle_raw_tx_pool(&ckb_client, storage.as_ref(), raw_pool).await,
             Err(e) => error!("get raw tx pool error {:?}", e),
         }
 
@@ -293,20 +618,24 @@ async fn update_tx_pool_cache(ckb_client: CkbRpcClient, flush_cache_interval: u6
     }
 }
 
-async fn handle_raw_tx_pool(ckb_client: &CkbRpcClient, raw_pool: RawTxPool) {
+async fn handle_raw_tx_pool(
+    ckb_client: &CkbRpcClientPool,
+    storage: &(dyn Storage + Send + Sync),
+    raw_pool: RawTxPool,
+) {
     let mut input_set: HashSet<packed::OutPoint> = HashSet::new();
+    let mut pending_txs = Vec::new();
     let hashes = tx_hash_list(raw_pool);
 
     if let Ok(res) = ckb_client.get_transactions(hashes).await {
-        for item in res.iter() {
+        for item in res.into_iter() {
             match item {
-                Some(TransactionWithStatus {
-                    transaction: Some(tx_view),
-                    ..
-                }) => {
+                Some(tx) if tx.transaction.is_some() => {
+                    let tx_view = tx.transaction.as_ref().unwrap();
                     tx_view.inner.inputs.iter().for_each(|input| {
                         input_set.insert(input.previous_output.clone().into());
                     });
+                    pending_txs.push(tx);
                 }
 
                 _ => warn!("Get transaction from pool failed"),
@@ -314,8 +643,48 @@ async fn handle_raw_tx_pool(ckb_client: &CkbRpcClient, raw_pool: RawTxPool) {
         }
     }
 
+    // the outputs of the pool are treated as if they were committed in the block after
+    // the tip
+    let tip_block_number = **CURRENT_BLOCK_NUMBER.load();
+    let tip_epoch_number = match ckb_client.get_current_epoch().await {
+        Ok(epoch) => {
+            let start_number: u64 = epoch.start_number.into();
+            let length: u64 = epoch.length.into();
+            let index = (tip_block_number + 1)
+                .saturating_sub(start_number)
+                .min(length.saturating_sub(1));
+            EpochNumberWithFraction::new_unchecked(epoch.number.into(), index, length).full_value()
+        }
+        Err(e) => {
+            error!("get current epoch error {:?}", e);
+            0
+        }
+    };
+    let previous_inputs = PENDING_TX_CACHE.read().input_cells();
+    let pending_txs = resolve_pending_transactions(
+        Context::new(),
+        storage,
+        pending_txs,
+        &previous_inputs,
+        tip_block_number,
+        tip_epoch_number,
+    )
+    .await;
+    let pending_tx_cache = PendingTxCache::new(pending_txs);
+
     let mut pool_cache = TX_POOL_CACHE.write();
     *pool_cache = input_set;
+    drop(pool_cache);
+
+    let mut pending_cache = PENDING_TX_CACHE.write();
+    *pending_cache = pending_tx_cache;
+}
+
+/// The index of the tip in the epoch starting at `start_number`.
+fn epoch_index(tip: BlockNumber, start_number: BlockNumber) -> Result<u64> {
+    tip.checked_sub(start_number)
+        .map(|index| index + 1)
+        .ok_or_else(|| anyhow!("tip {} is before the epoch start {}", tip, start_number))
 }
 
 fn tx_hash_list(raw_pool: RawTxPool) -> Vec<H256> {
@@ -357,4 +726,11 @@ mod tests {
 
         assert_eq!(byte32.raw_data(), h256.0.to_vec());
     }
+
+    #[test]
+    fn test_epoch_index() {
+        assert_eq!(super::epoch_index(100, 100).unwrap(), 1);
+        assert_eq!(super::epoch_index(120, 100).unwrap(), 21);
+        assert!(super::epoch_index(99, 100).is_err());
+    }
 }
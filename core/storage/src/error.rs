@@ -16,6 +16,13 @@ pub enum DBError {
 
     #[display(fmt = "Invalid parameter {}", _0)]
     InvalidParameter(String),
+
+    #[display(
+        fmt = "The database schema version {} is newer than the supported version {}",
+        _0,
+        _1
+    )]
+    NewerSchemaVersion(u32, u32),
 }
 
 impl std::error::Error for DBError {}
@@ -1,3 +1,10 @@
+mod storage;
+
+#[cfg(test)]
+mod tests;
+
+pub use storage::KVStorage;
+
 use common::Result;
 use db_protocol::{IteratorItem, KVStore, KVStoreBatch};
 use db_rocksdb::{DBIterator, IteratorMode, RocksdbBatch, RocksdbStore};
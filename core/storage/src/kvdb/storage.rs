@@ -0,0 +1,1548 @@
+use crate::error::DBError;
+use crate::kvdb::{PrefixKVStore, PrefixKVStoreBatch};
+use crate::relational::table::{IndexerCellTable, IO_TYPE_INPUT, IO_TYPE_OUTPUT};
+use crate::relational::to_rb_bytes;
+use crate::Storage;
+
+use common::{
+    async_trait,
+    utils::{decode_udt_amount, to_fixed_array},
+    Context, DetailedCell, Order, PaginationRequest, PaginationResponse, Range, Result,
+};
+use common_logger::{tracing, tracing_async};
+use db_protocol::{
+    CellFilter, CellIOType, ConsumeInfo, DBDriver, DBInfo, IteratorDirection, IteratorItem,
+    KVStore, KVStoreBatch, ReorgLog, ScriptBalance, SimpleBlock, SimpleTransaction,
+    TransactionWrapper,
+};
+
+use ckb_jsonrpc_types::TransactionWithStatus;
+use ckb_types::core::{
+    BlockNumber, BlockView, Capacity, EpochNumberWithFraction, HeaderView, TransactionView,
+};
+use ckb_types::{bytes::Bytes, packed, prelude::*, H160, H256};
+
+use std::collections::{HashMap, HashSet};
+
+const HASH160_LEN: usize = 20;
+const TX_LOCATION_LEN: usize = 52;
+const CONSUME_INFO_LEN: usize = 88;
+const REORG_LOG_LEN: usize = 104;
+
+/// The key layout of the KV storage. Every key starts with a one byte prefix, and all
+/// the numbers in keys are big endian so that the iteration order is the chain order.
+///
+/// | prefix             | key                                             | value                                 |
+/// | ------------------ | ----------------------------------------------- | ------------------------------------- |
+/// | TIP                |                                                 | block number, block hash              |
+/// | BLOCK              | block hash                                      | packed block                          |
+/// | CANONICAL          | block number                                    | block hash                            |
+/// | TRANSACTION        | tx hash                                         | tx location, packed transaction       |
+/// | TX_ORDER           | block number, tx index                          | tx hash                               |
+/// | CELL               | tx hash, output index                           | tx location, packed cell output, data |
+/// | LIVE_CELL          | tx hash, output index                           |                                       |
+/// | CONSUMED_CELL      | tx hash, output index                           | consume info                          |
+/// | LOCK_INDEX         | lock hash, block number, tx index, output index | tx hash                               |
+/// | TYPE_INDEX         | type hash, block number, tx index, output index | tx hash                               |
+/// | SCRIPT             | script hash 160                                 | packed script                         |
+/// | SCRIPT_INDEX       | code hash, args, script hash 160                | packed script                         |
+/// | REGISTERED_ADDRESS | lock hash 160                                   | address                               |
+/// | REORG_LOG          | timestamp, fork block number                    | depth, old tip, old/new block hash    |
+mod key_prefix {
+    pub const TIP: u8 = 0x00;
+    pub const BLOCK: u8 = 0x01;
+    pub const CANONICAL: u8 = 0x02;
+    pub const TRANSACTION: u8 = 0x03;
+    pub const TX_ORDER: u8 = 0x04;
+    pub const CELL: u8 = 0x05;
+    pub const LIVE_CELL: u8 = 0x06;
+    pub const CONSUMED_CELL: u8 = 0x07;
+    pub const LOCK_INDEX: u8 = 0x08;
+    pub const TYPE_INDEX: u8 = 0x09;
+    pub const SCRIPT: u8 = 0x0a;
+    pub const REGISTERED_ADDRESS: u8 = 0x0b;
+    pub const REORG_LOG: u8 = 0x0c;
+    pub const SCRIPT_INDEX: u8 = 0x0d;
+}
+
+use key_prefix::*;
+
+/// A storage backed by RocksDB. It keeps the same data as the relational storage
+/// with the key layout above, and appends or rolls back a block in a single write batch.
+#[derive(Clone)]
+pub struct KVStorage {
+    store: PrefixKVStore,
+}
+
+impl std::fmt::Debug for KVStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KVStorage").finish()
+    }
+}
+
+#[async_trait]
+impl Storage for KVStorage {
+    #[tracing_async]
+    async fn append_block(&self, _ctx: Context, block: BlockView) -> Result<()> {
+        let mut batch = self.store.batch()?;
+        let block_hash: H256 = block.hash().unpack();
+        let block_number = block.number();
+        let epoch_number = block.epoch().full_value();
+
+        batch.put(hash_key(BLOCK, &block_hash.0), block.data().as_slice())?;
+        batch.put(number_key(CANONICAL, block_number), block_hash.as_bytes())?;
+        batch.put([TIP], tip_value(block_number, &block_hash))?;
+
+        for (tx_index, tx) in block.transactions().iter().enumerate() {
+            let tx_index = tx_index as u32;
+            let tx_hash: H256 = tx.hash().unpack();
+            let location = TxLocation {
+                block_hash: block_hash.clone(),
+                block_number,
+                tx_index,
+                epoch_number,
+            };
+
+            let mut tx_value = location.to_bytes();
+            tx_value.extend_from_slice(tx.data().as_slice());
+            batch.put(hash_key(TRANSACTION, &tx_hash.0), tx_value)?;
+            batch.put(tx_order_key(block_number, tx_index), tx_hash.as_bytes())?;
+
+            // the input of a cellbase is not a real cell
+            if tx_index != 0 {
+                for (input_index, input) in tx.inputs().into_iter().enumerate() {
+                    let out_point = input.previous_output();
+                    let info = ConsumeInfo {
+                        output_point: out_point.clone(),
+                        since: input.since().unpack(),
+                        input_index: input_index as u32,
+                        consumed_block_number: block_number,
+                        consumed_block_hash: block_hash.clone(),
+                        consumed_tx_hash: tx_hash.clone(),
+                        consumed_tx_index: tx_index,
+                    };
+                    batch.delete(out_point_key(LIVE_CELL, &out_point))?;
+                    batch.put(
+                        out_point_key(CONSUMED_CELL, &out_point),
+                        encode_consume_info(&info),
+                    )?;
+                }
+            }
+
+            for (output_index, (cell_output, data)) in tx.outputs_with_data_iter().enumerate() {
+                let output_index = output_index as u32;
+                let out_point = packed::OutPoint::new(tx_hash.pack(), output_index);
+
+                let mut cell_value = location.to_bytes();
+                cell_value.extend_from_slice(&(cell_output.as_slice().len() as u32).to_be_bytes());
+                cell_value.extend_from_slice(cell_output.as_slice());
+                cell_value.extend_from_slice(&data);
+                batch.put(out_point_key(CELL, &out_point), cell_value)?;
+                batch.put(out_point_key(LIVE_CELL, &out_point), Bytes::new())?;
+
+                let lock_script = cell_output.lock();
+                let lock_hash = lock_script.calc_script_hash();
+                batch.put(
+                    script_index_key(
+                        LOCK_INDEX,
+                        lock_hash.as_slice(),
+                        block_number,
+                        tx_index,
+                        output_index,
+                    ),
+                    tx_hash.as_bytes(),
+                )?;
+                batch.put(
+                    hash_key(SCRIPT, &lock_hash.as_slice()[0..HASH160_LEN]),
+                    lock_script.as_slice(),
+                )?;
+                batch.put(script_code_key(&lock_script), lock_script.as_slice())?;
+
+                if let Some(type_script) = cell_output.type_().to_opt() {
+                    let type_hash = type_script.calc_script_hash();
+                    batch.put(
+                        script_index_key(
+                            TYPE_INDEX,
+                            type_hash.as_slice(),
+                            block_number,
+                            tx_index,
+                            output_index,
+                        ),
+                        tx_hash.as_bytes(),
+                    )?;
+                    batch.put(
+                        hash_key(SCRIPT, &type_hash.as_slice()[0..HASH160_LEN]),
+                        type_script.as_slice(),
+                    )?;
+                    batch.put(script_code_key(&type_script), type_script.as_slice())?;
+                }
+            }
+        }
+
+        batch.commit()?;
+        Ok(())
+    }
+
+    #[tracing_async]
+    async fn rollback_block(
+        &self,
+        _ctx: Context,
+        block_number: BlockNumber,
+        block_hash: H256,
+    ) -> Result<()> {
+        let mut batch = self.store.batch()?;
+        self.rollback_block_in_batch(&mut batch, block_number, &block_hash)?;
+        batch.commit()?;
+        Ok(())
+    }
+
+    #[tracing_async]
+    async fn rollback_reorg(
+        &self,
+        _ctx: Context,
+        blocks: Vec<(BlockNumber, H256)>,
+        reorg_log: ReorgLog,
+    ) -> Result<()> {
+        let mut batch = self.store.batch()?;
+        for (block_number, block_hash) in blocks.iter() {
+            self.rollback_block_in_batch(&mut batch, *block_number, block_hash)?;
+        }
+
+        let (key, value) = encode_reorg_log(&reorg_log);
+        batch.put(key, value)?;
+        batch.commit()?;
+        Ok(())
+    }
+
+    #[tracing_async]
+    async fn get_reorg_logs(&self, _ctx: Context) -> Result<Vec<ReorgLog>> {
+        Ok(self
+            .store
+            .iter([REORG_LOG], IteratorDirection::Forward)?
+            .take_while(|(key, _)| key.starts_with(&[REORG_LOG]))
+            .map(|(key, value)| decode_reorg_log(&key, &value))
+            .collect())
+    }
+
+    #[tracing_async]
+    async fn get_cells(
+        &self,
+        _ctx: Context,
+        out_point: Option<packed::OutPoint>,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<DetailedCell>> {
+        if out_point.is_none() && block_range.is_none() && !pagination.return_count {
+            if let Some((prefix, script_hash)) = single_script_index(&lock_hashes, &type_hashes) {
+                return self.paginate_script_index(prefix, script_hash, false, &pagination);
+            }
+        }
+
+        let cells = self.query_cells(out_point, lock_hashes, type_hashes, block_range, false)?;
+        Ok(paginate(cells, &pagination))
+    }
+
+    #[tracing_async]
+    async fn get_live_cells(
+        &self,
+        _ctx: Context,
+        out_point: Option<packed::OutPoint>,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<DetailedCell>> {
+        if out_point.is_none() && block_range.is_none() && !pagination.return_count {
+            if let Some((prefix, script_hash)) = single_script_index(&lock_hashes, &type_hashes) {
+                return self.paginate_script_index(prefix, script_hash, true, &pagination);
+            }
+        }
+
+        let cells = self.query_cells(out_point, lock_hashes, type_hashes, block_range, true)?;
+        Ok(paginate(cells, &pagination))
+    }
+
+    #[tracing_async]
+    async fn get_historical_live_cells(
+        &self,
+        _ctx: Context,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        tip_block_number: BlockNumber,
+    ) -> Result<Vec<DetailedCell>> {
+        if lock_hashes.is_empty() {
+            return Err(DBError::InvalidParameter(
+                "no valid parameter to query transactions".to_owned(),
+            )
+            .into());
+        }
+
+        let cells = self
+            .query_cells(None, lock_hashes, type_hashes, None, false)?
+            .into_iter()
+            .map(|(_, cell)| cell)
+            .filter(|cell| {
+                cell.block_number <= tip_block_number
+                    && cell
+                        .consumed_block_number
+                        .map(|number| number > tip_block_number)
+                        .unwrap_or(true)
+            })
+            .collect();
+        Ok(cells)
+    }
+
+    #[tracing_async]
+    async fn get_transactions(
+        &self,
+        _ctx: Context,
+        tx_hashes: Vec<H256>,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<TransactionWrapper>> {
+        if tx_hashes.is_empty()
+            && block_range.is_none()
+            && lock_hashes.is_empty()
+            && type_hashes.is_empty()
+        {
+            return Err(DBError::InvalidParameter(
+                "no valid parameter to query transactions".to_owned(),
+            )
+            .into());
+        }
+
+        let txs = self.query_transactions(tx_hashes, lock_hashes, type_hashes, block_range)?;
+        Ok(paginate(txs, &pagination))
+    }
+
+    #[tracing_async]
+    async fn get_transactions_by_hashes(
+        &self,
+        _ctx: Context,
+        tx_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<TransactionWrapper>> {
+        if tx_hashes.is_empty() && block_range.is_none() {
+            return Err(DBError::InvalidParameter(
+                "no valid parameter to query transactions".to_owned(),
+            )
+            .into());
+        }
+
+        let txs = self.query_transactions(tx_hashes, vec![], vec![], block_range)?;
+        Ok(paginate(txs, &pagination))
+    }
+
+    #[tracing_async]
+    async fn get_transactions_by_scripts(
+        &self,
+        _ctx: Context,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<TransactionWrapper>> {
+        if block_range.is_none() && lock_hashes.is_empty() && type_hashes.is_empty() {
+            return Err(DBError::InvalidParameter(
+                "no valid parameter to query transactions".to_owned(),
+            )
+            .into());
+        }
+
+        let txs = self.query_transactions(vec![], lock_hashes, type_hashes, block_range)?;
+        Ok(paginate(txs, &pagination))
+    }
+
+    #[tracing_async]
+    async fn get_cells_by_filter(
+        &self,
+        _ctx: Context,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        filter: CellFilter,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<DetailedCell>> {
+        let cells = self.query_cells_by_filter(lock_hashes, type_hashes, block_range, &filter)?;
+        Ok(paginate(cells, &pagination))
+    }
+
+    #[tracing_async]
+    async fn get_transactions_by_filter(
+        &self,
+        _ctx: Context,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        filter: CellFilter,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<TransactionWrapper>> {
+        if block_range.is_none() && lock_hashes.is_empty() && type_hashes.is_empty() {
+            return Err(DBError::InvalidParameter(
+                "no valid parameter to query transactions".to_owned(),
+            )
+            .into());
+        }
+
+        let mut tx_hashes = HashSet::new();
+        for io_type in [CellIOType::Input, CellIOType::Output] {
+            if filter.io_type.is_some() && filter.io_type != Some(io_type) {
+                continue;
+            }
+
+            let cell_filter = CellFilter {
+                io_type: Some(io_type),
+                data_prefix: filter.data_prefix.clone(),
+            };
+            for (_, cell) in self.query_cells_by_filter(
+                lock_hashes.clone(),
+                type_hashes.clone(),
+                block_range.clone(),
+                &cell_filter,
+            )? {
+                if io_type == CellIOType::Output {
+                    tx_hashes.insert(cell.out_point.tx_hash().unpack());
+                } else if let Some(hash) = cell.consumed_tx_hash {
+                    tx_hashes.insert(hash);
+                }
+            }
+        }
+
+        if tx_hashes.is_empty() {
+            return Ok(paginate(vec![], &pagination));
+        }
+
+        let txs =
+            self.query_transactions(tx_hashes.into_iter().collect(), vec![], vec![], block_range)?;
+        Ok(paginate(txs, &pagination))
+    }
+
+    /// The balances are aggregated from the lock index on the fly instead of being
+    /// materialized.
+    #[tracing_async]
+    async fn get_balances(
+        &self,
+        _ctx: Context,
+        lock_hashes: Vec<H256>,
+    ) -> Result<Vec<ScriptBalance>> {
+        if lock_hashes.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut balances: HashMap<(H256, Option<H256>), ScriptBalance> = HashMap::new();
+        for (_, cell) in self.query_cells(None, lock_hashes, vec![], None, true)? {
+            let lock_hash: H256 = cell.cell_output.lock().calc_script_hash().unpack();
+            let type_hash: Option<H256> = cell
+                .cell_output
+                .type_()
+                .to_opt()
+                .map(|script| script.calc_script_hash().unpack());
+            let occupied_capacity = Capacity::bytes(cell.cell_data.len())
+                .and_then(|data_occupied| cell.cell_output.occupied_capacity(data_occupied))
+                .map_err(|e| DBError::InvalidParameter(e.to_string()))?
+                .as_u64();
+            let udt_amount = if type_hash.is_some() {
+                decode_udt_amount(&cell.cell_data)
+            } else {
+                0
+            };
+
+            let balance = balances
+                .entry((lock_hash.clone(), type_hash.clone()))
+                .or_insert_with(|| ScriptBalance {
+                    lock_hash,
+                    type_hash,
+                    capacity: 0,
+                    occupied_capacity: 0,
+                    udt_amount: 0,
+                    cell_count: 0,
+                });
+            let capacity: u64 = cell.cell_output.capacity().unpack();
+            balance.capacity += capacity;
+            balance.occupied_capacity += occupied_capacity;
+            balance.udt_amount = balance.udt_amount.wrapping_add(udt_amount);
+            balance.cell_count += 1;
+        }
+
+        Ok(balances.into_values().collect())
+    }
+
+    #[tracing_async]
+    async fn get_block(
+        &self,
+        _ctx: Context,
+        block_hash: Option<H256>,
+        block_number: Option<BlockNumber>,
+    ) -> Result<BlockView> {
+        self.query_block(block_hash, block_number)
+    }
+
+    #[tracing_async]
+    async fn get_block_header(
+        &self,
+        _ctx: Context,
+        block_hash: Option<H256>,
+        block_number: Option<BlockNumber>,
+    ) -> Result<HeaderView> {
+        Ok(self.query_block(block_hash, block_number)?.header())
+    }
+
+    #[tracing_async]
+    async fn get_scripts(
+        &self,
+        _ctx: Context,
+        script_hashes: Vec<H160>,
+        code_hashes: Vec<H256>,
+        args_len: Option<usize>,
+        args: Vec<Bytes>,
+    ) -> Result<Vec<packed::Script>> {
+        if script_hashes.is_empty()
+            && code_hashes.is_empty()
+            && args_len.is_none()
+            && args.is_empty()
+        {
+            return Err(DBError::InvalidParameter(
+                "no valid parameter to query scripts".to_owned(),
+            )
+            .into());
+        }
+
+        let is_matched = |script: &packed::Script| {
+            let code_hash: H256 = script.code_hash().unpack();
+            let script_args = script.args().raw_data();
+            (code_hashes.is_empty() || code_hashes.contains(&code_hash))
+                && (args.is_empty() || args.contains(&script_args))
+                && args_len.map(|len| len == script_args.len()).unwrap_or(true)
+        };
+
+        if !script_hashes.is_empty() {
+            let mut scripts = Vec::new();
+            for script_hash in script_hashes.iter() {
+                if let Some(value) = self.store.get(hash_key(SCRIPT, script_hash.as_bytes()))? {
+                    let script = decode_script(&value)?;
+                    if is_matched(&script) {
+                        scripts.push(script);
+                    }
+                }
+            }
+            return Ok(scripts);
+        }
+
+        if code_hashes.is_empty() {
+            return self
+                .iter_prefix(vec![SCRIPT])?
+                .map(|(_, value)| decode_script(&value))
+                .filter(|script| script.as_ref().map_or(true, &is_matched))
+                .collect();
+        }
+
+        // The scripts are found by the prefixes of code hash and args in the script index.
+        // An args may be the prefix of another one, so the scripts are deduplicated.
+        let arg_prefixes = if args.is_empty() {
+            vec![Bytes::new()]
+        } else {
+            args.clone()
+        };
+        let mut seen = HashSet::new();
+        let mut scripts = Vec::new();
+        for code_hash in code_hashes.iter() {
+            for arg_prefix in arg_prefixes.iter() {
+                let mut prefix = hash_key(SCRIPT_INDEX, code_hash.as_bytes());
+                prefix.extend_from_slice(arg_prefix);
+                for (_, value) in self.iter_prefix(prefix)? {
+                    let script = decode_script(&value)?;
+                    if is_matched(&script) && seen.insert(script.as_bytes()) {
+                        scripts.push(script);
+                    }
+                }
+            }
+        }
+        Ok(scripts)
+    }
+
+    #[tracing_async]
+    async fn get_tip(&self, _ctx: Context) -> Result<Option<(BlockNumber, H256)>> {
+        self.query_tip()
+    }
+
+    #[tracing_async]
+    async fn get_spent_transaction_hash(
+        &self,
+        _ctx: Context,
+        out_point: packed::OutPoint,
+    ) -> Result<Option<H256>> {
+        Ok(self
+            .store
+            .get(out_point_key(CONSUMED_CELL, &out_point))?
+            .map(|value| decode_consume_info(out_point, &value).consumed_tx_hash))
+    }
+
+    #[tracing_async]
+    async fn get_canonical_block_hash(
+        &self,
+        _ctx: Context,
+        block_number: BlockNumber,
+    ) -> Result<H256> {
+        let value = self
+            .store
+            .get(number_key(CANONICAL, block_number))?
+            .ok_or(DBError::CannotFind)?;
+        Ok(H256(to_fixed_array::<32>(&value)))
+    }
+
+    #[tracing_async]
+    async fn get_simple_transaction_by_hash(
+        &self,
+        _ctx: Context,
+        tx_hash: H256,
+    ) -> Result<SimpleTransaction> {
+        let value = self
+            .store
+            .get(hash_key(TRANSACTION, &tx_hash.0))?
+            .ok_or(DBError::CannotFind)?;
+        let location = TxLocation::from_slice(&value);
+
+        Ok(SimpleTransaction {
+            epoch_number: EpochNumberWithFraction::from_full_value(location.epoch_number)
+                .to_rational(),
+            block_number: location.block_number,
+            block_hash: location.block_hash,
+            tx_index: location.tx_index,
+        })
+    }
+
+    #[tracing_async]
+    async fn get_scripts_by_partial_arg(
+        &self,
+        _ctx: Context,
+        code_hash: H256,
+        arg: Bytes,
+        offset_location: (u32, u32),
+    ) -> Result<Vec<packed::Script>> {
+        let (from, to) = (offset_location.0 as usize, offset_location.1 as usize);
+
+        // The args starting from the beginning are a prefix of the script index key.
+        let mut prefix = hash_key(SCRIPT_INDEX, code_hash.as_bytes());
+        if from == 0 {
+            prefix.extend_from_slice(&arg);
+        }
+
+        self.iter_prefix(prefix)?
+            .map(|(_, value)| decode_script(&value))
+            .filter(|script| {
+                script.as_ref().map_or(true, |script| {
+                    let script_args = script.args().raw_data();
+                    script_args.len() >= to && script_args[from..to] == arg[..]
+                })
+            })
+            .collect()
+    }
+
+    #[tracing_async]
+    async fn get_registered_address(
+        &self,
+        _ctx: Context,
+        lock_hash: H160,
+    ) -> Result<Option<String>> {
+        let value = self
+            .store
+            .get(hash_key(REGISTERED_ADDRESS, lock_hash.as_bytes()))?;
+        value
+            .map(|bytes| {
+                String::from_utf8(bytes).map_err(|e| DBError::FetchDataError(e.to_string()).into())
+            })
+            .transpose()
+    }
+
+    #[tracing_async]
+    async fn register_addresses(
+        &self,
+        _ctx: Context,
+        addresses: Vec<(H160, String)>,
+    ) -> Result<Vec<H160>> {
+        let mut batch = self.store.batch()?;
+        for (lock_hash, address) in addresses.iter() {
+            batch.put(
+                hash_key(REGISTERED_ADDRESS, lock_hash.as_bytes()),
+                address.as_bytes(),
+            )?;
+        }
+        batch.commit()?;
+
+        Ok(addresses
+            .into_iter()
+            .map(|(lock_hash, _)| lock_hash)
+            .collect())
+    }
+
+    #[tracing]
+    fn get_db_info(&self, _ctx: Context) -> Result<DBInfo> {
+        Ok(DBInfo {
+            version: clap::crate_version!().to_string(),
+            db: DBDriver::RocksDB,
+            conn_size: 0,
+            center_id: 0,
+            machine_id: 0,
+        })
+    }
+
+    #[tracing_async]
+    async fn get_simple_block(
+        &self,
+        _ctx: Context,
+        block_hash: Option<H256>,
+        block_number: Option<BlockNumber>,
+    ) -> Result<SimpleBlock> {
+        let block = self.query_block(block_hash, block_number)?;
+
+        Ok(SimpleBlock {
+            block_number: block.number(),
+            block_hash: block.hash().unpack(),
+            parent_hash: block.parent_hash().unpack(),
+            timestamp: block.timestamp(),
+            transactions: block.tx_hashes().iter().map(|hash| hash.unpack()).collect(),
+        })
+    }
+
+    #[tracing_async]
+    async fn get_indexer_transactions(
+        &self,
+        _ctx: Context,
+        lock_script: Option<packed::Script>,
+        type_script: Option<packed::Script>,
+        block_range: Option<Range>,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<IndexerCellTable>> {
+        if lock_script.is_none() && type_script.is_none() && block_range.is_none() {
+            return Err(DBError::InvalidParameter(
+                "No valid parameter to query indexer cell".to_string(),
+            )
+            .into());
+        }
+
+        let cells = if lock_script.is_none() && type_script.is_none() {
+            let mut cells = Vec::new();
+            for (_, tx) in self.query_transactions(vec![], vec![], vec![], block_range.clone())? {
+                cells.extend(tx.input_cells);
+                cells.extend(tx.output_cells);
+            }
+            cells
+        } else {
+            let lock_hashes = lock_script
+                .map(|script| vec![script.calc_script_hash().unpack()])
+                .unwrap_or_default();
+            let type_hashes = type_script
+                .map(|script| vec![script.calc_script_hash().unpack()])
+                .unwrap_or_default();
+            self.query_cells(None, lock_hashes, type_hashes, None, false)?
+                .into_iter()
+                .map(|(_, cell)| cell)
+                .collect()
+        };
+
+        let mut set = HashSet::new();
+        let mut records = Vec::new();
+        for cell in cells.iter() {
+            for (key, record) in to_indexer_cells(cell) {
+                let in_range = block_range
+                    .as_ref()
+                    .map(|range| range.is_in(record.block_number))
+                    .unwrap_or(true);
+                if in_range && set.insert(key.clone()) {
+                    records.push((key, record));
+                }
+            }
+        }
+
+        Ok(paginate(records, &pagination))
+    }
+}
+
+impl KVStorage {
+    pub fn new(path: &str) -> Self {
+        KVStorage {
+            store: PrefixKVStore::new(path),
+        }
+    }
+
+    /// Rollback a block in the batch. The rolled back blocks of a reorg share a batch, in
+    /// which the later puts and deletes of a key override the former ones.
+    fn rollback_block_in_batch(
+        &self,
+        batch: &mut PrefixKVStoreBatch,
+        block_number: BlockNumber,
+        block_hash: &H256,
+    ) -> Result<()> {
+        let block = self
+            .get_block_by_hash(block_hash)?
+            .ok_or(DBError::CannotFind)?;
+        if block.number() != block_number {
+            return Err(DBError::MismatchBlockHash.into());
+        }
+
+        // walk the transactions backwards, so that a cell created and consumed in the same
+        // block is revived by the consuming transaction before the creating one removes it
+        for (tx_index, tx) in block.transactions().iter().enumerate().rev() {
+            let tx_index = tx_index as u32;
+            let tx_hash: H256 = tx.hash().unpack();
+
+            if tx_index != 0 {
+                for out_point in tx.input_pts_iter() {
+                    batch.delete(out_point_key(CONSUMED_CELL, &out_point))?;
+                    batch.put(out_point_key(LIVE_CELL, &out_point), Bytes::new())?;
+                }
+            }
+
+            for (output_index, cell_output) in tx.outputs().into_iter().enumerate() {
+                let output_index = output_index as u32;
+                let out_point = packed::OutPoint::new(tx_hash.pack(), output_index);
+                batch.delete(out_point_key(CELL, &out_point))?;
+                batch.delete(out_point_key(LIVE_CELL, &out_point))?;
+                batch.delete(out_point_key(CONSUMED_CELL, &out_point))?;
+                batch.delete(script_index_key(
+                    LOCK_INDEX,
+                    cell_output.lock().calc_script_hash().as_slice(),
+                    block_number,
+                    tx_index,
+                    output_index,
+                ))?;
+                if let Some(type_script) = cell_output.type_().to_opt() {
+                    batch.delete(script_index_key(
+                        TYPE_INDEX,
+                        type_script.calc_script_hash().as_slice(),
+                        block_number,
+                        tx_index,
+                        output_index,
+                    ))?;
+                }
+            }
+
+            batch.delete(hash_key(TRANSACTION, &tx_hash.0))?;
+            batch.delete(tx_order_key(block_number, tx_index))?;
+        }
+
+        batch.delete(hash_key(BLOCK, &block_hash.0))?;
+        if self.store.get(number_key(CANONICAL, block_number))? == Some(block_hash.0.to_vec()) {
+            batch.delete(number_key(CANONICAL, block_number))?;
+        }
+        if block_number == 0 {
+            batch.delete([TIP])?;
+        } else {
+            let parent_hash: H256 = block.parent_hash().unpack();
+            batch.put([TIP], tip_value(block_number - 1, &parent_hash))?;
+        }
+
+        Ok(())
+    }
+
+    fn query_tip(&self) -> Result<Option<(BlockNumber, H256)>> {
+        Ok(self.store.get([TIP])?.map(|value| {
+            (
+                BlockNumber::from_be_bytes(to_fixed_array::<8>(&value[0..8])),
+                H256(to_fixed_array::<32>(&value[8..40])),
+            )
+        }))
+    }
+
+    fn get_block_by_hash(&self, block_hash: &H256) -> Result<Option<BlockView>> {
+        self.store
+            .get(hash_key(BLOCK, &block_hash.0))?
+            .map(|value| {
+                packed::Block::from_slice(&value)
+                    .map(|block| block.into_view())
+                    .map_err(|e| DBError::FetchDataError(e.to_string()).into())
+            })
+            .transpose()
+    }
+
+    fn query_block(
+        &self,
+        block_hash: Option<H256>,
+        block_number: Option<BlockNumber>,
+    ) -> Result<BlockView> {
+        match (block_hash, block_number) {
+            (None, None) => {
+                let (_, tip_hash) = self.query_tip()?.ok_or(DBError::CannotFind)?;
+                Ok(self
+                    .get_block_by_hash(&tip_hash)?
+                    .ok_or(DBError::CannotFind)?)
+            }
+            (None, Some(block_number)) => {
+                let value = self
+                    .store
+                    .get(number_key(CANONICAL, block_number))?
+                    .ok_or(DBError::WrongHeight)?;
+                Ok(self
+                    .get_block_by_hash(&H256(to_fixed_array::<32>(&value)))?
+                    .ok_or(DBError::WrongHeight)?)
+            }
+            (Some(block_hash), None) => Ok(self
+                .get_block_by_hash(&block_hash)?
+                .ok_or(DBError::CannotFind)?),
+            (Some(block_hash), Some(block_number)) => {
+                let block = self
+                    .get_block_by_hash(&block_hash)?
+                    .ok_or(DBError::CannotFind)?;
+                if block.number() != block_number {
+                    return Err(DBError::MismatchBlockHash.into());
+                }
+                Ok(block)
+            }
+        }
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(self
+            .iter_prefix(prefix.to_vec())?
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect())
+    }
+
+    /// Iterate the records of the prefix lazily, so that the records are filtered or
+    /// paged while iterating instead of being loaded all at once.
+    fn iter_prefix(&self, prefix: Vec<u8>) -> Result<impl Iterator<Item = IteratorItem> + '_> {
+        Ok(self
+            .store
+            .iter(&prefix, IteratorDirection::Forward)?
+            .take_while(move |(key, _)| key.starts_with(&prefix)))
+    }
+
+    /// The script index is in the chain order, so the cells of a single script are paged
+    /// while iterating the index. The count is not returned since the index is not
+    /// iterated to the end.
+    fn paginate_script_index(
+        &self,
+        prefix: u8,
+        script_hash: &H256,
+        live_only: bool,
+        pagination: &PaginationRequest,
+    ) -> Result<PaginationResponse<DetailedCell>> {
+        let key_prefix = hash_key(prefix, &script_hash.0);
+        let mut from_key = key_prefix.clone();
+        match pagination.cursor.as_ref() {
+            Some(cursor) => from_key.extend_from_slice(cursor),
+            // the key after the last cell of the script
+            None if !pagination.order.is_asc() => from_key.extend_from_slice(&[u8::MAX; 16]),
+            None => (),
+        }
+        let direction = if pagination.order.is_asc() {
+            IteratorDirection::Forward
+        } else {
+            IteratorDirection::Reverse
+        };
+        let limit = pagination
+            .limit
+            .map(|limit| limit as usize)
+            .unwrap_or(usize::MAX);
+        let mut skip = pagination.skip.unwrap_or(0) as usize;
+
+        let mut page: Vec<(Vec<u8>, DetailedCell)> = Vec::new();
+        let mut next_cursor = None;
+        for (key, value) in self
+            .store
+            .iter(&from_key, direction)?
+            .take_while(|(key, _)| key.starts_with(&key_prefix))
+        {
+            let sort_key = &key[key_prefix.len()..];
+            if pagination.cursor.as_deref() == Some(sort_key) {
+                continue;
+            }
+            let output_index = u32::from_be_bytes(to_fixed_array::<4>(&key[45..49]));
+            let out_point =
+                packed::OutPoint::new(H256(to_fixed_array::<32>(&value)).pack(), output_index);
+            if live_only && !self.store.exists(out_point_key(LIVE_CELL, &out_point))? {
+                continue;
+            }
+            let cell = match self.get_detailed_cell(&out_point)? {
+                Some(cell) => cell,
+                None => continue,
+            };
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            if page.len() == limit {
+                next_cursor = page.last().map(|(key, _)| Bytes::from(key.clone()));
+                break;
+            }
+            page.push((sort_key.to_vec(), cell));
+        }
+
+        Ok(PaginationResponse {
+            response: page.into_iter().map(|(_, cell)| cell).collect(),
+            next_cursor,
+            count: None,
+        })
+    }
+
+    fn scan_script_index(
+        &self,
+        prefix: u8,
+        script_hashes: &[H256],
+    ) -> Result<Vec<packed::OutPoint>> {
+        let script_hashes = script_hashes.iter().collect::<HashSet<_>>();
+        let mut out_points = Vec::new();
+
+        for script_hash in script_hashes.into_iter() {
+            for (key, value) in self.scan_prefix(&hash_key(prefix, &script_hash.0))? {
+                let output_index = u32::from_be_bytes(to_fixed_array::<4>(&key[45..49]));
+                out_points.push(packed::OutPoint::new(
+                    H256(to_fixed_array::<32>(&value)).pack(),
+                    output_index,
+                ));
+            }
+        }
+
+        Ok(out_points)
+    }
+
+    fn scan_tx_hashes(&self, block_range: &Range) -> Result<Vec<H256>> {
+        Ok(self
+            .store
+            .iter(
+                number_key(TX_ORDER, block_range.min()),
+                IteratorDirection::Forward,
+            )?
+            .take_while(|(key, _)| {
+                key[0] == TX_ORDER
+                    && BlockNumber::from_be_bytes(to_fixed_array::<8>(&key[1..9]))
+                        <= block_range.max()
+            })
+            .map(|(_, value)| H256(to_fixed_array::<32>(&value)))
+            .collect())
+    }
+
+    fn get_detailed_cell(&self, out_point: &packed::OutPoint) -> Result<Option<DetailedCell>> {
+        let value = match self.store.get(out_point_key(CELL, out_point))? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let location = TxLocation::from_slice(&value);
+        let output_end = TX_LOCATION_LEN
+            + 4
+            + u32::from_be_bytes(to_fixed_array::<4>(
+                &value[TX_LOCATION_LEN..TX_LOCATION_LEN + 4],
+            )) as usize;
+        let cell_output = packed::CellOutput::from_slice(&value[TX_LOCATION_LEN + 4..output_end])
+            .map_err(|e| DBError::FetchDataError(e.to_string()))?;
+        let consume_info = self
+            .store
+            .get(out_point_key(CONSUMED_CELL, out_point))?
+            .map(|value| decode_consume_info(out_point.clone(), &value));
+
+        Ok(Some(DetailedCell {
+            epoch_number: location.epoch_number,
+            block_number: location.block_number,
+            block_hash: location.block_hash,
+            tx_index: location.tx_index,
+            out_point: out_point.clone(),
+            cell_output,
+            cell_data: Bytes::from(value[output_end..].to_vec()),
+            consumed_block_number: consume_info.as_ref().map(|info| info.consumed_block_number),
+            consumed_block_hash: consume_info
+                .as_ref()
+                .map(|info| info.consumed_block_hash.clone()),
+            consumed_tx_hash: consume_info
+                .as_ref()
+                .map(|info| info.consumed_tx_hash.clone()),
+            consumed_tx_index: consume_info.as_ref().map(|info| info.consumed_tx_index),
+            consumed_input_index: consume_info.as_ref().map(|info| info.input_index),
+            since: consume_info.as_ref().map(|info| info.since),
+        }))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_live_cell(&self, out_point: &packed::OutPoint) -> Result<bool> {
+        self.store.exists(out_point_key(LIVE_CELL, out_point))
+    }
+
+    fn query_cells(
+        &self,
+        out_point: Option<packed::OutPoint>,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        live_only: bool,
+    ) -> Result<Vec<(Vec<u8>, DetailedCell)>> {
+        let out_points = if let Some(out_point) = out_point {
+            vec![out_point]
+        } else if !lock_hashes.is_empty() {
+            self.scan_script_index(LOCK_INDEX, &lock_hashes)?
+        } else if !type_hashes.is_empty() {
+            self.scan_script_index(TYPE_INDEX, &type_hashes)?
+        } else if let Some(range) = block_range.as_ref() {
+            let mut out_points = Vec::new();
+            for tx_hash in self.scan_tx_hashes(range)? {
+                if let Some(tx) = self.get_transaction_view(&tx_hash)? {
+                    out_points.extend(tx.output_pts_iter());
+                }
+            }
+            out_points
+        } else {
+            return Err(
+                DBError::InvalidParameter("no valid parameter to query cells".to_owned()).into(),
+            );
+        };
+
+        let mut cells = Vec::new();
+        for out_point in out_points.iter() {
+            if live_only && !self.store.exists(out_point_key(LIVE_CELL, out_point))? {
+                continue;
+            }
+            let cell = match self.get_detailed_cell(out_point)? {
+                Some(cell) => cell,
+                None => continue,
+            };
+
+            if !lock_hashes.is_empty() {
+                let lock_hash: H256 = cell.cell_output.lock().calc_script_hash().unpack();
+                if !lock_hashes.contains(&lock_hash) {
+                    continue;
+                }
+            }
+            if !type_hashes.is_empty() {
+                let type_hash: H256 = match cell.cell_output.type_().to_opt() {
+                    Some(type_script) => type_script.calc_script_hash().unpack(),
+                    None => continue,
+                };
+                if !type_hashes.contains(&type_hash) {
+                    continue;
+                }
+            }
+            if let Some(range) = block_range.as_ref() {
+                if !range.is_in(cell.block_number) {
+                    continue;
+                }
+            }
+
+            cells.push((cell_sort_key(&cell), cell));
+        }
+
+        Ok(cells)
+    }
+
+    /// The inputs are matched by the consuming block, so the cells consumed in the range
+    /// are found from the inputs of the transactions in it when no script is given.
+    fn query_cells_by_filter(
+        &self,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        filter: &CellFilter,
+    ) -> Result<Vec<(Vec<u8>, DetailedCell)>> {
+        let is_input = filter.io_type == Some(CellIOType::Input);
+        let cells = if is_input && lock_hashes.is_empty() && type_hashes.is_empty() {
+            let range = block_range.as_ref().ok_or_else(|| {
+                DBError::InvalidParameter("no valid parameter to query cells".to_owned())
+            })?;
+            let mut cells = Vec::new();
+            for tx_hash in self.scan_tx_hashes(range)? {
+                let tx = match self.get_transaction_view(&tx_hash)? {
+                    Some(tx) => tx,
+                    None => continue,
+                };
+                for out_point in tx.input_pts_iter() {
+                    if let Some(cell) = self.get_detailed_cell(&out_point)? {
+                        cells.push((cell_sort_key(&cell), cell));
+                    }
+                }
+            }
+            cells
+        } else {
+            self.query_cells(
+                None,
+                lock_hashes,
+                type_hashes,
+                if is_input { None } else { block_range.clone() },
+                filter.io_type.is_none(),
+            )?
+        };
+
+        let data_prefix = filter.data_prefix.clone().unwrap_or_default();
+        Ok(cells
+            .into_iter()
+            .filter(|(_, cell)| cell.cell_data.starts_with(&data_prefix))
+            .filter(|(_, cell)| {
+                if !is_input {
+                    return true;
+                }
+                match (cell.consumed_block_number, block_range.as_ref()) {
+                    (Some(number), Some(range)) => range.is_in(number),
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                }
+            })
+            .collect())
+    }
+
+    fn get_transaction_view(&self, tx_hash: &H256) -> Result<Option<TransactionView>> {
+        self.store
+            .get(hash_key(TRANSACTION, &tx_hash.0))?
+            .map(|value| {
+                packed::Transaction::from_slice(&value[TX_LOCATION_LEN..])
+                    .map(|tx| tx.into_view())
+                    .map_err(|e| DBError::FetchDataError(e.to_string()).into())
+            })
+            .transpose()
+    }
+
+    fn get_transaction_wrapper(
+        &self,
+        tx_hash: &H256,
+    ) -> Result<Option<(TxLocation, TransactionWrapper)>> {
+        let value = match self.store.get(hash_key(TRANSACTION, &tx_hash.0))? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let location = TxLocation::from_slice(&value);
+        let transaction_view = packed::Transaction::from_slice(&value[TX_LOCATION_LEN..])
+            .map_err(|e| DBError::FetchDataError(e.to_string()))?
+            .into_view();
+        let is_cellbase = location.tx_index == 0;
+
+        let mut input_cells = Vec::new();
+        if !is_cellbase {
+            for out_point in transaction_view.input_pts_iter() {
+                input_cells.push(
+                    self.get_detailed_cell(&out_point)?
+                        .ok_or(DBError::CannotFind)?,
+                );
+            }
+        }
+
+        let mut output_cells = Vec::new();
+        for out_point in transaction_view.output_pts_iter() {
+            if let Some(cell) = self.get_detailed_cell(&out_point)? {
+                output_cells.push(cell);
+            }
+        }
+
+        let transaction_with_status = TransactionWithStatus::with_committed(
+            Some(transaction_view.clone()),
+            location.block_hash.clone(),
+        );
+
+        Ok(Some((
+            location,
+            TransactionWrapper {
+                transaction_with_status,
+                transaction_view,
+                input_cells,
+                output_cells,
+                is_cellbase,
+            },
+        )))
+    }
+
+    fn query_transactions(
+        &self,
+        tx_hashes: Vec<H256>,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+    ) -> Result<Vec<(Vec<u8>, TransactionWrapper)>> {
+        let mut set = tx_hashes.into_iter().collect::<HashSet<_>>();
+
+        if !lock_hashes.is_empty() || !type_hashes.is_empty() {
+            for (_, cell) in self.query_cells(None, lock_hashes, type_hashes, None, false)? {
+                set.insert(cell.out_point.tx_hash().unpack());
+                if let Some(hash) = cell.consumed_tx_hash {
+                    set.insert(hash);
+                }
+            }
+        } else if set.is_empty() {
+            if let Some(range) = block_range.as_ref() {
+                set.extend(self.scan_tx_hashes(range)?);
+            }
+        }
+
+        let mut txs = Vec::new();
+        for tx_hash in set.iter() {
+            let (location, tx) = match self.get_transaction_wrapper(tx_hash)? {
+                Some(res) => res,
+                None => continue,
+            };
+            if let Some(range) = block_range.as_ref() {
+                if !range.is_in(location.block_number) {
+                    continue;
+                }
+            }
+            txs.push((tx_sort_key(location.block_number, location.tx_index), tx));
+        }
+
+        Ok(txs)
+    }
+}
+
+struct TxLocation {
+    block_hash: H256,
+    block_number: BlockNumber,
+    tx_index: u32,
+    epoch_number: u64,
+}
+
+impl TxLocation {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(TX_LOCATION_LEN);
+        ret.extend_from_slice(self.block_hash.as_bytes());
+        ret.extend_from_slice(&self.block_number.to_be_bytes());
+        ret.extend_from_slice(&self.tx_index.to_be_bytes());
+        ret.extend_from_slice(&self.epoch_number.to_be_bytes());
+        ret
+    }
+
+    fn from_slice(slice: &[u8]) -> Self {
+        TxLocation {
+            block_hash: H256(to_fixed_array::<32>(&slice[0..32])),
+            block_number: BlockNumber::from_be_bytes(to_fixed_array::<8>(&slice[32..40])),
+            tx_index: u32::from_be_bytes(to_fixed_array::<4>(&slice[40..44])),
+            epoch_number: u64::from_be_bytes(to_fixed_array::<8>(&slice[44..52])),
+        }
+    }
+}
+
+fn encode_consume_info(info: &ConsumeInfo) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(CONSUME_INFO_LEN);
+    ret.extend_from_slice(info.consumed_block_hash.as_bytes());
+    ret.extend_from_slice(&info.consumed_block_number.to_be_bytes());
+    ret.extend_from_slice(info.consumed_tx_hash.as_bytes());
+    ret.extend_from_slice(&info.consumed_tx_index.to_be_bytes());
+    ret.extend_from_slice(&info.input_index.to_be_bytes());
+    ret.extend_from_slice(&info.since.to_be_bytes());
+    ret
+}
+
+fn decode_consume_info(output_point: packed::OutPoint, slice: &[u8]) -> ConsumeInfo {
+    ConsumeInfo {
+        output_point,
+        consumed_block_hash: H256(to_fixed_array::<32>(&slice[0..32])),
+        consumed_block_number: u64::from_be_bytes(to_fixed_array::<8>(&slice[32..40])),
+        consumed_tx_hash: H256(to_fixed_array::<32>(&slice[40..72])),
+        consumed_tx_index: u32::from_be_bytes(to_fixed_array::<4>(&slice[72..76])),
+        input_index: u32::from_be_bytes(to_fixed_array::<4>(&slice[76..80])),
+        since: u64::from_be_bytes(to_fixed_array::<8>(&slice[80..88])),
+    }
+}
+
+fn encode_reorg_log(log: &ReorgLog) -> (Vec<u8>, Vec<u8>) {
+    let mut key = vec![REORG_LOG];
+    key.extend_from_slice(&log.timestamp.to_be_bytes());
+    key.extend_from_slice(&log.fork_block_number.to_be_bytes());
+
+    let mut value = Vec::with_capacity(REORG_LOG_LEN);
+    value.extend_from_slice(&log.depth.to_be_bytes());
+    value.extend_from_slice(log.old_tip_hash.as_bytes());
+    value.extend_from_slice(log.old_block_hash.as_bytes());
+    value.extend_from_slice(log.new_block_hash.as_bytes());
+    (key, value)
+}
+
+fn decode_reorg_log(key: &[u8], value: &[u8]) -> ReorgLog {
+    ReorgLog {
+        timestamp: u64::from_be_bytes(to_fixed_array::<8>(&key[1..9])),
+        fork_block_number: u64::from_be_bytes(to_fixed_array::<8>(&key[9..17])),
+        depth: u64::from_be_bytes(to_fixed_array::<8>(&value[0..8])),
+        old_tip_hash: H256(to_fixed_array::<32>(&value[8..40])),
+        old_block_hash: H256(to_fixed_array::<32>(&value[40..72])),
+        new_block_hash: H256(to_fixed_array::<32>(&value[72..104])),
+    }
+}
+
+fn decode_script(slice: &[u8]) -> Result<packed::Script> {
+    packed::Script::from_slice(slice).map_err(|e| DBError::FetchDataError(e.to_string()).into())
+}
+
+fn to_indexer_cells(cell: &DetailedCell) -> Vec<(Vec<u8>, IndexerCellTable)> {
+    let output_index: u32 = cell.out_point.index().unpack();
+    let tx_hash: H256 = cell.out_point.tx_hash().unpack();
+    let mut ret = vec![indexer_cell(
+        cell,
+        cell.block_number,
+        IO_TYPE_OUTPUT,
+        output_index,
+        &tx_hash,
+        cell.tx_index,
+    )];
+
+    if let (Some(block_number), Some(tx_hash), Some(tx_index), Some(input_index)) = (
+        cell.consumed_block_number,
+        cell.consumed_tx_hash.as_ref(),
+        cell.consumed_tx_index,
+        cell.consumed_input_index,
+    ) {
+        ret.push(indexer_cell(
+            cell,
+            block_number,
+            IO_TYPE_INPUT,
+            input_index,
+            tx_hash,
+            tx_index,
+        ));
+    }
+
+    ret
+}
+
+fn indexer_cell(
+    cell: &DetailedCell,
+    block_number: BlockNumber,
+    io_type: u8,
+    io_index: u32,
+    tx_hash: &H256,
+    tx_index: u32,
+) -> (Vec<u8>, IndexerCellTable) {
+    let mut key = tx_sort_key(block_number, tx_index);
+    key.push(io_type);
+    key.extend_from_slice(&io_index.to_be_bytes());
+
+    let mut record = IndexerCellTable::new_with_empty_scripts(
+        block_number,
+        io_type,
+        io_index,
+        to_rb_bytes(tx_hash.as_bytes()),
+        tx_index,
+    );
+    let lock_script = cell.cell_output.lock();
+    record.lock_hash = to_rb_bytes(lock_script.calc_script_hash().as_slice());
+    record.lock_code_hash = to_rb_bytes(lock_script.code_hash().as_slice());
+    record.lock_args = to_rb_bytes(&lock_script.args().raw_data());
+    record.lock_script_type = lock_script.hash_type().into();
+    if let Some(type_script) = cell.cell_output.type_().to_opt() {
+        record.type_hash = to_rb_bytes(type_script.calc_script_hash().as_slice());
+        record.type_code_hash = to_rb_bytes(type_script.code_hash().as_slice());
+        record.type_args = to_rb_bytes(&type_script.args().raw_data());
+        record.type_script_type = type_script.hash_type().into();
+    }
+
+    (key, record)
+}
+
+/// The records are sorted by their keys, and the cursor is the key of the last record
+/// in the page.
+fn paginate<T>(
+    mut records: Vec<(Vec<u8>, T)>,
+    pagination: &PaginationRequest,
+) -> PaginationResponse<T> {
+    records.sort_by(|a, b| a.0.cmp(&b.0));
+    if pagination.order == Order::Desc {
+        records.reverse();
+    }
+    let count = records.len() as u64;
+
+    if let Some(cursor) = pagination.cursor.as_ref() {
+        records.retain(|(key, _)| {
+            if pagination.order.is_asc() {
+                key.as_slice() > cursor.as_ref()
+            } else {
+                key.as_slice() < cursor.as_ref()
+            }
+        });
+    }
+
+    let remain = records
+        .len()
+        .saturating_sub(pagination.skip.unwrap_or(0) as usize);
+    let page = records
+        .into_iter()
+        .skip(pagination.skip.unwrap_or(0) as usize)
+        .take(
+            pagination
+                .limit
+                .map(|limit| limit as usize)
+                .unwrap_or(usize::MAX),
+        )
+        .collect::<Vec<_>>();
+    let next_cursor = if remain > page.len() {
+        page.last().map(|(key, _)| Bytes::from(key.clone()))
+    } else {
+        None
+    };
+
+    PaginationResponse {
+        response: page.into_iter().map(|(_, record)| record).collect(),
+        next_cursor,
+        count: Some(count),
+    }
+}
+
+fn tip_value(block_number: BlockNumber, block_hash: &H256) -> Vec<u8> {
+    let mut ret = block_number.to_be_bytes().to_vec();
+    ret.extend_from_slice(block_hash.as_bytes());
+    ret
+}
+
+fn hash_key(prefix: u8, hash: &[u8]) -> Vec<u8> {
+    let mut key = vec![prefix];
+    key.extend_from_slice(hash);
+    key
+}
+
+fn number_key(prefix: u8, block_number: BlockNumber) -> Vec<u8> {
+    let mut key = vec![prefix];
+    key.extend_from_slice(&block_number.to_be_bytes());
+    key
+}
+
+fn tx_order_key(block_number: BlockNumber, tx_index: u32) -> Vec<u8> {
+    let mut key = number_key(TX_ORDER, block_number);
+    key.extend_from_slice(&tx_index.to_be_bytes());
+    key
+}
+
+fn out_point_key(prefix: u8, out_point: &packed::OutPoint) -> Vec<u8> {
+    let index: u32 = out_point.index().unpack();
+    let mut key = hash_key(prefix, out_point.tx_hash().as_slice());
+    key.extend_from_slice(&index.to_be_bytes());
+    key
+}
+
+fn script_code_key(script: &packed::Script) -> Vec<u8> {
+    let mut key = hash_key(SCRIPT_INDEX, script.code_hash().as_slice());
+    key.extend_from_slice(&script.args().raw_data());
+    key.extend_from_slice(&script.calc_script_hash().as_slice()[0..HASH160_LEN]);
+    key
+}
+
+/// The script index to page the cells of a single lock or type script in.
+fn single_script_index<'a>(
+    lock_hashes: &'a [H256],
+    type_hashes: &'a [H256],
+) -> Option<(u8, &'a H256)> {
+    match (lock_hashes, type_hashes) {
+        ([lock_hash], []) => Some((LOCK_INDEX, lock_hash)),
+        ([], [type_hash]) => Some((TYPE_INDEX, type_hash)),
+        _ => None,
+    }
+}
+
+fn script_index_key(
+    prefix: u8,
+    script_hash: &[u8],
+    block_number: BlockNumber,
+    tx_index: u32,
+    output_index: u32,
+) -> Vec<u8> {
+    let mut key = hash_key(prefix, script_hash);
+    key.extend_from_slice(&tx_sort_key(block_number, tx_index));
+    key.extend_from_slice(&output_index.to_be_bytes());
+    key
+}
+
+fn tx_sort_key(block_number: BlockNumber, tx_index: u32) -> Vec<u8> {
+    let mut key = block_number.to_be_bytes().to_vec();
+    key.extend_from_slice(&tx_index.to_be_bytes());
+    key
+}
+
+fn cell_sort_key(cell: &DetailedCell) -> Vec<u8> {
+    let output_index: u32 = cell.out_point.index().unpack();
+    let mut key = tx_sort_key(cell.block_number, cell.tx_index);
+    key.extend_from_slice(&output_index.to_be_bytes());
+    key
+}
@@ -0,0 +1,504 @@
+use crate::{CellFilter, CellIOType, KVStorage, ReorgLog, Storage};
+
+use common::{Context, DetailedCell, Order, PaginationRequest, Range};
+
+use ckb_jsonrpc_types::BlockView as JsonBlockView;
+use ckb_types::core::{BlockBuilder, BlockView, Capacity, TransactionBuilder};
+use ckb_types::{bytes::Bytes, packed, prelude::*, H160, H256};
+
+const BLOCK_DIR: &str = "../../devtools/test_data/blocks/";
+
+fn read_block_view(number: u64) -> BlockView {
+    let path = BLOCK_DIR.to_string() + &number.to_string() + ".json";
+    let block: JsonBlockView = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+    block.into()
+}
+
+async fn open_and_insert_blocks(dir: &tempfile::TempDir) -> KVStorage {
+    let store = KVStorage::new(dir.path().to_str().unwrap());
+    for i in 0..10 {
+        store
+            .append_block(Context::new(), read_block_view(i))
+            .await
+            .unwrap();
+    }
+    store
+}
+
+async fn get_live_genesis_cell(store: &KVStorage) -> DetailedCell {
+    let cellbase = read_block_view(0).transactions()[0].clone();
+    let lock_hash: H256 = cellbase
+        .outputs()
+        .get(0)
+        .unwrap()
+        .lock()
+        .calc_script_hash()
+        .unpack();
+    store
+        .get_live_cells(
+            Context::new(),
+            None,
+            vec![lock_hash],
+            vec![],
+            None,
+            PaginationRequest::default().set_limit(Some(1)),
+        )
+        .await
+        .unwrap()
+        .response
+        .remove(0)
+}
+
+/// Build a block on top of the parent, in which the last transaction consumes the output
+/// created by the former one.
+fn build_block_with_same_block_spend(
+    parent: &BlockView,
+    input: packed::OutPoint,
+    lock: packed::Script,
+) -> BlockView {
+    let output = packed::CellOutput::new_builder()
+        .capacity(Capacity::shannons(100_000_000_000).pack())
+        .lock(lock)
+        .build();
+    let cellbase = TransactionBuilder::default()
+        .input(packed::CellInput::new_cellbase_input(parent.number() + 1))
+        .build();
+    let create_tx = TransactionBuilder::default()
+        .input(packed::CellInput::new(input, 0))
+        .output(output.clone())
+        .output_data(Bytes::new().pack())
+        .build();
+    let consume_tx = TransactionBuilder::default()
+        .input(packed::CellInput::new(
+            packed::OutPoint::new(create_tx.hash(), 0),
+            0,
+        ))
+        .output(output)
+        .output_data(Bytes::new().pack())
+        .build();
+
+    BlockBuilder::default()
+        .parent_hash(parent.hash())
+        .number((parent.number() + 1).pack())
+        .epoch(parent.epoch().pack())
+        .timestamp((parent.timestamp() + 1).pack())
+        .transactions(vec![cellbase, create_tx, consume_tx])
+        .build()
+}
+
+#[tokio::test]
+async fn test_kv_get_block() {
+    let dir = tempfile::Builder::new()
+        .prefix("test_kv_get_block")
+        .tempdir()
+        .unwrap();
+    let store = open_and_insert_blocks(&dir).await;
+
+    let block = read_block_view(9);
+    let tip = store.get_tip(Context::new()).await.unwrap().unwrap();
+    assert_eq!(tip, (9, block.hash().unpack()));
+
+    let res = store
+        .get_block(Context::new(), None, Some(1))
+        .await
+        .unwrap();
+    assert_eq!(read_block_view(1).data(), res.data());
+    let res = store
+        .get_block_header(Context::new(), None, Some(0))
+        .await
+        .unwrap();
+    assert_eq!(read_block_view(0).header(), res);
+
+    let tx_hash: H256 = block.transactions()[0].hash().unpack();
+    let tx = store
+        .get_simple_transaction_by_hash(Context::new(), tx_hash)
+        .await
+        .unwrap();
+    assert_eq!(tx.block_number, 9);
+    assert_eq!(tx.tx_index, 0);
+}
+
+#[tokio::test]
+async fn test_kv_get_cells() {
+    let dir = tempfile::Builder::new()
+        .prefix("test_kv_get_cells")
+        .tempdir()
+        .unwrap();
+    let store = open_and_insert_blocks(&dir).await;
+
+    let cellbase = read_block_view(0).transactions()[0].clone();
+    let lock_hash: H256 = cellbase
+        .outputs()
+        .get(0)
+        .unwrap()
+        .lock()
+        .calc_script_hash()
+        .unpack();
+    let cells = store
+        .get_live_cells(
+            Context::new(),
+            None,
+            vec![lock_hash.clone()],
+            vec![],
+            None,
+            PaginationRequest::default().set_limit(Some(1)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(cells.response.len(), 1);
+    assert_eq!(cells.response[0].block_number, 0);
+    assert_eq!(
+        cells.response[0].cell_output.lock().calc_script_hash(),
+        lock_hash.pack()
+    );
+}
+
+#[tokio::test]
+async fn test_kv_get_cells_paged_by_script() {
+    let dir = tempfile::Builder::new()
+        .prefix("test_kv_get_cells_paged_by_script")
+        .tempdir()
+        .unwrap();
+    let store = open_and_insert_blocks(&dir).await;
+    let lock_hash: H256 = get_live_genesis_cell(&store)
+        .await
+        .cell_output
+        .lock()
+        .calc_script_hash()
+        .unpack();
+
+    for order in [Order::Asc, Order::Desc] {
+        // the count is returned by loading all the cells
+        let all = store
+            .get_cells(
+                Context::new(),
+                None,
+                vec![lock_hash.clone()],
+                vec![],
+                None,
+                PaginationRequest::new(None, order.clone(), None, None, true),
+            )
+            .await
+            .unwrap();
+        assert!(all.response.len() > 1);
+
+        // the cells are paged while iterating the script index
+        let mut cursor = None;
+        let mut paged = Vec::new();
+        loop {
+            let page = store
+                .get_cells(
+                    Context::new(),
+                    None,
+                    vec![lock_hash.clone()],
+                    vec![],
+                    None,
+                    PaginationRequest::new(cursor, order.clone(), Some(1), None, false),
+                )
+                .await
+                .unwrap();
+            assert!(page.count.is_none());
+            paged.extend(page.response);
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(paged, all.response);
+
+        let skipped = store
+            .get_cells(
+                Context::new(),
+                None,
+                vec![lock_hash.clone()],
+                vec![],
+                None,
+                PaginationRequest::new(None, order, Some(1), Some(1), false),
+            )
+            .await
+            .unwrap();
+        assert_eq!(skipped.response, all.response[1..2].to_vec());
+    }
+}
+
+#[tokio::test]
+async fn test_kv_get_scripts_by_index() {
+    let dir = tempfile::Builder::new()
+        .prefix("test_kv_get_scripts_by_index")
+        .tempdir()
+        .unwrap();
+    let store = open_and_insert_blocks(&dir).await;
+    let lock = read_block_view(0).transactions()[0]
+        .outputs()
+        .get(5)
+        .unwrap()
+        .lock();
+    let code_hash: H256 = lock.code_hash().unpack();
+    let args = lock.args().raw_data();
+
+    let scripts = store
+        .get_scripts(
+            Context::new(),
+            vec![],
+            vec![code_hash.clone()],
+            None,
+            vec![args.clone()],
+        )
+        .await
+        .unwrap();
+    assert!(scripts.contains(&lock));
+    assert!(scripts
+        .iter()
+        .all(|script| script.code_hash() == lock.code_hash() && script.args().raw_data() == args));
+
+    let script_hash = H160::from_slice(&lock.calc_script_hash().as_slice()[0..20]).unwrap();
+    let scripts = store
+        .get_scripts(Context::new(), vec![script_hash], vec![], None, vec![])
+        .await
+        .unwrap();
+    assert_eq!(scripts, vec![lock.clone()]);
+
+    let scripts = store
+        .get_scripts_by_partial_arg(Context::new(), code_hash.clone(), args.slice(0..4), (0, 4))
+        .await
+        .unwrap();
+    assert!(scripts.contains(&lock));
+
+    let scripts = store
+        .get_scripts_by_partial_arg(Context::new(), code_hash, args.slice(4..8), (4, 8))
+        .await
+        .unwrap();
+    assert!(scripts.contains(&lock));
+}
+
+#[tokio::test]
+async fn test_kv_rollback_block() {
+    let dir = tempfile::Builder::new()
+        .prefix("test_kv_rollback_block")
+        .tempdir()
+        .unwrap();
+    let store = open_and_insert_blocks(&dir).await;
+
+    let block = read_block_view(9);
+    store
+        .rollback_block(Context::new(), 9, block.hash().unpack())
+        .await
+        .unwrap();
+
+    let tip = store.get_tip(Context::new()).await.unwrap().unwrap();
+    assert_eq!(tip, (8, read_block_view(8).hash().unpack()));
+    assert!(store
+        .get_block(Context::new(), None, Some(9))
+        .await
+        .is_err());
+
+    let tx_hash: H256 = block.transactions()[0].hash().unpack();
+    assert!(store
+        .get_simple_transaction_by_hash(Context::new(), tx_hash)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_kv_rollback_reorg() {
+    let dir = tempfile::Builder::new()
+        .prefix("test_kv_rollback_reorg")
+        .tempdir()
+        .unwrap();
+    let store = open_and_insert_blocks(&dir).await;
+    let genesis_cell = get_live_genesis_cell(&store).await;
+    let block = build_block_with_same_block_spend(
+        &read_block_view(9),
+        genesis_cell.out_point.clone(),
+        genesis_cell.cell_output.lock(),
+    );
+    store
+        .append_block(Context::new(), block.clone())
+        .await
+        .unwrap();
+
+    let mut blocks: Vec<(u64, H256)> = vec![(10, block.hash().unpack())];
+    blocks.extend(
+        (7..10)
+            .rev()
+            .map(|number| (number, read_block_view(number).hash().unpack())),
+    );
+    let reorg_log = ReorgLog {
+        fork_block_number: 6,
+        depth: 4,
+        old_tip_hash: blocks[0].1.clone(),
+        old_block_hash: blocks[3].1.clone(),
+        new_block_hash: H256::default(),
+        timestamp: 1,
+    };
+    store
+        .rollback_reorg(Context::new(), blocks, reorg_log.clone())
+        .await
+        .unwrap();
+
+    let tip = store.get_tip(Context::new()).await.unwrap().unwrap();
+    assert_eq!(tip, (6, read_block_view(6).hash().unpack()));
+    assert!(store
+        .get_block(Context::new(), None, Some(7))
+        .await
+        .is_err());
+    assert_eq!(
+        store.get_reorg_logs(Context::new()).await.unwrap(),
+        vec![reorg_log]
+    );
+    assert!(store.is_live_cell(&genesis_cell.out_point).unwrap());
+    for tx in block.transactions().iter().skip(1) {
+        assert!(!store
+            .is_live_cell(&packed::OutPoint::new(tx.hash(), 0))
+            .unwrap());
+    }
+}
+
+#[tokio::test]
+async fn test_kv_rollback_same_block_spend() {
+    let dir = tempfile::Builder::new()
+        .prefix("test_kv_rollback_same_block_spend")
+        .tempdir()
+        .unwrap();
+    let store = open_and_insert_blocks(&dir).await;
+
+    let genesis_cell = get_live_genesis_cell(&store).await;
+    let block = build_block_with_same_block_spend(
+        &read_block_view(9),
+        genesis_cell.out_point.clone(),
+        genesis_cell.cell_output.lock(),
+    );
+    store
+        .append_block(Context::new(), block.clone())
+        .await
+        .unwrap();
+    let created = packed::OutPoint::new(block.transactions()[1].hash(), 0);
+    let change = packed::OutPoint::new(block.transactions()[2].hash(), 0);
+    assert!(!store.is_live_cell(&genesis_cell.out_point).unwrap());
+    assert!(!store.is_live_cell(&created).unwrap());
+    assert!(store.is_live_cell(&change).unwrap());
+
+    store
+        .rollback_block(Context::new(), 10, block.hash().unpack())
+        .await
+        .unwrap();
+    assert!(store.is_live_cell(&genesis_cell.out_point).unwrap());
+    assert!(!store.is_live_cell(&created).unwrap());
+    assert!(!store.is_live_cell(&change).unwrap());
+}
+
+#[tokio::test]
+async fn test_kv_get_cells_by_filter() {
+    let dir = tempfile::Builder::new()
+        .prefix("test_kv_get_cells_by_filter")
+        .tempdir()
+        .unwrap();
+    let store = open_and_insert_blocks(&dir).await;
+
+    let genesis_cell = get_live_genesis_cell(&store).await;
+    let lock = packed::Script::new_builder()
+        .args(Bytes::from(vec![1u8; 20]).pack())
+        .build();
+    let lock_hash: H256 = lock.calc_script_hash().unpack();
+    let block = build_block_with_same_block_spend(
+        &read_block_view(9),
+        genesis_cell.out_point.clone(),
+        lock,
+    );
+    store
+        .append_block(Context::new(), block.clone())
+        .await
+        .unwrap();
+    let created = packed::OutPoint::new(block.transactions()[1].hash(), 0);
+    let change = packed::OutPoint::new(block.transactions()[2].hash(), 0);
+
+    let get_cells = |lock_hashes: Vec<H256>, io_type, data_prefix, block_range| {
+        store.get_cells_by_filter(
+            Context::new(),
+            lock_hashes,
+            vec![],
+            block_range,
+            CellFilter {
+                io_type,
+                data_prefix,
+            },
+            PaginationRequest::default(),
+        )
+    };
+    let out_points = |cells: Vec<DetailedCell>| {
+        cells
+            .into_iter()
+            .map(|cell| cell.out_point)
+            .collect::<Vec<_>>()
+    };
+
+    let cells = get_cells(vec![lock_hash.clone()], None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(out_points(cells.response), vec![change.clone()]);
+    let cells = get_cells(vec![lock_hash.clone()], Some(CellIOType::Input), None, None)
+        .await
+        .unwrap();
+    assert_eq!(out_points(cells.response), vec![created.clone()]);
+    let cells = get_cells(
+        vec![lock_hash.clone()],
+        Some(CellIOType::Output),
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(out_points(cells.response), vec![created, change]);
+
+    // the inputs are matched by the consuming block
+    let cells = get_cells(
+        vec![lock_hash.clone()],
+        Some(CellIOType::Input),
+        None,
+        Some(Range::new(0, 9)),
+    )
+    .await
+    .unwrap();
+    assert!(cells.response.is_empty());
+    let cells = get_cells(
+        vec![],
+        Some(CellIOType::Input),
+        None,
+        Some(Range::new(10, 10)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(cells.response.len(), 2);
+    assert!(out_points(cells.response).contains(&genesis_cell.out_point));
+
+    // the data prefix is compared before the pagination
+    let cells = get_cells(
+        vec![lock_hash.clone()],
+        Some(CellIOType::Output),
+        Some(Bytes::from(vec![1u8])),
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(cells.response.is_empty());
+
+    let txs = store
+        .get_transactions_by_filter(
+            Context::new(),
+            vec![lock_hash],
+            vec![],
+            None,
+            CellFilter {
+                io_type: Some(CellIOType::Input),
+                data_prefix: None,
+            },
+            PaginationRequest::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(txs.response.len(), 1);
+    assert_eq!(
+        txs.response[0].transaction_view.hash(),
+        block.transactions()[2].hash()
+    );
+}
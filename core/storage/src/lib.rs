@@ -5,7 +5,8 @@ pub mod relational;
 
 pub mod error;
 
-pub use db_protocol::{DBDriver, DBInfo};
+pub use db_protocol::{CellFilter, CellIOType, DBDriver, DBInfo, ReorgLog, ScriptBalance};
+pub use kvdb::KVStorage;
 pub use relational::RelationalStorage;
 
 use relational::table::IndexerCellTable;
@@ -31,6 +32,18 @@ pub trait Storage {
         block_hash: H256,
     ) -> Result<()>;
 
+    /// Rollback the blocks of a reorg in one transaction and save the reorg log. The blocks
+    /// are given from the tip down to the block after the fork point.
+    async fn rollback_reorg(
+        &self,
+        ctx: Context,
+        blocks: Vec<(BlockNumber, H256)>,
+        reorg_log: ReorgLog,
+    ) -> Result<()>;
+
+    /// Get the reorg logs in the order they happened.
+    async fn get_reorg_logs(&self, ctx: Context) -> Result<Vec<ReorgLog>>;
+
     /// Get live cells from the database according to the given arguments.
     async fn get_live_cells(
         &self,
@@ -90,6 +103,40 @@ pub trait Storage {
         pagination: PaginationRequest,
     ) -> Result<PaginationResponse<TransactionWrapper>>;
 
+    /// Get the cells matching the filter, which is applied before the pagination. The
+    /// cells are the live cells if the io type is not given, the consumed cells if it is
+    /// input, and all the cells if it is output.
+    async fn get_cells_by_filter(
+        &self,
+        ctx: Context,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        filter: CellFilter,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<DetailedCell>>;
+
+    /// Get the transactions consuming or creating the cells matching the filter, which is
+    /// applied before the pagination. Both the inputs and the outputs are matched if the
+    /// io type is not given.
+    async fn get_transactions_by_filter(
+        &self,
+        ctx: Context,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        filter: CellFilter,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<TransactionWrapper>>;
+
+    /// Get the balances of the live cells of the given lock scripts, aggregated by type
+    /// script.
+    async fn get_balances(
+        &self,
+        ctx: Context,
+        lock_hashes: Vec<H256>,
+    ) -> Result<Vec<ScriptBalance>>;
+
     /// Get the block from the database.
     /// There are four situations for the combination of `block_hash` and `block_number`:
     /// 1. `block_hash` and `block_number` are both `Some`. Firstly get block by hash and
@@ -0,0 +1,293 @@
+use crate::error::DBError;
+use crate::relational::fetch::rb_bytes_to_h256;
+use crate::relational::table::{BalanceTable, CellTable};
+use crate::relational::{sql, to_rb_bytes, RelationalStorage, BATCH_SIZE_THRESHOLD};
+
+use common::{utils::decode_udt_amount, Result};
+use db_protocol::ScriptBalance;
+use db_xsql::rbatis::core::types::byte::RbBytes;
+use db_xsql::rbatis::{crud::CRUDMut, executor::RBatisTxExecutor};
+
+use ckb_types::H256;
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+const BYTE_SHANNONS: u64 = 100_000_000;
+const REBUILD_PAGE_SIZE: u64 = 10_000;
+
+/// The amounts of some cells with the same lock script and type script. The UDT amount
+/// wraps on overflow, a fake UDT can mint anything and the wrapping keeps every change
+/// reversible.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+struct CellAmount {
+    capacity: u64,
+    occupied_capacity: u64,
+    udt_amount: u128,
+    cell_count: u64,
+}
+
+impl CellAmount {
+    fn from_cell(cell: &CellTable) -> Self {
+        let type_size = if cell.has_type_script() {
+            32 + 1 + cell.type_args.rb_bytes.len() as u64
+        } else {
+            0
+        };
+        let size = 8
+            + 32
+            + 1
+            + cell.lock_args.rb_bytes.len() as u64
+            + type_size
+            + cell.data.rb_bytes.len() as u64;
+        let udt_amount = if cell.has_type_script() {
+            decode_udt_amount(&cell.data.rb_bytes)
+        } else {
+            0
+        };
+
+        CellAmount {
+            capacity: cell.capacity,
+            occupied_capacity: size * BYTE_SHANNONS,
+            udt_amount,
+            cell_count: 1,
+        }
+    }
+
+    fn add(&mut self, other: &CellAmount) {
+        self.capacity += other.capacity;
+        self.occupied_capacity += other.occupied_capacity;
+        self.udt_amount = self.udt_amount.wrapping_add(other.udt_amount);
+        self.cell_count += other.cell_count;
+    }
+
+    fn sub(&mut self, other: &CellAmount) {
+        self.capacity = self.capacity.saturating_sub(other.capacity);
+        self.occupied_capacity = self
+            .occupied_capacity
+            .saturating_sub(other.occupied_capacity);
+        self.udt_amount = self.udt_amount.wrapping_sub(other.udt_amount);
+        self.cell_count = self.cell_count.saturating_sub(other.cell_count);
+    }
+
+    fn into_table(self, lock_hash: Vec<u8>, type_hash: Vec<u8>) -> BalanceTable {
+        BalanceTable {
+            lock_hash: to_rb_bytes(&lock_hash),
+            type_hash: to_rb_bytes(&type_hash),
+            capacity: self.capacity,
+            occupied_capacity: self.occupied_capacity,
+            udt_amount: self.udt_amount.to_string(),
+            cell_count: self.cell_count,
+        }
+    }
+}
+
+impl TryFrom<&BalanceTable> for CellAmount {
+    type Error = DBError;
+
+    fn try_from(table: &BalanceTable) -> std::result::Result<Self, Self::Error> {
+        Ok(CellAmount {
+            capacity: table.capacity,
+            occupied_capacity: table.occupied_capacity,
+            udt_amount: table
+                .udt_amount
+                .parse()
+                .map_err(|_| DBError::FetchDataError("balance".to_string()))?,
+            cell_count: table.cell_count,
+        })
+    }
+}
+
+/// The balance changes keyed by lock hash and type hash. The added and the removed
+/// amounts are kept apart, so that no signed number is needed.
+#[derive(Default, Debug)]
+pub(crate) struct BalanceDelta {
+    changes: HashMap<(Vec<u8>, Vec<u8>), (CellAmount, CellAmount)>,
+}
+
+impl BalanceDelta {
+    pub(crate) fn add_cell(&mut self, cell: &CellTable) {
+        self.entry(cell).0.add(&CellAmount::from_cell(cell));
+    }
+
+    pub(crate) fn sub_cell(&mut self, cell: &CellTable) {
+        self.entry(cell).1.add(&CellAmount::from_cell(cell));
+    }
+
+    fn entry(&mut self, cell: &CellTable) -> &mut (CellAmount, CellAmount) {
+        self.changes
+            .entry((
+                cell.lock_hash.rb_bytes.clone(),
+                cell.type_hash.rb_bytes.clone(),
+            ))
+            .or_default()
+    }
+}
+
+impl RelationalStorage {
+    /// The outputs of the block are added to the balance table and the consumed cells are
+    /// subtracted.
+    pub(crate) async fn append_balance(
+        &self,
+        output_cells: &[CellTable],
+        tx_hashes: &[RbBytes],
+        tx: &mut RBatisTxExecutor<'_>,
+    ) -> Result<()> {
+        let mut delta = BalanceDelta::default();
+        output_cells.iter().for_each(|cell| delta.add_cell(cell));
+        for cell in self.fetch_consumed_cells(tx_hashes, tx).await?.iter() {
+            delta.sub_cell(cell);
+        }
+
+        self.update_balance_table(delta, tx).await
+    }
+
+    /// The reverse of `append_balance`, which must be called before the consumed cells
+    /// are restored.
+    pub(crate) async fn rollback_balance(
+        &self,
+        block_hash: RbBytes,
+        tx_hashes: &[RbBytes],
+        tx: &mut RBatisTxExecutor<'_>,
+    ) -> Result<()> {
+        if tx_hashes.is_empty() {
+            return Ok(());
+        }
+
+        let mut delta = BalanceDelta::default();
+        let w = self
+            .pool
+            .wrapper()
+            .in_array("tx_hash", tx_hashes)
+            .and()
+            .eq("block_hash", block_hash);
+        let output_cells: Vec<CellTable> = tx.fetch_list_by_wrapper(w).await?;
+        output_cells.iter().for_each(|cell| delta.sub_cell(cell));
+        for cell in self.fetch_consumed_cells(tx_hashes, tx).await?.iter() {
+            delta.add_cell(cell);
+        }
+
+        self.update_balance_table(delta, tx).await
+    }
+
+    pub(crate) async fn query_balances(
+        &self,
+        lock_hashes: Vec<H256>,
+    ) -> Result<Vec<ScriptBalance>> {
+        if lock_hashes.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let lock_hashes = lock_hashes
+            .iter()
+            .map(|hash| to_rb_bytes(&hash.0))
+            .collect::<Vec<_>>();
+        let w = self.pool.wrapper().in_array("lock_hash", &lock_hashes);
+        let mut conn = self.pool.acquire().await?;
+        let tables: Vec<BalanceTable> = conn.fetch_list_by_wrapper(w).await?;
+
+        tables
+            .iter()
+            .map(|table| -> Result<ScriptBalance> {
+                let amount = CellAmount::try_from(table)?;
+                let type_hash = rb_bytes_to_h256(&table.type_hash);
+                Ok(ScriptBalance {
+                    lock_hash: rb_bytes_to_h256(&table.lock_hash),
+                    type_hash: (type_hash != H256::default()).then(|| type_hash),
+                    capacity: amount.capacity,
+                    occupied_capacity: amount.occupied_capacity,
+                    udt_amount: amount.udt_amount,
+                    cell_count: amount.cell_count,
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) async fn fetch_consumed_cells(
+        &self,
+        tx_hashes: &[RbBytes],
+        tx: &mut RBatisTxExecutor<'_>,
+    ) -> Result<Vec<CellTable>> {
+        if tx_hashes.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let w = self.pool.wrapper().in_array("consumed_tx_hash", tx_hashes);
+        let cells: Vec<CellTable> = tx.fetch_list_by_wrapper(w).await?;
+
+        // The cells of a rolled back block stay in the cell table, so an out point may
+        // appear more than once.
+        let mut set = HashSet::new();
+        Ok(cells
+            .into_iter()
+            .filter(|cell| set.insert((cell.tx_hash.rb_bytes.clone(), cell.output_index)))
+            .collect())
+    }
+
+    async fn update_balance_table(
+        &self,
+        delta: BalanceDelta,
+        tx: &mut RBatisTxExecutor<'_>,
+    ) -> Result<()> {
+        for ((lock_hash, type_hash), (added, removed)) in delta.changes.into_iter() {
+            if added == removed {
+                continue;
+            }
+
+            let w = self
+                .pool
+                .wrapper()
+                .eq("lock_hash", to_rb_bytes(&lock_hash))
+                .and()
+                .eq("type_hash", to_rb_bytes(&type_hash));
+            let table: Option<BalanceTable> = tx.fetch_by_wrapper(w.clone()).await?;
+            let mut amount = match table {
+                Some(table) => {
+                    tx.remove_by_wrapper::<BalanceTable>(w).await?;
+                    CellAmount::try_from(&table)?
+                }
+                None => CellAmount::default(),
+            };
+
+            amount.add(&added);
+            amount.sub(&removed);
+            if amount.cell_count > 0 {
+                tx.save(&amount.into_table(lock_hash, type_hash), &[])
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Rebuild the balance table from the live cell table. The parallel synchronization
+/// writes the cells directly, so the balance table is rebuilt after it.
+pub async fn rebuild_balance_table(tx: &mut RBatisTxExecutor<'_>) -> Result<()> {
+    sql::delete_balance_table(tx).await?;
+
+    let mut delta = BalanceDelta::default();
+    let mut last_id = i64::MIN;
+    loop {
+        let cells = sql::fetch_live_cells_after(tx, last_id, REBUILD_PAGE_SIZE).await?;
+        match cells.last() {
+            Some(cell) => last_id = cell.id,
+            None => break,
+        }
+        cells
+            .into_iter()
+            .for_each(|cell| delta.add_cell(&cell.into()));
+    }
+
+    let tables = delta
+        .changes
+        .into_iter()
+        .map(|((lock_hash, type_hash), (added, _))| added.into_table(lock_hash, type_hash))
+        .collect::<Vec<_>>();
+    if !tables.is_empty() {
+        tx.save_batch_slice(&tables, BATCH_SIZE_THRESHOLD, &[])
+            .await?;
+    }
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use crate::error::DBError;
 use crate::relational::table::{
     decode_since, BlockTable, CanonicalChainTable, CellTable, IndexerCellTable, LiveCellTable,
-    RegisteredAddressTable, ScriptTable, TransactionTable,
+    RegisteredAddressTable, ReorgLogTable, ScriptTable, TransactionTable,
 };
 use crate::relational::{to_rb_bytes, RelationalStorage};
 
@@ -10,7 +10,9 @@ use common::{
     Range, Result,
 };
 use common_logger::tracing_async;
-use db_protocol::{SimpleBlock, SimpleTransaction, TransactionWrapper};
+use db_protocol::{
+    CellFilter, CellIOType, ReorgLog, SimpleBlock, SimpleTransaction, TransactionWrapper,
+};
 use db_xsql::page::PageRequest;
 use db_xsql::rbatis::core::types::byte::RbBytes;
 use db_xsql::rbatis::{crud::CRUDMut, plugin::page::Page};
@@ -405,6 +407,23 @@ impl RelationalStorage {
         Ok(rb_bytes_to_h256(&ret.block_hash))
     }
 
+    pub(crate) async fn query_reorg_logs(&self) -> Result<Vec<ReorgLog>> {
+        let w = self.pool.wrapper().order_by(true, &["timestamp", "id"]);
+        let tables: Vec<ReorgLogTable> = self.pool.fetch_list_by_wrapper(w).await?;
+
+        Ok(tables
+            .into_iter()
+            .map(|table| ReorgLog {
+                fork_block_number: table.fork_block_number,
+                depth: table.depth,
+                old_tip_hash: rb_bytes_to_h256(&table.old_tip_hash),
+                old_block_hash: rb_bytes_to_h256(&table.old_block_hash),
+                new_block_hash: rb_bytes_to_h256(&table.new_block_hash),
+                timestamp: table.timestamp,
+            })
+            .collect())
+    }
+
     async fn query_live_cell_by_out_point(
         &self,
         out_point: packed::OutPoint,
@@ -567,6 +586,81 @@ impl RelationalStorage {
         Ok(to_pagination_response(res, next_cursor, cells.total))
     }
 
+    /// The filter is compared in the database, so that every page is filled with the
+    /// matched cells only.
+    #[tracing_async]
+    pub(crate) async fn query_cells_by_filter(
+        &self,
+        _ctx: Context,
+        lock_hashes: Vec<RbBytes>,
+        type_hashes: Vec<RbBytes>,
+        block_range: Option<Range>,
+        filter: &CellFilter,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<DetailedCell>> {
+        if lock_hashes.is_empty() && type_hashes.is_empty() && block_range.is_none() {
+            return Err(
+                DBError::InvalidParameter("no valid parameter to query cells".to_owned()).into(),
+            );
+        }
+
+        let mut wrapper = self.pool.wrapper();
+
+        if !lock_hashes.is_empty() {
+            wrapper = wrapper.in_array("lock_hash", &lock_hashes);
+        }
+
+        if !type_hashes.is_empty() {
+            wrapper = wrapper.and().in_array("type_hash", &type_hashes);
+        }
+
+        if let Some(prefix) = filter.data_prefix.as_ref().filter(|p| !p.is_empty()) {
+            wrapper = wrapper.and().eq(
+                &format!("substr(data, 1, {})", prefix.len()),
+                to_rb_bytes(prefix),
+            );
+        }
+
+        if filter.io_type == Some(CellIOType::Input) {
+            wrapper = wrapper.and().push_sql("consumed_block_number IS NOT NULL");
+        }
+
+        if let Some(range) = block_range {
+            let column = if filter.io_type == Some(CellIOType::Input) {
+                "consumed_block_number"
+            } else {
+                "block_number"
+            };
+            wrapper = wrapper.and().between(column, range.min(), range.max());
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        let mut res = Vec::new();
+
+        if filter.io_type.is_none() {
+            let cells: Page<LiveCellTable> = conn
+                .fetch_page_by_wrapper(wrapper, &PageRequest::from(pagination.clone()))
+                .await?;
+            let next_cursor = build_next_cursor!(cells, pagination);
+            for r in cells.records.iter() {
+                let cell_data = r.data.rb_bytes.clone();
+                res.push(self.build_detailed_cell(r.clone().into(), cell_data));
+            }
+            return Ok(to_pagination_response(res, next_cursor, cells.total));
+        }
+
+        let cells: Page<CellTable> = conn
+            .fetch_page_by_wrapper(wrapper, &PageRequest::from(pagination.clone()))
+            .await?;
+        let next_cursor = build_next_cursor!(cells, pagination);
+        for r in cells.records.iter() {
+            let cell_data = r.data.rb_bytes.clone();
+            res.push(self.build_detailed_cell(r.clone(), cell_data));
+        }
+
+        Ok(to_pagination_response(res, next_cursor, cells.total))
+    }
+
     #[tracing_async]
     pub(crate) async fn query_historical_live_cells(
         &self,
@@ -1,11 +1,13 @@
 use crate::relational::table::{
     BlockTable, CanonicalChainTable, CellTable, ConsumedInfo, IndexerCellTable, LiveCellTable,
-    RegisteredAddressTable, ScriptTable, TransactionTable, IO_TYPE_INPUT, IO_TYPE_OUTPUT,
+    RegisteredAddressTable, ReorgLogTable, ScriptTable, TransactionTable, IO_TYPE_INPUT,
+    IO_TYPE_OUTPUT,
 };
 use crate::relational::{generate_id, sql, to_rb_bytes, RelationalStorage};
 
 use common::{Context, Result};
 use common_logger::tracing_async;
+use db_protocol::ReorgLog;
 use db_xsql::rbatis::core::types::byte::RbBytes;
 use db_xsql::rbatis::{crud::CRUDMut, executor::RBatisTxExecutor};
 
@@ -27,6 +29,18 @@ macro_rules! save_batch_slice {
 }
 
 impl RelationalStorage {
+    #[tracing_async]
+    pub(crate) async fn insert_reorg_log_table(
+        &self,
+        _ctx: Context,
+        reorg_log: &ReorgLog,
+        tx: &mut RBatisTxExecutor<'_>,
+    ) -> Result<()> {
+        let table = ReorgLogTable::new(generate_id(reorg_log.fork_block_number), reorg_log);
+        tx.save(&table, &[]).await?;
+        Ok(())
+    }
+
     #[tracing_async]
     pub(crate) async fn insert_block_table(
         &self,
@@ -94,6 +108,13 @@ impl RelationalStorage {
         save_batch_slice!(tx, tx_set, output_cell_set, live_cell_set, script_batch);
 
         self.update_consumed_cells(&consumed_infos, tx).await?;
+
+        let tx_hashes = tx_set
+            .iter()
+            .map(|table| table.tx_hash.clone())
+            .collect::<Vec<_>>();
+        self.append_balance(&output_cell_set, &tx_hashes, tx)
+            .await?;
         // self.fill_and_save_indexer_cells(block_number, indexer_cells, &consumed_infos, tx)
         //     .await?;
 
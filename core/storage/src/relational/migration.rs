@@ -0,0 +1,134 @@
+use crate::error::DBError;
+use crate::relational::table::SchemaVersionTable;
+use crate::relational::RelationalStorage;
+
+use common::Result;
+use db_protocol::DBDriver;
+use db_xsql::rbatis::{crud::CRUDMut, executor::Executor};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The schema version that this binary works with, which is the version of the last
+/// migration.
+pub const SCHEMA_VERSION: u32 = 4;
+
+const CREATE_SCHEMA_VERSION_TABLE: &str = "CREATE TABLE IF NOT EXISTS mercury_schema_version(
+    version int NOT NULL PRIMARY KEY,
+    description {text} NOT NULL,
+    applied_at bigint NOT NULL
+)";
+
+struct Migration {
+    version: u32,
+    description: &'static str,
+    sql: &'static str,
+}
+
+/// The migrations in ascending order of version. A released migration must never be
+/// modified, every change of the schema is a new migration appended here.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create tables",
+        sql: include_str!("migrations/v1_init.sql"),
+    },
+    Migration {
+        version: 2,
+        description: "create balance table",
+        sql: include_str!("migrations/v2_balance.sql"),
+    },
+    Migration {
+        version: 3,
+        description: "create sync checkpoint table",
+        sql: include_str!("migrations/v3_sync_checkpoint.sql"),
+    },
+    Migration {
+        version: 4,
+        description: "create reorg log table",
+        sql: include_str!("migrations/v4_reorg_log.sql"),
+    },
+];
+
+impl RelationalStorage {
+    /// Create or upgrade the schema to `SCHEMA_VERSION`. The applied versions are recorded
+    /// in the `mercury_schema_version` table, and a database with a newer schema is refused.
+    /// Each migration runs in a transaction, notice that the DDL of MySQL is not transactional.
+    pub async fn migrate(&self, db_driver: DBDriver) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        conn.exec(&render(CREATE_SCHEMA_VERSION_TABLE, &db_driver)?, vec![])
+            .await?;
+        drop(conn);
+
+        let current_version = match self.query_schema_version().await? {
+            Some(version) => version,
+            // The tables created by the SQL scripts before the migrations are version 1.
+            None if self.block_count().await.is_ok() => {
+                let mut tx = self.pool.transaction().await?;
+                tx.save(&new_schema_version_table(&MIGRATIONS[0]), &[])
+                    .await?;
+                tx.commit().await?;
+                MIGRATIONS[0].version
+            }
+            None => 0,
+        };
+
+        if current_version > SCHEMA_VERSION {
+            return Err(DBError::NewerSchemaVersion(current_version, SCHEMA_VERSION).into());
+        }
+
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current_version) {
+            log::info!(
+                "apply schema migration {}: {}",
+                migration.version,
+                migration.description
+            );
+
+            let sql = render(migration.sql, &db_driver)?;
+            let mut tx = self.pool.transaction().await?;
+            for statement in sql.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+                tx.exec(statement, vec![]).await?;
+            }
+            tx.save(&new_schema_version_table(migration), &[]).await?;
+            tx.commit().await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn query_schema_version(&self) -> Result<Option<u32>> {
+        let tables: Vec<SchemaVersionTable> = self.pool.fetch_list().await?;
+        Ok(tables.iter().map(|table| table.version).max())
+    }
+}
+
+/// The binary columns differ between databases. MySQL cannot index a blob without a
+/// prefix length, so the hashes and args are stored as varbinary.
+fn render(sql: &str, db_driver: &DBDriver) -> Result<String> {
+    let (hash, bytes, blob, text) = match db_driver {
+        DBDriver::PostgreSQL => ("bytea", "bytea", "bytea", "varchar"),
+        DBDriver::MySQL => ("varbinary(32)", "varbinary(1024)", "longblob", "text"),
+        DBDriver::SQLite => ("blob", "blob", "blob", "text"),
+        DBDriver::RocksDB => {
+            return Err(
+                DBError::InvalidParameter("rocksdb has no relational schema".to_string()).into(),
+            )
+        }
+    };
+
+    Ok(sql
+        .replace("{hash}", hash)
+        .replace("{bytes}", bytes)
+        .replace("{blob}", blob)
+        .replace("{text}", text))
+}
+
+fn new_schema_version_table(migration: &Migration) -> SchemaVersionTable {
+    SchemaVersionTable {
+        version: migration.version,
+        description: migration.description.to_string(),
+        applied_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time before unix epoch")
+            .as_secs(),
+    }
+}
@@ -0,0 +1,159 @@
+CREATE TABLE mercury_block(
+    block_hash {hash} PRIMARY KEY,
+    block_number int NOT NULL,
+    version smallint NOT NULL,
+    compact_target int NOT NULL,
+    block_timestamp bigint NOT NULL,
+    epoch_number int NOT NULL,
+    epoch_index int NOT NULL,
+    epoch_length int NOT NULL,
+    parent_hash {hash} NOT NULL,
+    transactions_root {hash} NOT NULL,
+    proposals_hash {hash} NOT NULL,
+    uncles_hash {hash},
+    uncles {blob},
+    uncles_count int,
+    dao {hash} NOT NULL,
+    nonce {hash} NOT NULL,
+    proposals {blob}
+);
+
+CREATE TABLE mercury_transaction(
+    id bigint PRIMARY KEY,
+    tx_hash {hash} NOT NULL,
+    tx_index int NOT NULL,
+    input_count int NOT NULL,
+    output_count int NOT NULL,
+    block_number int NOT NULL,
+    block_hash {hash} NOT NULL,
+    tx_timestamp bigint NOT NULL,
+    version smallint NOT NULL,
+    cell_deps {blob},
+    header_deps {blob},
+    witnesses {blob}
+);
+
+CREATE TABLE mercury_cell(
+    id bigint PRIMARY KEY,
+    tx_hash {hash} NOT NULL,
+    output_index int NOT NULL,
+    tx_index int NOT NULL,
+    block_hash {hash} NOT NULL,
+    block_number int NOT NULL,
+    epoch_number int NOT NULL,
+    epoch_index int NOT NULL,
+    epoch_length int NOT NULL,
+    capacity bigint NOT NULL,
+    lock_hash {hash},
+    lock_code_hash {hash},
+    lock_args {bytes},
+    lock_script_type smallint,
+    type_hash {hash},
+    type_code_hash {hash},
+    type_args {bytes},
+    type_script_type smallint,
+    data {blob},
+    consumed_block_number bigint,
+    consumed_block_hash {hash},
+    consumed_tx_hash {hash},
+    consumed_tx_index int,
+    input_index int,
+    since {bytes}
+);
+
+CREATE TABLE mercury_live_cell(
+    id bigint PRIMARY KEY,
+    tx_hash {hash} NOT NULL,
+    output_index int NOT NULL,
+    tx_index int NOT NULL,
+    block_hash {hash} NOT NULL,
+    block_number int NOT NULL,
+    epoch_number int NOT NULL,
+    epoch_index int NOT NULL,
+    epoch_length int NOT NULL,
+    capacity bigint NOT NULL,
+    lock_hash {hash},
+    lock_code_hash {hash},
+    lock_args {bytes},
+    lock_script_type smallint,
+    type_hash {hash},
+    type_code_hash {hash},
+    type_args {bytes},
+    type_script_type smallint,
+    data {blob}
+);
+
+CREATE TABLE mercury_indexer_cell(
+    id bigint PRIMARY KEY,
+    block_number int NOT NULL,
+    io_type smallint NOT NULL,
+    io_index int NOT NULL,
+    tx_hash {hash} NOT NULL,
+    tx_index int NOT NULL,
+    lock_hash {hash},
+    lock_code_hash {hash},
+    lock_args {bytes},
+    lock_script_type smallint,
+    type_hash {hash},
+    type_code_hash {hash},
+    type_args {bytes},
+    type_script_type smallint
+);
+
+CREATE TABLE mercury_script(
+    script_hash {hash} NOT NULL PRIMARY KEY,
+    script_hash_160 {hash} NOT NULL,
+    script_code_hash {hash} NOT NULL,
+    script_args {bytes},
+    script_type smallint NOT NULL,
+    script_args_len int
+);
+
+CREATE TABLE mercury_uncle_relationship(
+    block_hash {hash},
+    uncle_hashes {bytes},
+    PRIMARY KEY(block_hash, uncle_hashes)
+);
+
+CREATE TABLE mercury_canonical_chain(
+    block_number int PRIMARY KEY,
+    block_hash {hash} NOT NULL
+);
+
+CREATE TABLE mercury_registered_address(
+    lock_hash {hash} NOT NULL PRIMARY KEY,
+    address {text} NOT NULL
+);
+
+CREATE TABLE mercury_sync_status(
+    block_number int NOT NULL PRIMARY KEY
+);
+
+CREATE TABLE mercury_in_update(
+    is_in bool NOT NULL PRIMARY KEY
+);
+
+CREATE INDEX index_block_table_block_number ON mercury_block (block_number);
+
+CREATE INDEX index_live_cell_table_block_hash ON mercury_live_cell (block_hash);
+CREATE INDEX index_live_cell_table_block_number ON mercury_live_cell (block_number);
+CREATE INDEX index_live_cell_table_tx_hash_and_output_index ON mercury_live_cell (tx_hash, output_index);
+CREATE INDEX index_live_cell_table_lock_hash ON mercury_live_cell (lock_hash);
+CREATE INDEX index_live_cell_table_type_hash ON mercury_live_cell (type_hash);
+CREATE INDEX index_live_cell_table_lock_code_hash_and_lock_script_type ON mercury_live_cell (lock_code_hash, lock_script_type);
+CREATE INDEX index_live_cell_table_type_code_hash_and_type_script_type ON mercury_live_cell (type_code_hash, type_script_type);
+
+CREATE INDEX index_script_table_script_hash ON mercury_script (script_hash);
+CREATE INDEX index_script_table_code_hash ON mercury_script (script_code_hash);
+CREATE INDEX index_script_table_args ON mercury_script (script_args);
+
+CREATE INDEX index_cell_table_tx_hash_and_output_index ON mercury_cell (tx_hash, output_index);
+CREATE INDEX index_cell_table_lock_hash ON mercury_cell (lock_hash);
+CREATE INDEX index_cell_table_type_hash ON mercury_cell (type_hash);
+CREATE INDEX index_cell_table_lock_code_hash_and_lock_script_type ON mercury_cell (lock_code_hash, lock_script_type);
+CREATE INDEX index_cell_table_type_code_hash_and_type_script_type ON mercury_cell (type_code_hash, type_script_type);
+CREATE INDEX index_cell_table_consume_tx_hash_and_consumed_tx_index ON mercury_cell (consumed_tx_hash, consumed_tx_index);
+CREATE INDEX index_cell_table_block_number ON mercury_cell (block_number);
+CREATE INDEX index_cell_table_consumed_block_number ON mercury_cell (consumed_block_number);
+
+CREATE INDEX index_transaction_table_tx_hash ON mercury_transaction (tx_hash);
@@ -0,0 +1,9 @@
+CREATE TABLE mercury_balance(
+    lock_hash {hash} NOT NULL,
+    type_hash {hash} NOT NULL,
+    capacity bigint NOT NULL,
+    occupied_capacity bigint NOT NULL,
+    udt_amount {text} NOT NULL,
+    cell_count bigint NOT NULL,
+    PRIMARY KEY(lock_hash, type_hash)
+);
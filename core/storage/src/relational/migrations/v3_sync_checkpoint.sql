@@ -0,0 +1,6 @@
+CREATE TABLE mercury_sync_checkpoint(
+    phase smallint NOT NULL,
+    range_start bigint NOT NULL,
+    range_end bigint NOT NULL,
+    PRIMARY KEY(phase, range_start)
+);
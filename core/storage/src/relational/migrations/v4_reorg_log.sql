@@ -0,0 +1,9 @@
+CREATE TABLE mercury_reorg_log(
+    id bigint NOT NULL PRIMARY KEY,
+    fork_block_number bigint NOT NULL,
+    depth bigint NOT NULL,
+    old_tip_hash {hash} NOT NULL,
+    old_block_hash {hash} NOT NULL,
+    new_block_hash {hash} NOT NULL,
+    timestamp bigint NOT NULL
+);
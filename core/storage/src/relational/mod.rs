@@ -1,5 +1,7 @@
+mod balance;
 mod fetch;
 mod insert;
+mod migration;
 mod remove;
 mod snowflake;
 mod sql;
@@ -8,7 +10,9 @@ pub mod table;
 #[cfg(test)]
 mod tests;
 
+pub use balance::rebuild_balance_table;
 pub use insert::BATCH_SIZE_THRESHOLD;
+pub use migration::SCHEMA_VERSION;
 
 use crate::relational::{
     fetch::to_pagination_response, snowflake::Snowflake, table::IndexerCellTable,
@@ -20,7 +24,10 @@ use common::{
     PaginationResponse, Range, Result,
 };
 use common_logger::{tracing, tracing_async};
-use db_protocol::{DBDriver, DBInfo, SimpleBlock, SimpleTransaction, TransactionWrapper};
+use db_protocol::{
+    CellFilter, CellIOType, DBDriver, DBInfo, ReorgLog, ScriptBalance, SimpleBlock,
+    SimpleTransaction, TransactionWrapper,
+};
 use db_xsql::{rbatis::core::types::byte::RbBytes, XSQLPool};
 
 use ckb_types::core::{BlockNumber, BlockView, HeaderView};
@@ -75,6 +82,34 @@ impl Storage for RelationalStorage {
         Ok(())
     }
 
+    #[tracing_async]
+    async fn rollback_reorg(
+        &self,
+        ctx: Context,
+        blocks: Vec<(BlockNumber, H256)>,
+        reorg_log: ReorgLog,
+    ) -> Result<()> {
+        let mut tx = self.pool.transaction().await?;
+        for (block_number, block_hash) in blocks.into_iter() {
+            let block_hash = to_rb_bytes(&block_hash.0);
+            self.remove_tx_and_cell(ctx.clone(), block_number, block_hash.clone(), &mut tx)
+                .await?;
+            self.remove_block_table(ctx.clone(), block_number, block_hash, &mut tx)
+                .await?;
+        }
+
+        self.insert_reorg_log_table(ctx.clone(), &reorg_log, &mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[tracing_async]
+    async fn get_reorg_logs(&self, _ctx: Context) -> Result<Vec<ReorgLog>> {
+        self.query_reorg_logs().await
+    }
+
     #[tracing_async]
     async fn get_cells(
         &self,
@@ -295,6 +330,57 @@ impl Storage for RelationalStorage {
         type_hashes: Vec<H256>,
         block_range: Option<Range>,
         pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<TransactionWrapper>> {
+        self.get_transactions_by_filter(
+            ctx,
+            lock_hashes,
+            type_hashes,
+            block_range,
+            CellFilter::default(),
+            pagination,
+        )
+        .await
+    }
+
+    #[tracing_async]
+    async fn get_cells_by_filter(
+        &self,
+        ctx: Context,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        filter: CellFilter,
+        pagination: PaginationRequest,
+    ) -> Result<PaginationResponse<DetailedCell>> {
+        let lock_hashes = lock_hashes
+            .into_iter()
+            .map(|hash| to_rb_bytes(hash.as_bytes()))
+            .collect::<Vec<_>>();
+        let type_hashes = type_hashes
+            .into_iter()
+            .map(|hash| to_rb_bytes(hash.as_bytes()))
+            .collect::<Vec<_>>();
+
+        self.query_cells_by_filter(
+            ctx,
+            lock_hashes,
+            type_hashes,
+            block_range,
+            &filter,
+            pagination,
+        )
+        .await
+    }
+
+    #[tracing_async]
+    async fn get_transactions_by_filter(
+        &self,
+        ctx: Context,
+        lock_hashes: Vec<H256>,
+        type_hashes: Vec<H256>,
+        block_range: Option<Range>,
+        filter: CellFilter,
+        pagination: PaginationRequest,
     ) -> Result<PaginationResponse<TransactionWrapper>> {
         if block_range.is_none() && lock_hashes.is_empty() && type_hashes.is_empty() {
             return Err(DBError::InvalidParameter(
@@ -312,34 +398,51 @@ impl Storage for RelationalStorage {
             .map(|hash| to_rb_bytes(hash.as_bytes()))
             .collect::<Vec<_>>();
 
+        // The inputs are matched by the consuming block, and the outputs by the creating
+        // block, so the two sides are queried separately.
         let mut set = HashSet::new();
-        for cell in self
-            .query_cells(
-                ctx.clone(),
-                None,
-                lock_hashes,
-                type_hashes,
-                block_range.clone(),
-                Default::default(),
-            )
-            .await?
-            .response
-            .iter()
-        {
-            set.insert(TxHashInfo::new(
-                cell.out_point.tx_hash().unpack(),
-                cell.block_number,
-                cell.tx_index,
-            ));
-            if let Some(hash) = &cell.consumed_tx_hash {
-                set.insert(TxHashInfo::new(
-                    hash.clone(),
-                    cell.consumed_block_number.unwrap(),
-                    cell.consumed_tx_index.unwrap(),
-                ));
+        for io_type in [CellIOType::Input, CellIOType::Output] {
+            if filter.io_type.is_some() && filter.io_type != Some(io_type) {
+                continue;
+            }
+
+            let cell_filter = CellFilter {
+                io_type: Some(io_type),
+                data_prefix: filter.data_prefix.clone(),
+            };
+            for cell in self
+                .query_cells_by_filter(
+                    ctx.clone(),
+                    lock_hashes.clone(),
+                    type_hashes.clone(),
+                    block_range.clone(),
+                    &cell_filter,
+                    Default::default(),
+                )
+                .await?
+                .response
+                .iter()
+            {
+                if io_type == CellIOType::Output {
+                    set.insert(TxHashInfo::new(
+                        cell.out_point.tx_hash().unpack(),
+                        cell.block_number,
+                        cell.tx_index,
+                    ));
+                } else if let Some(hash) = &cell.consumed_tx_hash {
+                    set.insert(TxHashInfo::new(
+                        hash.clone(),
+                        cell.consumed_block_number.unwrap(),
+                        cell.consumed_tx_index.unwrap(),
+                    ));
+                }
             }
         }
 
+        if set.is_empty() {
+            return Ok(to_pagination_response(vec![], None, 0));
+        }
+
         let mut cells = set.into_iter().collect::<Vec<_>>();
         cells.sort();
         if pagination.order == Order::Desc {
@@ -418,6 +521,15 @@ impl Storage for RelationalStorage {
         ))
     }
 
+    #[tracing_async]
+    async fn get_balances(
+        &self,
+        _ctx: Context,
+        lock_hashes: Vec<H256>,
+    ) -> Result<Vec<ScriptBalance>> {
+        self.query_balances(lock_hashes).await
+    }
+
     #[tracing_async]
     async fn get_block(
         &self,
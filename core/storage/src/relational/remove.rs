@@ -1,5 +1,7 @@
 use crate::relational::table::{BlockTable, CanonicalChainTable, LiveCellTable, TransactionTable};
-use crate::relational::{empty_rb_bytes, sql, to_rb_bytes, RelationalStorage};
+use crate::relational::{
+    empty_rb_bytes, sql, to_rb_bytes, RelationalStorage, BATCH_SIZE_THRESHOLD,
+};
 
 use common::{Context, Result};
 use common_logger::tracing_async;
@@ -10,6 +12,8 @@ use db_xsql::rbatis::{crud::CRUDMut, executor::RBatisTxExecutor};
 
 use ckb_types::{core::BlockNumber, packed};
 
+use std::collections::HashSet;
+
 impl RelationalStorage {
     pub(crate) async fn remove_tx_and_cell(
         &self,
@@ -24,11 +28,31 @@ impl RelationalStorage {
             .map(|hash| hash.inner())
             .collect::<Vec<_>>();
 
+        self.rollback_balance(block_hash.clone(), &tx_hashes, tx)
+            .await?;
         tx.remove_by_column::<TransactionTable, RbBytes>("block_hash", &block_hash)
             .await?;
         tx.remove_batch_by_column::<LiveCellTable, RbBytes>("tx_hash", &tx_hashes)
             .await?;
 
+        // revive the cells consumed by the block, except the ones created in it, which
+        // are removed above
+        let created = tx_hashes
+            .iter()
+            .map(|hash| hash.rb_bytes.clone())
+            .collect::<HashSet<_>>();
+        let revived_cells = self
+            .fetch_consumed_cells(&tx_hashes, tx)
+            .await?
+            .into_iter()
+            .filter(|cell| !created.contains(&cell.tx_hash.rb_bytes))
+            .map(LiveCellTable::from)
+            .collect::<Vec<_>>();
+        if !revived_cells.is_empty() {
+            tx.save_batch_slice(&revived_cells, BATCH_SIZE_THRESHOLD, &[])
+                .await?;
+        }
+
         for tx_hash in tx_hashes.iter() {
             sql::rollback_consume_cell(tx, empty_rb_bytes(), tx_hash.clone()).await?;
         }
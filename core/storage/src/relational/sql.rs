@@ -1,4 +1,4 @@
-use crate::relational::table::{MercuryId, ScriptTable, TxHash};
+use crate::relational::table::{LiveCellTable, MercuryId, ScriptTable, TxHash};
 
 use db_xsql::rbatis::executor::{RBatisConnExecutor, RBatisTxExecutor};
 use db_xsql::rbatis::{core::types::byte::RbBytes, sql};
@@ -98,6 +98,20 @@ pub async fn update_sync_dead_cell(
 ) -> () {
 }
 
+#[sql(tx, "DELETE FROM mercury_balance")]
+pub async fn delete_balance_table(tx: &mut RBatisTxExecutor<'_>) -> () {}
+
+#[sql(
+    tx,
+    "SELECT * FROM mercury_live_cell WHERE id > $1 ORDER BY id LIMIT $2"
+)]
+pub async fn fetch_live_cells_after(
+    tx: &mut RBatisTxExecutor<'_>,
+    id: i64,
+    limit: u64,
+) -> Vec<LiveCellTable> {
+}
+
 #[cfg(test)]
 #[sql(conn, "SELECT COUNT(1) FROM mercury_consume_info")]
 pub async fn fetch_cunsumed_cell_count(conn: &mut RBatisConnExecutor<'_>) -> u64 {}
@@ -1,6 +1,7 @@
 use crate::relational::{empty_rb_bytes, to_rb_bytes};
 
 use common::utils::to_fixed_array;
+use db_protocol::ReorgLog;
 use db_xsql::rbatis::{core::types::byte::RbBytes, crud_table};
 
 use ckb_types::core::{BlockView, EpochNumberWithFraction, TransactionView};
@@ -587,6 +588,60 @@ impl RegisteredAddressTable {
     }
 }
 
+#[crud_table(
+    table_name: "mercury_balance" | formats_pg: "
+    lock_hash:{}::bytea,
+    type_hash:{}::bytea"
+)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BalanceTable {
+    pub lock_hash: RbBytes,
+    pub type_hash: RbBytes,
+    pub capacity: u64,
+    pub occupied_capacity: u64,
+    pub udt_amount: String,
+    pub cell_count: u64,
+}
+
+#[crud_table(table_name: "mercury_schema_version")]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SchemaVersionTable {
+    pub version: u32,
+    pub description: String,
+    pub applied_at: u64,
+}
+
+#[crud_table(
+    table_name: "mercury_reorg_log" | formats_pg: "
+    old_tip_hash:{}::bytea,
+    old_block_hash:{}::bytea,
+    new_block_hash:{}::bytea"
+)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReorgLogTable {
+    pub id: i64,
+    pub fork_block_number: u64,
+    pub depth: u64,
+    pub old_tip_hash: RbBytes,
+    pub old_block_hash: RbBytes,
+    pub new_block_hash: RbBytes,
+    pub timestamp: u64,
+}
+
+impl ReorgLogTable {
+    pub fn new(id: i64, log: &ReorgLog) -> Self {
+        ReorgLogTable {
+            id,
+            fork_block_number: log.fork_block_number,
+            depth: log.depth,
+            old_tip_hash: to_rb_bytes(log.old_tip_hash.as_bytes()),
+            old_block_hash: to_rb_bytes(log.old_block_hash.as_bytes()),
+            new_block_hash: to_rb_bytes(log.new_block_hash.as_bytes()),
+            timestamp: log.timestamp,
+        }
+    }
+}
+
 pub fn decode_since(input: &[u8]) -> u64 {
     u64::from_be_bytes(to_fixed_array::<8>(input))
 }
@@ -25,3 +25,150 @@ async fn test_is_live_cell() {
         .unwrap();
     assert!(res.is_some());
 }
+
+async fn get_cells_by_filter(
+    pool: &RelationalStorage,
+    lock_hash: H256,
+    io_type: Option<CellIOType>,
+    data_prefix: Option<Bytes>,
+    block_range: Option<Range>,
+) -> Vec<packed::OutPoint> {
+    pool.get_cells_by_filter(
+        Context::new(),
+        vec![lock_hash],
+        vec![],
+        block_range,
+        CellFilter {
+            io_type,
+            data_prefix,
+        },
+        PaginationRequest::default(),
+    )
+    .await
+    .unwrap()
+    .response
+    .into_iter()
+    .map(|cell| cell.out_point)
+    .collect()
+}
+
+async fn get_transactions_by_filter(
+    pool: &RelationalStorage,
+    lock_hash: H256,
+    io_type: Option<CellIOType>,
+) -> Vec<packed::Byte32> {
+    pool.get_transactions_by_filter(
+        Context::new(),
+        vec![lock_hash],
+        vec![],
+        None,
+        CellFilter {
+            io_type,
+            data_prefix: None,
+        },
+        PaginationRequest::default(),
+    )
+    .await
+    .unwrap()
+    .response
+    .into_iter()
+    .map(|tx| tx.transaction_view.hash())
+    .collect()
+}
+
+#[tokio::test]
+async fn test_get_cells_by_filter() {
+    let pool = connect_and_insert_blocks().await;
+    let genesis: BlockView = read_block_view(0, String::from(BLOCK_DIR)).into();
+    let genesis_cell = pool
+        .get_live_cells(
+            Context::new(),
+            None,
+            vec![genesis.transactions()[0]
+                .outputs()
+                .get(0)
+                .unwrap()
+                .calc_lock_hash()
+                .unpack()],
+            vec![],
+            None,
+            PaginationRequest::default().set_limit(Some(1)),
+        )
+        .await
+        .unwrap()
+        .response
+        .remove(0);
+    let lock = packed::Script::new_builder()
+        .args(Bytes::from(vec![1u8; 20]).pack())
+        .build();
+    let lock_hash: H256 = lock.calc_script_hash().unpack();
+    let new_block = build_block_with_same_block_spend(
+        &read_block_view(9, String::from(BLOCK_DIR)).into(),
+        genesis_cell.out_point,
+        lock,
+    );
+    pool.append_block(Context::new(), new_block.clone())
+        .await
+        .unwrap();
+    let create_tx = new_block.transactions()[1].clone();
+    let consume_tx = new_block.transactions()[2].clone();
+
+    let cells = get_cells_by_filter(&pool, lock_hash.clone(), None, None, None).await;
+    assert_eq!(cells, vec![packed::OutPoint::new(consume_tx.hash(), 0)]);
+    let cells = get_cells_by_filter(
+        &pool,
+        lock_hash.clone(),
+        Some(CellIOType::Input),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(cells, vec![packed::OutPoint::new(create_tx.hash(), 0)]);
+    let cells = get_cells_by_filter(
+        &pool,
+        lock_hash.clone(),
+        Some(CellIOType::Output),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(cells.len(), 2);
+
+    // the inputs are matched by the consuming block
+    let cells = get_cells_by_filter(
+        &pool,
+        lock_hash.clone(),
+        Some(CellIOType::Input),
+        None,
+        Some(Range::new(10, 10)),
+    )
+    .await;
+    assert_eq!(cells.len(), 1);
+    let cells = get_cells_by_filter(
+        &pool,
+        lock_hash.clone(),
+        Some(CellIOType::Input),
+        None,
+        Some(Range::new(0, 9)),
+    )
+    .await;
+    assert!(cells.is_empty());
+
+    // the data prefix is compared before the pagination
+    let cells = get_cells_by_filter(
+        &pool,
+        lock_hash.clone(),
+        Some(CellIOType::Output),
+        Some(Bytes::from(vec![1u8])),
+        None,
+    )
+    .await;
+    assert!(cells.is_empty());
+
+    let txs = get_transactions_by_filter(&pool, lock_hash.clone(), Some(CellIOType::Input)).await;
+    assert_eq!(txs, vec![consume_tx.hash()]);
+    let txs = get_transactions_by_filter(&pool, lock_hash.clone(), Some(CellIOType::Output)).await;
+    assert_eq!(txs.len(), 2);
+    let txs = get_transactions_by_filter(&pool, lock_hash, None).await;
+    assert_eq!(txs.len(), 2);
+}
@@ -6,12 +6,13 @@ mod single_sql_test;
 
 use crate::relational::fetch::rb_bytes_to_h256;
 use crate::relational::{sql, to_rb_bytes, DBDriver, PaginationRequest, XSQLPool};
-use crate::{relational::RelationalStorage, Storage};
+use crate::{relational::RelationalStorage, CellFilter, CellIOType, Storage};
 
 use common::{Context, Order, Range};
 
 use ckb_jsonrpc_types::BlockView as JsonBlockView;
-use ckb_types::{bytes::Bytes, core::BlockView, h160, prelude::*, H256};
+use ckb_types::core::{BlockBuilder, BlockView, Capacity, TransactionBuilder};
+use ckb_types::{bytes::Bytes, h160, packed, prelude::*, H256};
 
 const MEMORY_DB: &str = ":memory:";
 const POSTGRES_DB: &str = "127.0.0.1";
@@ -57,10 +58,62 @@ pub fn read_block_view(number: u64, dir_path: String) -> JsonBlockView {
     serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
 }
 
+/// Build a block on top of the parent, in which the last transaction consumes the output
+/// created by the former one.
+fn build_block_with_same_block_spend(
+    parent: &BlockView,
+    input: packed::OutPoint,
+    lock: packed::Script,
+) -> BlockView {
+    let output = packed::CellOutput::new_builder()
+        .capacity(Capacity::shannons(100_000_000_000).pack())
+        .lock(lock)
+        .build();
+    let cellbase = TransactionBuilder::default()
+        .input(packed::CellInput::new_cellbase_input(parent.number() + 1))
+        .build();
+    let create_tx = TransactionBuilder::default()
+        .input(packed::CellInput::new(input, 0))
+        .output(output.clone())
+        .output_data(Bytes::new().pack())
+        .build();
+    let consume_tx = TransactionBuilder::default()
+        .input(packed::CellInput::new(
+            packed::OutPoint::new(create_tx.hash(), 0),
+            0,
+        ))
+        .output(output)
+        .output_data(Bytes::new().pack())
+        .build();
+
+    BlockBuilder::default()
+        .parent_hash(parent.hash())
+        .number((parent.number() + 1).pack())
+        .epoch(parent.epoch().pack())
+        .timestamp((parent.timestamp() + 1).pack())
+        .transactions(vec![cellbase, create_tx, consume_tx])
+        .build()
+}
+
+async fn is_live_cell(pool: &RelationalStorage, out_point: packed::OutPoint) -> bool {
+    !pool
+        .get_live_cells(
+            Context::new(),
+            Some(out_point),
+            vec![],
+            vec![],
+            None,
+            PaginationRequest::default(),
+        )
+        .await
+        .unwrap()
+        .response
+        .is_empty()
+}
+
 async fn connect_and_insert_blocks() -> RelationalStorage {
     let pool = connect_sqlite().await;
-    let mut tx = pool.pool.transaction().await.unwrap();
-    xsql_test::create_tables(&mut tx).await.unwrap();
+    pool.migrate(DBDriver::SQLite).await.unwrap();
 
     let data_path = String::from(BLOCK_DIR);
     for i in 0..10 {
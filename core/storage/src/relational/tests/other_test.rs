@@ -1,4 +1,7 @@
 use super::*;
+use crate::ReorgLog;
+
+use db_xsql::rbatis::crud::CRUDMut;
 
 #[tokio::test]
 async fn test_insert() {
@@ -15,8 +18,7 @@ async fn test_remove_all() {
 #[tokio::test]
 async fn test_register_addresses() {
     let pool = connect_sqlite().await;
-    let mut tx = pool.pool.transaction().await.unwrap();
-    xsql_test::create_tables(&mut tx).await.unwrap();
+    pool.migrate(DBDriver::SQLite).await.unwrap();
 
     let lock_hash = h160!("0xb39bbc0b3673c7d36450bc14cfcdad2d559c6c64");
     let address = String::from("ckb1qyqt8xaupvm8837nv3gtc9x0ekkj64vud3jqfwyw5v");
@@ -56,3 +58,145 @@ async fn test_get_tx_hash() {
         .unwrap();
     println!("{:?}", res);
 }
+
+#[tokio::test]
+async fn test_migrate() {
+    let pool = connect_sqlite().await;
+    pool.migrate(DBDriver::SQLite).await.unwrap();
+    assert_eq!(
+        pool.query_schema_version().await.unwrap(),
+        Some(crate::relational::SCHEMA_VERSION)
+    );
+    assert_eq!(pool.block_count().await.unwrap(), 0);
+
+    // migrating an up-to-date database does nothing
+    pool.migrate(DBDriver::SQLite).await.unwrap();
+
+    let mut tx = pool.pool.transaction().await.unwrap();
+    tx.save(
+        &crate::relational::table::SchemaVersionTable {
+            version: crate::relational::SCHEMA_VERSION + 1,
+            description: String::from("from the future"),
+            applied_at: 0,
+        },
+        &[],
+    )
+    .await
+    .unwrap();
+    tx.commit().await.unwrap();
+    assert!(pool.migrate(DBDriver::SQLite).await.is_err());
+}
+
+#[tokio::test]
+async fn test_balance_table() {
+    let pool = connect_and_insert_blocks().await;
+    let genesis: BlockView = read_block_view(0, String::from(BLOCK_DIR)).into();
+    let lock_hash: H256 = genesis.transactions()[0]
+        .outputs()
+        .get(0)
+        .unwrap()
+        .lock()
+        .calc_script_hash()
+        .unpack();
+
+    let cells = pool
+        .get_live_cells(
+            Context::new(),
+            None,
+            vec![lock_hash.clone()],
+            vec![],
+            None,
+            PaginationRequest::default(),
+        )
+        .await
+        .unwrap()
+        .response;
+    let balances = pool
+        .get_balances(Context::new(), vec![lock_hash.clone()])
+        .await
+        .unwrap();
+    let capacity: u64 = cells
+        .iter()
+        .map(|cell| -> u64 { cell.cell_output.capacity().unpack() })
+        .sum();
+    assert_eq!(balances.iter().map(|b| b.capacity).sum::<u64>(), capacity);
+    assert_eq!(
+        balances.iter().map(|b| b.cell_count).sum::<u64>(),
+        cells.len() as u64
+    );
+
+    for number in (0..10).rev() {
+        let block: BlockView = read_block_view(number, String::from(BLOCK_DIR)).into();
+        pool.rollback_block(Context::new(), number, block.hash().unpack())
+            .await
+            .unwrap();
+    }
+    let balances = pool
+        .get_balances(Context::new(), vec![lock_hash])
+        .await
+        .unwrap();
+    assert!(balances.is_empty());
+}
+
+#[tokio::test]
+async fn test_rollback_reorg() {
+    let pool = connect_and_insert_blocks().await;
+    let genesis: BlockView = read_block_view(0, String::from(BLOCK_DIR)).into();
+    let genesis_cell = pool
+        .get_live_cells(
+            Context::new(),
+            None,
+            vec![genesis.transactions()[0]
+                .outputs()
+                .get(0)
+                .unwrap()
+                .calc_lock_hash()
+                .unpack()],
+            vec![],
+            None,
+            PaginationRequest::default().set_limit(Some(1)),
+        )
+        .await
+        .unwrap()
+        .response
+        .remove(0);
+    let new_block = build_block_with_same_block_spend(
+        &read_block_view(9, String::from(BLOCK_DIR)).into(),
+        genesis_cell.out_point.clone(),
+        genesis_cell.cell_output.lock(),
+    );
+    pool.append_block(Context::new(), new_block.clone())
+        .await
+        .unwrap();
+    assert!(!is_live_cell(&pool, genesis_cell.out_point.clone()).await);
+
+    let mut blocks: Vec<(u64, H256)> = vec![(10, new_block.hash().unpack())];
+    blocks.extend((7..10).rev().map(|number| {
+        let block: BlockView = read_block_view(number, String::from(BLOCK_DIR)).into();
+        (number, block.hash().unpack())
+    }));
+    let reorg_log = ReorgLog {
+        fork_block_number: 6,
+        depth: 4,
+        old_tip_hash: blocks[0].1.clone(),
+        old_block_hash: blocks[3].1.clone(),
+        new_block_hash: H256::default(),
+        timestamp: 1,
+    };
+
+    pool.rollback_reorg(Context::new(), blocks, reorg_log.clone())
+        .await
+        .unwrap();
+
+    let block: BlockView = read_block_view(6, String::from(BLOCK_DIR)).into();
+    let tip = pool.get_tip(Context::new()).await.unwrap().unwrap();
+    assert_eq!(tip, (6, block.hash().unpack()));
+    assert_eq!(
+        pool.get_reorg_logs(Context::new()).await.unwrap(),
+        vec![reorg_log]
+    );
+    assert!(is_live_cell(&pool, genesis_cell.out_point).await);
+    for tx in new_block.transactions().iter().skip(1) {
+        assert!(!is_live_cell(&pool, packed::OutPoint::new(tx.hash(), 0)).await);
+    }
+}
@@ -0,0 +1,372 @@
+use crate::SyncAdapter;
+
+use common::{anyhow::anyhow, async_trait, Result};
+
+use ckb_jsonrpc_types::BlockView as JsonBlockView;
+use ckb_types::core::{BlockNumber, BlockView};
+use ckb_types::{packed, prelude::*};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+const JSON_EXTENSION: &str = "json";
+const LENGTH_PREFIX_SIZE: u64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockDumpFormat {
+    /// A directory of `<block_number>.json` files, like `devtools/test_data/blocks`.
+    Json,
+    /// A file of molecule encoded blocks, each one prefixed by its length in a little
+    /// endian u32.
+    Molecule,
+}
+
+impl FromStr for BlockDumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(BlockDumpFormat::Json),
+            "molecule" => Ok(BlockDumpFormat::Molecule),
+            _ => Err(format!("invalid block dump format {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum DumpIndex {
+    Json(BTreeSet<BlockNumber>),
+    // the offset of the record of each block in the stream
+    Molecule(BTreeMap<BlockNumber, u64>),
+}
+
+/// A sync adapter reading the blocks from a block dump instead of a node, so that a new
+/// instance can be bootstrapped from the blocks exported by another one.
+#[derive(Clone, Debug)]
+pub struct FileSyncAdapter {
+    path: PathBuf,
+    index: Arc<DumpIndex>,
+}
+
+#[async_trait]
+impl SyncAdapter for FileSyncAdapter {
+    async fn pull_blocks(&self, block_numbers: Vec<BlockNumber>) -> Result<Vec<BlockView>> {
+        let adapter = self.clone();
+        tokio::task::spawn_blocking(move || adapter.read_blocks(&block_numbers)).await?
+    }
+}
+
+impl FileSyncAdapter {
+    /// Open a block dump, a directory is read as JSON blocks and a file is read as a
+    /// molecule stream.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let index = if path.is_dir() {
+            DumpIndex::Json(index_json_dir(&path)?)
+        } else {
+            DumpIndex::Molecule(index_molecule_stream(&path)?)
+        };
+
+        Ok(FileSyncAdapter {
+            path,
+            index: Arc::new(index),
+        })
+    }
+
+    pub fn format(&self) -> BlockDumpFormat {
+        match self.index.as_ref() {
+            DumpIndex::Json(_) => BlockDumpFormat::Json,
+            DumpIndex::Molecule(_) => BlockDumpFormat::Molecule,
+        }
+    }
+
+    /// The last block of the dump continuous from the genesis. The blocks after a gap are
+    /// not synchronized from the dump, since the gap could never be filled.
+    pub fn tip_number(&self) -> Option<BlockNumber> {
+        let numbers: Vec<BlockNumber> = match self.index.as_ref() {
+            DumpIndex::Json(numbers) => numbers.iter().copied().collect(),
+            DumpIndex::Molecule(offsets) => offsets.keys().copied().collect(),
+        };
+
+        numbers
+            .into_iter()
+            .enumerate()
+            .take_while(|(i, number)| *i as u64 == *number)
+            .last()
+            .map(|(_, number)| number)
+    }
+
+    fn read_blocks(&self, block_numbers: &[BlockNumber]) -> Result<Vec<BlockView>> {
+        let mut ret = Vec::with_capacity(block_numbers.len());
+        match self.index.as_ref() {
+            DumpIndex::Json(numbers) => {
+                for number in block_numbers.iter() {
+                    if !numbers.contains(number) {
+                        log::error!("[sync] Get none block {:?} from dump", number);
+                        continue;
+                    }
+
+                    let file = File::open(self.path.join(json_file_name(*number)))?;
+                    let block: JsonBlockView = serde_json::from_reader(BufReader::new(file))?;
+                    ret.push(block.into());
+                }
+            }
+
+            DumpIndex::Molecule(offsets) => {
+                let mut file = File::open(&self.path)?;
+                for number in block_numbers.iter() {
+                    let offset = match offsets.get(number) {
+                        Some(offset) => *offset,
+                        None => {
+                            log::error!("[sync] Get none block {:?} from dump", number);
+                            continue;
+                        }
+                    };
+
+                    file.seek(SeekFrom::Start(offset))?;
+                    let len = read_length(&mut file)?;
+                    let mut buf = vec![0u8; len as usize];
+                    file.read_exact(&mut buf)?;
+                    let block = packed::Block::from_slice(&buf)
+                        .map_err(|e| anyhow!("invalid block {} in dump: {}", number, e))?;
+                    ret.push(block.into_view());
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Write the blocks to a block dump readable by `FileSyncAdapter`. A molecule stream is
+/// appended to, so that an interrupted export can be continued. The record cut off by the
+/// interruption is truncated before appending.
+pub struct BlockDumpWriter {
+    path: PathBuf,
+    stream: Option<BufWriter<File>>,
+}
+
+impl BlockDumpWriter {
+    pub fn create<P: AsRef<Path>>(path: P, format: BlockDumpFormat) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let stream = match format {
+            BlockDumpFormat::Json => {
+                fs::create_dir_all(&path)?;
+                None
+            }
+            BlockDumpFormat::Molecule => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let file = OpenOptions::new()
+                    .create(true)
+                    .read(true)
+                    .append(true)
+                    .open(&path)?;
+                let complete_len = complete_records_len(&file)?;
+                if complete_len < file.metadata()?.len() {
+                    log::warn!(
+                        "[export] truncate the partial block at offset {} of dump",
+                        complete_len
+                    );
+                    file.set_len(complete_len)?;
+                }
+                Some(BufWriter::new(file))
+            }
+        };
+
+        Ok(BlockDumpWriter { path, stream })
+    }
+
+    pub fn write(&mut self, block: &BlockView) -> Result<()> {
+        match self.stream.as_mut() {
+            Some(stream) => {
+                let data = block.data();
+                stream.write_all(&(data.as_slice().len() as u32).to_le_bytes())?;
+                stream.write_all(data.as_slice())?;
+            }
+            None => {
+                let file = File::create(self.path.join(json_file_name(block.number())))?;
+                let mut writer = BufWriter::new(file);
+                serde_json::to_writer_pretty(&mut writer, &JsonBlockView::from(block.clone()))?;
+                writer.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(stream) = self.stream.as_mut() {
+            stream.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn json_file_name(block_number: BlockNumber) -> String {
+    format!("{}.{}", block_number, JSON_EXTENSION)
+}
+
+fn index_json_dir(path: &Path) -> Result<BTreeSet<BlockNumber>> {
+    let mut numbers = BTreeSet::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(JSON_EXTENSION) {
+            continue;
+        }
+
+        if let Some(number) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<BlockNumber>().ok())
+        {
+            numbers.insert(number);
+        }
+    }
+
+    Ok(numbers)
+}
+
+/// Index the blocks of a molecule stream by reading only their headers. The header is the
+/// first field of the block table, located by the first offset after the total size.
+fn index_molecule_stream(path: &Path) -> Result<BTreeMap<BlockNumber, u64>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let stream_len = reader.get_ref().metadata()?.len();
+    let mut offsets = BTreeMap::new();
+    let mut offset = 0u64;
+
+    while offset < stream_len {
+        let len = read_length(&mut reader)? as u64;
+        let end = offset + LENGTH_PREFIX_SIZE + len;
+        if end > stream_len {
+            return Err(anyhow!("truncated block at offset {} of dump", offset));
+        }
+
+        let _total_size = read_length(&mut reader)?;
+        let header_start = read_length(&mut reader)? as u64;
+        reader.seek(SeekFrom::Start(offset + LENGTH_PREFIX_SIZE + header_start))?;
+        let mut buf = vec![0u8; packed::Header::TOTAL_SIZE];
+        reader.read_exact(&mut buf)?;
+        let header = packed::Header::from_slice(&buf)
+            .map_err(|e| anyhow!("invalid block at offset {} of dump: {}", offset, e))?;
+
+        offsets.insert(header.raw().number().unpack(), offset);
+        reader.seek(SeekFrom::Start(end))?;
+        offset = end;
+    }
+
+    Ok(offsets)
+}
+
+/// The length of the complete records at the start of a molecule stream.
+fn complete_records_len(file: &File) -> Result<u64> {
+    let mut reader = BufReader::new(file);
+    let stream_len = file.metadata()?.len();
+    let mut offset = 0u64;
+
+    while offset + LENGTH_PREFIX_SIZE <= stream_len {
+        reader.seek(SeekFrom::Start(offset))?;
+        let end = offset + LENGTH_PREFIX_SIZE + read_length(&mut reader)? as u64;
+        if end > stream_len {
+            break;
+        }
+        offset = end;
+    }
+
+    Ok(offset)
+}
+
+fn read_length<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; LENGTH_PREFIX_SIZE as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_DIR: &str = "../../devtools/test_data/blocks/";
+
+    #[tokio::test]
+    async fn test_json_dump() {
+        let adapter = FileSyncAdapter::open(BLOCK_DIR).unwrap();
+        assert_eq!(adapter.format(), BlockDumpFormat::Json);
+        // block 100 is after a gap
+        assert_eq!(adapter.tip_number(), Some(9));
+
+        let blocks = adapter.pull_blocks(vec![0, 9, 50]).await.unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].number(), 0);
+        assert_eq!(blocks[1].number(), 9);
+    }
+
+    async fn json_blocks() -> Vec<BlockView> {
+        FileSyncAdapter::open(BLOCK_DIR)
+            .unwrap()
+            .pull_blocks((0..10).collect())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_molecule_dump() {
+        let blocks = json_blocks().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks");
+
+        let mut writer = BlockDumpWriter::create(&path, BlockDumpFormat::Molecule).unwrap();
+        for block in blocks.iter().rev() {
+            writer.write(block).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let adapter = FileSyncAdapter::open(&path).unwrap();
+        assert_eq!(adapter.format(), BlockDumpFormat::Molecule);
+        assert_eq!(adapter.tip_number(), Some(9));
+        let pulled = adapter.pull_blocks(vec![3, 7]).await.unwrap();
+        assert_eq!(pulled[0].hash(), blocks[3].hash());
+        assert_eq!(pulled[1].hash(), blocks[7].hash());
+    }
+
+    #[tokio::test]
+    async fn test_molecule_dump_truncate_partial_block() {
+        let blocks = json_blocks().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks");
+
+        let mut writer = BlockDumpWriter::create(&path, BlockDumpFormat::Molecule).unwrap();
+        for block in blocks[0..5].iter() {
+            writer.write(block).unwrap();
+        }
+        writer.flush().unwrap();
+        let complete_len = fs::metadata(&path).unwrap().len();
+
+        // an export interrupted in the middle of block 5
+        let data = blocks[5].data();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&(data.as_slice().len() as u32).to_le_bytes())
+            .unwrap();
+        file.write_all(&data.as_slice()[0..10]).unwrap();
+        drop(file);
+        assert!(FileSyncAdapter::open(&path).is_err());
+
+        let mut writer = BlockDumpWriter::create(&path, BlockDumpFormat::Molecule).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), complete_len);
+        for block in blocks[5..].iter() {
+            writer.write(block).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let adapter = FileSyncAdapter::open(&path).unwrap();
+        assert_eq!(adapter.tip_number(), Some(9));
+        let pulled = adapter.pull_blocks(vec![4, 5]).await.unwrap();
+        assert_eq!(pulled[0].hash(), blocks[4].hash());
+        assert_eq!(pulled[1].hash(), blocks[5].hash());
+    }
+}
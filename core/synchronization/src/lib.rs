@@ -1,34 +1,32 @@
+mod dump;
+mod pipeline;
 mod sql;
+mod state;
 mod table;
 
-use crate::table::{ConsumeInfoTable, InUpdate};
+pub use crate::dump::{BlockDumpFormat, BlockDumpWriter, FileSyncAdapter};
+pub use crate::state::{sync_state, SyncPhase, SyncState};
 
-use common::{async_trait, Result};
+use crate::table::{InUpdate, SyncCheckpoint};
+
+use common::{anyhow::anyhow, async_trait, Result};
 use core_storage::relational::table::{
-    BlockTable, CanonicalChainTable, CellTable, IndexerCellTable, TransactionTable, IO_TYPE_INPUT,
-    IO_TYPE_OUTPUT,
+    BlockTable, CellTable, IndexerCellTable, IO_TYPE_INPUT, IO_TYPE_OUTPUT,
 };
-use core_storage::relational::{generate_id, to_rb_bytes, BATCH_SIZE_THRESHOLD};
+use core_storage::relational::{generate_id, rebuild_balance_table, BATCH_SIZE_THRESHOLD};
 use db_xsql::{rbatis::crud::CRUDMut, XSQLPool};
 
 use ckb_types::core::{BlockNumber, BlockView};
-use ckb_types::prelude::*;
-use parking_lot::RwLock;
 use rbatis::executor::RBatisTxExecutor;
-use tokio::time::sleep;
 
 use std::collections::HashSet;
-use std::{ops::Range, sync::Arc, time::Duration};
+use std::{ops::Range, sync::Arc};
 
-const PULL_BLOCK_BATCH_SIZE: usize = 10;
 const INSERT_INTO_BATCH_SIZE: usize = 200_000;
+const MAX_RESYNC_TIMES: usize = 3;
 #[allow(dead_code)]
 const INSERT_INDEXER_CELL_TABLE_SIZE: usize = 2_500;
 
-lazy_static::lazy_static! {
-    static ref CURRENT_TASK_NUMBER: RwLock<usize> = RwLock::new(0);
-}
-
 #[async_trait]
 pub trait SyncAdapter: Sync + Send + 'static {
     /// Pull blocks by block number when synchronizing.
@@ -58,59 +56,130 @@ impl<T: SyncAdapter> Synchronization<T> {
         }
     }
 
+    /// Synchronize the blocks to `chain_tip` and build the tables from them. The completed
+    /// ranges of each phase are saved as checkpoints together with their data, so that a
+    /// synchronization interrupted by a crash resumes where it stopped.
     pub async fn do_sync(&self, chain_tip: BlockNumber) -> Result<()> {
-        let sync_list = self.build_to_sync_list(chain_tip).await?;
         self.try_create_consume_info_table().await?;
-        self.sync_batch_insert(chain_tip, sync_list).await;
         self.set_in_update().await?;
-        self.wait_insertion_complete().await;
+        let mut checkpoints = self.get_checkpoints().await?;
+        if checkpoints.is_empty() {
+            checkpoints = self.save_synced_checkpoints().await?;
+        }
+
+        // the blocks are not pulled any more once the tables are being built from them
+        let chain_tip = match pulled_tip(&checkpoints) {
+            Some(tip) => tip,
+            None => {
+                self.pull_blocks(chain_tip, &checkpoints).await?;
+                chain_tip
+            }
+        };
 
         let current_count = {
             let w = self.pool.wrapper();
             self.pool.fetch_count_by_wrapper::<BlockTable>(w).await?
         };
-
         log::info!("[sync] current block count {}", current_count);
 
-        let mut num = 1;
-        while let Some(set) = self.check_synchronization().await? {
-            log::info!("[sync] resync {} time", num);
-            self.sync_batch_insert(chain_tip, set).await;
-            self.wait_insertion_complete().await;
-            num += 1;
+        let checkpoints = self.get_checkpoints().await?;
+        let ranges = page_range(chain_tip, INSERT_INTO_BATCH_SIZE)
+            .step_by(INSERT_INTO_BATCH_SIZE)
+            .map(|i| (i, i + INSERT_INTO_BATCH_SIZE as u32))
+            .collect::<Vec<_>>();
+
+        let phase = SyncPhase::FillConsumedInfo;
+        let todo = todo_pages(&checkpoints, phase, &ranges);
+        state::start_phase(
+            phase,
+            (ranges.len() - todo.len()) as u64,
+            ranges.len() as u64,
+        );
+        for (start, end) in todo.into_iter() {
+            log::info!("[sync] update cell table from {} to {}", start, end);
+            let mut tx = self.pool.transaction().await?;
+            sql::update_cell_table(&mut tx, start, end).await?;
+            tx.save(&SyncCheckpoint::new(phase, start as u64, end as u64), &[])
+                .await?;
+            tx.commit().await?;
+            state::advance(phase, 1);
         }
 
-        log::info!("[sync] insert into live cell table");
-        let mut tx = self.pool.transaction().await.unwrap();
-        sql::drop_live_cell_table(&mut tx).await.unwrap();
-        sql::drop_script_table(&mut tx).await.unwrap();
-        sql::create_live_cell_table(&mut tx).await.unwrap();
-        sql::create_script_table(&mut tx).await.unwrap();
-
-        for i in page_range(chain_tip, INSERT_INTO_BATCH_SIZE).step_by(INSERT_INTO_BATCH_SIZE) {
-            let end = i + INSERT_INTO_BATCH_SIZE as u32;
-            log::info!("[sync] update cell table from {} to {}", i, end);
-            sql::update_cell_table(&mut tx, i, end).await.unwrap();
+        let phase = SyncPhase::BuildLiveCellTable;
+        let todo = todo_pages(&checkpoints, phase, &ranges);
+        state::start_phase(
+            phase,
+            (ranges.len() - todo.len()) as u64,
+            ranges.len() as u64,
+        );
+        if todo.len() == ranges.len() {
+            let mut tx = self.pool.transaction().await?;
+            sql::drop_live_cell_table(&mut tx).await?;
+            sql::drop_script_table(&mut tx).await?;
+            sql::create_live_cell_table(&mut tx).await?;
+            sql::create_script_table(&mut tx).await?;
+            tx.commit().await?;
         }
-
-        for i in page_range(chain_tip, INSERT_INTO_BATCH_SIZE).step_by(INSERT_INTO_BATCH_SIZE) {
-            let end = i + INSERT_INTO_BATCH_SIZE as u32;
-            log::info!("[sync] insert into live cell table {} to {}", i, end);
-            sql::insert_into_live_cell(&mut tx, i, end).await.unwrap();
+        for (start, end) in todo.into_iter() {
+            log::info!("[sync] insert into live cell table {} to {}", start, end);
+            let mut tx = self.pool.transaction().await?;
+            sql::insert_into_live_cell(&mut tx, start, end).await?;
+            tx.save(&SyncCheckpoint::new(phase, start as u64, end as u64), &[])
+                .await?;
+            tx.commit().await?;
+            state::advance(phase, 1);
         }
 
+        // the last phase removes the checkpoints with the in update flag
+        state::start_phase(SyncPhase::BuildScriptTable, 0, 1);
+        let mut tx = self.pool.transaction().await?;
         log::info!("[sync] insert into script table");
-        sql::insert_into_script(&mut tx).await.unwrap();
+        sql::insert_into_script(&mut tx).await?;
+
+        log::info!("[sync] rebuild balance table");
+        rebuild_balance_table(&mut tx).await?;
 
         // log::info!("[sync] build indexer cell table");
         // self.build_indexer_cell_table(chain_tip, &mut tx)
         //     .await
         //     .unwrap();
 
-        sql::drop_consume_info_table(&mut tx).await.unwrap();
-        self.remove_in_update(&mut tx).await.unwrap();
+        sql::drop_consume_info_table(&mut tx).await?;
+        sql::delete_sync_checkpoints(&mut tx).await?;
+        self.remove_in_update(&mut tx).await?;
         tx.commit().await.expect("insert into");
         let _ = tx.take_conn().unwrap().close().await;
+        state::start_phase(SyncPhase::Synced, 0, 0);
+
+        Ok(())
+    }
+
+    async fn pull_blocks(
+        &self,
+        chain_tip: BlockNumber,
+        checkpoints: &[SyncCheckpoint],
+    ) -> Result<()> {
+        let sync_list = build_to_sync_list(chain_tip, checkpoints);
+        state::start_phase(
+            SyncPhase::PullBlocks,
+            chain_tip + 1 - sync_list.len() as u64,
+            chain_tip + 1,
+        );
+        self.sync_batch_insert(chain_tip, sync_list).await?;
+
+        let mut num = 1;
+        while let Some(set) = self.check_synchronization(chain_tip).await? {
+            if num > MAX_RESYNC_TIMES {
+                return Err(anyhow!(
+                    "{} blocks are still missing after {} resyncs",
+                    set.len(),
+                    MAX_RESYNC_TIMES
+                ));
+            }
+            log::info!("[sync] resync {} time", num);
+            self.sync_batch_insert(chain_tip, set).await?;
+            num += 1;
+        }
 
         Ok(())
     }
@@ -184,41 +253,46 @@ impl<T: SyncAdapter> Synchronization<T> {
         Ok(())
     }
 
-    async fn sync_batch_insert(&self, chain_tip: u64, sync_list: Vec<u64>) {
+    async fn sync_batch_insert(&self, chain_tip: u64, sync_list: Vec<u64>) -> Result<()> {
         log::info!(
             "[sync] chain tip is {}, need sync {}",
             chain_tip,
             sync_list.len()
         );
 
-        for set in sync_list.chunks(self.sync_task_size) {
-            let sync_set = set.to_vec();
-            let (rdb, adapter) = (self.pool.clone(), Arc::clone(&self.adapter));
-
-            loop {
-                let task_num = current_task_count();
-                if task_num < self.max_task_number {
-                    add_one_task();
-                    tokio::spawn(async move {
-                        sync_process(sync_set, rdb, adapter).await;
-                    });
-
-                    break;
-                } else {
-                    sleep(Duration::from_secs(5)).await;
-                }
-            }
-        }
+        pipeline::run(
+            self.pool.clone(),
+            Arc::clone(&self.adapter),
+            sync_list,
+            self.sync_task_size,
+            self.max_task_number,
+        )
+        .await
     }
 
-    async fn build_to_sync_list(&self, chain_tip: u64) -> Result<Vec<BlockNumber>> {
-        let mut to_sync_number_set = (0..=chain_tip).collect::<HashSet<_>>();
-        let sync_completed_set = self.get_sync_completed_numbers().await?;
-        sync_completed_set.iter().for_each(|num| {
-            to_sync_number_set.remove(num);
-        });
+    async fn get_checkpoints(&self) -> Result<Vec<SyncCheckpoint>> {
+        let checkpoints: Vec<SyncCheckpoint> = self.pool.fetch_list().await?;
+        Ok(checkpoints)
+    }
 
-        Ok(to_sync_number_set.into_iter().collect())
+    /// The blocks synchronized before the checkpoints were introduced are saved as the
+    /// checkpoints of pulling blocks.
+    async fn save_synced_checkpoints(&self) -> Result<Vec<SyncCheckpoint>> {
+        let mut numbers = self.get_sync_completed_numbers().await?;
+        numbers.sort_unstable();
+        let checkpoints = to_ranges(&numbers)
+            .into_iter()
+            .map(|range| SyncCheckpoint::new(SyncPhase::PullBlocks, range.start, range.end))
+            .collect::<Vec<_>>();
+
+        if !checkpoints.is_empty() {
+            let mut tx = self.pool.transaction().await?;
+            tx.save_batch_slice(&checkpoints, BATCH_SIZE_THRESHOLD, &[])
+                .await?;
+            tx.commit().await?;
+        }
+
+        Ok(checkpoints)
     }
 
     async fn get_sync_completed_numbers(&self) -> Result<Vec<BlockNumber>> {
@@ -227,19 +301,9 @@ impl<T: SyncAdapter> Synchronization<T> {
         Ok(res.iter().map(|t| t.block_number).collect())
     }
 
-    async fn get_tip_number(&self) -> Result<BlockNumber> {
-        let w = self
-            .pool
-            .wrapper()
-            .order_by(false, &["block_number"])
-            .limit(1);
-        let res = self.pool.fetch_by_wrapper::<CanonicalChainTable>(w).await?;
-        Ok(res.block_number)
-    }
-
-    async fn check_synchronization(&self) -> Result<Option<Vec<BlockNumber>>> {
-        let tip_number = self.get_tip_number().await?;
-        let set = self.build_to_sync_list(tip_number).await?;
+    async fn check_synchronization(&self, chain_tip: u64) -> Result<Option<Vec<BlockNumber>>> {
+        let checkpoints = self.get_checkpoints().await?;
+        let set = build_to_sync_list(chain_tip, &checkpoints);
         if set.is_empty() {
             Ok(None)
         } else {
@@ -247,19 +311,6 @@ impl<T: SyncAdapter> Synchronization<T> {
         }
     }
 
-    async fn wait_insertion_complete(&self) {
-        loop {
-            sleep(Duration::from_secs(5)).await;
-
-            let task_num = current_task_count();
-            if task_num == 0 {
-                return;
-            }
-
-            log::info!("current thread number {}", current_task_count());
-        }
-    }
-
     pub async fn is_previous_in_update(&self) -> Result<bool> {
         let w = self.pool.wrapper().eq("is_in", true);
         Ok(self.pool.fetch_count_by_wrapper::<InUpdate>(w).await? == 1)
@@ -287,113 +338,78 @@ impl<T: SyncAdapter> Synchronization<T> {
     }
 }
 
-async fn sync_process<T: SyncAdapter>(task: Vec<BlockNumber>, rdb: XSQLPool, adapter: Arc<T>) {
-    for subtask in task.chunks(PULL_BLOCK_BATCH_SIZE) {
-        let (rdb_clone, adapter_clone) = (rdb.clone(), Arc::clone(&adapter));
-
-        if let Err(err) = sync_blocks(subtask.to_vec(), rdb_clone, adapter_clone).await {
-            log::error!("[sync] sync block {:?} error {:?}", subtask, err)
-        }
+/// The tip that the blocks were pulled to, if the tables are being built from them.
+fn pulled_tip(checkpoints: &[SyncCheckpoint]) -> Option<BlockNumber> {
+    let pull_blocks = SyncPhase::PullBlocks.to_u16();
+    if checkpoints.iter().all(|c| c.phase == pull_blocks) {
+        return None;
     }
 
-    free_one_task();
+    checkpoints
+        .iter()
+        .filter(|c| c.phase == pull_blocks)
+        .map(|c| c.range_end - 1)
+        .max()
 }
 
-async fn sync_blocks<T: SyncAdapter>(
-    task: Vec<BlockNumber>,
-    rdb: XSQLPool,
-    adapter: Arc<T>,
-) -> Result<()> {
-    let blocks = adapter.pull_blocks(task.clone()).await?;
-    let mut block_table_batch: Vec<BlockTable> = Vec::new();
-    let mut tx_table_batch: Vec<TransactionTable> = Vec::new();
-    let mut cell_table_batch: Vec<CellTable> = Vec::new();
-    let mut consume_info_batch: Vec<ConsumeInfoTable> = Vec::new();
-    let mut canonical_data_table_batch: Vec<CanonicalChainTable> = Vec::new();
-    let mut tx = rdb.transaction().await?;
-
-    for block in blocks.iter() {
-        let block_number = block.number();
-        let block_hash = block.hash().raw_data().to_vec();
-        let block_timestamp = block.timestamp();
-        let block_epoch = block.epoch();
-
-        block_table_batch.push(block.into());
-        canonical_data_table_batch.push(CanonicalChainTable::new(
-            block_number,
-            to_rb_bytes(&block_hash),
-        ));
-
-        for (tx_idx, transaction) in block.transactions().iter().enumerate() {
-            let tx_hash = to_rb_bytes(&transaction.hash().raw_data());
-            tx_table_batch.push(TransactionTable::from_view(
-                transaction,
-                generate_id(block_number),
-                tx_idx as u32,
-                to_rb_bytes(&block_hash),
-                block_number,
-                block_timestamp,
-            ));
-
-            // skip cellbase
-            if tx_idx != 0 {
-                for (input_idx, input) in transaction.inputs().into_iter().enumerate() {
-                    consume_info_batch.push(ConsumeInfoTable::new(
-                        input.previous_output(),
-                        block_number,
-                        to_rb_bytes(&block_hash),
-                        tx_hash.clone(),
-                        tx_idx as u32,
-                        input_idx as u32,
-                        input.since().unpack(),
-                    ));
-                }
-            }
-
-            for (output_idx, (cell, data)) in transaction.outputs_with_data_iter().enumerate() {
-                cell_table_batch.push(CellTable::from_cell(
-                    &cell,
-                    generate_id(block_number),
-                    tx_hash.clone(),
-                    output_idx as u32,
-                    tx_idx as u32,
-                    block_number,
-                    to_rb_bytes(&block_hash),
-                    block_epoch,
-                    &data,
-                ));
-            }
+/// The block numbers in `0..=chain_tip` which are not covered by the checkpoints of pulling
+/// blocks, in ascending order.
+fn build_to_sync_list(chain_tip: BlockNumber, checkpoints: &[SyncCheckpoint]) -> Vec<BlockNumber> {
+    let mut synced = checkpoints
+        .iter()
+        .filter(|c| c.phase == SyncPhase::PullBlocks.to_u16())
+        .map(|c| c.range_start..c.range_end)
+        .collect::<Vec<_>>();
+    synced.sort_unstable_by_key(|range| range.start);
+
+    let mut list = Vec::new();
+    let mut next = 0;
+    for range in synced.into_iter() {
+        if next > chain_tip {
+            break;
         }
+        if range.start > next {
+            list.extend(next..range.start.min(chain_tip + 1));
+        }
+        next = next.max(range.end);
+    }
+    if next <= chain_tip {
+        list.extend(next..=chain_tip);
     }
 
-    core_storage::save_batch_slice!(
-        tx,
-        block_table_batch,
-        tx_table_batch,
-        cell_table_batch,
-        consume_info_batch,
-        canonical_data_table_batch
-    );
-
-    tx.commit().await?;
-
-    let _ = tx.take_conn().unwrap().close().await;
-
-    Ok(())
+    list
 }
 
-fn current_task_count() -> usize {
-    *CURRENT_TASK_NUMBER.read()
+/// The pages of a phase which are not checkpointed.
+fn todo_pages(
+    checkpoints: &[SyncCheckpoint],
+    phase: SyncPhase,
+    pages: &[(u32, u32)],
+) -> Vec<(u32, u32)> {
+    let completed = checkpoints
+        .iter()
+        .filter(|c| c.phase == phase.to_u16())
+        .map(|c| c.range_start)
+        .collect::<HashSet<_>>();
+
+    pages
+        .iter()
+        .filter(|(start, _)| !completed.contains(&(*start as u64)))
+        .cloned()
+        .collect()
 }
 
-fn add_one_task() {
-    let mut num = CURRENT_TASK_NUMBER.write();
-    *num += 1;
-}
+/// Merge the ascending block numbers into the ranges of consecutive numbers.
+fn to_ranges(numbers: &[BlockNumber]) -> Vec<Range<u64>> {
+    let mut ranges: Vec<Range<u64>> = Vec::new();
+    for number in numbers.iter() {
+        match ranges.last_mut() {
+            Some(range) if range.end == *number => range.end += 1,
+            _ => ranges.push(*number..*number + 1),
+        }
+    }
 
-fn free_one_task() {
-    let mut num = CURRENT_TASK_NUMBER.write();
-    *num -= 1;
+    ranges
 }
 
 fn page_range(chain_tip: u64, step_len: usize) -> Range<u32> {
@@ -408,6 +424,25 @@ fn page_range(chain_tip: u64, step_len: usize) -> Range<u32> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_to_ranges() {
+        assert_eq!(to_ranges(&[0, 1, 2, 5, 6, 9]), vec![0..3, 5..7, 9..10]);
+        assert!(to_ranges(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_build_to_sync_list() {
+        let checkpoints = vec![
+            SyncCheckpoint::new(SyncPhase::PullBlocks, 3, 5),
+            SyncCheckpoint::new(SyncPhase::PullBlocks, 0, 2),
+            SyncCheckpoint::new(SyncPhase::FillConsumedInfo, 0, 200_000),
+        ];
+        assert_eq!(build_to_sync_list(7, &checkpoints), vec![2, 5, 6, 7]);
+        assert_eq!(build_to_sync_list(3, &checkpoints), vec![2]);
+        assert_eq!(pulled_tip(&checkpoints), Some(4));
+        assert_eq!(pulled_tip(&checkpoints[0..2]), None);
+    }
+
     #[test]
     fn test_range() {
         let range = page_range(1_000_000, INSERT_INTO_BATCH_SIZE);
@@ -0,0 +1,377 @@
+use crate::state::{self, SyncPhase};
+use crate::table::{ConsumeInfoTable, SyncCheckpoint};
+use crate::{to_ranges, SyncAdapter};
+
+use common::{anyhow::anyhow, anyhow::Error, Result};
+use core_storage::relational::table::{
+    BlockTable, CanonicalChainTable, CellTable, TransactionTable,
+};
+use core_storage::relational::{generate_id, to_rb_bytes, BATCH_SIZE_THRESHOLD};
+use db_xsql::{rbatis::crud::CRUDMut, XSQLPool};
+
+use ckb_types::core::{BlockNumber, BlockView};
+use ckb_types::prelude::*;
+use futures::stream::{self, StreamExt};
+use rbatis::executor::RBatisTxExecutor;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+
+use std::future::Future;
+use std::{sync::Arc, time::Duration};
+
+const PULL_BLOCK_BATCH_SIZE: usize = 10;
+const MAX_RETRY_TIMES: usize = 3;
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The tables decoded from the blocks, which are inserted in one database transaction
+/// together with the checkpoints of the blocks.
+#[derive(Default)]
+struct SyncBatch {
+    block_numbers: Vec<BlockNumber>,
+    block_table: Vec<BlockTable>,
+    tx_table: Vec<TransactionTable>,
+    cell_table: Vec<CellTable>,
+    consume_info_table: Vec<ConsumeInfoTable>,
+    canonical_chain_table: Vec<CanonicalChainTable>,
+}
+
+impl SyncBatch {
+    fn append(&mut self, blocks: &[BlockView]) {
+        for block in blocks.iter() {
+            let block_number = block.number();
+            let block_hash = block.hash().raw_data().to_vec();
+            let block_timestamp = block.timestamp();
+            let block_epoch = block.epoch();
+
+            self.block_numbers.push(block_number);
+            self.block_table.push(block.into());
+            self.canonical_chain_table.push(CanonicalChainTable::new(
+                block_number,
+                to_rb_bytes(&block_hash),
+            ));
+
+            for (tx_idx, transaction) in block.transactions().iter().enumerate() {
+                let tx_hash = to_rb_bytes(&transaction.hash().raw_data());
+                self.tx_table.push(TransactionTable::from_view(
+                    transaction,
+                    generate_id(block_number),
+                    tx_idx as u32,
+                    to_rb_bytes(&block_hash),
+                    block_number,
+                    block_timestamp,
+                ));
+
+                // skip cellbase
+                if tx_idx != 0 {
+                    for (input_idx, input) in transaction.inputs().into_iter().enumerate() {
+                        self.consume_info_table.push(ConsumeInfoTable::new(
+                            input.previous_output(),
+                            block_number,
+                            to_rb_bytes(&block_hash),
+                            tx_hash.clone(),
+                            tx_idx as u32,
+                            input_idx as u32,
+                            input.since().unpack(),
+                        ));
+                    }
+                }
+
+                for (output_idx, (cell, data)) in transaction.outputs_with_data_iter().enumerate() {
+                    self.cell_table.push(CellTable::from_cell(
+                        &cell,
+                        generate_id(block_number),
+                        tx_hash.clone(),
+                        output_idx as u32,
+                        tx_idx as u32,
+                        block_number,
+                        to_rb_bytes(&block_hash),
+                        block_epoch,
+                        &data,
+                    ));
+                }
+            }
+        }
+    }
+
+    fn checkpoints(&self) -> Vec<SyncCheckpoint> {
+        let mut numbers = self.block_numbers.clone();
+        numbers.sort_unstable();
+        to_ranges(&numbers)
+            .into_iter()
+            .map(|range| SyncCheckpoint::new(SyncPhase::PullBlocks, range.start, range.end))
+            .collect()
+    }
+}
+
+/// Pull the blocks through a staged pipeline: the blocks are fetched by the adapter, decoded
+/// into the tables and inserted in batches of `batch_size` blocks. The stages are connected
+/// by bounded channels and at most `max_task_number` fetches and inserts run at once, so a
+/// slow node or database holds back the other stages. The fetched blocks are decoded in the
+/// order of the block numbers. A failed fetch or insert is retried with a doubled interval,
+/// and the synchronization fails once an operation fails all the retries.
+pub(crate) async fn run<T: SyncAdapter>(
+    pool: XSQLPool,
+    adapter: Arc<T>,
+    block_numbers: Vec<BlockNumber>,
+    batch_size: usize,
+    max_task_number: usize,
+) -> Result<()> {
+    if batch_size == 0 || max_task_number == 0 {
+        return Err(anyhow!(
+            "sync batch size {} and max task number {} must be at least 1",
+            batch_size,
+            max_task_number
+        ));
+    }
+
+    let (blocks_sender, blocks_receiver) = mpsc::channel(max_task_number);
+    let (batch_sender, batch_receiver) = mpsc::channel(max_task_number);
+    let insert = move |batch: Arc<SyncBatch>| {
+        let pool = pool.clone();
+        async move { insert_batch(&pool, &batch).await }
+    };
+
+    tokio::try_join!(
+        fetch_blocks(adapter, block_numbers, max_task_number, blocks_sender),
+        decode_blocks(blocks_receiver, batch_sender, batch_size),
+        insert_batches(batch_receiver, max_task_number, insert),
+    )?;
+
+    Ok(())
+}
+
+async fn fetch_blocks<T: SyncAdapter>(
+    adapter: Arc<T>,
+    block_numbers: Vec<BlockNumber>,
+    max_task_number: usize,
+    sender: mpsc::Sender<Vec<BlockView>>,
+) -> Result<()> {
+    let chunks = block_numbers
+        .chunks(PULL_BLOCK_BATCH_SIZE)
+        .map(|numbers| numbers.to_vec())
+        .collect::<Vec<_>>();
+    let mut fetches = stream::iter(chunks)
+        .map(|numbers| {
+            let adapter = Arc::clone(&adapter);
+            tokio::spawn(async move { retry(|| adapter.pull_blocks(numbers.clone())).await })
+        })
+        .buffered(max_task_number);
+
+    while let Some(blocks) = fetches.next().await {
+        sender
+            .send(blocks??)
+            .await
+            .map_err(|_| anyhow!("decode stage of sync is closed"))?;
+    }
+
+    Ok(())
+}
+
+async fn decode_blocks(
+    mut receiver: mpsc::Receiver<Vec<BlockView>>,
+    sender: mpsc::Sender<SyncBatch>,
+    batch_size: usize,
+) -> Result<()> {
+    let mut batch = SyncBatch::default();
+
+    while let Some(blocks) = receiver.recv().await {
+        batch.append(&blocks);
+        if batch.block_numbers.len() >= batch_size {
+            sender
+                .send(std::mem::take(&mut batch))
+                .await
+                .map_err(|_| anyhow!("insert stage of sync is closed"))?;
+        }
+    }
+
+    if !batch.block_numbers.is_empty() {
+        sender
+            .send(batch)
+            .await
+            .map_err(|_| anyhow!("insert stage of sync is closed"))?;
+    }
+
+    Ok(())
+}
+
+async fn insert_batches<F, Fut>(
+    receiver: mpsc::Receiver<SyncBatch>,
+    max_task_number: usize,
+    insert: F,
+) -> Result<()>
+where
+    F: Fn(Arc<SyncBatch>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let insert = Arc::new(insert);
+    let batches = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|batch| (batch, receiver))
+    });
+    let mut inserts = batches
+        .map(|batch| {
+            let (insert, batch) = (Arc::clone(&insert), Arc::new(batch));
+            tokio::spawn(async move {
+                retry(|| insert(Arc::clone(&batch))).await?;
+                state::advance(SyncPhase::PullBlocks, batch.block_numbers.len() as u64);
+                Ok::<_, Error>(())
+            })
+        })
+        .buffer_unordered(max_task_number);
+
+    while let Some(res) = inserts.next().await {
+        res??;
+    }
+
+    Ok(())
+}
+
+async fn insert_batch(pool: &XSQLPool, batch: &SyncBatch) -> Result<()> {
+    let mut tx = pool.transaction().await?;
+    if let Err(err) = save_batch(&mut tx, batch).await {
+        let _ = tx.rollback().await;
+        return Err(err);
+    }
+
+    tx.commit().await?;
+    let _ = tx.take_conn().unwrap().close().await;
+    Ok(())
+}
+
+async fn save_batch(tx: &mut RBatisTxExecutor<'_>, batch: &SyncBatch) -> Result<()> {
+    tx.save_batch_slice(&batch.block_table, BATCH_SIZE_THRESHOLD, &[])
+        .await?;
+    tx.save_batch_slice(&batch.tx_table, BATCH_SIZE_THRESHOLD, &[])
+        .await?;
+    tx.save_batch_slice(&batch.cell_table, BATCH_SIZE_THRESHOLD, &[])
+        .await?;
+    tx.save_batch_slice(&batch.consume_info_table, BATCH_SIZE_THRESHOLD, &[])
+        .await?;
+    tx.save_batch_slice(&batch.canonical_chain_table, BATCH_SIZE_THRESHOLD, &[])
+        .await?;
+    tx.save_batch_slice(&batch.checkpoints(), BATCH_SIZE_THRESHOLD, &[])
+        .await?;
+    Ok(())
+}
+
+/// Retry an operation `MAX_RETRY_TIMES` times, the interval is doubled after each failure.
+async fn retry<F, Fut, R>(mut operation: F) -> Result<R>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<R>>,
+{
+    let mut interval = RETRY_INTERVAL;
+    let mut times = 1;
+
+    loop {
+        match operation().await {
+            Ok(res) => return Ok(res),
+            Err(err) if times < MAX_RETRY_TIMES => {
+                log::warn!("[sync] retry {} time after error {:?}", times, err);
+                sleep(interval).await;
+                interval *= 2;
+                times += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use common::async_trait;
+
+    use ckb_types::core::{BlockBuilder, HeaderBuilder};
+    use parking_lot::Mutex;
+
+    use std::collections::HashMap;
+
+    struct MockAdapter;
+
+    #[async_trait]
+    impl SyncAdapter for MockAdapter {
+        async fn pull_blocks(&self, block_numbers: Vec<BlockNumber>) -> Result<Vec<BlockView>> {
+            // the later blocks are fetched faster
+            sleep(Duration::from_millis(100 - block_numbers[0])).await;
+            Ok(block_numbers.into_iter().map(new_block).collect())
+        }
+    }
+
+    fn new_block(number: BlockNumber) -> BlockView {
+        BlockBuilder::default()
+            .header(HeaderBuilder::default().number(number.pack()).build())
+            .build()
+    }
+
+    fn new_batch(numbers: Vec<BlockNumber>) -> SyncBatch {
+        let mut batch = SyncBatch::default();
+        batch.append(&numbers.into_iter().map(new_block).collect::<Vec<_>>());
+        batch
+    }
+
+    #[tokio::test]
+    async fn test_decode_blocks_in_order() {
+        let (blocks_sender, blocks_receiver) = mpsc::channel(4);
+        let (batch_sender, mut batch_receiver) = mpsc::channel(100);
+
+        tokio::try_join!(
+            fetch_blocks(Arc::new(MockAdapter), (0..95).collect(), 4, blocks_sender),
+            decode_blocks(blocks_receiver, batch_sender, 20),
+        )
+        .unwrap();
+
+        let mut batches = vec![];
+        while let Some(batch) = batch_receiver.recv().await {
+            batches.push(batch.block_numbers);
+        }
+        assert_eq!(batches.len(), 5);
+        assert_eq!(batches.concat(), (0..95).collect::<Vec<_>>());
+        assert_eq!(batches[4], (80..95).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_insert_retried_batch() {
+        let (sender, receiver) = mpsc::channel(4);
+        let inserted = Arc::new(Mutex::new(HashMap::new()));
+        let attempts = Arc::clone(&inserted);
+        // the first insert of the batch from block 10 fails
+        let insert = move |batch: Arc<SyncBatch>| {
+            let attempts = Arc::clone(&attempts);
+            async move {
+                let mut attempts = attempts.lock();
+                let times = attempts.entry(batch.block_numbers[0]).or_insert(0);
+                *times += 1;
+                if batch.block_numbers[0] == 10 && *times == 1 {
+                    return Err(anyhow!("insert error"));
+                }
+                Ok(())
+            }
+        };
+
+        for start in (0..30).step_by(10) {
+            sender
+                .send(new_batch((start..start + 10).collect()))
+                .await
+                .unwrap();
+        }
+        drop(sender);
+        insert_batches(receiver, 2, insert).await.unwrap();
+
+        let inserted = inserted.lock();
+        assert_eq!(inserted.len(), 3);
+        assert_eq!(inserted[&0], 1);
+        assert_eq!(inserted[&10], 2);
+        assert_eq!(inserted[&20], 1);
+    }
+
+    #[tokio::test]
+    async fn test_insert_batch_exceed_retry_times() {
+        let (sender, receiver) = mpsc::channel(4);
+        let insert = |_batch: Arc<SyncBatch>| async { Err(anyhow!("insert error")) };
+
+        sender.send(new_batch(vec![0, 1])).await.unwrap();
+        drop(sender);
+        assert!(insert_batches(receiver, 2, insert).await.is_err());
+    }
+}
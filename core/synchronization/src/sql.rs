@@ -46,6 +46,9 @@ pub async fn drop_live_cell_table(tx: &mut RBatisTxExecutor<'_>) -> () {}
 #[sql(tx, "DROP TABLE mercury_script")]
 pub async fn drop_script_table(tx: &mut RBatisTxExecutor<'_>) -> () {}
 
+#[sql(tx, "DELETE FROM mercury_sync_checkpoint")]
+pub async fn delete_sync_checkpoints(tx: &mut RBatisTxExecutor<'_>) -> () {}
+
 #[sql(tx, "DROP TABLE mercury_consume_info")]
 pub async fn drop_consume_info_table(tx: &mut RBatisTxExecutor<'_>) -> () {}
 
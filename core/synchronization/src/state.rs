@@ -0,0 +1,116 @@
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use std::time::Instant;
+
+lazy_static::lazy_static! {
+    static ref SYNC_PROGRESS: RwLock<SyncProgress> = RwLock::new(SyncProgress::default());
+}
+
+/// The phases of the parallel synchronization, in the order they run.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SyncPhase {
+    PullBlocks,
+    FillConsumedInfo,
+    BuildLiveCellTable,
+    BuildScriptTable,
+    Synced,
+}
+
+impl SyncPhase {
+    pub(crate) fn to_u16(self) -> u16 {
+        match self {
+            SyncPhase::PullBlocks => 0,
+            SyncPhase::FillConsumedInfo => 1,
+            SyncPhase::BuildLiveCellTable => 2,
+            SyncPhase::BuildScriptTable => 3,
+            SyncPhase::Synced => 4,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SyncState {
+    pub phase: SyncPhase,
+    pub completed: u64,
+    pub total: u64,
+    pub percentage: f64,
+    pub eta_seconds: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+struct SyncProgress {
+    phase: SyncPhase,
+    completed: u64,
+    total: u64,
+    // The work completed before this run is excluded from the speed.
+    resumed: u64,
+    started_at: Instant,
+}
+
+impl Default for SyncProgress {
+    fn default() -> Self {
+        SyncProgress {
+            phase: SyncPhase::Synced,
+            completed: 0,
+            total: 0,
+            resumed: 0,
+            started_at: Instant::now(),
+        }
+    }
+}
+
+/// The state of the synchronization. The progress is counted in blocks when pulling blocks,
+/// in pages of blocks when filling the consumed info and building the live cell table, and
+/// the script table is built in a single step.
+pub fn sync_state() -> SyncState {
+    let progress = SYNC_PROGRESS.read().clone();
+    let percentage = if progress.phase == SyncPhase::Synced || progress.total == 0 {
+        100.0
+    } else {
+        progress.completed as f64 * 100.0 / progress.total as f64
+    };
+
+    let done_in_run = progress.completed.saturating_sub(progress.resumed);
+    let eta_seconds = if progress.phase == SyncPhase::Synced {
+        Some(0)
+    } else if done_in_run == 0 {
+        None
+    } else {
+        let elapsed = progress.started_at.elapsed().as_secs_f64();
+        let remaining = progress.total.saturating_sub(progress.completed);
+        Some((elapsed / done_in_run as f64 * remaining as f64) as u64)
+    };
+
+    SyncState {
+        phase: progress.phase,
+        completed: progress.completed,
+        total: progress.total,
+        percentage,
+        eta_seconds,
+    }
+}
+
+pub(crate) fn start_phase(phase: SyncPhase, completed: u64, total: u64) {
+    log::info!(
+        "[sync] phase {:?}, {} of {} completed",
+        phase,
+        completed,
+        total
+    );
+
+    *SYNC_PROGRESS.write() = SyncProgress {
+        phase,
+        completed,
+        total,
+        resumed: completed,
+        started_at: Instant::now(),
+    };
+}
+
+pub(crate) fn advance(phase: SyncPhase, count: u64) {
+    let mut progress = SYNC_PROGRESS.write();
+    if progress.phase == phase {
+        progress.completed = (progress.completed + count).min(progress.total);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::state::SyncPhase;
+
 use core_storage::relational::to_rb_bytes;
 use core_storage::single_sql_return;
 use db_xsql::rbatis::{core::types::byte::RbBytes, crud_table};
@@ -58,3 +60,22 @@ impl ConsumeInfoTable {
 pub struct InUpdate {
     pub is_in: bool,
 }
+
+/// A range of blocks `[range_start, range_end)` completed in a phase of the synchronization.
+#[crud_table(table_name: "mercury_sync_checkpoint")]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SyncCheckpoint {
+    pub phase: u16,
+    pub range_start: u64,
+    pub range_end: u64,
+}
+
+impl SyncCheckpoint {
+    pub fn new(phase: SyncPhase, range_start: u64, range_end: u64) -> Self {
+        SyncCheckpoint {
+            phase: phase.to_u16(),
+            range_start,
+            range_end,
+        }
+    }
+}
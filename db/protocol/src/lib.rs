@@ -1,7 +1,7 @@
 use common::{DetailedCell, Result};
 
 use ckb_types::core::{BlockNumber, RationalU256, TransactionView};
-use ckb_types::{packed, H256};
+use ckb_types::{bytes::Bytes, packed, H256};
 
 use ckb_jsonrpc_types::TransactionWithStatus;
 use serde::{Deserialize, Serialize};
@@ -11,6 +11,7 @@ pub type IteratorItem = (Box<[u8]>, Box<[u8]>);
 pub const MYSQL: &str = "mysql://";
 pub const PGSQL: &str = "postgres://";
 pub const SQLITE: &str = "sqlite://";
+pub const ROCKSDB: &str = "rocksdb://";
 
 pub enum IteratorDirection {
     Forward,
@@ -52,6 +53,7 @@ pub enum DBDriver {
     PostgreSQL,
     MySQL,
     SQLite,
+    RocksDB,
 }
 
 impl Default for DBDriver {
@@ -67,6 +69,7 @@ impl DBDriver {
             "postgres" => DBDriver::PostgreSQL,
             "mysql" => DBDriver::MySQL,
             "sqlite" => DBDriver::SQLite,
+            "rocksdb" => DBDriver::RocksDB,
             _ => panic!("Invalid DB driver type"),
         }
     }
@@ -88,6 +91,7 @@ impl Into<&str> for DBDriver {
             DBDriver::PostgreSQL => PGSQL,
             DBDriver::MySQL => MYSQL,
             DBDriver::SQLite => SQLITE,
+            DBDriver::RocksDB => ROCKSDB,
         }
     }
 }
@@ -109,6 +113,47 @@ pub struct SimpleBlock {
     pub transactions: Vec<H256>,
 }
 
+/// The cells consumed by a transaction are its inputs, and the cells created by a
+/// transaction are its outputs.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum CellIOType {
+    Input,
+    Output,
+}
+
+/// The conditions on the cells applied by the storage before the pagination.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct CellFilter {
+    pub io_type: Option<CellIOType>,
+    pub data_prefix: Option<Bytes>,
+}
+
+/// The live cells of a lock script aggregated by type script. The `udt_amount` is
+/// decoded from the first 16 bytes of the data, it is meaningful only when the type
+/// script is a UDT.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScriptBalance {
+    pub lock_hash: H256,
+    pub type_hash: Option<H256>,
+    pub capacity: u64,
+    pub occupied_capacity: u64,
+    pub udt_amount: u128,
+    pub cell_count: u64,
+}
+
+/// A chain reorganization handled by rolling back the blocks after the fork point. The
+/// old block is the first block rolled back, and the new block is the block of the same
+/// number on the new branch. The timestamp is in seconds.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReorgLog {
+    pub fork_block_number: BlockNumber,
+    pub depth: u64,
+    pub old_tip_hash: H256,
+    pub old_block_hash: H256,
+    pub new_block_hash: H256,
+    pub timestamp: u64,
+}
+
 pub struct ConsumeInfo {
     pub output_point: packed::OutPoint,
     pub since: u64,